@@ -0,0 +1,14 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use pokebell::two_touch_input::Converter;
+
+fn construct(c: &mut Criterion) {
+    c.bench_function("Converter::new", |b| b.iter(Converter::new));
+    c.bench_function("Converter::without_reserved_words", |b| {
+        b.iter(Converter::without_reserved_words)
+    });
+    let converter = Converter::new();
+    c.bench_function("Converter::clone", |b| b.iter(|| converter.clone()));
+}
+
+criterion_group!(benches, construct);
+criterion_main!(benches);
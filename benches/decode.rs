@@ -0,0 +1,64 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use pokebell::two_touch_input::Converter;
+
+fn decode_short(c: &mut Criterion) {
+    let converter = Converter::new();
+    // おはよう
+    c.bench_function("convert_from_two_touch_string おはよう", |b| {
+        b.iter(|| converter.convert_from_two_touch_string(black_box("15618513")))
+    });
+    c.bench_function("convert_from_two_touch_string 20桁", |b| {
+        b.iter(|| converter.convert_from_two_touch_string(black_box("25035242612503524261")))
+    });
+}
+
+fn decode_long(c: &mut Criterion) {
+    let converter = Converter::new();
+    // 4KBと64KBほどの数字
+    let digits = "2503524261812252230840".repeat(200);
+    c.bench_function("convert_from_two_touch_string 4KB", |b| {
+        b.iter(|| converter.convert_from_two_touch_string(black_box(&digits)))
+    });
+    let digits = "2503524261812252230840".repeat(3000);
+    c.bench_function("convert_from_two_touch_string 64KB", |b| {
+        b.iter(|| converter.convert_from_two_touch_string(black_box(&digits)))
+    });
+    // 末尾だけ誤りがある場合も, 誤りの場所を探すのは最後だけ
+    let mut invalid = "2503524261812252230840".repeat(200);
+    invalid.push_str("1a");
+    c.bench_function("convert_from_two_touch_string 4KB invalid", |b| {
+        b.iter(|| converter.convert_from_two_touch_string(black_box(&invalid)))
+    });
+}
+
+fn decode_many(c: &mut Criterion) {
+    let converter = Converter::new();
+    // 短い数字を続けて戻す。`decode_into` は同じ `String` を使い回す
+    let codes: Vec<String> = (0..1000)
+        .map(|i| format!("{}{:02}", "81225223", 11 + i % 9))
+        .collect();
+    c.bench_function("convert_from_two_touch_string 1000件", |b| {
+        b.iter(|| {
+            for code in &codes {
+                black_box(
+                    converter
+                        .convert_from_two_touch_string(black_box(code))
+                        .unwrap(),
+                );
+            }
+        })
+    });
+    c.bench_function("decode_into 1000件", |b| {
+        let mut out = String::new();
+        b.iter(|| {
+            for code in &codes {
+                out.clear();
+                converter.decode_into(black_box(code), &mut out).unwrap();
+                black_box(&out);
+            }
+        })
+    });
+}
+
+criterion_group!(benches, decode_short, decode_long, decode_many);
+criterion_main!(benches);
@@ -0,0 +1,27 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use pokebell::two_touch_input::Converter;
+
+fn encode_short(c: &mut Criterion) {
+    let converter = Converter::new();
+    c.bench_function("convert_to_two_touch ごくろうさん", |b| {
+        b.iter(|| converter.convert_to_two_touch(black_box("ごくろうさん")))
+    });
+    c.bench_function("convert_to_two_touch_string ごくろうさん", |b| {
+        b.iter(|| converter.convert_to_two_touch_string(black_box("ごくろうさん")))
+    });
+    c.bench_function("convert_to_two_touch_string こんにちは", |b| {
+        b.iter(|| converter.convert_to_two_touch_string(black_box("こんにちは")))
+    });
+}
+
+fn encode_long(c: &mut Criterion) {
+    let converter = Converter::without_reserved_words();
+    // 2KBほどの文字列
+    let text = "こんにちは ABC 1200 ばいばい".repeat(50);
+    c.bench_function("convert_to_two_touch_string 2KB", |b| {
+        b.iter(|| converter.convert_to_two_touch_string(black_box(&text)))
+    });
+}
+
+criterion_group!(benches, encode_short, encode_long);
+criterion_main!(benches);
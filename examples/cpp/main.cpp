@@ -7,8 +7,16 @@ extern "C"
     size_t len;
     const char **data;
   };
+  struct TwoTouchOptions
+  {
+    size_t max_input_chars;
+    size_t max_input_digits;
+  };
   TwoTouchStringResult convert_to_two_touch_string(const char *);
   const char *convert_from_two_touch_string(const char *);
+  TwoTouchOptions two_touch_default_options();
+  TwoTouchStringResult convert_to_two_touch_string_with_options(const char *, const TwoTouchOptions *);
+  const char *convert_from_two_touch_string_with_options(const char *, const TwoTouchOptions *);
 }
 
 int main()
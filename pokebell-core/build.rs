@@ -0,0 +1,199 @@
+//! `data/table.tsv` から2タッチ入力の変換表と正規化の表を作る
+//!
+//! 文字や数字の重複, 変換表にない文字への正規化などはビルドのエラーにする。
+
+use std::collections::HashMap;
+use std::env;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+const TABLE: &str = "data/table.tsv";
+
+/// `U+0020` のように書いた文字か, 1文字をそのまま読む
+fn parse_char(field: &str) -> Option<char> {
+    if let Some(hex) = field.strip_prefix("U+") {
+        return u32::from_str_radix(hex, 16).ok().and_then(char::from_u32);
+    }
+    let mut chars = field.chars();
+    match (chars.next(), chars.next()) {
+        (Some(ch), None) => Some(ch),
+        _ => None,
+    }
+}
+
+/// 全角の英数字と記号は, 対応する半角の文字に正規化しなければならない
+fn halfwidth(ch: char) -> Option<char> {
+    match ch {
+        '\u{FF01}'..='\u{FF5E}' => char::from_u32(ch as u32 - 0xFEE0),
+        _ => None,
+    }
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed={}", TABLE);
+    let source = fs::read_to_string(TABLE).unwrap_or_else(|e| panic!("{}: {}", TABLE, e));
+
+    let mut base: Vec<(char, String)> = Vec::new();
+    let mut normalization: Vec<(char, char)> = Vec::new();
+    let mut chars: HashMap<char, usize> = HashMap::new();
+    let mut codes: HashMap<String, (usize, char)> = HashMap::new();
+    let mut normalized: HashMap<char, usize> = HashMap::new();
+    let mut errors: Vec<String> = Vec::new();
+
+    for (index, line) in source.lines().enumerate() {
+        let number = index + 1;
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut error = |message: String| errors.push(format!("{}:{}: {}", TABLE, number, message));
+        let columns: Vec<&str> = line.split('\t').collect();
+        if columns.len() != 3 {
+            error(format!("3列ではありません: {:?}", line));
+            continue;
+        }
+        let ch = match parse_char(columns[1]) {
+            Some(ch) => ch,
+            None => {
+                error(format!("1文字ではありません: {:?}", columns[1]));
+                continue;
+            }
+        };
+        match columns[0] {
+            "base" => {
+                let code = columns[2];
+                if !(code.len() == 2 || code.len() == 4)
+                    || !code.bytes().all(|b| b.is_ascii_digit())
+                {
+                    error(format!(
+                        "{:?} の数字が2桁か4桁ではありません: {:?}",
+                        ch, code
+                    ));
+                    continue;
+                }
+                if let Some(first) = chars.insert(ch, number) {
+                    error(format!("{:?} は {} 行目にもあります", ch, first));
+                }
+                if let Some((first, other)) = codes.insert(code.to_string(), (number, ch)) {
+                    error(format!(
+                        "{} は {} 行目の {:?} と同じ数字です",
+                        code, first, other
+                    ));
+                }
+                base.push((ch, code.to_string()));
+            }
+            "normalize" => {
+                let to = match parse_char(columns[2]) {
+                    Some(to) => to,
+                    None => {
+                        error(format!("1文字ではありません: {:?}", columns[2]));
+                        continue;
+                    }
+                };
+                if let Some(first) = normalized.insert(ch, number) {
+                    error(format!("{:?} の正規化は {} 行目にもあります", ch, first));
+                }
+                if let Some(expected) = halfwidth(ch) {
+                    if expected != to {
+                        error(format!(
+                            "{:?} は {:?} ではなく {:?} に正規化します",
+                            ch, to, expected
+                        ));
+                    }
+                }
+                normalization.push((ch, to));
+            }
+            kind => error(format!("不明な種類です: {:?}", kind)),
+        }
+    }
+    for &(from, to) in &normalization {
+        if chars.contains_key(&from) {
+            errors.push(format!(
+                "{}: {:?} は変換表にあるので正規化できません",
+                TABLE, from
+            ));
+        }
+        if !chars.contains_key(&to) {
+            errors.push(format!(
+                "{}: {:?} の正規化先 {:?} が変換表にありません",
+                TABLE, from, to
+            ));
+        }
+    }
+    if !errors.is_empty() {
+        panic!("{} に誤りがあります\n{}", TABLE, errors.join("\n"));
+    }
+
+    base.sort_by(|a, b| a.1.cmp(&b.1));
+    normalization.sort_unstable();
+    let (short, composed): (Vec<_>, Vec<_>) = base.iter().partition(|(_, code)| code.len() == 2);
+
+    let mut out = String::new();
+    writeln!(
+        out,
+        "/// 2桁の数字の変換表。文字と数字の組を数字の順に並べたもの"
+    )
+    .unwrap();
+    writeln!(out, "pub const BASE_TABLE: &[(char, &str)] = &[").unwrap();
+    for (ch, code) in &short {
+        writeln!(out, "    ({:?}, {:?}),", ch, code).unwrap();
+    }
+    writeln!(out, "];").unwrap();
+    writeln!(
+        out,
+        "/// 濁音・半濁音など, 2組の数字を合わせた4桁の変換表。数字の順に並べたもの"
+    )
+    .unwrap();
+    writeln!(out, "pub const COMPOSED_TABLE: &[(char, &str)] = &[").unwrap();
+    for (ch, code) in &composed {
+        writeln!(out, "    ({:?}, {:?}),", ch, code).unwrap();
+    }
+    writeln!(out, "];").unwrap();
+    writeln!(
+        out,
+        "/// 正規化の表。置き換える文字と置き換え先の文字の組を, 置き換える文字の順に並べたもの"
+    )
+    .unwrap();
+    writeln!(out, "pub const NORMALIZATION: &[(char, char)] = &[").unwrap();
+    for (from, to) in &normalization {
+        writeln!(out, "    ({:?}, {:?}),", from, to).unwrap();
+    }
+    writeln!(out, "];").unwrap();
+    writeln!(
+        out,
+        "/// `BASE_TABLE` と `COMPOSED_TABLE` で文字の数字を引く"
+    )
+    .unwrap();
+    writeln!(out, "#[cfg(feature = \"encode\")]").unwrap();
+    writeln!(
+        out,
+        "pub fn builtin_code(ch: char) -> Option<&'static str> {{"
+    )
+    .unwrap();
+    writeln!(out, "    match ch {{").unwrap();
+    for (ch, code) in &base {
+        writeln!(out, "        {:?} => Some({:?}),", ch, code).unwrap();
+    }
+    writeln!(out, "        _ => None,").unwrap();
+    writeln!(out, "    }}").unwrap();
+    writeln!(out, "}}").unwrap();
+    writeln!(out, "/// `BASE_TABLE` の逆引き。`[1桁目][2桁目]`").unwrap();
+    writeln!(out, "#[cfg(feature = \"decode\")]").unwrap();
+    writeln!(out, "pub const PAIR_TABLE: [[Option<char>; 10]; 10] = [").unwrap();
+    for first in b'0'..=b'9' {
+        let row: Vec<String> = (b'0'..=b'9')
+            .map(|second| {
+                let code = String::from_utf8(vec![first, second]).unwrap();
+                match short.iter().find(|(_, other)| *other == code) {
+                    Some((ch, _)) => format!("Some({:?})", ch),
+                    None => "None".to_string(),
+                }
+            })
+            .collect();
+        writeln!(out, "    [{}],", row.join(", ")).unwrap();
+    }
+    writeln!(out, "];").unwrap();
+
+    let path = Path::new(&env::var("OUT_DIR").unwrap()).join("tables.rs");
+    fs::write(&path, out).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+}
@@ -0,0 +1,267 @@
+//! ポケベルの2タッチ入力の変換表と, ヒープに確保しない変換
+//!
+//! `#![no_std]` で, アロケータのない環境でも使えます。
+//! 予約語や `Profile` などの設定は扱わず, `pokebell` の `Converter::without_reserved_words()` を
+//! 既定の設定で使った `encode_to_slice` / `decode_to_slice` と同じ結果になります。
+//! エラーも `Copy` な値で, 作るときに確保しません。
+//!
+//! 向きごとに `encode` featureと `decode` feature (どちらもデフォルトで有効) で有効にします。
+//!
+//! ## Example
+//! ```
+//! # #[cfg(all(feature = "encode", feature = "decode"))] {
+//! let mut digits = [0; 8];
+//! assert_eq!(pokebell_core::encode_to_slice("やきにく", &mut digits), Ok(8));
+//! assert_eq!(&digits, b"81225223");
+//! let mut chars = ['\0'; 4];
+//! assert_eq!(pokebell_core::decode_to_slice("81225223", &mut chars), Ok(4));
+//! assert_eq!(chars, ['や', 'き', 'に', 'く']);
+//! # }
+//! ```
+
+#![no_std]
+
+use core::fmt;
+
+pub mod tables;
+
+/// `encode_to_slice` と `decode_to_slice` のエラー。
+/// 同じ名前の `pokebell::two_touch_input::ErrorKind` と同じ場合に返す
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// 2タッチ入力が空
+    EmptyInput,
+    /// 2タッチ入力に変換できない文字
+    UnsupportedCharacter {
+        /// 入力された (正規化前の) 文字
+        ch: char,
+        /// 入力先頭からの文字位置 (0始まり)
+        index: usize,
+    },
+    /// 2タッチ入力の表に存在しないコード
+    UnknownCode {
+        /// 変換できなかった2桁のコード (ASCIIの数字)
+        code: [u8; 2],
+        /// 何組目のコードか (0始まり)
+        pair_index: usize,
+        /// 入力先頭からのバイト位置
+        offset: usize,
+    },
+    /// 2タッチ入力の桁数が奇数
+    OddLength { len: usize },
+    /// 2タッチ入力にASCII以外の文字が含まれている
+    NonAscii { ch: char, offset: usize },
+    /// 2タッチ入力に数字以外の文字がある
+    NonDigit {
+        ch: char,
+        /// 入力先頭からのバイト位置
+        index: usize,
+    },
+    /// 出力先が短い
+    BufferTooSmall {
+        /// 必要な長さ (`encode_to_slice` はバイト数, `decode_to_slice` は文字数)
+        required: usize,
+        /// 渡された出力先の長さ
+        len: usize,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::EmptyInput => write!(f, "empty input"),
+            Error::UnsupportedCharacter { ch, index } => {
+                write!(f, "unsupported character {:?} at index {}", ch, index)
+            }
+            Error::UnknownCode {
+                code,
+                pair_index,
+                offset,
+            } => write!(
+                f,
+                "unknown code \"{}{}\" at pair {} (offset {})",
+                char::from(code[0]),
+                char::from(code[1]),
+                pair_index,
+                offset
+            ),
+            Error::OddLength { len } => write!(f, "odd number of digits ({})", len),
+            Error::NonAscii { ch, offset } => {
+                write!(f, "non-ASCII character {:?} at offset {}", ch, offset)
+            }
+            Error::NonDigit { ch, index } => {
+                write!(f, "non-digit character {:?} at index {}", ch, index)
+            }
+            Error::BufferTooSmall { required, len } => write!(
+                f,
+                "output buffer too small: {} required but {} available",
+                required, len
+            ),
+        }
+    }
+}
+
+/// 英字を大文字にしてから正規化の表を引き, 変換表の数字を返す
+#[cfg(feature = "encode")]
+fn encode_char(ch: char) -> Option<&'static str> {
+    let ch = ch.to_ascii_uppercase();
+    let ch = match tables::NORMALIZATION.binary_search_by_key(&ch, |&(from, _)| from) {
+        Ok(i) => tables::NORMALIZATION[i].1,
+        Err(_) => ch,
+    };
+    tables::builtin_code(ch)
+}
+
+/// 入力を1文字ずつ変換した数字をASCIIの数字として `out` に書き込み, 書き込んだバイト数を返す。
+///
+/// 変換できない文字がある場合はそのエラーを, `out` が短い場合は必要なバイト数を入れた
+/// `Error::BufferTooSmall` を返す。どちらの場合も `out` の内容は決まっていない。
+///
+/// ## Example
+/// ```
+/// # use pokebell_core::*;
+/// let mut out = [0; 8];
+/// assert_eq!(encode_to_slice("ＴＥＬ", &mut out), Ok(6));
+/// assert_eq!(&out[..6], b"401037");
+/// assert_eq!(
+///     encode_to_slice("やきにくたべたい", &mut out),
+///     Err(Error::BufferTooSmall { required: 18, len: 8 })
+/// );
+/// ```
+#[cfg(feature = "encode")]
+pub fn encode_to_slice(val: &str, out: &mut [u8]) -> Result<usize, Error> {
+    let mut required = 0;
+    for (index, ch) in val.chars().enumerate() {
+        let code = encode_char(ch).ok_or(Error::UnsupportedCharacter { ch, index })?;
+        if let Some(dest) = out.get_mut(required..required + code.len()) {
+            dest.copy_from_slice(code.as_bytes());
+        }
+        required += code.len();
+    }
+    if required > out.len() {
+        return Err(Error::BufferTooSmall {
+            required,
+            len: out.len(),
+        });
+    }
+    Ok(required)
+}
+
+/// 2タッチ入力を2桁ずつ戻して `out` に1文字ずつ書き込み, 書き込んだ文字数を返す。
+/// 濁点は `゛` のように1文字として戻す。
+///
+/// 戻せない数字がある場合はそのエラーを, `out` が短い場合は必要な文字数を入れた
+/// `Error::BufferTooSmall` を返す。どちらの場合も `out` の内容は決まっていない。
+///
+/// ## Example
+/// ```
+/// # use pokebell_core::*;
+/// let mut out = ['\0'; 2];
+/// assert_eq!(decode_to_slice("2104", &mut out), Ok(2));
+/// assert_eq!(out, ['か', '゛']);
+/// assert_eq!(decode_to_slice("211", &mut out), Err(Error::OddLength { len: 3 }));
+/// ```
+#[cfg(feature = "decode")]
+pub fn decode_to_slice(code: &str, out: &mut [char]) -> Result<usize, Error> {
+    if code.is_empty() {
+        return Err(Error::EmptyInput);
+    }
+    if let Some((offset, ch)) = code.char_indices().find(|(_, ch)| !ch.is_ascii()) {
+        return Err(Error::NonAscii { ch, offset });
+    }
+    if let Some((index, ch)) = code.char_indices().find(|(_, ch)| !ch.is_ascii_digit()) {
+        return Err(Error::NonDigit { ch, index });
+    }
+    if !code.len().is_multiple_of(2) {
+        return Err(Error::OddLength { len: code.len() });
+    }
+    for (pair_index, pair) in code.as_bytes().chunks_exact(2).enumerate() {
+        let ch = tables::PAIR_TABLE[usize::from(pair[0] - b'0')][usize::from(pair[1] - b'0')]
+            .ok_or(Error::UnknownCode {
+                code: [pair[0], pair[1]],
+                pair_index,
+                offset: pair_index * 2,
+            })?;
+        if let Some(dest) = out.get_mut(pair_index) {
+            *dest = ch;
+        }
+    }
+    let required = code.len() / 2;
+    if required > out.len() {
+        return Err(Error::BufferTooSmall {
+            required,
+            len: out.len(),
+        });
+    }
+    Ok(required)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "encode")]
+    #[test]
+    fn test_encode_to_slice() {
+        // ちょうどの長さ
+        let mut out = [0; 14];
+        assert_eq!(encode_to_slice("ごくろうさん", &mut out), Ok(14));
+        assert_eq!(&out, b"25042395133103");
+        // 全角は半角にそろえる
+        assert_eq!(encode_to_slice("ＴＥＬ", &mut out), Ok(6));
+        assert_eq!(&out[..6], b"401037");
+        assert_eq!(encode_to_slice("", &mut []), Ok(0));
+
+        assert_eq!(
+            encode_to_slice("ごくろうさん", &mut out[..13]),
+            Err(Error::BufferTooSmall {
+                required: 14,
+                len: 13
+            })
+        );
+        // 変換できない場合は, 長さよりそのエラーを返す
+        assert_eq!(
+            encode_to_slice("やき肉", &mut [0; 2]),
+            Err(Error::UnsupportedCharacter {
+                ch: '肉', index: 2
+            })
+        );
+    }
+
+    #[cfg(feature = "decode")]
+    #[test]
+    fn test_decode_to_slice() {
+        // ちょうどの長さ
+        let mut out = ['\0'; 4];
+        assert_eq!(decode_to_slice("81225223", &mut out), Ok(4));
+        assert_eq!(out, ['や', 'き', 'に', 'く']);
+
+        assert_eq!(
+            decode_to_slice("81225223", &mut out[..3]),
+            Err(Error::BufferTooSmall {
+                required: 4,
+                len: 3
+            })
+        );
+        assert_eq!(decode_to_slice("", &mut out), Err(Error::EmptyInput));
+        assert_eq!(
+            decode_to_slice("11１", &mut out),
+            Err(Error::NonAscii {
+                ch: '１',
+                offset: 2
+            })
+        );
+        assert_eq!(
+            decode_to_slice("811a", &mut out),
+            Err(Error::NonDigit { ch: 'a', index: 3 })
+        );
+        assert_eq!(
+            decode_to_slice("0080", &mut []),
+            Err(Error::UnknownCode {
+                code: *b"80",
+                pair_index: 1,
+                offset: 2
+            })
+        );
+    }
+}
@@ -0,0 +1,17 @@
+//! 2タッチ入力の変換表と正規化の表
+//!
+//! build.rs が `data/table.tsv` から作ります。
+//! `pokebell` の `Converter::new()` も同じ表を使います。
+//!
+//! 変換表はどれも数字の順 (1桁目, 2桁目の順) に, 正規化の表は置き換える文字の順 (文字コードの順) に並べます。
+//! この順は変えません。
+//!
+//! ## Example
+//! ```
+//! use pokebell_core::tables::{BASE_TABLE, COMPOSED_TABLE, NORMALIZATION};
+//! assert_eq!(BASE_TABLE[0], ('0', "00"));
+//! assert!(COMPOSED_TABLE.contains(&('が', "2104")));
+//! assert!(NORMALIZATION.contains(&('ぁ', 'あ')));
+//! ```
+
+include!(concat!(env!("OUT_DIR"), "/tables.rs"));
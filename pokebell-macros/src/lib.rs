@@ -0,0 +1,190 @@
+//! ポケベルの2タッチ入力をコンパイル時に変換するマクロ
+//!
+//! 変換には実行時と同じ `pokebell::two_touch_input::Converter` を使うので, 結果が食い違うことはありません。
+//! 予約語は使わず, 1文字ずつ変換します。
+//!
+//! ## Example
+//! ```
+//! use pokebell_macros::{from_two_touch, two_touch};
+//!
+//! const GREETING: &str = two_touch!("おはよう");
+//! assert_eq!(GREETING, "15618513");
+//! assert_eq!(from_two_touch!("81225223"), "やきにく");
+//! ```
+//!
+//! `reserved_words!` で作った辞書は `pokebell::dictionary::StaticDictionary` になり,
+//! `ConverterBuilder::merge_static_dictionary` で重ねられます。
+
+extern crate proc_macro;
+
+use std::collections::HashMap;
+
+use pokebell::two_touch_input::Converter;
+use proc_macro::{Literal, TokenStream, TokenTree};
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{bracketed, parse_macro_input, LitStr, Token};
+
+/// 文字列リテラルを1文字ずつ2タッチ入力の数字に変換した文字列リテラルにする。
+/// 変換できない文字がある場合はコンパイルエラーになる。
+///
+/// ```compile_fail
+/// const CODE: &str = pokebell_macros::two_touch!("焼肉");
+/// ```
+#[proc_macro]
+pub fn two_touch(input: TokenStream) -> TokenStream {
+    let lit = parse_macro_input!(input as LitStr);
+    let mut digits = String::new();
+    match Converter::new().encode_into(&lit.value(), &mut digits) {
+        Ok(_) => string_literal(&digits),
+        Err(e) => compile_error(&lit, &e),
+    }
+}
+
+/// 2タッチ入力の数字の文字列リテラルを, 変換した文字列リテラルにする。
+/// 濁点・半濁点は別の文字になる (`DakutenMode::Raw`)。表にない組などがある場合はコンパイルエラーになる。
+///
+/// ```compile_fail
+/// const TEXT: &str = pokebell_macros::from_two_touch!("8022");
+/// ```
+///
+/// ```compile_fail
+/// const TEXT: &str = pokebell_macros::from_two_touch!("812");
+/// ```
+#[proc_macro]
+pub fn from_two_touch(input: TokenStream) -> TokenStream {
+    let lit = parse_macro_input!(input as LitStr);
+    match Converter::new().convert_from_two_touch_string(lit.value()) {
+        Ok(text) => string_literal(&text),
+        Err(e) => compile_error(&lit, &e),
+    }
+}
+
+/// `"予約語" => ["数字", ...]` の1項目
+struct Entry {
+    phrase: LitStr,
+    bracket: proc_macro2::Span,
+    codes: Vec<LitStr>,
+}
+
+impl Parse for Entry {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let phrase = input.parse()?;
+        input.parse::<Token![=>]>()?;
+        let content;
+        let bracket = bracketed!(content in input).span.join();
+        let codes = Punctuated::<LitStr, Token![,]>::parse_terminated(&content)?;
+        Ok(Entry {
+            phrase,
+            bracket,
+            codes: codes.into_iter().collect(),
+        })
+    }
+}
+
+/// 予約語の辞書を `pokebell::dictionary::StaticDictionary` の定数にする。
+/// 辞書の名前は `StaticDictionary::DEFAULT_NAME` で, `with_name` で変えられる。
+///
+/// 数字が空か数字以外を含む場合, 数字の一覧が空の場合, 同じ予約語が2回ある場合はコンパイルエラーになる。
+///
+/// ## Example
+/// ```
+/// use pokebell::dictionary::{MergeStrategy, StaticDictionary};
+/// use pokebell::two_touch_input::Converter;
+/// use pokebell_macros::reserved_words;
+///
+/// const TEAM: StaticDictionary = reserved_words! {
+///     "なおちゃん" => ["703"],
+///     "おやすみ" => ["833", "0833"],
+/// }
+/// .with_name("team");
+///
+/// let c = Converter::builder()
+///     .merge_static_dictionary(&TEAM, MergeStrategy::Replace)
+///     .build()
+///     .unwrap();
+/// assert_eq!(c.convert_to_two_touch_string("なおちゃん").unwrap()[0], "703");
+/// ```
+///
+/// ```compile_fail
+/// const TEAM: pokebell::dictionary::StaticDictionary = pokebell_macros::reserved_words! {
+///     "よろしく" => ["46a9"],
+/// };
+/// ```
+#[proc_macro]
+pub fn reserved_words(input: TokenStream) -> TokenStream {
+    let entries = parse_macro_input!(input with Punctuated::<Entry, Token![,]>::parse_terminated);
+    let mut phrases: HashMap<String, usize> = HashMap::new();
+    let mut errors: Option<syn::Error> = None;
+    let mut error = |span, message: String| {
+        let e = syn::Error::new(span, message);
+        match &mut errors {
+            Some(errors) => errors.combine(e),
+            None => errors = Some(e),
+        }
+    };
+    for (index, entry) in entries.iter().enumerate() {
+        let phrase = entry.phrase.value();
+        if phrase.is_empty() {
+            error(entry.phrase.span(), "reserved word is empty".to_string());
+        } else if let Some(first) = phrases.insert(phrase.clone(), index) {
+            error(
+                entry.phrase.span(),
+                format!(
+                    "duplicate reserved word {:?} (first defined in entry {})",
+                    phrase, first
+                ),
+            );
+        }
+        if entry.codes.is_empty() {
+            error(entry.bracket, format!("no codes for {:?}", phrase));
+        }
+        for code in &entry.codes {
+            let value = code.value();
+            if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+                error(
+                    code.span(),
+                    format!(
+                        "invalid code {:?} for {:?}: codes must be digits",
+                        value, phrase
+                    ),
+                );
+            }
+        }
+    }
+    if let Some(errors) = errors {
+        // 式の位置で複数の `compile_error!` を並べられるように, ブロックで囲む
+        let errors = errors.to_compile_error();
+        return quote!({ #errors }).into();
+    }
+    let entries = entries.iter().map(|entry| {
+        let phrase = &entry.phrase;
+        let codes = &entry.codes;
+        quote! {
+            ::pokebell::dictionary::StaticEntry {
+                phrase: #phrase,
+                codes: &[#(#codes),*],
+            }
+        }
+    });
+    quote! {
+        {
+            const DICTIONARY: ::pokebell::dictionary::StaticDictionary =
+                ::pokebell::dictionary::StaticDictionary::new(
+                    ::pokebell::dictionary::StaticDictionary::DEFAULT_NAME,
+                    &[#(#entries),*],
+                );
+            DICTIONARY
+        }
+    }
+    .into()
+}
+
+fn string_literal(val: &str) -> TokenStream {
+    TokenTree::Literal(Literal::string(val)).into()
+}
+
+fn compile_error(lit: &LitStr, e: &dyn std::fmt::Display) -> TokenStream {
+    syn::Error::new(lit.span(), e).to_compile_error().into()
+}
@@ -0,0 +1,65 @@
+use pokebell::dictionary::{MergeStrategy, StaticDictionary, StaticEntry};
+use pokebell::two_touch_input::Converter;
+use pokebell_macros::{from_two_touch, reserved_words, two_touch};
+
+const GREETING: &str = two_touch!("おはよう");
+const YAKINIKU: &str = from_two_touch!("81225223");
+
+#[test]
+fn test_same_as_converter() {
+    let c = Converter::new();
+    let mut digits = String::new();
+    c.encode_into("おはよう", &mut digits).unwrap();
+    assert_eq!(GREETING, digits);
+    assert_eq!(two_touch!("ごくろうさん"), "25042395133103");
+    assert_eq!(two_touch!("TEL"), "401037");
+    assert_eq!(
+        YAKINIKU,
+        c.convert_from_two_touch_string("81225223").unwrap()
+    );
+    assert_eq!(from_two_touch!("2104"), "か゛");
+}
+
+const TEAM: StaticDictionary = reserved_words! {
+    "なおちゃん" => ["703"],
+    "おやすみ" => ["833", "0833"],
+};
+
+#[test]
+fn test_reserved_words() {
+    assert_eq!(TEAM.name, StaticDictionary::DEFAULT_NAME);
+    assert_eq!(
+        TEAM.entries,
+        [
+            StaticEntry {
+                phrase: "なおちゃん",
+                codes: &["703"],
+            },
+            StaticEntry {
+                phrase: "おやすみ",
+                codes: &["833", "0833"],
+            },
+        ]
+    );
+    let team = TEAM.with_name("team");
+    let c = Converter::builder()
+        .merge_static_dictionary(&team, MergeStrategy::Replace)
+        .build()
+        .unwrap();
+    assert_eq!(
+        c.convert_to_two_touch_string("おやすみ").unwrap()[..2],
+        ["833", "0833"]
+    );
+    let entry = c
+        .effective_dictionary()
+        .into_iter()
+        .find(|entry| entry.phrase == "なおちゃん")
+        .unwrap();
+    assert_eq!(entry.sources, ["team"]);
+    assert_eq!(reserved_words! {}.entries, []);
+}
+
+#[test]
+fn test_reserved_words_diagnostics() {
+    trybuild::TestCases::new().compile_fail("tests/ui/*.rs");
+}
@@ -0,0 +1,10 @@
+use pokebell::dictionary::StaticDictionary;
+use pokebell_macros::reserved_words;
+
+const TEAM: StaticDictionary = reserved_words! {
+    "よろしく" => ["4649"],
+    "おやすみ" => ["833"],
+    "よろしく" => ["4640"],
+};
+
+fn main() {}
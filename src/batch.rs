@@ -0,0 +1,160 @@
+//! 複数の入力をまとめて変換する
+//!
+//! `parallel` featureを有効にすると [rayon](https://docs.rs/rayon) で並列に変換します。
+//! どちらの場合も結果は入力と同じ順に並びます。
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use super::two_touch_input::{Converter, Error};
+
+impl Converter {
+    /// 複数の入力をそれぞれ `convert_to_two_touch_string` で変換する。
+    /// 変換に失敗した入力も含めて, 結果は入力と同じ順に並ぶ。
+    /// `parallel` featureの有無でシグネチャが変わらないように, 入力には常に `Sync` を求める。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::Converter;
+    /// let c = Converter::new();
+    /// let results = c.convert_many(vec!["やきにく", "筋肉"]);
+    /// assert!(results[1].is_err());
+    /// ```
+    pub fn convert_many<I, S>(&self, msgs: I) -> Vec<Result<Vec<String>, Error>>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str> + Sync,
+    {
+        #[cfg(not(feature = "parallel"))]
+        {
+            msgs.into_iter()
+                .map(|msg| self.convert_to_two_touch_string(msg))
+                .collect()
+        }
+        #[cfg(feature = "parallel")]
+        {
+            let msgs: Vec<S> = msgs.into_iter().collect();
+            msgs.par_iter()
+                .map(|msg| self.convert_to_two_touch_string(msg))
+                .collect()
+        }
+    }
+
+    /// 複数の2タッチ入力をそれぞれ `convert_from_two_touch_string` で変換する。
+    /// 変換に失敗した入力も含めて, 結果は入力と同じ順に並ぶ。
+    /// `convert_many` と同じく, 入力には常に `Sync` を求める。
+    pub fn decode_many<I, S>(&self, codes: I) -> Vec<Result<String, Error>>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str> + Sync,
+    {
+        #[cfg(not(feature = "parallel"))]
+        {
+            codes
+                .into_iter()
+                .map(|code| self.convert_from_two_touch_string(code))
+                .collect()
+        }
+        #[cfg(feature = "parallel")]
+        {
+            let codes: Vec<S> = codes.into_iter().collect();
+            codes
+                .par_iter()
+                .map(|code| self.convert_from_two_touch_string(code))
+                .collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::two_touch_input::ErrorKind;
+
+    // `parallel` featureの有無にかかわらず, 1つずつ変換した結果と同じになる
+    #[test]
+    fn test_convert_many() {
+        let c = Converter::new();
+        let msgs: Vec<String> = (0..200)
+            .map(|i| match i % 4 {
+                0 => "ごくろうさん".to_string(),
+                1 => "筋肉".to_string(),
+                2 => format!("あした{}じ", i),
+                _ => String::new(),
+            })
+            .collect();
+        let results = c.convert_many(&msgs);
+        assert_eq!(results.len(), msgs.len());
+        for (msg, result) in msgs.iter().zip(results) {
+            let expected = c.convert_to_two_touch_string(msg);
+            match (result, expected) {
+                (Ok(result), Ok(expected)) => assert_eq!(result, expected),
+                (Err(result), Err(expected)) => assert_eq!(result.kind(), expected.kind()),
+                _ => panic!("mismatch for {:?}", msg),
+            }
+        }
+    }
+
+    // `parallel` featureの有無にかかわらず同じ値と比べ, 失敗した入力の位置とエラーも確かめる
+    #[test]
+    fn test_convert_many_fixed() {
+        let c = Converter::without_reserved_words();
+        let msgs = ["やきにく", "筋肉", "", "あいしてる", "やき肉"];
+        let expected = vec![
+            Ok(vec!["81225223".to_string()]),
+            Err(ErrorKind::UnsupportedCharacter {
+                ch: '筋', index: 0
+            }),
+            Err(ErrorKind::EmptyInput),
+            Ok(vec!["1112324493".to_string()]),
+            Err(ErrorKind::UnsupportedCharacter {
+                ch: '肉', index: 2
+            }),
+        ];
+        // 並列に変換しても順番がずれないように, 何回も繰り返して並べる
+        let results: Vec<_> = c
+            .convert_many(msgs.iter().cycle().take(msgs.len() * 100))
+            .into_iter()
+            .map(|result| result.map_err(|err| err.kind().clone()))
+            .collect();
+        assert_eq!(results.len(), msgs.len() * 100);
+        for chunk in results.chunks(msgs.len()) {
+            assert_eq!(chunk, expected.as_slice());
+        }
+
+        let codes = ["81225223", "111", "8080", "", "11筋"];
+        let expected = vec![
+            Ok("やきにく".to_string()),
+            Err(ErrorKind::OddLength { len: 3 }),
+            Err(ErrorKind::UnknownCode {
+                code: "80".to_string(),
+                pair_index: 0,
+                offset: 0,
+            }),
+            Err(ErrorKind::EmptyInput),
+            Err(ErrorKind::NonAscii {
+                ch: '筋',
+                offset: 2,
+            }),
+        ];
+        let results: Vec<_> = c
+            .decode_many(codes.iter().cycle().take(codes.len() * 100))
+            .into_iter()
+            .map(|result| result.map_err(|err| err.kind().clone()))
+            .collect();
+        for chunk in results.chunks(codes.len()) {
+            assert_eq!(chunk, expected.as_slice());
+        }
+    }
+
+    #[test]
+    fn test_decode_many() {
+        let c = Converter::new();
+        let codes = ["81225223", "8080", "", "250459868884"];
+        let results = c.decode_many(codes.iter());
+        assert_eq!(results[0].as_ref().unwrap(), "やきにく");
+        assert!(results[1].is_err());
+        assert!(results[2].is_err());
+        assert_eq!(results[3].as_ref().unwrap(), "こ゛X* )");
+    }
+}
@@ -1,10 +1,21 @@
+#[cfg(any(feature = "encode", feature = "decode"))]
 use std::ffi::{CStr, CString};
+#[cfg(any(feature = "encode", feature = "decode"))]
 use std::ptr;
 
-use lazy_static::lazy_static;
 use libc::{c_char, size_t};
 
-use super::two_touch_input::Converter;
+#[cfg(any(feature = "encode", feature = "decode"))]
+use super::two_touch_input::shared_converter;
+#[cfg(feature = "encode")]
+use super::two_touch_input::ConvertOptions;
+#[cfg(feature = "decode")]
+use super::two_touch_input::DecodeOptions;
+
+/// C言語から呼ぶ場合の入力の最大文字数の既定値
+pub const DEFAULT_MAX_INPUT_CHARS: usize = 4096;
+/// C言語から呼ぶ場合の入力の最大桁数の既定値
+pub const DEFAULT_MAX_INPUT_DIGITS: usize = 4 * DEFAULT_MAX_INPUT_CHARS;
 
 #[repr(C)]
 pub struct TwoTouchStringResult {
@@ -12,9 +23,64 @@ pub struct TwoTouchStringResult {
     data: *const *const c_char,
 }
 
+/// C言語から呼ぶ場合の変換の設定
+///
+/// 値が0の項目は制限しない。`two_touch_default_options` で既定値を取得できる。
+#[repr(C)]
+pub struct TwoTouchOptions {
+    /// 2タッチ入力に変換する入力の最大文字数
+    pub max_input_chars: size_t,
+    /// 2タッチ入力から変換する入力の最大桁数
+    pub max_input_digits: size_t,
+}
+
+impl Default for TwoTouchOptions {
+    fn default() -> Self {
+        TwoTouchOptions {
+            max_input_chars: DEFAULT_MAX_INPUT_CHARS,
+            max_input_digits: DEFAULT_MAX_INPUT_DIGITS,
+        }
+    }
+}
+
+#[cfg(any(feature = "encode", feature = "decode"))]
+fn limit(value: size_t) -> Option<usize> {
+    if value == 0 {
+        None
+    } else {
+        Some(value)
+    }
+}
+
+/// 既定の設定を返す
+#[no_mangle]
+pub extern "C" fn two_touch_default_options() -> TwoTouchOptions {
+    TwoTouchOptions::default()
+}
+
+/// `two_touch_default_options` の設定で `convert_to_two_touch_string_with_options` を呼ぶ。
+/// `val` はNUL終端された有効な文字列へのポインタを渡す。
+///
+/// Rustからも呼べるように `unsafe` にはしない。
+#[cfg(feature = "encode")]
 #[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 pub extern "C" fn convert_to_two_touch_string(val: *const c_char) -> TwoTouchStringResult {
-    let c_str = unsafe { CStr::from_ptr(val) };
+    unsafe { convert_to_two_touch_string_with_options(val, ptr::null()) }
+}
+
+/// # Safety
+///
+/// `val` はNUL終端された有効な文字列へのポインタでなければならない。
+/// `options` はNULLか, 有効な `TwoTouchOptions` へのポインタでなければならない。
+/// NULLの場合は既定の設定を使う。
+#[cfg(feature = "encode")]
+#[no_mangle]
+pub unsafe extern "C" fn convert_to_two_touch_string_with_options(
+    val: *const c_char,
+    options: *const TwoTouchOptions,
+) -> TwoTouchStringResult {
+    let c_str = CStr::from_ptr(val);
     let s = match c_str.to_str() {
         Ok(s) => s,
         Err(_) => {
@@ -24,7 +90,12 @@ pub extern "C" fn convert_to_two_touch_string(val: *const c_char) -> TwoTouchStr
             }
         }
     };
-    let results = CONVERTER.convert_to_two_touch_string(s.to_string());
+    let max_input_chars = match options.as_ref() {
+        Some(options) => options.max_input_chars,
+        None => DEFAULT_MAX_INPUT_CHARS,
+    };
+    let options = ConvertOptions::default().with_max_input_chars(limit(max_input_chars));
+    let results = shared_converter().convert_to_two_touch_string_with(s, &options);
     let results = match results {
         Ok(r) => r,
         Err(_) => {
@@ -49,20 +120,45 @@ pub extern "C" fn convert_to_two_touch_string(val: *const c_char) -> TwoTouchStr
     }
     let two_touch_string_result = TwoTouchStringResult {
         len: data.len(),
-        data: data.as_ptr() as *const *const c_char,
+        data: data.as_ptr(),
     };
     std::mem::forget(data);
     two_touch_string_result
 }
 
+/// `two_touch_default_options` の設定で `convert_from_two_touch_string_with_options` を呼ぶ。
+/// `val` はNUL終端された有効な文字列へのポインタを渡す。
+///
+/// Rustからも呼べるように `unsafe` にはしない。
+#[cfg(feature = "decode")]
 #[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 pub extern "C" fn convert_from_two_touch_string(val: *const c_char) -> *const c_char {
-    let c_str = unsafe { CStr::from_ptr(val) };
+    unsafe { convert_from_two_touch_string_with_options(val, ptr::null()) }
+}
+
+/// # Safety
+///
+/// `val` はNUL終端された有効な文字列へのポインタでなければならない。
+/// `options` はNULLか, 有効な `TwoTouchOptions` へのポインタでなければならない。
+/// NULLの場合は既定の設定を使う。
+#[cfg(feature = "decode")]
+#[no_mangle]
+pub unsafe extern "C" fn convert_from_two_touch_string_with_options(
+    val: *const c_char,
+    options: *const TwoTouchOptions,
+) -> *const c_char {
+    let c_str = CStr::from_ptr(val);
     let s = match c_str.to_str() {
         Ok(s) => s,
         Err(_) => return ptr::null(),
     };
-    let result = CONVERTER.convert_from_two_touch_string(s.to_string());
+    let max_input_digits = match options.as_ref() {
+        Some(options) => options.max_input_digits,
+        None => DEFAULT_MAX_INPUT_DIGITS,
+    };
+    let options = DecodeOptions::default().with_max_input_digits(limit(max_input_digits));
+    let result = shared_converter().convert_from_two_touch_string_with(s, &options);
     let result = match result {
         Ok(r) => r,
         Err(_) => return ptr::null(),
@@ -74,6 +170,32 @@ pub extern "C" fn convert_from_two_touch_string(val: *const c_char) -> *const c_
     result.into_raw()
 }
 
-lazy_static! {
-    static ref CONVERTER: Converter = Converter::new();
+#[cfg(all(test, feature = "encode", feature = "decode"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_input_length_limits() {
+        let at_limit = CString::new("あ".repeat(DEFAULT_MAX_INPUT_CHARS)).unwrap();
+        let over_limit = CString::new("あ".repeat(DEFAULT_MAX_INPUT_CHARS + 1)).unwrap();
+        let digits = CString::new("11".repeat(DEFAULT_MAX_INPUT_DIGITS / 2 + 1)).unwrap();
+        let options = TwoTouchOptions {
+            max_input_chars: 0,
+            max_input_digits: 0,
+        };
+        unsafe {
+            assert_eq!(convert_to_two_touch_string(at_limit.as_ptr()).len, 1);
+            assert!(convert_to_two_touch_string(over_limit.as_ptr())
+                .data
+                .is_null());
+            assert_eq!(
+                convert_to_two_touch_string_with_options(over_limit.as_ptr(), &options).len,
+                1
+            );
+            assert!(convert_from_two_touch_string(digits.as_ptr()).is_null());
+            assert!(
+                !convert_from_two_touch_string_with_options(digits.as_ptr(), &options).is_null()
+            );
+        }
+    }
 }
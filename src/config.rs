@@ -0,0 +1,260 @@
+//! 変えたConverterの設定を保存し, 同じConverterを作り直す
+//!
+//! `serde` featureで有効になります。`ConverterConfig` は既定の表や辞書との違いだけを持つので,
+//! 利用者が追加した予約語や文字の対応を保存するのに使えます。
+//! 知らない項目は読み飛ばし, ない項目は既定値にするので, 版の違う設定も読み込めます。
+//!
+//! ```json
+//! {"reserved_words": [{"phrase": "なおちゃん", "codes": ["703"]}], "removed_reserved_words": ["TEL"]}
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::two_touch_input::{
+    BuildError, CharacterCode, ConvertOptions, Converter, ConverterBuilder, Entry, Normalization,
+    Profile,
+};
+
+/// `Converter::to_config` で書き出す設定
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConverterConfig {
+    /// 変換先のポケベルで表示できる文字の範囲
+    pub profile: Profile,
+    /// `convert_to_two_touch_string` などで使う設定
+    pub options: ConvertOptions,
+    /// 既定の変換表と違う文字。`ConverterBuilder::override_char` で入れる
+    pub chars: Vec<CharacterCode>,
+    /// 既定の変換表から取り除いた文字
+    pub removed_chars: Vec<char>,
+    /// 既定の正規化の表と違う置き換え。`ConverterBuilder::add_normalization` で入れる
+    pub normalizations: Vec<Normalization>,
+    /// 1文字を複数の文字にする置き換え。`ConverterBuilder::add_normalization_str` で入れる
+    pub expansions: Vec<Expansion>,
+    /// 既定の辞書にないか, 既定の辞書と違う予約語
+    pub reserved_words: Vec<Entry>,
+    /// 既定の辞書から取り除いた予約語
+    pub removed_reserved_words: Vec<String>,
+}
+
+/// `from` を変換の前に複数の文字 `to` に置き換える
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Expansion {
+    pub from: char,
+    pub to: String,
+}
+
+impl ConverterConfig {
+    /// 変換表, 正規化の表と既定の辞書を入れた `ConverterBuilder` を返す。
+    /// 既定の辞書の数字を `profile` で減らさないように, `profile` は作った後で設定する
+    fn table_builder(&self) -> ConverterBuilder {
+        let mut builder = Converter::builder();
+        for entry in &self.chars {
+            builder = builder.override_char(entry.ch, &entry.code);
+        }
+        for &ch in &self.removed_chars {
+            builder = builder.remove_char(ch);
+        }
+        for normalization in &self.normalizations {
+            builder = builder.add_normalization(normalization.from, normalization.to);
+        }
+        for expansion in &self.expansions {
+            builder = builder.add_normalization_str(expansion.from, &expansion.to);
+        }
+        builder
+    }
+}
+
+impl Converter {
+    /// `to_config` で書き出した設定からConverterを作る
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::config::ConverterConfig;
+    /// # use pokebell::two_touch_input::*;
+    /// let mut c = Converter::new();
+    /// c.add_reserved_word("なおちゃん", &["703"]).unwrap();
+    /// let json = serde_json::to_string(&c.to_config()).unwrap();
+    /// let config: ConverterConfig = serde_json::from_str(&json).unwrap();
+    /// let restored = Converter::from_config(&config).unwrap();
+    /// assert_eq!(
+    ///     restored.convert_to_two_touch_string("なおちゃん").unwrap(),
+    ///     c.convert_to_two_touch_string("なおちゃん").unwrap()
+    /// );
+    /// ```
+    pub fn from_config(config: &ConverterConfig) -> Result<Converter, BuildError> {
+        let mut builder = config.table_builder();
+        for phrase in &config.removed_reserved_words {
+            builder = builder.remove_reserved_word(phrase);
+        }
+        for entry in &config.reserved_words {
+            builder = builder.add_reserved_word(&entry.phrase, &entry.codes);
+        }
+        builder
+            .build()?
+            .with_profile(config.profile.clone())
+            .with_convert_options(config.options.clone())
+            .map_err(|err| BuildError::InvalidOptions {
+                message: err.to_string(),
+            })
+    }
+
+    /// `ConverterBuilder` や `add_reserved_word` などで変えた内容を, 既定の表や辞書との違いとして書き出す。
+    /// 項目はそれぞれ文字や予約語の順に並べる。
+    /// 予約語を追加した辞書の名前 (`Converter::effective_dictionary` の `sources`) は書き出さない。
+    pub fn to_config(&self) -> ConverterConfig {
+        let default = Converter::without_reserved_words();
+        let default_chars: HashMap<char, &str> =
+            default.table().chain(default.composed_table()).collect();
+        let chars: HashMap<char, &str> = self.table().chain(self.composed_table()).collect();
+        let mut config = ConverterConfig {
+            profile: self.profile().clone(),
+            options: self.convert_options().clone(),
+            ..ConverterConfig::default()
+        };
+
+        for (&ch, &code) in &chars {
+            if default_chars.get(&ch) != Some(&code) {
+                config.chars.push(CharacterCode {
+                    ch,
+                    code: code.to_string(),
+                });
+            }
+        }
+        config.chars.sort_by_key(|entry| entry.ch);
+        config.removed_chars = default_chars
+            .keys()
+            .filter(|ch| !chars.contains_key(ch))
+            .copied()
+            .collect();
+        config.removed_chars.sort_unstable();
+
+        let default_normalizations: HashMap<char, char> = default.normalizations().collect();
+        config.normalizations = self
+            .normalizations()
+            .filter(|(from, to)| default_normalizations.get(from) != Some(to))
+            .map(|(from, to)| Normalization { from, to })
+            .collect();
+        config.expansions = self
+            .expansions()
+            .into_iter()
+            .map(|(from, to)| Expansion {
+                from,
+                to: to.to_string(),
+            })
+            .collect();
+
+        // 同じ変換表で作った既定の辞書と比べる
+        let baseline = match config.table_builder().build() {
+            Ok(baseline) => baseline,
+            Err(_) => return config,
+        };
+        config.reserved_words = self
+            .reserved_phrases()
+            .filter(|&(phrase, codes)| baseline.reserved_entry(phrase) != Some((phrase, codes)))
+            .map(|(phrase, codes)| Entry {
+                phrase: phrase.to_string(),
+                codes: codes.iter().map(|code| code.to_string()).collect(),
+            })
+            .collect();
+        config.removed_reserved_words = baseline
+            .reserved_phrases()
+            .filter(|(phrase, _)| self.reserved_entry(phrase).is_none())
+            .map(|(phrase, _)| phrase.to_string())
+            .collect();
+        config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::two_touch_input::GroupingOptions;
+    use std::num::NonZeroUsize;
+
+    fn customized() -> Converter {
+        let builder = Converter::builder()
+            .override_char('ゐ', "02")
+            .add_char_mapping('☆', "9090")
+            .add_normalization('ｱ', 'あ')
+            .add_normalization('、', ' ')
+            .add_normalization_str('㈱', "(かふ)")
+            .add_reserved_word("なおちゃん", &["703"])
+            .add_reserved_word("おやすみ", &["833", "0833"]);
+        #[cfg(feature = "builtin-dictionary")]
+        let builder = builder
+            .remove_reserved_word("TEL")
+            .add_reserved_word("よろしく", &["4640"]);
+        let mut c = builder.build().unwrap();
+        c.add_reserved_word("すし", &["44"]).unwrap();
+        let grouping = GroupingOptions::default().with_group_every(NonZeroUsize::new(4));
+        c.with_grouping_options(grouping).unwrap()
+    }
+
+    #[test]
+    fn test_config_round_trip() {
+        let c = customized();
+        let config = c.to_config();
+        assert!(config.chars.contains(&CharacterCode {
+            ch: '☆',
+            code: "9090".to_string()
+        }));
+        assert_eq!(config.removed_chars, ['を']);
+        assert_eq!(config.expansions.len(), 1);
+        let json = serde_json::to_string(&config).unwrap();
+        let restored = Converter::from_config(&serde_json::from_str(&json).unwrap()).unwrap();
+        assert_eq!(restored.to_config(), config);
+
+        let corpus = [
+            "なおちゃん",
+            "おやすみ",
+            "よろしく",
+            "TELして",
+            "すしたべたい",
+            "ゐをアｱ",
+            "☆㈱、あ",
+            "ごくろうさん",
+            "こんにちは 12:00",
+        ];
+        for text in corpus {
+            assert_eq!(
+                restored.convert_to_two_touch_string(text),
+                c.convert_to_two_touch_string(text),
+                "{}",
+                text
+            );
+        }
+        for digits in ["02909011", "4640", "7033"] {
+            assert_eq!(
+                restored.convert_from_two_touch_string(digits),
+                c.convert_from_two_touch_string(digits),
+                "{}",
+                digits
+            );
+        }
+    }
+
+    #[test]
+    fn test_default_config() {
+        let config = Converter::new().to_config();
+        assert_eq!(config, ConverterConfig::default());
+        assert_eq!(
+            serde_json::from_str::<ConverterConfig>("{}").unwrap(),
+            config
+        );
+        let config: ConverterConfig = serde_json::from_str(
+            r#"{"reserved_words": [{"phrase": "なおちゃん", "codes": ["703"]}], "theme": "dark"}"#,
+        )
+        .unwrap();
+        assert_eq!(config.reserved_words.len(), 1);
+        let config: ConverterConfig = serde_json::from_str(
+            r#"{"options": {"lenient": {"skip_unsupported": true, "fallback": "?"}}}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            Converter::from_config(&config),
+            Err(BuildError::InvalidOptions { .. })
+        ));
+    }
+}
@@ -0,0 +1,198 @@
+//! C言語などから使うライブラリのテストのための, 変換の入力と期待する結果の一覧
+//!
+//! `conformance` featureで有効になります。一覧 (`conformance.json`) は変換表と既定の辞書から `generate` で作ったもので,
+//! このクレートのテストで `generate` の結果と比べます。変換表や辞書を変えた場合は
+//! `UPDATE_GOLDEN=1 cargo test --features conformance` で作り直し, 差分を確かめてください。
+//!
+//! 期待する結果は既定のfeatureで作った `pokebell::encode` と `pokebell::decode` (C言語の関数と同じ) のものです。
+
+use serde::{Deserialize, Serialize};
+use std::io::{self, Write};
+
+use super::two_touch_input::{shared_converter, Error};
+
+/// `TestVectors` の形式の版。項目の形や意味を変えたら上げる
+pub const TEST_VECTORS_VERSION: u32 = 1;
+
+/// 埋め込んだテストの一覧のJSON
+const TEST_VECTORS: &str = include_str!("conformance.json");
+
+/// 変換の入力と期待する結果の一覧
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TestVectors {
+    /// 形式の版 (`TEST_VECTORS_VERSION`)
+    pub version: u32,
+    /// `encode` が成功する入力
+    pub encode: Vec<EncodeVector>,
+    /// `decode` が成功する入力
+    pub decode: Vec<DecodeVector>,
+    /// エラーになる入力
+    pub errors: Vec<ErrorVector>,
+}
+
+/// `encode(input)` の候補が `expected` になる
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EncodeVector {
+    pub input: String,
+    pub expected: Vec<String>,
+}
+
+/// `decode(code)` が `expected` になる
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DecodeVector {
+    pub code: String,
+    pub expected: String,
+}
+
+/// `operation` の `input` がエラーになる
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorVector {
+    pub operation: Operation,
+    pub input: String,
+    /// `ErrorKind` をJSONにしたときの `kind` (`unsupported_character` など)
+    pub kind: String,
+    /// エラーの `Display`
+    pub message: String,
+}
+
+/// テストする関数
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Operation {
+    Encode,
+    Decode,
+}
+
+/// 文字や予約語のほかに確かめる, 2タッチ入力に変換する入力
+const ENCODE_SAMPLES: &[&str] = &[
+    "こんにちは",
+    "やきにく",
+    "ごくろうさん",
+    "ＴＥＬ",
+    "{0840}おはよう",
+    "こんにちは筋肉",
+    "",
+];
+
+/// 文字の数字のほかに確かめる, 2タッチ入力から変換する入力
+const DECODE_SAMPLES: &[&str] = &["81225223", "2503524261", "", "123", "1a", "1100", "あ"];
+
+/// 埋め込んだテストの一覧を返す
+pub fn vectors() -> TestVectors {
+    serde_json::from_str(TEST_VECTORS).expect("conformance.json")
+}
+
+/// 変換表, 既定の辞書と決まった入力から, 今の変換の結果でテストの一覧を作る。
+/// 変換表の文字は数字の順, 予約語は予約語の順に並べ, 続けて決まった入力を並べる
+pub fn generate() -> TestVectors {
+    let converter = shared_converter();
+    let mut vectors = TestVectors {
+        version: TEST_VECTORS_VERSION,
+        encode: Vec::new(),
+        decode: Vec::new(),
+        errors: Vec::new(),
+    };
+    let table: Vec<(char, &str)> = converter
+        .table()
+        .chain(converter.composed_table())
+        .collect();
+
+    let mut inputs: Vec<String> = table.iter().map(|(ch, _)| ch.to_string()).collect();
+    inputs.extend(
+        converter
+            .reserved_phrases()
+            .map(|(phrase, _)| phrase.to_string()),
+    );
+    inputs.extend(ENCODE_SAMPLES.iter().map(|input| input.to_string()));
+    for input in inputs {
+        match crate::encode(&input) {
+            Ok(expected) => vectors.encode.push(EncodeVector { input, expected }),
+            Err(err) => vectors
+                .errors
+                .push(error_vector(Operation::Encode, input, &err)),
+        }
+    }
+
+    let mut codes: Vec<String> = table.iter().map(|(_, code)| code.to_string()).collect();
+    codes.extend(DECODE_SAMPLES.iter().map(|code| code.to_string()));
+    for code in codes {
+        match crate::decode(&code) {
+            Ok(expected) => vectors.decode.push(DecodeVector { code, expected }),
+            Err(err) => vectors
+                .errors
+                .push(error_vector(Operation::Decode, code, &err)),
+        }
+    }
+    vectors
+}
+
+fn error_vector(operation: Operation, input: String, err: &Error) -> ErrorVector {
+    let kind = serde_json::to_value(err.kind())
+        .ok()
+        .and_then(|value| value["kind"].as_str().map(|kind| kind.to_string()))
+        .unwrap_or_default();
+    ErrorVector {
+        operation,
+        input,
+        kind,
+        message: err.to_string(),
+    }
+}
+
+/// 埋め込んだテストの一覧をJSONで `w` に書き出す
+///
+/// ## Example
+/// ```
+/// let mut out = Vec::new();
+/// pokebell::conformance::export_json(&mut out).unwrap();
+/// let vectors: pokebell::conformance::TestVectors = serde_json::from_slice(&out).unwrap();
+/// assert!(vectors.encode.iter().any(|vector| vector.input == "や" && vector.expected == ["81"]));
+/// ```
+pub fn export_json<W: Write>(mut w: W) -> io::Result<()> {
+    w.write_all(TEST_VECTORS.as_bytes())
+}
+
+// 一覧は既定のfeatureでの結果なので, 辞書を変えるfeatureでは比べない
+#[cfg(all(
+    test,
+    feature = "builtin-dictionary",
+    not(feature = "dict-stations"),
+    not(feature = "dict-dates")
+))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vectors_up_to_date() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/src/conformance.json");
+        let json = serde_json::to_string_pretty(&generate()).unwrap() + "\n";
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            std::fs::write(path, &json).unwrap();
+        }
+        assert_eq!(json, TEST_VECTORS);
+    }
+
+    #[test]
+    fn test_vectors() {
+        let vectors = vectors();
+        assert_eq!(vectors.version, TEST_VECTORS_VERSION);
+        assert!(vectors.encode.len() > 200);
+        for vector in &vectors.encode {
+            assert_eq!(crate::encode(&vector.input).unwrap(), vector.expected);
+        }
+        for vector in &vectors.decode {
+            assert_eq!(crate::decode(&vector.code).unwrap(), vector.expected);
+        }
+        for vector in &vectors.errors {
+            let err = match vector.operation {
+                Operation::Encode => crate::encode(&vector.input).unwrap_err(),
+                Operation::Decode => crate::decode(&vector.input).unwrap_err(),
+            };
+            assert_eq!(err.to_string(), vector.message, "{}", vector.input);
+        }
+        assert!(vectors
+            .errors
+            .iter()
+            .any(|vector| vector.kind == "unsupported_character"));
+    }
+}
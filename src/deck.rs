@@ -0,0 +1,164 @@
+//! 変換表と予約語を, 暗記カード用のタブ区切りの表 (Ankiで読み込める形式) で書き出す
+//!
+//! 1行が1枚のカードで, 1列目が表, 2列目が裏です。先頭の `#` の行はAnkiに区切り文字を伝える見出しです。
+//! 列の中のタブ, 改行と `\` は `\t`, `\n`, `\\` のように書きます。
+
+use std::io::{self, Write};
+
+use super::two_touch_input::Converter;
+
+/// `Converter::export_study_deck` の設定
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct DeckOptions {
+    /// かな, 英字と数字以外の記号の文字のカードも書き出す (既定は書き出す)
+    pub include_symbols: bool,
+    /// 表に数字, 裏に文字や予約語を書く (既定は表が文字や予約語)
+    pub swap_sides: bool,
+}
+
+impl Default for DeckOptions {
+    fn default() -> Self {
+        DeckOptions {
+            include_symbols: true,
+            swap_sides: false,
+        }
+    }
+}
+
+impl DeckOptions {
+    /// 記号の文字のカードを書き出すか設定する
+    pub fn with_include_symbols(mut self, include_symbols: bool) -> Self {
+        self.include_symbols = include_symbols;
+        self
+    }
+
+    /// 表と裏を入れ替えるか設定する
+    pub fn with_swap_sides(mut self, swap_sides: bool) -> Self {
+        self.swap_sides = swap_sides;
+        self
+    }
+}
+
+/// タブ区切りの表の1列として書けるように, タブ, 改行と `\` を書き換える
+fn escape_field(field: &str) -> String {
+    let mut escaped = String::with_capacity(field.len());
+    for ch in field.chars() {
+        match ch {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+/// 文字のカードの表。空白はカードに表示されないので `空白` と書く
+fn char_label(ch: char) -> String {
+    match ch {
+        ' ' => "空白".to_string(),
+        _ => ch.to_string(),
+    }
+}
+
+impl Converter {
+    /// 2桁の変換表の文字と予約語をカードにして `w` に書き出す。
+    /// 文字は数字の順, 予約語は予約語の順に並べる。
+    /// 予約語の裏は数字を優先する順に `, ` で区切り, 語呂合わせの由来があれば `(4よ 6ろ 4し 9く)` のように続ける。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::deck::DeckOptions;
+    /// # use pokebell::two_touch_input::*;
+    /// let mut c = Converter::without_reserved_words();
+    /// c.add_reserved_word("なおちゃん", &["703"]).unwrap();
+    /// let mut out = Vec::new();
+    /// c.export_study_deck(&DeckOptions::default(), &mut out).unwrap();
+    /// let deck = String::from_utf8(out).unwrap();
+    /// assert!(deck.contains("\nあ\t11\n"));
+    /// assert!(deck.ends_with("\nなおちゃん\t703\n"));
+    /// ```
+    pub fn export_study_deck<W: Write>(&self, options: &DeckOptions, mut w: W) -> io::Result<()> {
+        writeln!(w, "#separator:tab")?;
+        writeln!(w, "#html:false")?;
+        let mut card = |front: &str, back: &str| {
+            let (front, back) = if options.swap_sides {
+                (back, front)
+            } else {
+                (front, back)
+            };
+            writeln!(w, "{}\t{}", escape_field(front), escape_field(back))
+        };
+        for (ch, code) in self.table() {
+            if !options.include_symbols && !ch.is_alphanumeric() {
+                continue;
+            }
+            card(&char_label(ch), code)?;
+        }
+        for (phrase, codes) in self.reserved_phrases() {
+            let mut back = codes.join(", ");
+            let derivation = codes.first().and_then(|code| {
+                self.explain_reserved(code)
+                    .into_iter()
+                    .find(|info| info.phrase == phrase)
+                    .map(|info| info.derivation)
+            });
+            if let Some(derivation) = derivation.filter(|derivation| !derivation.is_empty()) {
+                let puns: Vec<String> = derivation
+                    .iter()
+                    .map(|pun| format!("{}{}", pun.digits, pun.reading))
+                    .collect();
+                back.push_str(&format!(" ({})", puns.join(" ")));
+            }
+            card(phrase, &back)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn export(c: &Converter, options: &DeckOptions) -> Vec<String> {
+        let mut out = Vec::new();
+        c.export_study_deck(options, &mut out).unwrap();
+        String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .skip(2)
+            .map(|line| line.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_export_study_deck() {
+        let mut c = Converter::new();
+        c.add_reserved_word("a\tb", &["16"]).unwrap();
+        let rows = export(&c, &DeckOptions::default());
+        assert_eq!(rows.len(), c.table().count() + c.reserved_len());
+        assert!(rows.iter().all(|row| row.split('\t').count() == 2));
+        assert!(rows.contains(&"空白\t88".to_string()));
+        assert!(rows.contains(&"#\t87".to_string()));
+        assert!(rows.contains(&"a\\tb\t16".to_string()));
+
+        let symbols = c.table().filter(|(ch, _)| !ch.is_alphanumeric()).count();
+        let options = DeckOptions::default()
+            .with_include_symbols(false)
+            .with_swap_sides(true);
+        let rows = export(&c, &options);
+        assert_eq!(rows.len(), c.table().count() - symbols + c.reserved_len());
+        assert_eq!(rows[0], "00\t0");
+        assert!(!rows.iter().any(|row| row.ends_with("\t?")));
+    }
+
+    #[cfg(feature = "builtin-dictionary")]
+    #[test]
+    fn test_export_study_deck_reserved() {
+        let rows = export(&Converter::new(), &DeckOptions::default());
+        assert!(rows.contains(&"愛してる\t14106, 114106, 1410".to_string()));
+        assert!(rows.contains(&"ご苦労さん\t5963 (5ご 9く 6ろう 3さん)".to_string()));
+    }
+}
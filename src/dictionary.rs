@@ -0,0 +1,1069 @@
+//! 予約語の辞書を項目の一覧やファイルから読み込む
+//!
+//! `serde` featureでは, JSONとTOMLの辞書ファイルを `ConverterBuilder` で読み込めます。
+//! どちらも `entries` に項目の一覧を持ちます。
+//!
+//! ```json
+//! {"entries": [{"phrase": "やきにく", "codes": ["8129"], "aliases": ["焼肉"]}]}
+//! ```
+//!
+//! ```toml
+//! [[entries]]
+//! phrase = "やきにく"
+//! codes = ["8129"]
+//! aliases = ["焼肉"]
+//! ```
+//!
+//! `aliases` は省略でき, それぞれ `phrase` と同じ数字の予約語として追加します。
+//! 読み (`reading`), 語呂合わせの由来 (`derivation`) と注記 (`note`) も省略でき,
+//! `Converter::explain_reserved` で返します。由来は `{"digits": "8", "reading": "や"}` の一覧です。
+//!
+//! `csv` featureでは, 表計算ソフトで編集したCSVの辞書も読み込めます。
+//! 1行目は見出しで, 列は `phrase`, `code`, `priority`, `comment` の順です。
+//!
+//! ```csv
+//! phrase,code,priority,comment
+//! やきにく,8129,0,
+//! やきにく,0129,1,"予備, 古い機種向け"
+//! ```
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "decode")]
+use std::sync::Arc;
+
+#[cfg(feature = "decode")]
+use super::two_touch_input::{BuildError, ConverterBuilder, ReservedMetadata};
+use super::two_touch_input::{Converter, Pun};
+
+/// 辞書の1項目
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
+pub struct DictionaryEntry {
+    pub phrase: String,
+    /// 数字。1つ以上必要で, 先頭のものを優先する
+    pub codes: Vec<String>,
+    /// 同じ数字にする別の表記
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    pub aliases: Vec<String>,
+    /// かなで書いた読み。`Converter::explain_reserved` で返す
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub reading: Option<String>,
+    /// 先頭の数字の語呂合わせの由来
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    pub derivation: Vec<Pun>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub note: Option<String>,
+}
+
+impl DictionaryEntry {
+    pub fn new<S: AsRef<str>>(phrase: &str, codes: &[S]) -> Self {
+        DictionaryEntry {
+            phrase: phrase.to_string(),
+            codes: codes.iter().map(|code| code.as_ref().to_string()).collect(),
+            aliases: Vec::new(),
+            reading: None,
+            derivation: Vec::new(),
+            note: None,
+        }
+    }
+
+    /// 別の表記を追加する
+    pub fn with_alias(mut self, alias: &str) -> Self {
+        self.aliases.push(alias.to_string());
+        self
+    }
+
+    /// 読みを設定する
+    pub fn with_reading(mut self, reading: &str) -> Self {
+        self.reading = Some(reading.to_string());
+        self
+    }
+
+    /// 語呂合わせの由来を設定する。数字をつなげたものは `codes` のどれかでなければならない
+    pub fn with_derivation(mut self, derivation: Vec<Pun>) -> Self {
+        self.derivation = derivation;
+        self
+    }
+
+    /// 注記を設定する
+    pub fn with_note(mut self, note: &str) -> Self {
+        self.note = Some(note.to_string());
+        self
+    }
+
+    /// 読み, 由来と注記。どれもない場合は `None`
+    #[cfg(feature = "decode")]
+    fn metadata(&self) -> Option<Arc<ReservedMetadata>> {
+        if self.reading.is_none() && self.derivation.is_empty() && self.note.is_none() {
+            return None;
+        }
+        Some(Arc::new(ReservedMetadata {
+            reading: self.reading.clone(),
+            derivation: self.derivation.clone(),
+            note: self.note.clone(),
+        }))
+    }
+
+    /// 値を確かめる。`index` はエラーに入れる項目の位置
+    #[cfg(feature = "decode")]
+    fn validate(&self, index: usize) -> Result<(), BuildError> {
+        let invalid = |field, value: &str| BuildError::InvalidDictionaryEntry {
+            index,
+            field,
+            value: value.to_string(),
+        };
+        if self.phrase.is_empty() {
+            return Err(invalid("phrase", ""));
+        }
+        if self.codes.is_empty() {
+            return Err(invalid("codes", ""));
+        }
+        if let Some(code) = self
+            .codes
+            .iter()
+            .find(|code| code.is_empty() || !code.bytes().all(|b| b.is_ascii_digit()))
+        {
+            return Err(invalid("codes", code));
+        }
+        if let Some(alias) = self.aliases.iter().find(|alias| alias.is_empty()) {
+            return Err(invalid("aliases", alias));
+        }
+        if !self.derivation.is_empty() {
+            let digits: String = self
+                .derivation
+                .iter()
+                .map(|pun| pun.digits.as_str())
+                .collect();
+            if self
+                .derivation
+                .iter()
+                .any(|pun| pun.digits.is_empty() || pun.reading.is_empty())
+                || !self.codes.contains(&digits)
+            {
+                return Err(invalid("derivation", &digits));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 名前の付いた辞書。`ConverterBuilder::merge_dictionary` で重ねる
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dictionary {
+    /// `Converter::effective_dictionary` で予約語を追加した辞書として返す名前
+    pub name: String,
+    pub entries: Vec<DictionaryEntry>,
+}
+
+impl Dictionary {
+    pub fn new(name: &str, entries: Vec<DictionaryEntry>) -> Self {
+        Dictionary {
+            name: name.to_string(),
+            entries,
+        }
+    }
+}
+
+/// プログラムに埋め込む辞書の1項目
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StaticEntry {
+    pub phrase: &'static str,
+    /// 数字。先頭のものを優先する
+    pub codes: &'static [&'static str],
+}
+
+/// プログラムに埋め込む辞書。`ConverterBuilder::merge_static_dictionary` で重ねる
+///
+/// `pokebell_macros::reserved_words!` で作ると, 数字や予約語の重複をコンパイル時に確かめられる。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StaticDictionary {
+    /// `Converter::effective_dictionary` で予約語を追加した辞書として返す名前
+    pub name: &'static str,
+    pub entries: &'static [StaticEntry],
+}
+
+impl StaticDictionary {
+    /// `reserved_words!` で作った辞書の名前
+    pub const DEFAULT_NAME: &'static str = "static";
+
+    pub const fn new(name: &'static str, entries: &'static [StaticEntry]) -> Self {
+        StaticDictionary { name, entries }
+    }
+
+    /// 名前を変える
+    pub const fn with_name(self, name: &'static str) -> Self {
+        StaticDictionary {
+            name,
+            entries: self.entries,
+        }
+    }
+}
+
+impl From<&StaticDictionary> for Dictionary {
+    fn from(dictionary: &StaticDictionary) -> Self {
+        let entries = dictionary
+            .entries
+            .iter()
+            .map(|entry| DictionaryEntry::new(entry.phrase, entry.codes))
+            .collect();
+        Dictionary::new(dictionary.name, entries)
+    }
+}
+
+/// 辞書を重ねるときに, すでにある予約語と同じ予約語をどうするか
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeStrategy {
+    /// 後から重ねた辞書の数字に置き換える
+    Replace,
+    /// すでにある数字の後に, まだない数字を加える
+    Append,
+    /// すでにある予約語をそのまま残す
+    KeepExisting,
+}
+
+/// 重ねる前の辞書の問題を調べる
+///
+/// `merge_dictionary` は同じ予約語を黙って合わせるので, 重ねる前に確かめるのに使う。
+/// `ConverterBuilder` とは別に使える。
+///
+/// ## Example
+/// ```
+/// # use pokebell::dictionary::*;
+/// let team = Dictionary::new("team", vec![DictionaryEntry::new("よろしく", &["4640"])]);
+/// let user = Dictionary::new("user", vec![DictionaryEntry::new("ヨロシク", &["0464"])]);
+/// let report = DictionaryLint::check(&[team, user]);
+/// assert!(report.errors.is_empty());
+/// assert_eq!(report.warnings[0].kind, LintKind::ConflictingCodes { other_source: "team".to_string() });
+/// ```
+pub struct DictionaryLint;
+
+impl DictionaryLint {
+    /// 既定の変換表で辞書を調べる
+    pub fn check(sources: &[Dictionary]) -> LintReport {
+        Self::check_with(&Converter::without_reserved_words(), sources)
+    }
+
+    /// `converter` の変換表と予約語のそろえ方で辞書を調べる。`converter` の予約語は使わない。
+    /// 問題は辞書の順, 項目の順に並べ, 2つの項目の間の問題は後の項目に付ける
+    pub fn check_with(converter: &Converter, sources: &[Dictionary]) -> LintReport {
+        let mut report = LintReport::default();
+        // (辞書の名前, 項目, そろえた表記) を, 別の表記も含めて並べる
+        let mut phrases: Vec<(&str, &DictionaryEntry, String)> = Vec::new();
+        let mut codes: Vec<(&str, &DictionaryEntry, &str)> = Vec::new();
+        for source in sources {
+            for entry in &source.entries {
+                let lint = |kind| Lint {
+                    kind,
+                    source: source.name.clone(),
+                    entry: entry.clone(),
+                };
+                if entry.codes.is_empty() {
+                    report.errors.push(lint(LintKind::InvalidCode {
+                        code: String::new(),
+                    }));
+                }
+                for code in &entry.codes {
+                    if code.is_empty() || !code.bytes().all(|b| b.is_ascii_digit()) {
+                        report
+                            .errors
+                            .push(lint(LintKind::InvalidCode { code: code.clone() }));
+                        continue;
+                    }
+                    if let Some((_, other, _)) = codes.iter().find(|&&(_, other, other_code)| {
+                        other_code == code && other.phrase != entry.phrase
+                    }) {
+                        report.warnings.push(lint(LintKind::SharedCode {
+                            code: code.clone(),
+                            other_phrase: other.phrase.clone(),
+                        }));
+                    }
+                    codes.push((&source.name, entry, code));
+                }
+                for phrase in std::iter::once(&entry.phrase).chain(&entry.aliases) {
+                    let folded = converter.fold_phrase(phrase);
+                    let earlier = phrases
+                        .iter()
+                        .find(|(name, _, other)| *name != source.name.as_str() && *other == folded);
+                    if let Some(&(name, other, _)) = earlier {
+                        let other_source = name.to_string();
+                        report.warnings.push(lint(if other.codes == entry.codes {
+                            LintKind::Duplicate { other_source }
+                        } else {
+                            LintKind::ConflictingCodes { other_source }
+                        }));
+                    }
+                    phrases.push((&source.name, entry, folded));
+                }
+            }
+        }
+        // 予約語の数字が, 別の予約語を1文字ずつ変換した数字と同じなら, 受け取った側でその予約語と読める
+        for source in sources {
+            for entry in &source.entries {
+                for code in &entry.codes {
+                    let other = phrases.iter().find(|(_, other, _)| {
+                        other.phrase != entry.phrase
+                            && literal(converter, &other.phrase).as_deref() == Some(code)
+                    });
+                    if let Some((_, other, _)) = other {
+                        report.warnings.push(Lint {
+                            kind: LintKind::LiteralCollision {
+                                code: code.clone(),
+                                other_phrase: other.phrase.clone(),
+                            },
+                            source: source.name.clone(),
+                            entry: entry.clone(),
+                        });
+                    }
+                }
+            }
+        }
+        report
+    }
+}
+
+/// 予約語を使わずに1文字ずつ変換した数字。変換できなければNone
+fn literal(converter: &Converter, phrase: &str) -> Option<String> {
+    let mut code = String::new();
+    converter.encode_into(phrase, &mut code).ok().map(|_| code)
+}
+
+/// `DictionaryLint` で見つかった問題
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LintReport {
+    /// そのまま重ねられるが, 意図と違うかもしれないもの
+    pub warnings: Vec<Lint>,
+    /// 辞書として読み込めないもの
+    pub errors: Vec<Lint>,
+}
+
+impl LintReport {
+    /// 問題がなかったか
+    pub fn is_clean(&self) -> bool {
+        self.warnings.is_empty() && self.errors.is_empty()
+    }
+}
+
+/// 辞書の1項目の問題
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lint {
+    pub kind: LintKind,
+    /// 項目のある辞書の名前
+    pub source: String,
+    pub entry: DictionaryEntry,
+}
+
+/// `DictionaryLint` で見つかる問題の種類
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum LintKind {
+    /// 数字が空か, 数字以外を含む (数字がない項目は `code` が空)
+    InvalidCode { code: String },
+    /// 前の辞書に同じ予約語が別の数字である
+    ConflictingCodes { other_source: String },
+    /// 前の辞書に同じ予約語が同じ数字である
+    Duplicate { other_source: String },
+    /// 同じ数字の別の予約語が前にある
+    SharedCode { code: String, other_phrase: String },
+    /// 数字が, 別の予約語を1文字ずつ変換した数字と同じ
+    LiteralCollision { code: String, other_phrase: String },
+}
+
+/// 辞書ファイルの全体
+#[cfg(all(feature = "serde", feature = "decode"))]
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DictionaryFile {
+    entries: Vec<DictionaryEntry>,
+}
+
+#[cfg(feature = "decode")]
+impl ConverterBuilder {
+    /// 辞書の項目を順に予約語として追加する。
+    /// 同じ予約語がすでにある場合は `add_reserved_word` と同じく置き換えるので, 後に追加したものが残る。
+    /// 項目の値が正しくない場合は, `build` が `BuildError::InvalidDictionaryEntry` になる。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::dictionary::DictionaryEntry;
+    /// # use pokebell::two_touch_input::Converter;
+    /// let entries = vec![DictionaryEntry::new("やきにく", &["8129"]).with_alias("焼肉")];
+    /// let c = Converter::builder().add_dictionary(&entries).build().unwrap();
+    /// assert_eq!(c.convert_to_two_touch_string("焼肉").unwrap(), vec!["8129"]);
+    /// ```
+    pub fn add_dictionary(self, entries: &[DictionaryEntry]) -> Self {
+        let mut builder = self;
+        for (index, entry) in entries.iter().enumerate() {
+            if let Err(err) = entry.validate(index) {
+                return builder.fail(err);
+            }
+            let metadata = entry.metadata();
+            for phrase in std::iter::once(&entry.phrase).chain(&entry.aliases) {
+                builder = builder.add_reserved_word_with(phrase, &entry.codes, metadata.clone());
+            }
+        }
+        builder
+    }
+
+    /// 名前の付いた辞書を, すでにある予約語に重ねる。
+    /// 既定の辞書の上に, 呼んだ順に `strategy` で合わせ, 別の表記も同じように合わせる。
+    /// どの辞書の予約語が残ったかは `Converter::effective_dictionary` で確かめられる。
+    /// 項目の値が正しくない場合は, `build` が `BuildError::InvalidDictionaryEntry` になる。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::dictionary::{Dictionary, DictionaryEntry, MergeStrategy};
+    /// # use pokebell::two_touch_input::Converter;
+    /// # #[cfg(feature = "builtin-dictionary")] {
+    /// let team = Dictionary::new("team", vec![DictionaryEntry::new("よろしく", &["4640"])]);
+    /// let c = Converter::builder()
+    ///     .merge_dictionary(&team, MergeStrategy::Append)
+    ///     .build()
+    ///     .unwrap();
+    /// assert_eq!(c.convert_to_two_touch_string("よろしく").unwrap()[..2], ["4649", "4640"]);
+    /// # }
+    /// ```
+    pub fn merge_dictionary(self, dictionary: &Dictionary, strategy: MergeStrategy) -> Self {
+        let mut builder = self;
+        for (index, entry) in dictionary.entries.iter().enumerate() {
+            if let Err(err) = entry.validate(index) {
+                return builder.fail(err);
+            }
+            for phrase in std::iter::once(&entry.phrase).chain(&entry.aliases) {
+                builder = builder.merge_reserved_word(
+                    phrase,
+                    &entry.codes,
+                    &dictionary.name,
+                    entry.metadata(),
+                    strategy,
+                );
+            }
+        }
+        builder
+    }
+
+    /// プログラムに埋め込んだ辞書を, `merge_dictionary` と同じように重ねる
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::dictionary::{MergeStrategy, StaticDictionary, StaticEntry};
+    /// # use pokebell::two_touch_input::Converter;
+    /// const TEAM: StaticDictionary =
+    ///     StaticDictionary::new("team", &[StaticEntry { phrase: "なおちゃん", codes: &["703"] }]);
+    /// let c = Converter::builder()
+    ///     .merge_static_dictionary(&TEAM, MergeStrategy::Replace)
+    ///     .build()
+    ///     .unwrap();
+    /// assert_eq!(c.convert_to_two_touch_string("なおちゃん").unwrap()[0], "703");
+    /// ```
+    pub fn merge_static_dictionary(
+        self,
+        dictionary: &StaticDictionary,
+        strategy: MergeStrategy,
+    ) -> Self {
+        self.merge_dictionary(&dictionary.into(), strategy)
+    }
+
+    /// JSONの辞書ファイルを読み込み, `add_dictionary` で追加する。
+    /// 読み込めない場合は `build` が `BuildError::InvalidDictionary` になる
+    #[cfg(feature = "serde")]
+    pub fn load_dictionary_json<R: std::io::Read>(self, reader: R) -> Self {
+        match serde_json::from_reader::<_, DictionaryFile>(reader) {
+            Ok(file) => self.add_dictionary(&file.entries),
+            Err(err) => self.fail(BuildError::InvalidDictionary {
+                message: err.to_string(),
+            }),
+        }
+    }
+
+    /// TOMLの辞書ファイルを読み込み, `add_dictionary` で追加する。
+    /// 読み込めない場合は `build` が `BuildError::InvalidDictionary` になる
+    #[cfg(feature = "serde")]
+    pub fn load_dictionary_toml(self, toml: &str) -> Self {
+        match toml::from_str::<DictionaryFile>(toml) {
+            Ok(file) => self.add_dictionary(&file.entries),
+            Err(err) => self.fail(BuildError::InvalidDictionary {
+                message: err.to_string(),
+            }),
+        }
+    }
+
+    /// CSVの辞書を読み込み, `add_dictionary` で追加する。
+    /// 同じ予約語の行は1つにまとめ, 数字は `priority` の小さいものから候補に並べる (省略した場合は0)。
+    /// 空の行と `#` で始まる行は読み飛ばす。
+    /// 行の値が正しくない場合は, `build` が行番号の付いた `BuildError::InvalidDictionaryRow` になる
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::Converter;
+    /// let csv = "phrase,code,priority,comment\nやきにく,0129,1,\nやきにく,8129,0,\n";
+    /// let c = Converter::builder()
+    ///     .load_dictionary_csv(csv.as_bytes())
+    ///     .build()
+    ///     .unwrap();
+    /// assert_eq!(c.convert_to_two_touch_string("やきにく").unwrap()[..2], ["8129", "0129"]);
+    /// ```
+    #[cfg(feature = "csv")]
+    pub fn load_dictionary_csv<R: std::io::Read>(self, reader: R) -> Self {
+        match csv_entries(reader) {
+            Ok(entries) => self.add_dictionary(&entries),
+            Err(err) => self.fail(err),
+        }
+    }
+}
+
+/// CSVの辞書の1行
+#[cfg(all(feature = "csv", feature = "decode"))]
+struct CsvRow {
+    line: u64,
+    phrase: String,
+    code: String,
+    priority: i64,
+}
+
+/// CSVの辞書を読み, 同じ予約語の行をまとめた項目にする。
+/// 数字は `priority` の小さい順 (同じなら行の順) に並べ, 項目は予約語が最初に現れた順に並べる
+#[cfg(all(feature = "csv", feature = "decode"))]
+fn csv_entries<R: std::io::Read>(mut reader: R) -> Result<Vec<DictionaryEntry>, BuildError> {
+    let mut csv = Vec::new();
+    reader
+        .read_to_end(&mut csv)
+        .map_err(|err| BuildError::InvalidDictionary {
+            message: err.to_string(),
+        })?;
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .comment(Some(b'#'))
+        .from_reader(csv.as_slice());
+    let mut rows: Vec<CsvRow> = Vec::new();
+    for (i, record) in reader.records().enumerate() {
+        let record = record.map_err(|err| BuildError::InvalidDictionary {
+            message: err.to_string(),
+        })?;
+        // csvの位置は読み飛ばした空の行やコメントの前を指し, 行番号もそれらを数えないので数え直す
+        let mut offset = record
+            .position()
+            .map_or(0, |position| position.byte() as usize);
+        while let Some(&b) = csv.get(offset) {
+            match b {
+                b'\r' | b'\n' => offset += 1,
+                b'#' => {
+                    offset += csv[offset..]
+                        .iter()
+                        .position(|&b| b == b'\n')
+                        .unwrap_or(csv.len() - offset)
+                }
+                _ => break,
+            }
+        }
+        let line = 1 + csv[..offset].iter().filter(|&&b| b == b'\n').count() as u64;
+        let invalid = |field, value: &str| BuildError::InvalidDictionaryRow {
+            line,
+            field,
+            value: value.to_string(),
+        };
+        if i == 0 {
+            if record.get(0) != Some("phrase") {
+                return Err(invalid("header", record.get(0).unwrap_or_default()));
+            }
+            continue;
+        }
+        if record.iter().all(|field| field.trim().is_empty()) {
+            continue;
+        }
+        if !(2..=4).contains(&record.len()) {
+            return Err(invalid("row", record.as_slice()));
+        }
+        let phrase = record[0].trim();
+        let code = record[1].trim();
+        let priority = record.get(2).map(str::trim).unwrap_or_default();
+        if phrase.is_empty() {
+            return Err(invalid("phrase", phrase));
+        }
+        if code.is_empty() || !code.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(invalid("code", code));
+        }
+        let priority = if priority.is_empty() {
+            0
+        } else {
+            priority
+                .parse()
+                .map_err(|_| invalid("priority", priority))?
+        };
+        rows.push(CsvRow {
+            line,
+            phrase: phrase.to_string(),
+            code: code.to_string(),
+            priority,
+        });
+    }
+    rows.sort_by_key(|row| (row.priority, row.line));
+    let mut entries: Vec<(u64, DictionaryEntry)> = Vec::new();
+    for row in rows {
+        match entries
+            .iter_mut()
+            .find(|(_, entry)| entry.phrase == row.phrase)
+        {
+            Some((first, entry)) => {
+                *first = (*first).min(row.line);
+                entry.codes.push(row.code);
+            }
+            None => entries.push((row.line, DictionaryEntry::new(&row.phrase, &[row.code]))),
+        }
+    }
+    entries.sort_by_key(|&(first, _)| first);
+    Ok(entries.into_iter().map(|(_, entry)| entry).collect())
+}
+
+#[cfg(all(test, feature = "decode"))]
+mod tests {
+    use super::*;
+    use crate::two_touch_input::Converter;
+
+    #[test]
+    fn test_add_dictionary() {
+        let entries = vec![
+            DictionaryEntry::new("やきにく", &["8129"]).with_alias("焼肉"),
+            DictionaryEntry::new("よろしく", &["4640"]),
+        ];
+        let c = Converter::builder()
+            .add_dictionary(&entries)
+            .build()
+            .unwrap();
+        assert_eq!(c.convert_to_two_touch_string("焼肉").unwrap()[0], "8129");
+        assert_eq!(
+            c.convert_to_two_touch_string("よろしく").unwrap()[0],
+            "4640"
+        );
+
+        let entries = vec![
+            DictionaryEntry::new("やきにく", &["8129"]),
+            DictionaryEntry::new("すし", &["44", "4a"]),
+        ];
+        let err = Converter::builder().add_dictionary(&entries).build().err();
+        assert_eq!(
+            err,
+            Some(BuildError::InvalidDictionaryEntry {
+                index: 1,
+                field: "codes",
+                value: "4a".to_string(),
+            })
+        );
+        assert_eq!(
+            err.unwrap().to_string(),
+            "invalid codes \"4a\" in dictionary entry 1"
+        );
+        let entries = vec![DictionaryEntry::new("すし", &["44"]).with_alias("")];
+        let err = Converter::builder().add_dictionary(&entries).build().err();
+        assert_eq!(
+            err,
+            Some(BuildError::InvalidDictionaryEntry {
+                index: 0,
+                field: "aliases",
+                value: String::new(),
+            })
+        );
+        let entries = vec![DictionaryEntry::new::<&str>("すし", &[])];
+        let err = Converter::builder().add_dictionary(&entries).build().err();
+        assert!(matches!(
+            err,
+            Some(BuildError::InvalidDictionaryEntry { field: "codes", .. })
+        ));
+    }
+
+    #[test]
+    fn test_entry_metadata() {
+        let entry = DictionaryEntry::new("やきにく", &["8129"])
+            .with_alias("焼肉")
+            .with_reading("やきにく")
+            .with_derivation(vec![
+                Pun::new("8", "や"),
+                Pun::new("1", "い"),
+                Pun::new("2", "に"),
+                Pun::new("9", "く"),
+            ])
+            .with_note("8が「や」");
+        let c = Converter::builder()
+            .add_dictionary(std::slice::from_ref(&entry))
+            .build()
+            .unwrap();
+        for info in c.explain_reserved("8129") {
+            assert_eq!(info.reading.as_deref(), Some("やきにく"));
+            assert_eq!(info.derivation, entry.derivation);
+            assert_eq!(info.note.as_deref(), Some("8が「や」"));
+        }
+
+        let wrong = entry.clone().with_derivation(vec![Pun::new("81", "やい")]);
+        let err = Converter::builder().add_dictionary(&[wrong]).build().err();
+        assert_eq!(
+            err,
+            Some(BuildError::InvalidDictionaryEntry {
+                index: 0,
+                field: "derivation",
+                value: "81".to_string(),
+            })
+        );
+
+        #[cfg(feature = "serde")]
+        {
+            let json = serde_json::to_string(&entry).unwrap();
+            assert_eq!(
+                serde_json::from_str::<DictionaryEntry>(&json).unwrap(),
+                entry
+            );
+            let json = serde_json::to_string(&DictionaryEntry::new("すし", &["44"])).unwrap();
+            assert_eq!(json, r#"{"phrase":"すし","codes":["44"]}"#);
+        }
+    }
+
+    #[cfg(feature = "builtin-dictionary")]
+    #[test]
+    fn test_merge_dictionary() {
+        use crate::two_touch_input::{EffectiveEntry, BUILTIN_DICTIONARY};
+
+        // 既定の辞書の `よろしく` (4649) に, チームと自分の辞書を重ねる
+        let team = Dictionary::new(
+            "team",
+            vec![
+                DictionaryEntry::new("よろしく", &["4640", "4649"]),
+                DictionaryEntry::new("やきにく", &["8129"]),
+            ],
+        );
+        let user = Dictionary::new(
+            "user",
+            vec![
+                DictionaryEntry::new("ヨロシク", &["0464"]),
+                DictionaryEntry::new("すし", &["44"]),
+            ],
+        );
+        let merged = |strategy| {
+            let c = Converter::builder()
+                .merge_dictionary(&team, strategy)
+                .merge_dictionary(&user, strategy)
+                .build()
+                .unwrap();
+            c.effective_dictionary()
+                .into_iter()
+                .filter(|entry| {
+                    ["よろしく", "ヨロシク", "やきにく", "すし"].contains(&entry.phrase.as_str())
+                })
+                .collect::<Vec<_>>()
+        };
+        let entry = |phrase: &str, codes: &[&str], sources: &[&str]| EffectiveEntry {
+            phrase: phrase.to_string(),
+            codes: codes.iter().map(|code| code.to_string()).collect(),
+            sources: sources.iter().map(|source| source.to_string()).collect(),
+        };
+
+        // 予約語は `fold_phrase` でそろえて比べるので, `ヨロシク` は `よろしく` を置き換える
+        assert_eq!(
+            merged(MergeStrategy::Replace),
+            vec![
+                entry("すし", &["44"], &["user"]),
+                entry("やきにく", &["8129"], &["team"]),
+                entry("ヨロシク", &["0464"], &["user"]),
+            ]
+        );
+        assert_eq!(
+            merged(MergeStrategy::Append),
+            vec![
+                entry("すし", &["44"], &["user"]),
+                entry("やきにく", &["8129"], &["team"]),
+                entry(
+                    "よろしく",
+                    &["4649", "4640", "0464"],
+                    &[BUILTIN_DICTIONARY, "team", "user"]
+                ),
+            ]
+        );
+        assert_eq!(
+            merged(MergeStrategy::KeepExisting),
+            vec![
+                entry("すし", &["44"], &["user"]),
+                entry("やきにく", &["8129"], &["team"]),
+                entry("よろしく", &["4649"], &[BUILTIN_DICTIONARY]),
+            ]
+        );
+
+        // 重ねる順に合わせ方を変えられる
+        let c = Converter::builder()
+            .merge_dictionary(&team, MergeStrategy::Replace)
+            .merge_dictionary(&user, MergeStrategy::KeepExisting)
+            .build()
+            .unwrap();
+        assert_eq!(
+            c.convert_to_two_touch_string("よろしく").unwrap()[..2],
+            ["4640", "4649"]
+        );
+        let c = Converter::builder()
+            .merge_dictionary(&team, MergeStrategy::KeepExisting)
+            .merge_dictionary(&user, MergeStrategy::Replace)
+            .build()
+            .unwrap();
+        assert_eq!(
+            c.convert_to_two_touch_string("よろしく").unwrap()[0],
+            "0464"
+        );
+    }
+
+    #[test]
+    fn test_dictionary_lint() {
+        let team = Dictionary::new(
+            "team",
+            vec![
+                DictionaryEntry::new("よろしく", &["4649"]),
+                DictionaryEntry::new("やき", &["8122"]),
+                DictionaryEntry::new("すし", &["44"]),
+            ],
+        );
+        let user = Dictionary::new(
+            "user",
+            vec![
+                DictionaryEntry::new("ヨロシク", &["0464"]),
+                DictionaryEntry::new("すし", &["44"]),
+                DictionaryEntry::new("ごめん", &["8122", "51"]),
+                DictionaryEntry::new("しーすー", &["44"]),
+                DictionaryEntry::new("だめ", &["4a"]),
+                DictionaryEntry::new::<&str>("なし", &[]),
+            ],
+        );
+        let report = DictionaryLint::check(&[team.clone(), user.clone()]);
+        let kinds = |lints: &[Lint]| -> Vec<(String, String, LintKind)> {
+            lints
+                .iter()
+                .map(|lint| {
+                    (
+                        lint.source.clone(),
+                        lint.entry.phrase.clone(),
+                        lint.kind.clone(),
+                    )
+                })
+                .collect()
+        };
+        let lint =
+            |source: &str, phrase: &str, kind| (source.to_string(), phrase.to_string(), kind);
+        assert_eq!(
+            kinds(&report.errors),
+            vec![
+                lint(
+                    "user",
+                    "だめ",
+                    LintKind::InvalidCode {
+                        code: "4a".to_string()
+                    }
+                ),
+                lint(
+                    "user",
+                    "なし",
+                    LintKind::InvalidCode {
+                        code: String::new()
+                    }
+                ),
+            ]
+        );
+        assert_eq!(
+            kinds(&report.warnings),
+            vec![
+                lint(
+                    "user",
+                    "ヨロシク",
+                    LintKind::ConflictingCodes {
+                        other_source: "team".to_string()
+                    }
+                ),
+                lint(
+                    "user",
+                    "すし",
+                    LintKind::Duplicate {
+                        other_source: "team".to_string()
+                    }
+                ),
+                lint(
+                    "user",
+                    "ごめん",
+                    LintKind::SharedCode {
+                        code: "8122".to_string(),
+                        other_phrase: "やき".to_string()
+                    }
+                ),
+                lint(
+                    "user",
+                    "しーすー",
+                    LintKind::SharedCode {
+                        code: "44".to_string(),
+                        other_phrase: "すし".to_string()
+                    }
+                ),
+                lint(
+                    "user",
+                    "ごめん",
+                    LintKind::LiteralCollision {
+                        code: "8122".to_string(),
+                        other_phrase: "やき".to_string()
+                    }
+                ),
+            ]
+        );
+        assert!(!report.is_clean());
+        assert!(DictionaryLint::check(&[team]).is_clean());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_load_dictionary_files() {
+        let json = include_str!("testdata/dictionary.json");
+        let toml = include_str!("testdata/dictionary.toml");
+        let from_json = Converter::builder()
+            .load_dictionary_json(json.as_bytes())
+            .build()
+            .unwrap();
+        let from_toml = Converter::builder()
+            .load_dictionary_toml(toml)
+            .build()
+            .unwrap();
+        for c in [&from_json, &from_toml] {
+            assert_eq!(c.convert_to_two_touch_string("焼肉").unwrap()[0], "8129");
+            assert_eq!(
+                c.convert_to_two_touch_string("やきにく").unwrap()[0],
+                "8129"
+            );
+            let result = c.convert_to_two_touch_string("ありがとう").unwrap();
+            assert_eq!(result[0], "39");
+            assert_eq!(result[1], "3999");
+        }
+
+        // 後から読み込んだ辞書の予約語に置き換える
+        let c = Converter::builder()
+            .load_dictionary_json(json.as_bytes())
+            .load_dictionary_toml("[[entries]]\nphrase = \"やきにく\"\ncodes = [\"1029\"]\n")
+            .build()
+            .unwrap();
+        assert_eq!(
+            c.convert_to_two_touch_string("やきにく").unwrap()[0],
+            "1029"
+        );
+        assert_eq!(c.convert_to_two_touch_string("焼肉").unwrap()[0], "8129");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_load_invalid_dictionary() {
+        let json =
+            r#"{"entries": [{"phrase": "すし", "codes": ["44"]}, {"phrase": "", "codes": ["1"]}]}"#;
+        let err = Converter::builder()
+            .load_dictionary_json(json.as_bytes())
+            .build()
+            .err();
+        assert_eq!(
+            err,
+            Some(BuildError::InvalidDictionaryEntry {
+                index: 1,
+                field: "phrase",
+                value: String::new(),
+            })
+        );
+        let err = Converter::builder()
+            .load_dictionary_json(r#"{"entries": [{"phrase": "すし", "code": ["44"]}]}"#.as_bytes())
+            .build()
+            .err();
+        assert!(matches!(err, Some(BuildError::InvalidDictionary { .. })));
+        let err = Converter::builder()
+            .load_dictionary_toml("[[entries]]\nphrase = \"すし\"\ncodes = [44]\n")
+            .build()
+            .err();
+        assert!(matches!(err, Some(BuildError::InvalidDictionary { .. })));
+    }
+
+    #[cfg(feature = "csv")]
+    #[test]
+    fn test_load_dictionary_csv() {
+        let csv = include_str!("testdata/dictionary.csv");
+        let c = Converter::builder()
+            .load_dictionary_csv(csv.as_bytes())
+            .build()
+            .unwrap();
+        let result = c.convert_to_two_touch_string("やきにく").unwrap();
+        assert_eq!(result[..3], ["8129", "0129", "81225223"]);
+        assert_eq!(
+            c.convert_to_two_touch_string("よろしく, ね").unwrap()[0],
+            "464902"
+        );
+        assert_eq!(c.convert_to_two_touch_string("すし").unwrap()[0], "44");
+
+        let err = Converter::builder()
+            .load_dictionary_csv("phrase,code\nすし,44\n\nやきにく,81a9\n".as_bytes())
+            .build()
+            .err();
+        assert_eq!(
+            err,
+            Some(BuildError::InvalidDictionaryRow {
+                line: 4,
+                field: "code",
+                value: "81a9".to_string(),
+            })
+        );
+        assert_eq!(
+            err.unwrap().to_string(),
+            "invalid code \"81a9\" in dictionary line 4"
+        );
+        let err = Converter::builder()
+            .load_dictionary_csv("phrase,code\n# memo\nすし,4x\n".as_bytes())
+            .build()
+            .err();
+        assert!(matches!(
+            err,
+            Some(BuildError::InvalidDictionaryRow { line: 3, .. })
+        ));
+        let err = Converter::builder()
+            .load_dictionary_csv("phrase,code,priority\nすし,44,high\n".as_bytes())
+            .build()
+            .err();
+        assert_eq!(
+            err,
+            Some(BuildError::InvalidDictionaryRow {
+                line: 2,
+                field: "priority",
+                value: "high".to_string(),
+            })
+        );
+        let err = Converter::builder()
+            .load_dictionary_csv("すし,44\n".as_bytes())
+            .build()
+            .err();
+        assert!(matches!(
+            err,
+            Some(BuildError::InvalidDictionaryRow {
+                line: 1,
+                field: "header",
+                ..
+            })
+        ));
+        let err = Converter::builder()
+            .load_dictionary_csv("phrase,code\nすし\n".as_bytes())
+            .build()
+            .err();
+        assert!(matches!(
+            err,
+            Some(BuildError::InvalidDictionaryRow {
+                line: 2,
+                field: "row",
+                ..
+            })
+        ));
+    }
+}
@@ -0,0 +1,105 @@
+use super::two_touch_input::{Converter, Error};
+
+/// 1文字ずつ入力しながら2タッチ入力の数字に変換する
+///
+/// 予約語は使わず, 入力された文字を1文字ずつ変換します。
+///
+/// ## Example
+/// ```
+/// # use pokebell::encoder::Encoder;
+/// # use pokebell::two_touch_input::Converter;
+/// let c = Converter::new();
+/// let mut encoder = Encoder::new(&c);
+/// for ch in "やきにく".chars() {
+///     encoder.push_char(ch).unwrap();
+/// }
+/// assert_eq!(encoder.output(), "81225223");
+/// ```
+pub struct Encoder<'a> {
+    converter: &'a Converter,
+    output: String,
+    chars: usize,
+}
+
+impl<'a> Encoder<'a> {
+    pub fn new(converter: &'a Converter) -> Self {
+        Encoder {
+            converter,
+            output: String::new(),
+            chars: 0,
+        }
+    }
+
+    /// 1文字変換して追記し, 追記した数字を返す。
+    /// 変換できない文字の場合はエラーを返し, それまでの結果は変わらない。
+    pub fn push_char(&mut self, ch: char) -> Result<&str, Error> {
+        let code = match self.converter.encode_char(ch) {
+            Some(code) => code,
+            None => return Err(self.converter.unsupported(ch, self.chars)),
+        };
+        let start = self.output.len();
+        self.output.push_str(code);
+        self.chars += 1;
+        Ok(&self.output[start..])
+    }
+
+    /// 文字列を変換して追記し, 追記した数字を返す。
+    /// 変換できない文字が含まれる場合はエラーを返し, それまでの結果は変わらない。
+    pub fn push_str(&mut self, val: &str) -> Result<&str, Error> {
+        let start = self.output.len();
+        let chars = self.chars;
+        for ch in val.chars() {
+            if let Err(err) = self.push_char(ch) {
+                self.output.truncate(start);
+                self.chars = chars;
+                return Err(err);
+            }
+        }
+        Ok(&self.output[start..])
+    }
+
+    /// これまでに変換した数字
+    pub fn output(&self) -> &str {
+        &self.output
+    }
+
+    /// 変換した結果を捨てて最初からやり直す
+    pub fn reset(&mut self) {
+        self.output.clear();
+        self.chars = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::two_touch_input::ErrorKind;
+
+    #[test]
+    fn test_encoder() {
+        let c = Converter::new();
+        let mut encoder = Encoder::new(&c);
+        assert_eq!(encoder.push_char('や').unwrap(), "81");
+        assert_eq!(encoder.push_char('ぎ').unwrap(), "2204");
+        assert_eq!(
+            encoder.push_char('ｔ').err().unwrap().to_string(),
+            "unsupported character 'ｔ' at index 2"
+        );
+        assert_eq!(encoder.output(), "812204");
+        assert_eq!(encoder.push_char('Ｔ').unwrap(), "40");
+
+        assert!(encoder.push_str("あ肉").is_err());
+        assert_eq!(encoder.output(), "81220440");
+        assert_eq!(encoder.push_str("ぁっ").unwrap(), "1143");
+
+        encoder.reset();
+        assert_eq!(encoder.output(), "");
+        let err = encoder.push_char('肉').err().unwrap();
+        assert_eq!(
+            *err.kind(),
+            ErrorKind::UnsupportedCharacter {
+                ch: '肉', index: 0
+            }
+        );
+    }
+}
@@ -0,0 +1,78 @@
+//! `&str` から直接2タッチ入力に変換するための拡張トレイト
+//!
+//! `ext` feature (デフォルトで有効) で使えます。
+
+use super::two_touch_input::{shared_converter, Converter, Error};
+
+/// 文字列を2タッチ入力と相互に変換する
+///
+/// `to_two_touch` と `from_two_touch` はC言語のインターフェースと同じ, `Converter::new()` で作った
+/// 共有のConverterを使います。設定を変えたConverterを使う場合は `_with` の付いたメソッドを使ってください。
+///
+/// ## Example
+/// ```
+/// use pokebell::ext::TwoTouchExt;
+///
+/// assert_eq!("やきにく".to_two_touch().unwrap(), vec!["81225223"]);
+/// assert_eq!("81225223".from_two_touch().unwrap(), "やきにく");
+/// ```
+pub trait TwoTouchExt {
+    /// `Converter::convert_to_two_touch_string` で変換する
+    fn to_two_touch(&self) -> Result<Vec<String>, Error> {
+        self.to_two_touch_with(shared_converter())
+    }
+
+    /// `Converter::convert_from_two_touch_string` で変換する
+    #[allow(clippy::wrong_self_convention)]
+    fn from_two_touch(&self) -> Result<String, Error> {
+        self.from_two_touch_with(shared_converter())
+    }
+
+    /// `converter` を使って `Converter::convert_to_two_touch_string` で変換する
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::ext::TwoTouchExt;
+    /// # use pokebell::two_touch_input::*;
+    /// let c = Converter::without_reserved_words();
+    /// assert_eq!("おはよう".to_two_touch_with(&c).unwrap(), vec!["15618513"]);
+    /// ```
+    fn to_two_touch_with(&self, converter: &Converter) -> Result<Vec<String>, Error>;
+
+    /// `converter` を使って `Converter::convert_from_two_touch_string` で変換する
+    #[allow(clippy::wrong_self_convention)]
+    fn from_two_touch_with(&self, converter: &Converter) -> Result<String, Error>;
+}
+
+impl TwoTouchExt for str {
+    fn to_two_touch_with(&self, converter: &Converter) -> Result<Vec<String>, Error> {
+        converter.convert_to_two_touch_string(self)
+    }
+
+    fn from_two_touch_with(&self, converter: &Converter) -> Result<String, Error> {
+        converter.convert_from_two_touch_string(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_shared_converter_from_threads() {
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                thread::spawn(|| {
+                    for _ in 0..50 {
+                        assert_eq!("やきにく".to_two_touch().unwrap(), vec!["81225223"]);
+                        assert_eq!("81225223".from_two_touch().unwrap(), "やきにく");
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
+}
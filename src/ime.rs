@@ -0,0 +1,116 @@
+//! 予約語の辞書を, IMEのユーザー辞書の形式で書き出す
+//!
+//! 読みを入力すると予約語の数字が候補に出るように, 予約語の読みを見出し, 数字を単語にします。
+//! 漢字の表記は, 同じ行に書いたかなの表記の読みを使います。読みの分からない予約語は書き出しません。
+
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+use super::two_touch_input::Converter;
+
+/// `Converter::export_ime` で書き出す形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImeFormat {
+    /// Google 日本語入力 / Mozc のタブ区切りの形式。1行が読み, 単語と品詞で, 数字ごとに1行にする
+    Mozc,
+    /// SKKの辞書 (`SKK-JISYO`) の送りなしの形式。1行が1つの読みで, 数字を優先する順に候補として並べる
+    Skk,
+}
+
+/// Mozcの形式で書き出す品詞
+const MOZC_PART_OF_SPEECH: &str = "名詞";
+
+impl Converter {
+    /// 予約語の辞書を `format` の形式で `w` に書き出す。
+    /// `add_reserved_word` などで変えた辞書もそのまま書き出す。読みの順に並べ, 同じ読みと数字は1つにまとめる
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::ime::ImeFormat;
+    /// # use pokebell::two_touch_input::*;
+    /// let mut c = Converter::without_reserved_words();
+    /// c.add_reserved_word("なおちゃん", &["703", "7030"]).unwrap();
+    /// let mut out = Vec::new();
+    /// c.export_ime(ImeFormat::Mozc, &mut out).unwrap();
+    /// assert_eq!(
+    ///     String::from_utf8(out).unwrap(),
+    ///     "なおちゃん\t703\t名詞\nなおちゃん\t7030\t名詞\n"
+    /// );
+    /// ```
+    pub fn export_ime<W: Write>(&self, format: ImeFormat, mut w: W) -> io::Result<()> {
+        let mut readings: BTreeMap<String, Vec<&str>> = BTreeMap::new();
+        for (reading, codes) in self.reserved_readings() {
+            let entry = readings.entry(reading).or_default();
+            for code in codes {
+                if !entry.contains(&code.as_ref()) {
+                    entry.push(code);
+                }
+            }
+        }
+        match format {
+            ImeFormat::Mozc => {
+                for (reading, codes) in &readings {
+                    for code in codes {
+                        writeln!(w, "{}\t{}\t{}", reading, code, MOZC_PART_OF_SPEECH)?;
+                    }
+                }
+            }
+            ImeFormat::Skk => {
+                writeln!(w, ";; -*- mode: fundamental; coding: utf-8 -*-")?;
+                writeln!(w, ";; okuri-ari entries.")?;
+                writeln!(w, ";; okuri-nasi entries.")?;
+                for (reading, codes) in &readings {
+                    writeln!(w, "{} /{}/", reading, codes.join("/"))?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn export(c: &Converter, format: ImeFormat) -> String {
+        let mut out = Vec::new();
+        c.export_ime(format, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    // 追加の辞書を有効にした場合は既定の辞書と違うので比べない
+    #[cfg(all(
+        feature = "builtin-dictionary",
+        not(feature = "dict-stations"),
+        not(feature = "dict-dates")
+    ))]
+    #[test]
+    fn test_export_ime_golden() {
+        // 辞書を変えた場合は `UPDATE_GOLDEN=1 cargo test` で書き直し, 差分を確かめる
+        let c = Converter::new();
+        for (format, file) in [
+            (ImeFormat::Mozc, "ime_mozc.tsv"),
+            (ImeFormat::Skk, "ime_skk.jisyo"),
+        ] {
+            let path = format!("{}/src/testdata/{}", env!("CARGO_MANIFEST_DIR"), file);
+            let text = export(&c, format);
+            if std::env::var_os("UPDATE_GOLDEN").is_some() {
+                std::fs::write(&path, &text).unwrap();
+            }
+            assert_eq!(text, std::fs::read_to_string(&path).unwrap(), "{}", file);
+        }
+    }
+
+    #[test]
+    fn test_export_ime() {
+        let mut c = Converter::without_reserved_words();
+        c.add_reserved_word("おやすみ", &["833", "0833"]).unwrap();
+        c.add_reserved_word("ハロー", &["860"]).unwrap();
+        c.add_reserved_word("焼肉", &["8129"]).unwrap();
+        assert_eq!(
+            export(&c, ImeFormat::Mozc),
+            "おやすみ\t833\t名詞\nおやすみ\t0833\t名詞\nはろー\t860\t名詞\n"
+        );
+        assert!(export(&c, ImeFormat::Skk).ends_with("\nおやすみ /833/0833/\nはろー /860/\n"));
+    }
+}
@@ -1,9 +1,92 @@
 //! ポケベルの2タッチ入力の相互変換ライブラリ
 //!
 //! 機種依存の入力方法はサポートしません。
+//!
+//! 変換は `encode` featureと `decode` feature (どちらもデフォルトで有効) で, 向きごとに有効にします。
+//! 片方だけを有効にすると, もう片方の向きで使う表や辞書とメソッドはライブラリに含めません。
+//! 両方の向きを使う `batch` などのモジュールは, 両方を有効にした場合だけ使えます。
+//!
+//! アロケータのない環境では, 変換表とヒープに確保しない変換だけを持つ `no_std` の
+//! `pokebell-core` を使ってください。このクレートの `tables` はその表をそのまま公開します。
+//!
+//! ## Example
+//! ```
+//! # #[cfg(all(feature = "encode", feature = "decode"))] {
+//! assert_eq!(pokebell::encode("やきにく").unwrap(), vec!["81225223"]);
+//! assert_eq!(pokebell::decode("81225223").unwrap(), "やきにく");
+//! # }
+//! ```
 
-#[macro_use]
-extern crate failure;
-
+#[cfg(all(feature = "encode", feature = "decode"))]
+pub mod batch;
 pub mod c_interface;
+#[cfg(all(feature = "serde", feature = "encode", feature = "decode"))]
+pub mod config;
+#[cfg(feature = "conformance")]
+pub mod conformance;
+#[cfg(feature = "encode")]
+pub mod deck;
+#[cfg(feature = "encode")]
+pub mod dictionary;
+#[cfg(feature = "encode")]
+pub mod encoder;
+#[cfg(feature = "ext")]
+pub mod ext;
+#[cfg(feature = "encode")]
+pub mod ime;
+#[cfg(feature = "encode")]
+pub mod message;
+#[cfg(feature = "ngram")]
+mod ngram;
+#[cfg(feature = "ruby")]
+pub mod ruby;
+#[cfg(all(feature = "encode", feature = "decode"))]
+pub mod scheme;
+pub mod tables;
+#[cfg(feature = "test-util")]
+pub mod test_util;
 pub mod two_touch_input;
+
+#[cfg(any(feature = "encode", feature = "decode"))]
+use two_touch_input::{shared_converter, Error};
+
+/// 入力された文字列を2タッチ入力の数字に変換する。
+/// ライブラリ全体で共有する, デフォルトの設定の `Converter` を使う。
+/// 予約語や候補の並び順などを変える場合は `two_touch_input::Converter` を使ってください。
+#[cfg(feature = "encode")]
+pub fn encode(val: &str) -> Result<Vec<String>, Error> {
+    shared_converter().convert_to_two_touch_string(val)
+}
+
+/// 2タッチ入力を文字列に変換する。`encode` と同じ共有の `Converter` を使う。
+#[cfg(feature = "decode")]
+pub fn decode(val: &str) -> Result<String, Error> {
+    shared_converter().convert_from_two_touch_string(val)
+}
+
+#[cfg(all(test, feature = "builtin-dictionary", feature = "decode"))]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_encode_decode_from_threads() {
+        let handles: Vec<_> = (0..8)
+            .map(|i| {
+                thread::spawn(move || {
+                    for _ in 0..50 {
+                        if i % 2 == 0 {
+                            assert_eq!(encode("ごくろうさん").unwrap()[0], "5963");
+                        } else {
+                            assert_eq!(decode("81225223").unwrap(), "やきにく");
+                        }
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert!(std::ptr::eq(shared_converter(), shared_converter()));
+    }
+}
@@ -0,0 +1,456 @@
+use std::collections::HashMap;
+
+use super::two_touch_input::{Converter, Error, ErrorKind};
+
+/// 自由な文, 予約語, そのまま送る数字を組み合わせてメッセージを作る
+///
+/// 文は予約語を使わずに1文字ずつ変換し, 予約語は文中にあっても必ずその数字にします。
+///
+/// ## Example
+/// ```
+/// # use pokebell::message::MessageBuilder;
+/// # use pokebell::two_touch_input::Converter;
+/// # #[cfg(feature = "builtin-dictionary")] {
+/// let c = Converter::new();
+/// let message = MessageBuilder::new(&c)
+///     .text("あした")
+///     .reserved("よろしく")
+///     .unwrap()
+///     .raw_digits("0312345678")
+///     .unwrap()
+///     .build()
+///     .unwrap();
+/// assert_eq!(message, "11324146490312345678");
+/// # }
+/// ```
+pub struct MessageBuilder<'a> {
+    converter: &'a Converter,
+    parts: Vec<MessagePart>,
+}
+
+/// `MessageBuilder` で組み立てたメッセージの1区間
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessagePart {
+    pub kind: MessagePartKind,
+    /// 追加したときの文字列
+    pub source: String,
+    /// 変換した数字 (`build` の前は空)
+    pub digits: String,
+}
+
+/// メッセージの区間の種類
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MessagePartKind {
+    /// 1文字ずつ変換する文
+    Text,
+    /// 予約語。`phrase` は辞書に登録された表記
+    Reserved { phrase: String },
+    /// そのまま送る数字
+    RawDigits,
+}
+
+impl<'a> MessageBuilder<'a> {
+    pub fn new(converter: &'a Converter) -> Self {
+        MessageBuilder {
+            converter,
+            parts: Vec::new(),
+        }
+    }
+
+    /// 1文字ずつ変換する文を追加する。変換できない文字は `build` でエラーになる
+    pub fn text(mut self, text: &str) -> Self {
+        self.parts.push(MessagePart {
+            kind: MessagePartKind::Text,
+            source: text.to_string(),
+            digits: String::new(),
+        });
+        self
+    }
+
+    /// 予約語を追加する。辞書にない場合はエラー
+    pub fn reserved(mut self, phrase: &str) -> Result<Self, Error> {
+        let (entry, codes) = match self.converter.reserved_entry(phrase) {
+            Some(found) => found,
+            None => {
+                return Err(Error::from(ErrorKind::UnknownReservedWord {
+                    phrase: phrase.to_string(),
+                }))
+            }
+        };
+        self.parts.push(MessagePart {
+            kind: MessagePartKind::Reserved {
+                phrase: entry.to_string(),
+            },
+            source: phrase.to_string(),
+            digits: codes[0].to_string(),
+        });
+        Ok(self)
+    }
+
+    /// 電話番号などの数字をそのまま追加する。数字以外の文字があればエラー
+    pub fn raw_digits(mut self, digits: &str) -> Result<Self, Error> {
+        self.parts.push(MessagePart {
+            kind: MessagePartKind::RawDigits,
+            source: digits.to_string(),
+            digits: raw_digits(digits)?,
+        });
+        Ok(self)
+    }
+
+    /// 区間ごとに変換した結果を返す。
+    /// 文に変換できない文字がある場合のエラーの位置は, その文の先頭からの文字位置になる。
+    pub fn build_parts(&self) -> Result<Vec<MessagePart>, Error> {
+        if self.parts.is_empty() {
+            return Err(Error::from(ErrorKind::EmptyInput));
+        }
+        let mut parts = self.parts.clone();
+        for part in parts.iter_mut() {
+            if part.kind == MessagePartKind::Text {
+                self.converter.encode_into(&part.source, &mut part.digits)?;
+            }
+        }
+        Ok(parts)
+    }
+
+    /// 区間ごとに変換した数字をつなげて返す
+    pub fn build(&self) -> Result<String, Error> {
+        Ok(self
+            .build_parts()?
+            .iter()
+            .map(|part| part.digits.as_str())
+            .collect())
+    }
+}
+
+/// `{place}` のような名前の付いた差し込み位置を持つ, 使い回せるメッセージのひな形
+///
+/// 差し込み位置以外の文は予約語を使わずに1文字ずつ変換します。
+/// `{{` と `}}` はそれぞれ1文字の `{` と `}` になり,
+/// `{0840}` のように数字だけを囲んだものは変換の入力と同じくそのまま数字として出力します。
+///
+/// ## Example
+/// ```
+/// # use std::collections::HashMap;
+/// # use pokebell::message::{Template, Value};
+/// # use pokebell::two_touch_input::Converter;
+/// let c = Converter::new();
+/// let template = Template::parse("{place}で{time}じ").unwrap();
+/// let mut values = HashMap::new();
+/// values.insert("place", Value::Text("えき".to_string()));
+/// values.insert("time", Value::Digits("7".to_string()));
+/// assert_eq!(template.render(&c, &values).unwrap(), vec!["1422440473204"]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    pieces: Vec<Piece>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Piece {
+    Text(String),
+    Digits(String),
+    Placeholder(String),
+}
+
+/// `Template::render` で差し込む値
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    /// 1文字ずつ変換する文
+    Text(String),
+    /// そのまま送る数字
+    Digits(String),
+    /// 予約語。辞書にない場合は `render` でエラーになる
+    Reserved(String),
+}
+
+impl Template {
+    /// ひな形を解釈する。`{` や `}` の対応が取れない場合は `ErrorKind::UnbalancedBrace`
+    pub fn parse(template: &str) -> Result<Self, Error> {
+        let mut pieces = Vec::new();
+        let mut text = String::new();
+        let mut chars = template.chars().enumerate().peekable();
+        while let Some((index, ch)) = chars.next() {
+            match ch {
+                '{' | '}' if chars.peek().map(|&(_, next)| next) == Some(ch) => {
+                    chars.next();
+                    text.push(ch);
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some((_, '}')) => break,
+                            Some((index, '{')) => {
+                                return Err(Error::from(ErrorKind::UnbalancedBrace { index }))
+                            }
+                            Some((_, ch)) => name.push(ch),
+                            None => return Err(Error::from(ErrorKind::UnbalancedBrace { index })),
+                        }
+                    }
+                    if !text.is_empty() {
+                        pieces.push(Piece::Text(std::mem::take(&mut text)));
+                    }
+                    if name.chars().all(|ch| ch.is_ascii_digit()) {
+                        pieces.push(Piece::Digits(name));
+                    } else {
+                        pieces.push(Piece::Placeholder(name));
+                    }
+                }
+                '}' => return Err(Error::from(ErrorKind::UnbalancedBrace { index })),
+                _ => text.push(ch),
+            }
+        }
+        if !text.is_empty() {
+            pieces.push(Piece::Text(text));
+        }
+        Ok(Template { pieces })
+    }
+
+    /// 差し込み位置の名前を, ひな形に現れた順に返す
+    pub fn placeholders(&self) -> impl Iterator<Item = &str> {
+        self.pieces.iter().filter_map(|piece| match piece {
+            Piece::Placeholder(name) => Some(name.as_str()),
+            _ => None,
+        })
+    }
+
+    /// 値を差し込んで変換する。
+    /// 予約語に数字が複数ある場合は, その組み合わせをすべて返す (先頭は辞書の最初の数字だけを使ったもの)。
+    /// 値のない差し込み位置は `ErrorKind::MissingPlaceholder`,
+    /// ひな形にない名前の値は `ErrorKind::UnexpectedPlaceholder` になる。
+    pub fn render(
+        &self,
+        converter: &Converter,
+        values: &HashMap<&str, Value>,
+    ) -> Result<Vec<String>, Error> {
+        if let Some(name) = values
+            .keys()
+            .filter(|name| !self.placeholders().any(|placeholder| placeholder == **name))
+            .min()
+        {
+            return Err(Error::from(ErrorKind::UnexpectedPlaceholder {
+                name: name.to_string(),
+            }));
+        }
+        let mut rendered = vec![String::new()];
+        for piece in &self.pieces {
+            let codes = match piece {
+                Piece::Text(text) => vec![encode(converter, text)?],
+                Piece::Digits(digits) => vec![digits.clone()],
+                Piece::Placeholder(name) => match values.get(name.as_str()) {
+                    Some(Value::Text(text)) => vec![encode(converter, text)?],
+                    Some(Value::Digits(digits)) => vec![raw_digits(digits)?],
+                    Some(Value::Reserved(phrase)) => match converter.reserved_entry(phrase) {
+                        Some((_, codes)) => codes.iter().map(|code| code.to_string()).collect(),
+                        None => {
+                            return Err(Error::from(ErrorKind::UnknownReservedWord {
+                                phrase: phrase.clone(),
+                            }))
+                        }
+                    },
+                    None => {
+                        return Err(Error::from(ErrorKind::MissingPlaceholder {
+                            name: name.clone(),
+                        }))
+                    }
+                },
+            };
+            rendered = rendered
+                .iter()
+                .flat_map(|head| codes.iter().map(move |code| format!("{}{}", head, code)))
+                .collect();
+        }
+        if rendered.iter().all(|message| message.is_empty()) {
+            return Err(Error::from(ErrorKind::EmptyInput));
+        }
+        Ok(rendered)
+    }
+}
+
+fn encode(converter: &Converter, text: &str) -> Result<String, Error> {
+    let mut digits = String::new();
+    converter.encode_into(text, &mut digits)?;
+    Ok(digits)
+}
+
+fn raw_digits(digits: &str) -> Result<String, Error> {
+    match digits
+        .chars()
+        .enumerate()
+        .find(|(_, ch)| !ch.is_ascii_digit())
+    {
+        Some((index, ch)) => Err(Error::from(ErrorKind::NonDigit { ch, index })),
+        None => Ok(digits.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "builtin-dictionary")]
+    #[test]
+    fn test_message_builder() {
+        let c = Converter::new();
+        let builder = MessageBuilder::new(&c)
+            .text("あした")
+            .reserved("ヨロシク")
+            .unwrap()
+            .raw_digits("0312345678")
+            .unwrap();
+        let parts = builder.build_parts().unwrap();
+        assert_eq!(
+            parts[1].kind,
+            MessagePartKind::Reserved {
+                phrase: "よろしく".to_string()
+            }
+        );
+        assert_eq!(parts[1].digits, "4649");
+        let digits: String = parts.iter().map(|part| part.digits.as_str()).collect();
+        assert_eq!(builder.build().unwrap(), digits);
+
+        let err = MessageBuilder::new(&c)
+            .reserved("なおちゃん")
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.kind(),
+            &ErrorKind::UnknownReservedWord {
+                phrase: "なおちゃん".to_string()
+            }
+        );
+        let err = MessageBuilder::new(&c).raw_digits("03-1234").err().unwrap();
+        assert_eq!(err.kind(), &ErrorKind::NonDigit { ch: '-', index: 2 });
+        let err = MessageBuilder::new(&c).text("筋肉").build().unwrap_err();
+        assert_eq!(
+            err.kind(),
+            &ErrorKind::UnsupportedCharacter {
+                ch: '筋', index: 0
+            }
+        );
+        assert_eq!(
+            MessageBuilder::new(&c).build().unwrap_err().kind(),
+            &ErrorKind::EmptyInput
+        );
+
+        let message = MessageBuilder::new(&c)
+            .raw_digits("0840")
+            .unwrap()
+            .raw_digits("4649")
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(message, "08404649");
+    }
+
+    #[cfg(feature = "builtin-dictionary")]
+    #[test]
+    fn test_template() {
+        let c = Converter::new();
+        let template = Template::parse("{place}で{time}にまってる").unwrap();
+        assert_eq!(
+            template.placeholders().collect::<Vec<_>>(),
+            vec!["place", "time"]
+        );
+        let mut values = HashMap::new();
+        values.insert("place", Value::Text("えき".to_string()));
+        values.insert("time", Value::Digits("7".to_string()));
+        let expected = MessageBuilder::new(&c)
+            .text("えき")
+            .text("て゛")
+            .raw_digits("7")
+            .unwrap()
+            .text("にまってる")
+            .build()
+            .unwrap();
+        assert_eq!(template.render(&c, &values).unwrap(), vec![expected]);
+
+        // 予約語の数字が複数あれば組み合わせをすべて返す
+        let template = Template::parse("{greeting}TELして{number}").unwrap();
+        let mut values = HashMap::new();
+        values.insert("greeting", Value::Reserved("おはよう".to_string()));
+        values.insert("number", Value::Digits("0312345678".to_string()));
+        let rendered = template.render(&c, &values).unwrap();
+        let tel = MessageBuilder::new(&c).text("TELして").build().unwrap();
+        assert_eq!(
+            rendered,
+            vec![
+                format!("840{}0312345678", tel),
+                format!("0840{}0312345678", tel)
+            ]
+        );
+        // 同じひな形を別の値で使い回せる
+        values.insert("greeting", Value::Reserved("よろしく".to_string()));
+        assert_eq!(
+            template.render(&c, &values).unwrap(),
+            vec![format!("4649{}0312345678", tel)]
+        );
+
+        values.insert("greeting", Value::Reserved("なおちゃん".to_string()));
+        let err = template.render(&c, &values).unwrap_err();
+        assert_eq!(
+            err.kind(),
+            &ErrorKind::UnknownReservedWord {
+                phrase: "なおちゃん".to_string()
+            }
+        );
+        values.insert("greeting", Value::Text("やあ".to_string()));
+        values.insert("number", Value::Digits("03-1234".to_string()));
+        let err = template.render(&c, &values).unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::NonDigit { ch: '-', index: 2 });
+        values.remove("number");
+        let err = template.render(&c, &values).unwrap_err();
+        assert_eq!(
+            err.kind(),
+            &ErrorKind::MissingPlaceholder {
+                name: "number".to_string()
+            }
+        );
+        values.insert("number", Value::Digits("0".to_string()));
+        values.insert("place", Value::Text("えき".to_string()));
+        let err = template.render(&c, &values).unwrap_err();
+        assert_eq!(
+            err.kind(),
+            &ErrorKind::UnexpectedPlaceholder {
+                name: "place".to_string()
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "\"place\" is not a placeholder in the template"
+        );
+    }
+
+    #[cfg(feature = "decode")]
+    #[test]
+    fn test_template_braces() {
+        let c = Converter::builder()
+            .add_char_mapping('{', "80")
+            .add_char_mapping('}', "70")
+            .build()
+            .unwrap();
+        assert_eq!(
+            Template::parse("{{name}}").unwrap().placeholders().count(),
+            0
+        );
+        let template = Template::parse("{{{name}}}{0840}").unwrap();
+        let mut values = HashMap::new();
+        values.insert("name", Value::Text("A".to_string()));
+        let expected = MessageBuilder::new(&c).text("{A}").build().unwrap() + "0840";
+        assert_eq!(template.render(&c, &values).unwrap(), vec![expected]);
+    }
+
+    #[test]
+    fn test_unbalanced_braces() {
+        let err = Template::parse("{name").unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::UnbalancedBrace { index: 0 });
+        let err = Template::parse("a}").unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::UnbalancedBrace { index: 1 });
+        let err = Template::parse("{a{b}").unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::UnbalancedBrace { index: 2 });
+        let err = Template::parse("")
+            .unwrap()
+            .render(&Converter::new(), &HashMap::new());
+        assert_eq!(err.unwrap_err().kind(), &ErrorKind::EmptyInput);
+    }
+}
@@ -0,0 +1,106 @@
+//! かなの2文字の並びの頻度から, 戻した文字列のもっともらしさを点数にする
+//!
+//! `ngram` featureで有効になります。
+//! 頻度表 (`kana_ngrams.tsv`) は, よく使う言葉に助詞を付けた短い言葉から数えた小さなものです。
+//! 点数は比べるためのもので, 確率としての意味はありません。
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use super::two_touch_input::Converter;
+
+/// 表にない文字も数えるために, 回数に足す文字の種類の数
+const VOCABULARY: f64 = 100.0;
+
+/// 1文字と2文字の並びの出現回数
+struct Table {
+    unigrams: HashMap<char, u32>,
+    bigrams: HashMap<(char, char), u32>,
+    total: u32,
+}
+
+fn table() -> &'static Table {
+    static TABLE: OnceLock<Table> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        let mut table = Table {
+            unigrams: HashMap::new(),
+            bigrams: HashMap::new(),
+            total: 0,
+        };
+        for line in include_str!("kana_ngrams.tsv").lines() {
+            if line.starts_with('#') {
+                continue;
+            }
+            let mut columns = line.split('\t');
+            let (key, count) = match (columns.next(), columns.next()) {
+                (Some(key), Some(count)) => (key, count.parse().unwrap_or(0)),
+                _ => continue,
+            };
+            let mut chars = key.chars();
+            match (chars.next(), chars.next()) {
+                (Some(a), None) => {
+                    table.unigrams.insert(a, count);
+                    table.total += count;
+                }
+                (Some(a), Some(b)) => {
+                    table.bigrams.insert((a, b), count);
+                }
+                _ => {}
+            }
+        }
+        table
+    })
+}
+
+impl Converter {
+    /// 戻した文字列のもっともらしさを返す。大きいほどもっともらしい。
+    /// 1文字目の出現確率と, 前の文字に続く確率の対数を足し, 文字数で割ったもの。
+    /// 文字は `normalize_char` で正規化してから数え, かな以外の文字は表にないものとして低く数える。
+    /// 空の文字列は `f64::NEG_INFINITY` になる。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// let c = Converter::new();
+    /// assert!(c.score_decoded("やきにく") > c.score_decoded("をぬへむ"));
+    /// ```
+    pub fn score_decoded(&self, text: &str) -> f64 {
+        let table = table();
+        let chars: Vec<char> = text.chars().map(|ch| self.normalize_char(ch)).collect();
+        let first = match chars.first() {
+            Some(first) => first,
+            None => return f64::NEG_INFINITY,
+        };
+        let count = |ch: &char| f64::from(table.unigrams.get(ch).copied().unwrap_or(0));
+        let mut score = ((count(first) + 1.0) / (f64::from(table.total) + VOCABULARY)).ln();
+        for pair in chars.windows(2) {
+            let bigram = table.bigrams.get(&(pair[0], pair[1])).copied().unwrap_or(0);
+            score += ((f64::from(bigram) + 1.0) / (count(&pair[0]) + VOCABULARY)).ln();
+        }
+        score / chars.len() as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_score_decoded() {
+        let c = Converter::new();
+        assert!(c.score_decoded("やきにく") > c.score_decoded("をぬへむ"));
+        assert!(c.score_decoded("こんにちは") > c.score_decoded("いYそけF"));
+        assert_eq!(c.score_decoded(""), f64::NEG_INFINITY);
+    }
+
+    #[test]
+    fn test_recovery_uses_score() {
+        let c = Converter::new();
+        // どちらもすべてかななので, featureがなければ飛ばした桁が少ない `うすき` が先になる
+        let candidates = c.decode_with_recovery("133322");
+        assert_eq!(candidates[0].message, "すし");
+        assert_eq!(candidates[0].skipped, vec![0, 5]);
+        assert_eq!(candidates[1].message, "うすき");
+        assert_eq!(candidates[0].score, candidates[1].score);
+    }
+}
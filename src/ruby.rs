@@ -0,0 +1,152 @@
+//! `<ruby>渋谷<rt>しぶや</rt></ruby>` のようなHTMLのルビを読みに置き換えて変換する
+//!
+//! `ruby` featureで有効になります。
+//! 扱うタグは `ruby`, `rt`, `rp` だけで, それ以外のタグや閉じていないタグはエラーになります。
+//! `rp` の中身は読みに含めません。
+
+use super::two_touch_input::{Converter, Error, ErrorKind};
+
+/// ルビのタグを取り除き, ルビの付いた部分を `rt` の読みに置き換えた文字列を返す。
+/// `rt` のない `ruby` は中身をそのまま残す。
+/// エラーの `index` はHTMLの先頭からの文字位置 (0始まり)。
+///
+/// ## Example
+/// ```
+/// # use pokebell::ruby::strip_ruby;
+/// let text = strip_ruby("<ruby>渋谷<rp>(</rp><rt>しぶや</rt><rp>)</rp></ruby>で").unwrap();
+/// assert_eq!(text, "しぶやで");
+/// ```
+pub fn strip_ruby(html: &str) -> Result<String, Error> {
+    #[derive(PartialEq)]
+    enum State {
+        Text,
+        Base,
+        Rt,
+        Rp,
+    }
+
+    let invalid = |index, reason| Err(Error::from(ErrorKind::InvalidRuby { index, reason }));
+    let mut text = String::with_capacity(html.len());
+    let mut base = String::new();
+    let mut reading = String::new();
+    let mut state = State::Text;
+    let mut ruby_start = 0;
+    let mut chars = html.chars().enumerate();
+    while let Some((index, ch)) = chars.next() {
+        if ch != '<' {
+            match state {
+                State::Text => text.push(ch),
+                State::Base => base.push(ch),
+                State::Rt => reading.push(ch),
+                State::Rp => {}
+            }
+            continue;
+        }
+        let mut tag = String::new();
+        let mut closed = false;
+        for (_, ch) in chars.by_ref() {
+            if ch == '>' {
+                closed = true;
+                break;
+            }
+            tag.push(ch);
+        }
+        if !closed {
+            return invalid(index, "unclosed tag");
+        }
+        let name = tag
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        state = match (state, name.as_str()) {
+            (State::Text, "ruby") => {
+                ruby_start = index;
+                base.clear();
+                reading.clear();
+                State::Base
+            }
+            (State::Base, "ruby") | (State::Rt, "ruby") | (State::Rp, "ruby") => {
+                return invalid(index, "nested ruby")
+            }
+            (State::Base, "rt") => State::Rt,
+            (State::Base, "rp") => State::Rp,
+            (State::Rt, "/rt") => State::Base,
+            (State::Rp, "/rp") => State::Base,
+            (State::Rt, _) => return invalid(index, "unclosed rt"),
+            (State::Base, "/ruby") => {
+                if reading.is_empty() {
+                    text.push_str(&base);
+                } else {
+                    text.push_str(&reading);
+                }
+                State::Text
+            }
+            _ => return invalid(index, "unexpected tag"),
+        };
+    }
+    if state != State::Text {
+        return invalid(ruby_start, "unclosed ruby");
+    }
+    Ok(text)
+}
+
+impl Converter {
+    /// `strip_ruby` でルビを読みに置き換えてから `convert_to_two_touch_string` で変換する。
+    /// 変換できない文字のエラーの位置は, タグを取り除いた後の文字列での位置になる。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::Converter;
+    /// let c = Converter::new();
+    /// c.convert_ruby("<ruby>渋谷<rt>しぶや</rt></ruby>").unwrap(); // ["428", "32630481"]
+    /// ```
+    pub fn convert_ruby(&self, html: &str) -> Result<Vec<String>, Error> {
+        self.convert_to_two_touch_string(strip_ruby(html)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_convert_ruby() {
+        let c = Converter::new();
+        let html = "<ruby>今日<rt>きょう</rt></ruby>は<RUBY class=\"r\">渋<rt>しぶ</rt>谷<rt>や</rt></RUBY>で\
+                    <ruby>待<rp>(</rp><rt>ま</rt><rp>)</rp></ruby>ってる";
+        assert_eq!(strip_ruby(html).unwrap(), "きょうはしぶやでまってる");
+        assert_eq!(
+            c.convert_ruby(html).unwrap(),
+            c.convert_to_two_touch_string("きょうはしぶやでまってる")
+                .unwrap()
+        );
+        assert_eq!(strip_ruby("<ruby>あ</ruby>い").unwrap(), "あい");
+        // エラーの位置はタグを取り除いた後の文字列で数える
+        assert_eq!(
+            c.convert_ruby("<ruby>渋谷<rt>しぶや</rt></ruby>筋")
+                .unwrap_err()
+                .kind(),
+            &ErrorKind::UnsupportedCharacter {
+                ch: '筋', index: 3
+            }
+        );
+    }
+
+    #[test]
+    fn test_malformed_ruby() {
+        let reason = |html: &str| match strip_ruby(html).unwrap_err().kind() {
+            ErrorKind::InvalidRuby { index, reason } => (*index, *reason),
+            kind => panic!("unexpected {:?}", kind),
+        };
+        assert_eq!(reason("<ruby>渋谷<rt>しぶや</ruby>"), (15, "unclosed rt"));
+        assert_eq!(
+            reason("<ruby>渋<ruby>谷</ruby><rt>しぶや</rt></ruby>"),
+            (7, "nested ruby")
+        );
+        assert_eq!(reason("あ<ruby>渋谷<rt>しぶや</rt>"), (1, "unclosed ruby"));
+        assert_eq!(reason("あ<b>い</b>"), (1, "unexpected tag"));
+        assert_eq!(reason("あ<ruby"), (1, "unclosed tag"));
+        assert_eq!(reason("</ruby>"), (0, "unexpected tag"));
+    }
+}
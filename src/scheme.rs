@@ -0,0 +1,139 @@
+//! 入力方式を切り替えて使うためのトレイト
+//!
+//! アプリケーションから入力方式をジェネリクスや `Box<dyn PagerScheme>` で扱えるようにします。
+//! `Converter` はプロファイルごとに別の方式として実装しています。
+
+use super::two_touch_input::{Converter, Error, ErrorKind, Profile};
+
+/// `scheme_by_name` で選べる方式の名前
+pub const SCHEME_NAMES: &[&str] = &["two-touch", "numeric-only"];
+
+/// ポケベルの入力方式
+///
+/// ## Example
+/// ```
+/// use pokebell::scheme::PagerScheme;
+/// use pokebell::two_touch_input::Converter;
+///
+/// fn roundtrip<S: PagerScheme>(scheme: &S, text: &str) -> String {
+///     let digits = scheme.encode(text).unwrap();
+///     scheme.decode(&digits[0]).unwrap()
+/// }
+/// assert_eq!(roundtrip(&Converter::new(), "やきにく"), "やきにく");
+/// ```
+pub trait PagerScheme {
+    /// 文字列を数字に変換し, 候補を返す
+    fn encode(&self, text: &str) -> Result<Vec<String>, Error>;
+
+    /// 数字を文字列に変換する
+    fn decode(&self, digits: &str) -> Result<String, Error>;
+
+    /// 方式の名前。`scheme_by_name` に渡すと同じ方式になる
+    fn name(&self) -> &str;
+}
+
+/// `Converter::convert_to_two_touch_string` と `Converter::convert_from_two_touch_string` を使う。
+/// 名前は `Profile::Full` では `two-touch`, `Profile::NumericOnly` では `numeric-only`
+impl PagerScheme for Converter {
+    fn encode(&self, text: &str) -> Result<Vec<String>, Error> {
+        self.convert_to_two_touch_string(text)
+    }
+
+    fn decode(&self, digits: &str) -> Result<String, Error> {
+        self.convert_from_two_touch_string(digits)
+    }
+
+    fn name(&self) -> &str {
+        match self.profile() {
+            Profile::NumericOnly => "numeric-only",
+            _ => "two-touch",
+        }
+    }
+}
+
+/// 名前から方式を作る。CLIやC言語のインターフェースで方式を選ぶのに使う。
+/// 使える名前は `SCHEME_NAMES` で, それ以外は `ErrorKind::UnknownScheme` になる。
+///
+/// ## Example
+/// ```
+/// # use pokebell::scheme::scheme_by_name;
+/// let scheme = scheme_by_name("numeric-only").unwrap();
+/// assert_eq!(scheme.encode("03-1234").unwrap(), vec!["03-1234"]);
+/// assert_eq!(scheme.name(), "numeric-only");
+/// ```
+pub fn scheme_by_name(name: &str) -> Result<Box<dyn PagerScheme + Send + Sync>, Error> {
+    match name {
+        "two-touch" => Ok(Box::new(Converter::new())),
+        "numeric-only" => Ok(Box::new(
+            Converter::new().with_profile(Profile::NumericOnly),
+        )),
+        _ => Err(Error::from(ErrorKind::UnknownScheme {
+            name: name.to_string(),
+        })),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 数字をそのまま送る方式
+    struct Passthrough;
+
+    impl PagerScheme for Passthrough {
+        fn encode(&self, text: &str) -> Result<Vec<String>, Error> {
+            match text.char_indices().find(|(_, ch)| !ch.is_ascii_digit()) {
+                Some((index, ch)) => Err(Error::from(ErrorKind::NonDigit { ch, index })),
+                None => Ok(vec![text.to_string()]),
+            }
+        }
+
+        fn decode(&self, digits: &str) -> Result<String, Error> {
+            self.encode(digits)
+                .map(|mut candidates| candidates.remove(0))
+        }
+
+        fn name(&self) -> &str {
+            "passthrough"
+        }
+    }
+
+    fn send<S: PagerScheme + ?Sized>(scheme: &S, text: &str) -> Result<(String, String), Error> {
+        let digits = scheme.encode(text)?.remove(0);
+        let decoded = scheme.decode(&digits)?;
+        Ok((digits, decoded))
+    }
+
+    #[test]
+    fn test_generic_schemes() {
+        let c = Converter::new();
+        assert_eq!(
+            send(&c, "やきにく").unwrap(),
+            ("81225223".to_string(), "やきにく".to_string())
+        );
+        assert_eq!(
+            send(&Passthrough, "0840").unwrap(),
+            ("0840".to_string(), "0840".to_string())
+        );
+        assert!(send(&Passthrough, "やきにく").is_err());
+
+        let schemes: Vec<Box<dyn PagerScheme + Send + Sync>> = SCHEME_NAMES
+            .iter()
+            .map(|name| scheme_by_name(name).unwrap())
+            .collect();
+        let names: Vec<&str> = schemes.iter().map(|scheme| scheme.name()).collect();
+        assert_eq!(names, SCHEME_NAMES);
+        assert_eq!(send(schemes[1].as_ref(), "0312").unwrap().0, "0312");
+        assert!(send(schemes[1].as_ref(), "やきにく").is_err());
+    }
+
+    #[test]
+    fn test_unknown_scheme() {
+        assert_eq!(
+            scheme_by_name("multi-tap").err().unwrap().kind(),
+            &ErrorKind::UnknownScheme {
+                name: "multi-tap".to_string()
+            }
+        );
+    }
+}
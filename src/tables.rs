@@ -0,0 +1,125 @@
+//! 2タッチ入力の変換表と正規化の表
+//!
+//! `Converter::new()` が使う表そのもので, `pokebell-core` の表をそのまま公開します。
+//! `Converter` を作らずに表だけを使う場合に使えます。
+//!
+//! 変換表はどれも数字の順 (1桁目, 2桁目の順) に, 正規化の表は置き換える文字の順 (文字コードの順) に並べます。
+//! この順は変えません。
+//!
+//! ## Example
+//! ```
+//! use pokebell::tables::{BASE_TABLE, COMPOSED_TABLE, NORMALIZATION};
+//! assert_eq!(BASE_TABLE[0], ('0', "00"));
+//! assert!(COMPOSED_TABLE.contains(&('が', "2104")));
+//! assert!(NORMALIZATION.contains(&('ぁ', 'あ')));
+//! ```
+
+pub use pokebell_core::tables::{BASE_TABLE, COMPOSED_TABLE, NORMALIZATION};
+
+#[cfg(feature = "encode")]
+pub(crate) use pokebell_core::tables::builtin_code;
+#[cfg(feature = "decode")]
+pub(crate) use pokebell_core::tables::PAIR_TABLE;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(all(feature = "encode", feature = "decode"))]
+    use crate::two_touch_input::{Converter, DakutenMode, DecodeOptions};
+
+    #[test]
+    fn test_generated_tables() {
+        assert_eq!(BASE_TABLE.len() + COMPOSED_TABLE.len(), 120);
+        assert_eq!(NORMALIZATION.len(), 57);
+        assert!(BASE_TABLE.contains(&('あ', "11")));
+        assert!(BASE_TABLE.contains(&(' ', "88")));
+        assert!(COMPOSED_TABLE.contains(&('ば', "6104")));
+        assert!(NORMALIZATION.contains(&('\u{3000}', ' ')));
+        assert!(NORMALIZATION.contains(&('Ｋ', 'K')));
+        for table in [BASE_TABLE, COMPOSED_TABLE] {
+            assert!(table.windows(2).all(|pair| pair[0].1 < pair[1].1));
+        }
+        assert!(BASE_TABLE.iter().all(|(_, code)| code.len() == 2));
+        assert!(COMPOSED_TABLE.iter().all(|(_, code)| code.len() == 4));
+        assert!(NORMALIZATION.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    }
+
+    #[cfg(feature = "encode")]
+    #[test]
+    fn test_builtin_code() {
+        for &(ch, code) in BASE_TABLE.iter().chain(COMPOSED_TABLE) {
+            assert_eq!(builtin_code(ch), Some(code));
+        }
+        assert_eq!(builtin_code('筋'), None);
+    }
+
+    #[cfg(feature = "decode")]
+    #[test]
+    fn test_pair_table() {
+        for &(ch, code) in BASE_TABLE {
+            let digits: Vec<usize> = code.bytes().map(|b| usize::from(b - b'0')).collect();
+            assert_eq!(PAIR_TABLE[digits[0]][digits[1]], Some(ch));
+        }
+        let pairs = PAIR_TABLE.iter().flatten().filter(|ch| ch.is_some());
+        assert_eq!(pairs.count(), BASE_TABLE.len());
+    }
+
+    #[cfg(all(feature = "encode", feature = "decode"))]
+    #[test]
+    fn test_converter_matches_tables() {
+        let c = Converter::without_reserved_words();
+        let code_of = |ch: char| {
+            BASE_TABLE
+                .iter()
+                .chain(COMPOSED_TABLE)
+                .find(|&&(other, _)| other == ch)
+                .map(|&(_, code)| code)
+        };
+        let composed = DecodeOptions::default().with_dakuten_mode(DakutenMode::Composed);
+        for &(ch, code) in BASE_TABLE.iter().chain(COMPOSED_TABLE) {
+            assert_eq!(c.encode_char(ch), Some(code), "{:?}", ch);
+            let decoded = c
+                .convert_from_two_touch_string_with(code, &composed)
+                .unwrap();
+            assert_eq!(decoded, ch.to_string(), "{}", code);
+        }
+        for &(from, to) in NORMALIZATION {
+            assert_eq!(c.encode_char(from), code_of(to), "{:?}", from);
+        }
+        assert!(c.table().eq(BASE_TABLE.iter().copied()));
+        assert!(c.composed_table().eq(COMPOSED_TABLE.iter().copied()));
+        assert!(c.normalizations().eq(NORMALIZATION.iter().copied()));
+    }
+
+    #[cfg(all(feature = "encode", feature = "decode"))]
+    #[test]
+    fn test_builtin_matches_builder() {
+        // `Converter::new()` は生成した関数で引き, builderで作ると表をHashMapにする。どちらも同じ結果になる
+        let builtin = Converter::new();
+        let built = Converter::builder().build().unwrap();
+        let composed = DecodeOptions::default().with_dakuten_mode(DakutenMode::Composed);
+        let chars = BASE_TABLE.iter().chain(COMPOSED_TABLE).map(|&(ch, _)| ch);
+        for ch in chars.chain(NORMALIZATION.iter().map(|&(from, _)| from)) {
+            assert_eq!(builtin.encode_char(ch), built.encode_char(ch), "{:?}", ch);
+            assert_eq!(builtin.normalize_char(ch), built.normalize_char(ch));
+        }
+        for first in 0..100 {
+            for second in [0, 4, 5] {
+                let code = format!("{:02}{:02}", first, second);
+                for code in [&code[..2], &code[..]] {
+                    assert_eq!(
+                        builtin.convert_from_two_touch_string_with(code, &composed),
+                        built.convert_from_two_touch_string_with(code, &composed),
+                        "{}",
+                        code
+                    );
+                }
+            }
+        }
+        assert_eq!(builtin.encode_char('筋'), built.encode_char('筋'));
+        assert!(builtin.table().eq(built.table()));
+        assert!(builtin.composed_table().eq(built.composed_table()));
+        assert!(builtin.normalizations().eq(built.normalizations()));
+        assert!(builtin.self_check().is_ok());
+    }
+}
@@ -0,0 +1,101 @@
+//! 利用者のプロパティテストで使う, 変換できる入力を作るproptestの `Strategy`
+//!
+//! `test-util` featureで有効になります。proptestを任意の依存として入れるので,
+//! `[dev-dependencies]` で有効にしてテストだけで使ってください。製品のビルドで使うものではありません。
+//!
+//! ```toml
+//! [dev-dependencies]
+//! pokebell = { version = "0.1", features = ["test-util"] }
+//! ```
+//!
+//! 作る値は, ライブラリ全体で共有する既定の `Converter` (`pokebell::encode` などと同じ) の表と辞書から選びます。
+
+use proptest::prelude::*;
+
+use super::two_touch_input::shared_converter;
+
+/// 1文字ずつ変換できる, 空白以外の文字。変換表の文字と, 変換表の文字に正規化される文字
+fn encodable_chars() -> Vec<char> {
+    let converter = shared_converter();
+    let mut chars: Vec<char> = converter
+        .table()
+        .chain(converter.composed_table())
+        .map(|(ch, _)| ch)
+        .chain(converter.normalizations().map(|(from, _)| from))
+        .filter(|&ch| !ch.is_whitespace() && converter.encode_char(ch).is_some())
+        .collect();
+    chars.sort_unstable();
+    chars.dedup();
+    chars
+}
+
+/// `pokebell::encode` が成功する, 1文字以上 `max_len` 文字以下の文字列
+///
+/// ## Example
+/// ```
+/// # use proptest::prelude::*;
+/// proptest!(|(text in pokebell::test_util::any_encodable_text(8))| {
+///     prop_assert!(pokebell::encode(&text).is_ok());
+/// });
+/// ```
+pub fn any_encodable_text(max_len: usize) -> impl Strategy<Value = String> {
+    proptest::collection::vec(
+        proptest::sample::select(encodable_chars()),
+        1..=max_len.max(1),
+    )
+    .prop_map(|chars| chars.into_iter().collect())
+}
+
+/// `pokebell::decode` が成功する, 変換表の2桁の数字を1組以上 `max_pairs` 組以下つなげた数字
+///
+/// ## Example
+/// ```
+/// # use proptest::prelude::*;
+/// proptest!(|(code in pokebell::test_util::any_valid_code(8))| {
+///     prop_assert!(pokebell::decode(&code).is_ok());
+/// });
+/// ```
+pub fn any_valid_code(max_pairs: usize) -> impl Strategy<Value = String> {
+    let codes: Vec<&'static str> = shared_converter().table().map(|(_, code)| code).collect();
+    proptest::collection::vec(proptest::sample::select(codes), 1..=max_pairs.max(1))
+        .prop_map(|codes| codes.concat())
+}
+
+/// 既定の辞書の予約語の表記。`pokebell::encode` の最初の候補が予約語の数字になる
+#[cfg(feature = "builtin-dictionary")]
+pub fn any_reserved_phrase() -> impl Strategy<Value = String> {
+    let phrases: Vec<String> = shared_converter()
+        .reserved_phrases()
+        .map(|(phrase, _)| phrase.to_string())
+        .collect();
+    proptest::sample::select(phrases)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    proptest! {
+        #[test]
+        fn prop_any_encodable_text(text in any_encodable_text(12)) {
+            let len = text.chars().count();
+            prop_assert!((1..=12).contains(&len));
+            prop_assert!(shared_converter().is_encodable(&text));
+            prop_assert!(crate::encode(&text).is_ok());
+        }
+
+        #[test]
+        fn prop_any_valid_code(code in any_valid_code(12)) {
+            prop_assert!(code.len() % 2 == 0 && (2..=24).contains(&code.len()));
+            let decoded = crate::decode(&code).unwrap();
+            prop_assert!(!crate::encode(&decoded).unwrap().is_empty());
+        }
+
+        #[cfg(feature = "builtin-dictionary")]
+        #[test]
+        fn prop_any_reserved_phrase(phrase in any_reserved_phrase()) {
+            let codes = shared_converter().reserved_codes_for(&phrase).unwrap();
+            prop_assert_eq!(&crate::encode(&phrase).unwrap()[0], &codes[0]);
+        }
+    }
+}
@@ -1,509 +1,9684 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "encode")]
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+#[cfg(all(feature = "encode", feature = "decode"))]
 use std::collections::HashMap;
+#[cfg(feature = "encode")]
+use std::collections::{BTreeMap, HashSet};
+use std::num::NonZeroUsize;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
 
+#[cfg(feature = "encode")]
+use super::dictionary::MergeStrategy;
+#[cfg(any(feature = "encode", feature = "decode"))]
+use super::tables;
+
+/// 2タッチ入力の数字。既定の表や辞書の数字は埋め込んだデータを指し, 利用者が追加した数字だけを確保する
+#[cfg(feature = "encode")]
+type Code = Cow<'static, str>;
+
+/// 2タッチ入力の変換器
+///
+/// `Clone` は変換表と予約語の辞書を複製せずに共有するので, スレッドごとに持つ場合も安く作れます。
+/// `add_reserved_word` などで予約語を変えると, 共有していた場合だけ辞書を複製してから変えます (copy-on-write)。
+/// ほかのConverterの辞書は変わりません。
+///
+/// `encode` featureを無効にすると2タッチ入力に変換するメソッドと予約語の辞書が,
+/// `decode` featureを無効にすると2タッチ入力から戻すメソッドがなくなります。
+/// `builder` や `self_check` のように両方の表を使うものは, 両方を有効にした場合だけ使えます。
+#[derive(Clone)]
 pub struct Converter {
-    base_map: HashMap<char, String>,
-    inversed_base_map: HashMap<String, char>,
-    normalization_map: HashMap<char, char>,
-    reserved_word_map: HashMap<String, Vec<String>>,
+    /// 変換表と正規化の表。作った後は変えない
+    #[cfg_attr(not(any(feature = "encode", feature = "decode")), allow(dead_code))]
+    tables: Arc<ConversionTables>,
+    /// 予約語の辞書。空なら既定の辞書を最初に引くときに入れる。変えるときは `Arc::make_mut` で複製する
+    #[cfg(feature = "encode")]
+    reserved: OnceLock<Arc<ReservedDictionary>>,
+    /// `convert_to_two_touch_string` などで使う設定
+    options: ConvertOptions,
+    profile: Profile,
+}
+
+/// 変換表, 逆引きの表と正規化の表
+#[derive(Debug, Clone)]
+struct ConversionTables {
+    #[cfg(feature = "encode")]
+    base_map: CharTable,
+    /// 変換表の逆引き
+    #[cfg(feature = "decode")]
+    inverse_table: InverseTable,
+    #[cfg(feature = "encode")]
+    normalization_map: NormalizationTable,
+    /// 1文字を複数の文字に置き換える規則。値は置き換え先の文字列と, それを変換した数字。
+    /// `ConverterBuilder` でだけ入れるので, builderと同じく両方のfeatureが必要
+    #[cfg(all(feature = "encode", feature = "decode"))]
+    expansion_map: HashMap<char, (String, String)>,
+}
+
+/// 予約語の辞書と, 数字で引くための索引
+#[cfg(feature = "encode")]
+#[derive(Clone, Default)]
+struct ReservedDictionary {
+    /// 表記の違いをそろえた予約語をキーにした辞書。前方一致で引けるようにキーの順に並べる
+    word_map: BTreeMap<String, ReservedWord>,
+    /// 予約語の数字を桁数ごとに分けたもの。値は数字と `word_map` のキー
+    code_index: BTreeMap<usize, Vec<(Code, String)>>,
+    /// 予約語の数字と `word_map` のキーを数字の順に並べたもの。前方一致で引くのに使う
+    prefix_index: BTreeSet<(Code, String)>,
 }
 
-impl Converter {
-    /// 入力された文字列を2タッチ入力の数字に変換
-    /// 入力可能な文字列は [2タッチ入力](https://ja.wikipedia.org/wiki/2%E3%82%BF%E3%83%83%E3%83%81%E5%85%A5%E5%8A%9B) , [ポケベル解読！数字の意味が分かる早見表！](https://koma-yome.com/archives/724) 参照
-    ///
-    /// ## Example
-    /// ```
-    /// let c = Converter::new();
-    /// c.convert_to_two_touch_string("ごくろうさん".to_string()).unwrap(); // ["5963", "25042395133103"]
-    /// ```
-    pub fn convert_to_two_touch_string(&self, val: String) -> Result<Vec<String>, Error> {
-        if val.is_empty() {
-            return Err(Error::from(ErrorKind::ParseError));
+impl Converter {
+    /// 入力された文字列を2タッチ入力の数字に変換
+    /// 入力可能な文字列は [2タッチ入力](https://ja.wikipedia.org/wiki/2%E3%82%BF%E3%83%83%E3%83%81%E5%85%A5%E5%8A%9B) , [ポケベル解読！数字の意味が分かる早見表！](https://koma-yome.com/archives/724) 参照
+    /// `ConvertOptions::with_escapes` を有効にした場合は, `{0840}` のように `{}` で囲まれた数字をそのまま出力する。
+    /// 入力全体が予約語に一致する場合は, "ご苦労さん" のように1文字ずつ変換できなくても予約語の候補だけを返す。
+    /// 以前は `String` だけを受け取っていたが, 現在は引数が `AsRef<str>` で `&str` も `String` も渡せる。
+    /// `&str` 用の別のメソッドと非推奨の古いメソッドを置く代わりにこのシグネチャにしたので,
+    /// `String` を渡していたコードは書き換えずにそのまま使える。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// let c = Converter::new();
+    /// c.convert_to_two_touch_string("ごくろうさん").unwrap(); // ["5963", "25042395133103"]
+    /// c.convert_to_two_touch_string("こんにちは筋肉").unwrap_err(); // unsupported character '筋' at index 5
+    /// ```
+    #[cfg(feature = "encode")]
+    pub fn convert_to_two_touch_string<S: AsRef<str>>(&self, val: S) -> Result<Vec<String>, Error> {
+        self.convert_to_two_touch_string_with(val.as_ref(), &self.options)
+    }
+
+    /// `options` の設定で `convert_to_two_touch_string` と同じように変換する。
+    /// Converterに設定した候補の並べ方などの代わりに `options` を使う。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// let c = Converter::new();
+    /// let options = ConvertOptions::default()
+    ///     .with_candidate_options(CandidateOptions::default().with_order(CandidateOrder::LiteralFirst))
+    ///     .with_fallback(Some('?'));
+    /// c.convert_to_two_touch_string_with("ごくろうさん", &options).unwrap(); // ["25042395133103", "5963"]
+    /// c.convert_to_two_touch_string_with("焼肉", &options).unwrap(); // ["6767"]
+    /// ```
+    #[cfg(feature = "encode")]
+    pub fn convert_to_two_touch_string_with(
+        &self,
+        val: &str,
+        options: &ConvertOptions,
+    ) -> Result<Vec<String>, Error> {
+        options.validate(self)?;
+        let mut handler = |_, _| options.lenient.fallback_action();
+        self.convert_tagged(val, &mut handler, options)
+            .map(|(candidates, _)| candidates.into_iter().map(Candidate::into_digits).collect())
+    }
+
+    /// 候補の中から1つだけ選んで返す。
+    /// 最も短い候補を選び, 同じ長さの場合は `CandidateOrder` で前に来るもの (既定では予約語) を選ぶ。
+    /// `CandidateOptions::rank_by_length` を有効にした `convert_to_two_touch_string` の先頭と同じ。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// # #[cfg(feature = "builtin-dictionary")] {
+    /// let c = Converter::new();
+    /// assert_eq!(c.encode_best("ごくろうさん").unwrap(), "5963");
+    /// # }
+    /// ```
+    #[cfg(feature = "encode")]
+    pub fn encode_best(&self, val: &str) -> Result<String, Error> {
+        self.encode_best_with(val, &self.options)
+    }
+
+    /// `options` の設定で `encode_best` と同じように1つだけ選んで返す。
+    /// `rank_by_length` の指定にかかわらず短い候補を選ぶ。
+    #[cfg(feature = "encode")]
+    pub fn encode_best_with(&self, val: &str, options: &ConvertOptions) -> Result<String, Error> {
+        let mut options = options.clone();
+        options.candidates.rank_by_length = true;
+        let candidates = self.convert_to_two_touch_string_with(val, &options)?;
+        // 候補は必ず1つ以上ある
+        Ok(candidates.into_iter().next().unwrap_or_default())
+    }
+
+    /// 2タッチ入力に変換できない文字を読み飛ばして変換する。
+    /// 読み飛ばした文字は入力先頭からの文字位置とともに `skipped` に入る。
+    /// すべての文字を読み飛ばした場合は, 入力全体が予約語に一致しない限りエラーになる。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// let c = Converter::new();
+    /// let result = c.convert_to_two_touch_string_lossy("やき肉").unwrap();
+    /// result.candidates; // ["8122"]
+    /// result.skipped; // [(2, '肉')]
+    /// ```
+    #[cfg(feature = "encode")]
+    pub fn convert_to_two_touch_string_lossy(&self, val: &str) -> Result<LossyConversion, Error> {
+        let options = LenientOptions {
+            skip_unsupported: true,
+            fallback: None,
+        };
+        self.convert_to_two_touch_string_lenient(val, &options)
+    }
+
+    /// `options` に従って, 2タッチ入力に変換できない文字を読み飛ばすか代替文字に置き換えて変換する。
+    /// どちらも指定しない場合は `convert_to_two_touch_string` と同じく最初の文字でエラーになる。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// let c = Converter::new();
+    /// let options = LenientOptions {
+    ///     skip_unsupported: false,
+    ///     fallback: Some('?'),
+    /// };
+    /// let result = c.convert_to_two_touch_string_lenient("筋肉A", &options).unwrap();
+    /// result.candidates; // ["676716"]
+    /// result.substituted; // [(0, '筋'), (1, '肉')]
+    /// ```
+    #[cfg(feature = "encode")]
+    pub fn convert_to_two_touch_string_lenient(
+        &self,
+        val: &str,
+        options: &LenientOptions,
+    ) -> Result<LossyConversion, Error> {
+        options.validate(self)?;
+        self.convert_to_two_touch_string_with_fallback(val, |_, _| options.fallback_action())
+    }
+
+    /// 2タッチ入力に変換できない文字が現れるたびに `handler` を呼び, その結果に従って変換する。
+    /// `handler` には正規化前の文字と入力先頭からの文字位置が渡される。
+    /// `Fallback::Replace` の文字列は通常どおり変換され, `handler` は再度呼ばれない。
+    /// 置き換えた文字列が変換できない場合は元の文字を指すエラーになる。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// let c = Converter::new();
+    /// let result = c
+    ///     .convert_to_two_touch_string_with_fallback("駅", |ch, _| match ch {
+    ///         '駅' => Fallback::Replace("えき".to_string()),
+    ///         _ => Fallback::Fail,
+    ///     })
+    ///     .unwrap();
+    /// result.candidates; // ["1422"]
+    /// ```
+    #[cfg(feature = "encode")]
+    pub fn convert_to_two_touch_string_with_fallback<F>(
+        &self,
+        val: &str,
+        mut handler: F,
+    ) -> Result<LossyConversion, Error>
+    where
+        F: FnMut(char, usize) -> Fallback,
+    {
+        let (candidates, conversion) = self.convert_tagged(val, &mut handler, &self.options)?;
+        Ok(LossyConversion {
+            candidates: candidates.into_iter().map(Candidate::into_digits).collect(),
+            ..conversion
+        })
+    }
+
+    /// 入力された文字列を2タッチ入力の数字に変換し, 各候補が予約語か1文字ずつ変換したものかを付けて返す。
+    /// 候補の並びは `convert_to_two_touch_string` と同じ。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// # #[cfg(feature = "builtin-dictionary")] {
+    /// let c = Converter::new();
+    /// let candidates = c.convert_to_two_touch("ごくろうさん").unwrap();
+    /// assert_eq!(candidates[0].digits(), "5963");
+    /// assert_eq!(candidates[0].phrase(), Some("ごくろうさん"));
+    /// assert!(!candidates[1].is_reserved());
+    /// # }
+    /// ```
+    #[cfg(feature = "encode")]
+    pub fn convert_to_two_touch(&self, val: &str) -> Result<Vec<Candidate>, Error> {
+        self.convert_tagged(val, &mut |_, _| Fallback::Fail, &self.options)
+            .map(|(candidates, _)| candidates)
+    }
+
+    /// `convert_to_two_touch_string_with_fallback` の本体。
+    /// 返す `LossyConversion` の `candidates` は空で, 候補は1つ目の値に入る。
+    #[cfg(feature = "encode")]
+    fn convert_tagged(
+        &self,
+        val: &str,
+        handler: &mut dyn FnMut(char, usize) -> Fallback,
+        options: &ConvertOptions,
+    ) -> Result<(Vec<Candidate>, LossyConversion), Error> {
+        let tokens = self.tokens(val, options)?;
+        self.convert_tokens(&tokens, handler, options)
+    }
+
+    /// `convert_tagged` の, 入力を `Token` に分けたあとの部分
+    #[cfg(feature = "encode")]
+    fn convert_tokens(
+        &self,
+        tokens: &[Token],
+        handler: &mut dyn FnMut(char, usize) -> Fallback,
+        options: &ConvertOptions,
+    ) -> Result<(Vec<Candidate>, LossyConversion), Error> {
+        let phrase: Option<String> = tokens.iter().map(Token::as_char).collect();
+        let reserved = phrase.and_then(|phrase| self.reserved_for(&phrase));
+        let tagged = |word: &ReservedWord| word.candidates.clone();
+        let mut literal = Literal::default();
+        match (reserved, self.encode_literal(tokens, handler, &mut literal)) {
+            (reserved, Ok(())) => {
+                let reserved = reserved.map(tagged).unwrap_or_default();
+                Ok((
+                    self.arrange_candidates(reserved, Some(literal.digits), options),
+                    LossyConversion {
+                        candidates: Vec::new(),
+                        skipped: literal.skipped,
+                        substituted: literal.substituted,
+                        literal_error: None,
+                    },
+                ))
+            }
+            // "ご苦労さん" のように漢字を含む予約語は1文字ずつには変換できないので,
+            // 入力全体が予約語に一致したときだけ予約語の候補を返す
+            (Some(reserved), Err(err)) => Ok((
+                self.arrange_candidates(tagged(reserved), None, options),
+                LossyConversion {
+                    candidates: Vec::new(),
+                    skipped: literal.skipped,
+                    substituted: literal.substituted,
+                    literal_error: Some(err.kind().clone()),
+                },
+            )),
+            (None, Err(err)) => Err(err),
+        }
+    }
+
+    /// 予約語などの候補と1文字ずつ変換した結果を `options` に従って並べ, 数字を区切る
+    #[cfg(feature = "encode")]
+    fn arrange_candidates(
+        &self,
+        reserved: Vec<Candidate>,
+        literal: Option<String>,
+        options: &ConvertOptions,
+    ) -> Vec<Candidate> {
+        let grouping = &options.grouping;
+        let options = &options.candidates;
+        let literal = literal.map(|digits| Candidate {
+            kind: CandidateKind::Literal,
+            digits: digits.into(),
+        });
+        let mut candidates = reserved;
+        if let Some(literal) = &literal {
+            match options.order {
+                CandidateOrder::ReservedFirst => candidates.push(literal.clone()),
+                CandidateOrder::LiteralFirst => candidates.insert(0, literal.clone()),
+            }
+        }
+        if options.rank_by_length {
+            candidates.sort_by_key(|c| c.digits.len());
+            let mut seen = HashSet::new();
+            candidates.retain(|c| seen.insert(c.digits.clone()));
+        }
+        if let Some(max) = options.max_candidates {
+            if candidates.len() > max {
+                candidates.truncate(max);
+                // 1つだけの場合は並び順の先頭を返す
+                if let Some(literal) = literal {
+                    if options.keep_literal
+                        && max > 1
+                        && !candidates.iter().any(|c| c.digits == literal.digits)
+                    {
+                        candidates[max - 1] = literal;
+                    }
+                }
+            }
+        }
+        if let Some(every) = grouping.group_every {
+            let separator = grouping.separator;
+            for candidate in candidates.iter_mut() {
+                candidate.digits = group_digits(&candidate.digits, every.get(), separator).into();
+            }
+        }
+        candidates
+    }
+
+    /// 入力を `Token` に分け, `options` に従って空白を取り除く。
+    /// 入力が長すぎる場合や, 何も残らない場合はエラーになる。
+    #[cfg(feature = "encode")]
+    fn tokens(&self, val: &str, options: &ConvertOptions) -> Result<Vec<Token>, Error> {
+        check_input_len(val.chars(), options.max_input_chars)?;
+        let mut tokens = tokenize(val, options.escapes)?;
+        if options.furigana {
+            tokens = self.apply_furigana(tokens);
+        }
+        if options.digit_runs == DigitRuns::Passthrough {
+            tokens = pass_digit_runs(tokens, options.min_digit_run);
+        }
+        let options = &options.spaces;
+        let is_space = |token: &Token| token.as_char().map(|ch| self.normalize(&ch)) == Some(' ');
+        if options.trim_spaces {
+            let start = tokens.iter().take_while(|t| is_space(t)).count();
+            tokens.drain(..start);
+            let end = tokens.iter().rev().take_while(|t| is_space(t)).count();
+            tokens.truncate(tokens.len() - end);
+        }
+        if options.collapse_spaces {
+            let mut prev_space = false;
+            tokens.retain(|token| {
+                let space = is_space(token);
+                let keep = !(space && prev_space);
+                prev_space = space;
+                keep
+            });
+        }
+        if tokens.is_empty() {
+            return Err(Error::from(ErrorKind::EmptyInput));
+        }
+        Ok(tokens)
+    }
+
+    /// `渋谷(しぶや)` のように, 変換できない文字の直後に括弧で囲まれたかなが続く部分を,
+    /// 括弧の中のかなだけにする。かなのトークンは入力での位置をそのまま持つ
+    #[cfg(feature = "encode")]
+    fn apply_furigana(&self, tokens: Vec<Token>) -> Vec<Token> {
+        let unsupported = |token: &Token| match *token {
+            Token::Char(_, ch) => self.encode_char(ch).is_none(),
+            Token::Digits(..) | Token::Passthrough(..) => false,
+        };
+        let mut keep = vec![true; tokens.len()];
+        let mut i = 0;
+        while i < tokens.len() {
+            let run = tokens[i..].iter().take_while(|t| unsupported(t)).count();
+            if run == 0 {
+                i += 1;
+                continue;
+            }
+            let open = i + run;
+            let reading = match furigana_reading(&tokens[open..]) {
+                Some(reading) => reading,
+                None => {
+                    i = open;
+                    continue;
+                }
+            };
+            let close = open + reading + 1;
+            for (k, keep) in keep.iter_mut().enumerate().take(close + 1).skip(i) {
+                *keep = open < k && k < close;
+            }
+            i = close + 1;
+        }
+        tokens
+            .into_iter()
+            .zip(keep)
+            .filter_map(|(token, keep)| if keep { Some(token) } else { None })
+            .collect()
+    }
+
+    /// `tokens` を1文字ずつ変換して `literal` に追記する。
+    /// エラーになった場合も, それまでに読み飛ばしたり置き換えたりした文字は `literal` に残る
+    #[cfg(feature = "encode")]
+    fn encode_literal(
+        &self,
+        tokens: &[Token],
+        handler: &mut dyn FnMut(char, usize) -> Fallback,
+        literal: &mut Literal,
+    ) -> Result<(), Error> {
+        for token in tokens {
+            self.encode_token(token, handler, literal)?;
+        }
+        if literal.digits.is_empty() {
+            // 結果が空になるのは, すべての文字を読み飛ばすか空文字列に置き換えたときだけ
+            let (index, ch) = match literal.skipped.first().or(literal.substituted.first()) {
+                Some(&first) => first,
+                None => return Err(Error::from(ErrorKind::EmptyInput)),
+            };
+            return Err(self.unsupported(ch, index));
+        }
+        Ok(())
+    }
+
+    /// 1文字 (または `{}` で囲まれた数字) を変換して `literal` に追記する
+    #[cfg(feature = "encode")]
+    fn encode_token(
+        &self,
+        token: &Token,
+        handler: &mut dyn FnMut(char, usize) -> Fallback,
+        literal: &mut Literal,
+    ) -> Result<(), Error> {
+        let (index, original) = match *token {
+            Token::Char(index, ch) => (index, ch),
+            Token::Digits(_, ref digits) | Token::Passthrough(_, ref digits) => {
+                literal.digits += digits;
+                return Ok(());
+            }
+        };
+        if let Some(s) = self.encode_char(original) {
+            literal.digits += s;
+            return Ok(());
+        }
+        let unsupported = || self.unsupported(original, index);
+        match handler(original, index) {
+            Fallback::Skip => literal.skipped.push((index, original)),
+            Fallback::Replace(replacement) => {
+                for ch in replacement.chars() {
+                    match self.encode_char(ch) {
+                        Some(s) => literal.digits += s,
+                        None => return Err(unsupported()),
+                    }
+                }
+                literal.substituted.push((index, original));
+            }
+            Fallback::Fail => return Err(unsupported()),
+        }
+        Ok(())
+    }
+
+    /// 文中に含まれる予約語を探し, 予約語の部分はその数字に, 残りは1文字ずつ変換する。
+    /// 予約語が重なる場合は, 先に始まるものの中で最も長いものを使う。
+    /// 予約語以外の部分の変換できない文字は `options` に従って扱う。
+    ///
+    /// 予約語を使った候補を先に返し, 最後に文全体を1文字ずつ変換した結果を置く
+    /// (並び順は `CandidateOptions` に従う)。
+    /// 文全体を1文字ずつ変換した結果は, 読み飛ばしや置き換えをせずに変換できる場合だけ置き,
+    /// 変換できない場合はその理由が `literal_error` に入る。
+    /// 1つの予約語に複数の数字がある場合はその組み合わせを返すが, 最大16通りまでにする。
+    /// 予約語を使った候補では, `ConvertOptions::min_digit_run` 文字以上続くASCIIの数字は
+    /// `DigitRuns::Passthrough` と同じくそのまま出力する。文全体を1文字ずつ変換した結果は `DigitRuns` に従う。
+    /// `skipped` と `substituted` は予約語を使った候補についてのもの。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// let c = Converter::new();
+    /// let result = c
+    ///     .convert_to_two_touch_string_segmented("しぶやで4649", &LenientOptions::default())
+    ///     .unwrap();
+    /// result.candidates; // ["42844044649", "32630481440499069909"]
+    /// ```
+    #[cfg(feature = "encode")]
+    pub fn convert_to_two_touch_string_segmented(
+        &self,
+        val: &str,
+        options: &LenientOptions,
+    ) -> Result<LossyConversion, Error> {
+        const MAX_SEGMENTED_CANDIDATES: usize = 16;
+
+        options.validate(self)?;
+        let mut handler = |_, _| options.fallback_action();
+        let tokens = self.tokens(val, &self.options)?;
+        // 予約語の部分まで読み飛ばした候補を混ぜないように, 文全体の変換は options に従わない
+        let mut literal = Literal::default();
+        let literal = self
+            .encode_literal(&tokens, &mut |_, _| Fallback::Fail, &mut literal)
+            .map(|()| literal.digits);
+        let tokens = pass_digit_runs(tokens, self.options.min_digit_run);
+        // 半角カナの濁点は1文字にまとめるので, 入力側は予約語の2倍の長さまで調べる
+        let max_len = self
+            .reserved()
+            .word_map
+            .keys()
+            .map(|key| key.chars().count() * 2)
+            .max()
+            .unwrap_or(0);
+
+        let mut segmented = vec![String::new()];
+        let mut gap = Literal::default();
+        // そのまま出力する数字も, 予約語と同じく通常の変換と違う候補になる
+        let mut matched = tokens
+            .iter()
+            .any(|token| matches!(token, Token::Passthrough(..)));
+        let mut i = 0;
+        while i < tokens.len() {
+            let run = tokens[i..]
+                .iter()
+                .take(max_len)
+                .take_while(|token| token.as_char().is_some())
+                .count();
+            let found = (1..=run).rev().find_map(|len| {
+                let phrase: String = tokens[i..(i + len)]
+                    .iter()
+                    .filter_map(Token::as_char)
+                    .collect();
+                self.reserved_for(&phrase).map(|word| (len, &word.codes))
+            });
+            let (len, codes) = match found {
+                Some(found) => found,
+                None => {
+                    self.encode_token(&tokens[i], &mut handler, &mut gap)?;
+                    i += 1;
+                    continue;
+                }
+            };
+            for partial in segmented.iter_mut() {
+                partial.push_str(&gap.digits);
+            }
+            gap.digits.clear();
+            segmented = segmented
+                .iter()
+                .flat_map(|partial| codes.iter().map(move |code| format!("{}{}", partial, code)))
+                .take(MAX_SEGMENTED_CANDIDATES)
+                .collect();
+            matched = true;
+            i += len;
+        }
+        if !matched {
+            return self.convert_to_two_touch_string_lenient(val, options);
+        }
+        for partial in segmented.iter_mut() {
+            partial.push_str(&gap.digits);
+        }
+
+        let literal_error = literal.as_ref().err().map(|err| err.kind().clone());
+        let literal = literal.ok();
+        let segmented = segmented
+            .into_iter()
+            .map(|digits| Candidate {
+                kind: CandidateKind::Segmented,
+                digits: digits.into(),
+            })
+            .collect();
+        Ok(LossyConversion {
+            candidates: self
+                .arrange_candidates(segmented, literal, &self.options)
+                .into_iter()
+                .map(Candidate::into_digits)
+                .collect(),
+            skipped: gap.skipped,
+            substituted: gap.substituted,
+            literal_error,
+        })
+    }
+
+    /// 文中の予約語を使って, 全体の桁数が最も短くなるように変換する。
+    /// `convert_to_two_touch_string_segmented` と違い, 先に始まる予約語を優先せず,
+    /// 予約語を使う部分と1文字ずつ変換する部分の組み合わせをすべて比べる。
+    /// 桁数が同じ場合は予約語を使う区間が少ないものを選ぶ。
+    /// 予約語の外にある変換できない文字はエラーになる。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// # #[cfg(feature = "builtin-dictionary")] {
+    /// let c = Converter::new();
+    /// let result = c.encode_shortest("しぶやでよろしく").unwrap();
+    /// assert_eq!(result.digits, "42844044649");
+    /// assert_eq!(result.segments[0].phrase.as_deref(), Some("しぶや"));
+    /// assert_eq!(result.segments[1].span, 3..4);
+    /// # }
+    /// ```
+    #[cfg(feature = "encode")]
+    pub fn encode_shortest(&self, val: &str) -> Result<ShortestEncoding, Error> {
+        let tokens = self.tokens(val, &self.options)?;
+        let chars: Vec<char> = val.chars().collect();
+        // 半角カナの濁点は1文字にまとめるので, 入力側は予約語の2倍の長さまで調べる
+        let max_len = self
+            .reserved()
+            .word_map
+            .keys()
+            .map(|key| key.chars().count() * 2)
+            .max()
+            .unwrap_or(0);
+
+        // best[i]: tokens[..i] を変換する最短の (桁数, 予約語の区間の数) と, 最後の区間
+        let mut best: Vec<Option<(usize, usize, ShortestStep)>> = vec![None; tokens.len() + 1];
+        best[0] = Some((0, 0, ShortestStep::Literal(0)));
+        for i in 0..tokens.len() {
+            let (digits, words) = match best[i] {
+                Some((digits, words, _)) => (digits, words),
+                None => continue,
+            };
+            let mut relax = |j: usize, cost: (usize, usize), step: ShortestStep| {
+                if best[j].as_ref().is_none_or(|&(d, w, _)| cost < (d, w)) {
+                    best[j] = Some((cost.0, cost.1, step));
+                }
+            };
+            let literal = match &tokens[i] {
+                Token::Char(_, ch) => self.encode_char(*ch).map(str::len),
+                Token::Digits(_, digits) | Token::Passthrough(_, digits) => Some(digits.len()),
+            };
+            if let Some(len) = literal {
+                relax(i + 1, (digits + len, words), ShortestStep::Literal(i));
+            }
+            let mut phrase = String::new();
+            for (j, token) in tokens.iter().enumerate().skip(i).take(max_len) {
+                match token.as_char() {
+                    Some(ch) => phrase.push(ch),
+                    None => break,
+                }
+                if let Some(word) = self.reserved_for(&phrase) {
+                    if let Some(code) = word.codes.iter().min_by_key(|code| code.len()) {
+                        relax(
+                            j + 1,
+                            (digits + code.len(), words + 1),
+                            ShortestStep::Reserved(i, code.to_string(), word.phrase.clone()),
+                        );
+                    }
+                }
+            }
+        }
+
+        if best[tokens.len()].is_none() {
+            // 到達できる位置のうち, 最も後ろにある変換できない文字をエラーにする
+            for (i, token) in tokens.iter().enumerate().rev() {
+                if let (Some(_), &Token::Char(index, ch)) = (&best[i], token) {
+                    if self.encode_char(ch).is_none() {
+                        return Err(self.unsupported(ch, index));
+                    }
+                }
+            }
+            return Err(Error::from(ErrorKind::EmptyInput));
+        }
+
+        let mut segments: Vec<ShortestSegment> = Vec::new();
+        let mut j = tokens.len();
+        while j > 0 {
+            let step = match &best[j] {
+                Some((_, _, step)) => step.clone(),
+                None => break,
+            };
+            let end = token_end(&chars, &tokens[j - 1]);
+            match step {
+                ShortestStep::Literal(i) => {
+                    let code = match &tokens[i] {
+                        Token::Char(_, ch) => self.encode_char(*ch).unwrap_or_default(),
+                        Token::Digits(_, digits) | Token::Passthrough(_, digits) => digits.as_str(),
+                    };
+                    let start = token_start(&tokens[i]);
+                    match segments.last_mut() {
+                        Some(last) if last.phrase.is_none() => {
+                            last.span.start = start;
+                            last.code.insert_str(0, code);
+                        }
+                        _ => segments.push(ShortestSegment {
+                            span: start..end,
+                            code: code.to_string(),
+                            phrase: None,
+                        }),
+                    }
+                    j = i;
+                }
+                ShortestStep::Reserved(i, code, phrase) => {
+                    segments.push(ShortestSegment {
+                        span: token_start(&tokens[i])..end,
+                        code,
+                        phrase: Some(phrase),
+                    });
+                    j = i;
+                }
+            }
+        }
+        segments.reverse();
+        let literal_len = tokens.iter().try_fold(0, |len, token| match token {
+            Token::Char(_, ch) => self.encode_char(*ch).map(|code| len + code.len()),
+            Token::Digits(_, digits) | Token::Passthrough(_, digits) => Some(len + digits.len()),
+        });
+        Ok(ShortestEncoding {
+            digits: segments
+                .iter()
+                .map(|segment| segment.code.as_str())
+                .collect(),
+            segments,
+            literal_len,
+        })
+    }
+
+    /// 入力を1文字ずつ2タッチ入力の数字に変換するイテレータを返す。
+    /// 文字ごとに2桁 (濁音などは4桁) の数字を返し, 予約語は使わない。
+    /// 変換できない文字があればエラーを1度だけ返して終わる。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// let c = Converter::new();
+    /// let codes: Vec<&str> = c.encode_iter("がっこう").collect::<Result<_, _>>().unwrap();
+    /// assert_eq!(codes, vec!["2104", "43", "25", "13"]);
+    /// ```
+    #[cfg(feature = "encode")]
+    pub fn encode_iter<'a>(
+        &'a self,
+        val: &'a str,
+    ) -> impl Iterator<Item = Result<&'a str, Error>> + 'a {
+        let mut failed = false;
+        val.chars().enumerate().map_while(move |(index, ch)| {
+            if failed {
+                return None;
+            }
+            match self.encode_char(ch) {
+                Some(code) => Some(Ok(code)),
+                None => {
+                    failed = true;
+                    Some(Err(self.unsupported(ch, index)))
+                }
+            }
+        })
+    }
+
+    /// 入力を1文字ずつ変換した数字を `out` に書き込み, 書き込んだ桁数を返す。
+    /// 予約語は使わない。
+    /// 途中で変換できない文字があった場合は, それまでの数字を書き込んだままエラーを返す。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// let c = Converter::new();
+    /// let mut out = String::from("TEL: ");
+    /// assert_eq!(c.encode_into("やきにく", &mut out).unwrap(), 8);
+    /// assert_eq!(out, "TEL: 81225223");
+    /// ```
+    #[cfg(feature = "encode")]
+    pub fn encode_into<W: fmt::Write>(&self, val: &str, out: &mut W) -> Result<usize, Error> {
+        let mut written = 0;
+        for code in self.encode_iter(val) {
+            let code = code?;
+            out.write_str(code)
+                .map_err(|_| Error::from(ErrorKind::WriteError))?;
+            written += code.len();
+        }
+        Ok(written)
+    }
+
+    /// `buf` を空にしてから `encode_into` で書き込む。`buf` の確保済みの領域はそのまま使う。
+    /// エラーの場合は, `buf` にはそれまでに変換した数字が入っている。
+    #[cfg(feature = "encode")]
+    pub fn encode_to_string_buf(&self, val: &str, buf: &mut String) -> Result<usize, Error> {
+        buf.clear();
+        self.encode_into(val, buf)
+    }
+
+    /// 入力を1文字ずつ変換し, 各文字がどの数字になったかを返す。
+    /// 予約語は使わず, 空白の整理と `{}` の扱いは `convert_to_two_touch_string` と同じ。
+    /// すべての区間の `code` をつなげると `convert_to_two_touch_string` の1文字ずつ変換した候補
+    /// (数字の区切りを除く) と一致する。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// let c = Converter::new();
+    /// let trace = c.encode_with_trace("がA").unwrap();
+    /// assert_eq!(trace[0].code, "2104");
+    /// assert_eq!(trace[1].span, 4..6);
+    /// ```
+    #[cfg(feature = "encode")]
+    pub fn encode_with_trace(&self, val: &str) -> Result<Vec<EncodedSegment>, Error> {
+        let mut segments = Vec::new();
+        let mut offset = 0;
+        for token in self.tokens(val, &self.options)? {
+            let (index, original, normalized, code, kind) = match token {
+                Token::Char(index, ch) => match self.encode_char(ch) {
+                    Some(code) => {
+                        let normalized = match self.profile {
+                            Profile::NumericOnly => Some(self.normalize(&ch)),
+                            _ => self.normalized_char(ch),
+                        };
+                        (index, ch, normalized, code, SegmentKind::Encoded)
+                    }
+                    None => return Err(self.unsupported(ch, index)),
+                },
+                Token::Digits(index, ref digits) => {
+                    (index, '{', None, digits.as_str(), SegmentKind::Escape)
+                }
+                Token::Passthrough(index, ref digits) => (
+                    index,
+                    digits.chars().next().unwrap_or_default(),
+                    None,
+                    digits.as_str(),
+                    SegmentKind::Raw,
+                ),
+            };
+            segments.push(EncodedSegment {
+                kind,
+                original,
+                normalized,
+                index,
+                code: code.to_string(),
+                span: offset..(offset + code.len()),
+            });
+            offset += code.len();
+        }
+        Ok(segments)
+    }
+
+    /// 複数行の入力を1行ずつ2タッチ入力の数字に変換する。
+    /// 各行の前後の空白は取り除き, 空の行は飛ばす。`split_on_period` を指定すると "。" でも区切る。
+    /// ある行の変換に失敗しても他の行は変換する。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// let c = Converter::new();
+    /// let lines = c.convert_lines("おはよう\r\n\nしぶやでまってる\n", false);
+    /// lines[1].line; // 3
+    /// ```
+    #[cfg(feature = "encode")]
+    pub fn convert_lines(&self, val: &str, split_on_period: bool) -> Vec<LineConversion> {
+        let mut ret = Vec::new();
+        for (i, line) in val.lines().enumerate() {
+            let sentences: Vec<&str> = if split_on_period {
+                line.split('。').collect()
+            } else {
+                vec![line]
+            };
+            for text in sentences {
+                let text = text.trim();
+                if text.is_empty() {
+                    continue;
+                }
+                ret.push(LineConversion {
+                    line: i + 1,
+                    text: text.to_string(),
+                    result: self.convert_to_two_touch_string(text),
+                });
+            }
+        }
+        ret
+    }
+
+    /// 長い文を, ポケベルに表示される文字数が `max_display_chars` 以下になるように分けてから変換する。
+    /// 文字数は `DakutenMode::Raw` の `display_len` と同じく数える (濁音は2文字)。
+    /// `{}` の扱いや空白の整理, 変換できない文字の扱いは `convert_to_two_touch_string` と同じで,
+    /// 変換した数字で数える (数字の区切りは数えない)。`{0840}` のような `{}` で囲まれた数字は途中で分けない。
+    /// 分ける位置は, できるだけ空白や句読点の直後にする。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// let c = Converter::new();
+    /// c.convert_split("おはよう こんにちは", 6).unwrap(); // [["1561851388"], ["2503524261"]]
+    /// ```
+    #[cfg(feature = "encode")]
+    pub fn convert_split(
+        &self,
+        val: &str,
+        max_display_chars: usize,
+    ) -> Result<Vec<Vec<String>>, Error> {
+        let options = &self.options;
+        options.validate(self)?;
+        let tokens = self.tokens(val, options)?;
+        let mut handler = |_, _| options.lenient.fallback_action();
+        self.split_tokens(&tokens, max_display_chars, &mut handler)?
+            .into_iter()
+            .map(|chunk| {
+                self.convert_tokens(&tokens[chunk], &mut handler, options)
+                    .map(|(candidates, _)| {
+                        candidates.into_iter().map(Candidate::into_digits).collect()
+                    })
+            })
+            .collect()
+    }
+
+    /// 長い文を `convert_split` と同じように分け, 順番が分かるように各部分に `1/3` のような番号を付けて変換する。
+    /// 番号も表示される文字数に含め, 番号を付けても `max_display_chars` を超えないように分ける。
+    /// 分けずに収まる場合は番号を付けない。番号の付け方は `MultipartOptions::default()`
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// let c = Converter::new();
+    /// let parts = c.convert_multipart("おはよう こんにちは", 6).unwrap();
+    /// assert_eq!(parts, vec!["966099156185", "9760991388", "986099250352", "9960994261"]);
+    /// # #[cfg(feature = "decode")]
+    /// assert_eq!(c.convert_from_two_touch_string(&parts[0]).unwrap(), "1/4おはよ");
+    /// ```
+    #[cfg(feature = "encode")]
+    pub fn convert_multipart(
+        &self,
+        val: &str,
+        max_display_chars: usize,
+    ) -> Result<Vec<String>, Error> {
+        self.convert_multipart_with(val, max_display_chars, &MultipartOptions::default())
+    }
+
+    /// `options` の番号の付け方で `convert_multipart` と同じように変換する。
+    /// 番号を除いた各部分は `convert_split` の各部分の先頭の候補と同じ数字になる。
+    /// 数字の区切りは番号を付けたあとの数字全体にかける。
+    #[cfg(feature = "encode")]
+    pub fn convert_multipart_with(
+        &self,
+        val: &str,
+        max_display_chars: usize,
+        options: &MultipartOptions,
+    ) -> Result<Vec<String>, Error> {
+        let convert_options = &self.options;
+        convert_options.validate(self)?;
+        let tokens = self.tokens(val, convert_options)?;
+        let mut handler = |_, _| convert_options.lenient.fallback_action();
+        let mut chunks = self.split_tokens(&tokens, max_display_chars, &mut handler)?;
+        if chunks.len() > 1 {
+            // 番号の分だけ短く分け直し, 分けた数の桁が増えて番号が長くなったらもう一度分ける
+            let mut reserved = 0;
+            loop {
+                let label = options.label(chunks.len(), chunks.len());
+                let width = self.text_width(&label)?;
+                if width <= reserved {
+                    break;
+                }
+                if width >= max_display_chars {
+                    return Err(Error::from(ErrorKind::InvalidOption("max_display_chars")));
+                }
+                reserved = width;
+                chunks = self.split_tokens(&tokens, max_display_chars - reserved, &mut handler)?;
+            }
+        }
+        // 番号を付けてから区切るので, 各部分は区切らずに変換する
+        let ungrouped = ConvertOptions {
+            grouping: GroupingOptions::default(),
+            ..convert_options.clone()
+        };
+        let total = chunks.len();
+        chunks
+            .into_iter()
+            .enumerate()
+            .map(|(i, chunk)| {
+                let (candidates, _) =
+                    self.convert_tokens(&tokens[chunk], &mut handler, &ungrouped)?;
+                // 候補は必ず1つ以上ある
+                let digits = candidates
+                    .into_iter()
+                    .next()
+                    .map(Candidate::into_digits)
+                    .unwrap_or_default();
+                let part = if total > 1 {
+                    let mut label = String::new();
+                    self.encode_into(&options.label(i + 1, total), &mut label)?;
+                    match options.position {
+                        LabelPosition::Prefix => label + &digits,
+                        LabelPosition::Suffix => digits + &label,
+                    }
+                } else {
+                    digits
+                };
+                Ok(match convert_options.grouping.group_every {
+                    Some(every) => {
+                        group_digits(&part, every.get(), convert_options.grouping.separator)
+                    }
+                    None => part,
+                })
+            })
+            .collect()
+    }
+
+    /// 1文字がポケベルに表示される文字数。濁音は2文字
+    #[cfg(feature = "encode")]
+    fn display_width(&self, ch: char, index: usize) -> Result<usize, Error> {
+        match (self.encode_char(ch), &self.profile) {
+            (Some(code), Profile::NumericOnly) => Ok(code.len()),
+            (Some(code), _) => Ok(code.len() / 2),
+            (None, _) => Err(self.unsupported(ch, index)),
+        }
+    }
+
+    #[cfg(feature = "encode")]
+    fn text_width(&self, val: &str) -> Result<usize, Error> {
+        val.chars()
+            .enumerate()
+            .map(|(index, ch)| self.display_width(ch, index))
+            .sum()
+    }
+
+    /// 変換した数字で表示される文字数を数え, 各部分が `max_display_chars` 以下になるように `tokens` を分ける
+    #[cfg(feature = "encode")]
+    fn split_tokens(
+        &self,
+        tokens: &[Token],
+        max_display_chars: usize,
+        handler: &mut dyn FnMut(char, usize) -> Fallback,
+    ) -> Result<Vec<std::ops::Range<usize>>, Error> {
+        let mut widths = Vec::with_capacity(tokens.len());
+        let mut chunks = Vec::new();
+        let mut start = 0;
+        let mut len = 0;
+        for (i, token) in tokens.iter().enumerate() {
+            let mut literal = Literal::default();
+            self.encode_token(token, handler, &mut literal)?;
+            let width = match self.profile {
+                Profile::NumericOnly => literal.digits.len(),
+                _ => literal.digits.len() / 2,
+            };
+            if width > max_display_chars {
+                return Err(Error::from(ErrorKind::InvalidOption("max_display_chars")));
+            }
+            if len + width > max_display_chars {
+                // 空白や句読点の直後で切れるならそこで切り, 残りは次に回す
+                let at = tokens[start..i]
+                    .iter()
+                    .rposition(|token| token.as_char().is_some_and(|ch| self.is_break_char(ch)))
+                    .map(|k| start + k + 1)
+                    .unwrap_or(i);
+                chunks.push(start..at);
+                start = at;
+                len = widths[at..i].iter().sum();
+            }
+            widths.push(width);
+            len += width;
+        }
+        chunks.push(start..tokens.len());
+        Ok(chunks)
+    }
+
+    #[cfg(feature = "encode")]
+    fn is_break_char(&self, ch: char) -> bool {
+        matches!(
+            self.normalize(&ch),
+            ' ' | '、' | '。' | ',' | '.' | '!' | '?'
+        )
+    }
+
+    /// 2タッチ入力に変換できない文字を, 入力先頭からの文字位置とともにすべて返す。
+    /// 同じ文字が複数回現れた場合はそれぞれの位置で返す。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// let c = Converter::new();
+    /// c.unsupported_chars("筋肉こんにちは筋"); // [(0, '筋'), (1, '肉'), (7, '筋')]
+    /// ```
+    #[cfg(feature = "encode")]
+    pub fn unsupported_chars(&self, val: &str) -> Vec<(usize, char)> {
+        // `{}` の対応が取れない場合は括弧も1文字として調べる
+        let tokens = tokenize(val, self.options.escapes).unwrap_or_else(|_| {
+            val.chars()
+                .enumerate()
+                .map(|(i, ch)| Token::Char(i, ch))
+                .collect()
+        });
+        tokens
+            .iter()
+            .filter_map(|token| match *token {
+                Token::Char(index, ch) if self.encode_char(ch).is_none() => Some((index, ch)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// 入力が `convert_to_two_touch_string` で変換できるかを, 数字を作らずに調べる。
+    /// 空白の扱いや `Profile` などの設定に従い, 入力全体が予約語に一致する場合も変換できるとみなす。
+    /// 変換できない場合は最初に見つかった理由を返す。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// let c = Converter::new();
+    /// assert_eq!(c.check_encodable("ＫＡＮＡ"), Ok(()));
+    /// assert_eq!(c.check_encodable(""), Err(EncodeIssue::Empty));
+    /// ```
+    #[cfg(feature = "encode")]
+    pub fn check_encodable(&self, val: &str) -> Result<(), EncodeIssue> {
+        let tokens = self
+            .tokens(val, &self.options)
+            .map_err(|err| match *err.kind() {
+                ErrorKind::UnbalancedBrace { index }
+                | ErrorKind::NonDigitInEscape { index, .. }
+                | ErrorKind::EmptyEscape { index }
+                | ErrorKind::OddLengthEscape { index, .. } => EncodeIssue::InvalidEscape { index },
+                ErrorKind::InputTooLong { len, limit } => EncodeIssue::InputTooLong { len, limit },
+                _ => EncodeIssue::Empty,
+            })?;
+        let unsupported = tokens.iter().find_map(|token| match *token {
+            Token::Char(index, ch) if self.encode_char(ch).is_none() => Some((index, ch)),
+            _ => None,
+        });
+        match unsupported {
+            Some((index, ch)) => {
+                let phrase: Option<String> = tokens.iter().map(Token::as_char).collect();
+                match phrase.and_then(|phrase| self.reserved_for(&phrase)) {
+                    Some(_) => Ok(()),
+                    None => Err(EncodeIssue::UnsupportedCharacter { ch, index }),
+                }
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// 入力が `convert_to_two_touch_string` で変換できるかどうか
+    #[cfg(feature = "encode")]
+    pub fn is_encodable(&self, val: &str) -> bool {
+        self.check_encodable(val).is_ok()
+    }
+
+    /// `check_encodable` で見つかった, 変換できない最初の文字と入力先頭からの文字位置。
+    /// 空の入力や `{}` の誤りなど, 文字以外が理由の場合はNone
+    #[cfg(feature = "encode")]
+    pub fn first_unsupported(&self, val: &str) -> Option<(usize, char)> {
+        match self.check_encodable(val) {
+            Err(EncodeIssue::UnsupportedCharacter { ch, index }) => Some((index, ch)),
+            _ => None,
+        }
+    }
+
+    /// 2タッチ入力から日本語に変換する。
+    /// 濁点等は別の文字になる。
+    /// 例: 2104 -> か゛
+    ///
+    /// `GroupingOptions` で区切りを設定している場合は, 区切り文字を取り除いてから変換する
+    /// (エラーの位置は区切り文字を取り除いた後のもの)。
+    /// `convert_to_two_touch_string` と同じく引数は `AsRef<str>` で, `&str` も `String` も渡せる。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// let c = Converter::new();
+    /// c.convert_from_two_touch_string("81225223").unwrap(); // "やきにく"
+    /// ```
+    #[cfg(feature = "decode")]
+    pub fn convert_from_two_touch_string<S: AsRef<str>>(&self, val: S) -> Result<String, Error> {
+        self.convert_from_two_touch_string_with(val.as_ref(), &self.default_decode_options())
+    }
+
+    /// `convert_from_two_touch_string` と同じように変換し, 結果を `out` の後ろに書き足す。
+    /// `out` の確保済みの領域を使うので, 同じ `String` を使い回せば変換のたびに確保しなくてよい。
+    /// エラーの場合は `out` を呼ぶ前の内容に戻す。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// let c = Converter::new();
+    /// let mut out = String::new();
+    /// for code in ["81225223", "2503524261", "1"] {
+    ///     out.clear();
+    ///     match c.decode_into(code, &mut out) {
+    ///         Ok(()) => assert!(!out.is_empty()),
+    ///         Err(_) => assert!(out.is_empty()),
+    ///     }
+    /// }
+    /// ```
+    #[cfg(feature = "decode")]
+    pub fn decode_into(&self, code: &str, out: &mut String) -> Result<(), Error> {
+        let len = out.len();
+        let result = self.decode_append(code, &self.default_decode_options(), out);
+        if result.is_err() {
+            out.truncate(len);
+        }
+        result
+    }
+
+    /// `convert_from_two_touch_string` の設定。`GroupingOptions` で区切りを設定していれば取り除く
+    #[cfg(feature = "decode")]
+    fn default_decode_options(&self) -> DecodeOptions {
+        let grouping = &self.options.grouping;
+        DecodeOptions::default().with_separator(grouping.group_every.map(|_| grouping.separator))
+    }
+
+    /// `options` の設定で `convert_from_two_touch_string` と同じように変換する
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// let c = Converter::new();
+    /// let options = DecodeOptions::default()
+    ///     .with_separator(Some('-'))
+    ///     .with_dakuten_mode(DakutenMode::Composed);
+    /// c.convert_from_two_touch_string_with("2104-4325-13", &options).unwrap(); // "がつこう"
+    /// ```
+    #[cfg(feature = "decode")]
+    pub fn convert_from_two_touch_string_with(
+        &self,
+        val: &str,
+        options: &DecodeOptions,
+    ) -> Result<String, Error> {
+        let mut ret = String::new();
+        self.decode_append(val, options, &mut ret)?;
+        Ok(ret)
+    }
+
+    /// `options` の設定で変換し, `out` の後ろに書き足す。エラーの場合は途中まで書き足したままにする
+    #[cfg(feature = "decode")]
+    fn decode_append(
+        &self,
+        val: &str,
+        options: &DecodeOptions,
+        out: &mut String,
+    ) -> Result<(), Error> {
+        options.validate()?;
+        // 桁数は文字数を超えないので, バイト数が上限までなら数えなくてよい
+        if options
+            .max_input_digits
+            .is_some_and(|limit| val.len() > limit)
+        {
+            check_input_len(
+                val.chars().filter(|&ch| Some(ch) != options.separator),
+                options.max_input_digits,
+            )?;
+        }
+        let ungrouped: String;
+        let val = match options.separator {
+            Some(separator) => {
+                ungrouped = val.chars().filter(|&ch| ch != separator).collect();
+                &ungrouped
+            }
+            None => val,
+        };
+        if options.dakuten_mode == DakutenMode::Composed {
+            let segments = self.decode_with_trace(val, DakutenMode::Composed)?;
+            out.extend(segments.iter().map(|segment| segment.ch));
+            return Ok(());
+        }
+        if val.is_empty() {
+            return Err(Error::from(ErrorKind::EmptyInput));
+        }
+        if self.profile == Profile::NumericOnly {
+            self.check_numeric(val)?;
+            out.push_str(val);
+            return Ok(());
+        }
+        check_pair_digits(val)?;
+        // かなはUTF-8で3バイトなので, 1組あたり3バイトを確保しておく
+        out.reserve(val.len() / 2 * 3);
+        self.decode_pairs(val.as_bytes(), |ch| out.push(ch))
+    }
+
+    /// `check_pair_digits` で確かめた数字を2桁ずつ戻し, 1文字ずつ `push` に渡す
+    #[cfg(feature = "decode")]
+    fn decode_pairs(&self, bytes: &[u8], mut push: impl FnMut(char)) -> Result<(), Error> {
+        for (i, pair) in bytes.chunks_exact(2).enumerate() {
+            // 数字だけなのは確認済み
+            let ch = self
+                .tables
+                .inverse_table
+                .pair(pair[0] - b'0', pair[1] - b'0');
+            if let Some(ch) = ch.filter(|&ch| self.profile.allows(ch)) {
+                push(ch);
+                continue;
+            }
+            // 誤りは `pair_char` で作る
+            let code = std::str::from_utf8(pair).unwrap_or_default();
+            push(self.pair_char(code, i)?);
+        }
+        Ok(())
+    }
+
+    /// 入力を1文字ずつ変換した数字をASCIIの数字として `out` に書き込み, 書き込んだバイト数を返す。
+    /// 予約語は使わず, `encode_into` と同じ数字になる。変換できた場合はヒープに確保しない。
+    /// エラーの値は確保することがあるので, アロケータのない環境では `pokebell_core::encode_to_slice` を使う。
+    ///
+    /// 変換できない文字がある場合はそのエラーを, `out` が短い場合は必要なバイト数を入れた
+    /// `ErrorKind::BufferTooSmall` を返す。どちらの場合も `out` の内容は決まっていない。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// let c = Converter::without_reserved_words();
+    /// let mut out = [0; 8];
+    /// assert_eq!(c.encode_to_slice("やきにく", &mut out).unwrap(), 8);
+    /// assert_eq!(&out, b"81225223");
+    /// let err = c.encode_to_slice("やきにくたべたい", &mut out).unwrap_err();
+    /// assert_eq!(*err.kind(), ErrorKind::BufferTooSmall { required: 18, len: 8 });
+    /// ```
+    #[cfg(feature = "encode")]
+    pub fn encode_to_slice(&self, val: &str, out: &mut [u8]) -> Result<usize, Error> {
+        let mut required = 0;
+        for code in self.encode_iter(val) {
+            let code = code?;
+            if let Some(dest) = out.get_mut(required..required + code.len()) {
+                dest.copy_from_slice(code.as_bytes());
+            }
+            required += code.len();
+        }
+        if required > out.len() {
+            return Err(Error::from(ErrorKind::BufferTooSmall {
+                required,
+                len: out.len(),
+            }));
+        }
+        Ok(required)
+    }
+
+    /// 2タッチ入力を `DecodeOptions::default()` の設定で戻して `out` に1文字ずつ書き込み, 書き込んだ文字数を返す。
+    /// 区切り文字は取り除かず, 結果は `convert_from_two_touch_string_with(code, &DecodeOptions::default())` と同じ。
+    /// 戻せた場合はヒープに確保しない。
+    /// エラーの値は確保することがあるので, アロケータのない環境では `pokebell_core::decode_to_slice` を使う。
+    ///
+    /// 戻せない数字がある場合はそのエラーを, `out` が短い場合は必要な文字数を入れた
+    /// `ErrorKind::BufferTooSmall` を返す。どちらの場合も `out` の内容は決まっていない。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// let c = Converter::without_reserved_words();
+    /// let mut out = ['\0'; 4];
+    /// assert_eq!(c.decode_to_slice("81225223", &mut out).unwrap(), 4);
+    /// assert_eq!(out, ['や', 'き', 'に', 'く']);
+    /// ```
+    #[cfg(feature = "decode")]
+    pub fn decode_to_slice(&self, code: &str, out: &mut [char]) -> Result<usize, Error> {
+        if code.is_empty() {
+            return Err(Error::from(ErrorKind::EmptyInput));
+        }
+        let mut written = 0;
+        let mut push = |ch| {
+            if let Some(dest) = out.get_mut(written) {
+                *dest = ch;
+            }
+            written += 1;
+        };
+        if self.profile == Profile::NumericOnly {
+            self.check_numeric(code)?;
+            code.chars().for_each(&mut push);
+        } else {
+            check_pair_digits(code)?;
+            self.decode_pairs(code.as_bytes(), &mut push)?;
+        }
+        if written > out.len() {
+            return Err(Error::from(ErrorKind::BufferTooSmall {
+                required: written,
+                len: out.len(),
+            }));
+        }
+        Ok(written)
+    }
+
+    /// 2タッチ入力を, 重複の確認やキーに使える1つの数字の並びにそろえる。
+    /// 空白と `GroupingOptions` の区切り文字を取り除き, `DakutenMode::Raw` で戻した文字を
+    /// 1文字ずつ変換表の2桁のコードにし直す (濁音は常に `2104` のような4桁になる)。
+    /// `SpaceOptions` で空白を取り除く設定をしている場合は, 前後や連続した `88` も取り除く。
+    ///
+    /// 既定の設定では, 2つのコードをそろえた結果が同じになるのは,
+    /// `convert_from_two_touch_string` で戻した文字列が同じ場合だけです。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// let c = Converter::new();
+    /// assert_eq!(c.canonicalize_code("2104 4325 13").unwrap(), "2104432513");
+    /// ```
+    #[cfg(all(feature = "encode", feature = "decode"))]
+    pub fn canonicalize_code(&self, code: &str) -> Result<String, Error> {
+        let separator = self.options.grouping.separator;
+        let code: String = code
+            .chars()
+            .filter(|&ch| ch != separator && !ch.is_whitespace())
+            .collect();
+        let decoded = self.convert_from_two_touch_string_with(&code, &DecodeOptions::default())?;
+        if self.profile == Profile::NumericOnly {
+            return Ok(decoded);
+        }
+        let spaces = &self.options.spaces;
+        let mut chars: Vec<char> = decoded.chars().collect();
+        if spaces.trim_spaces {
+            let start = chars.iter().take_while(|&&ch| ch == ' ').count();
+            chars.drain(..start);
+            let end = chars.iter().rev().take_while(|&&ch| ch == ' ').count();
+            chars.truncate(chars.len() - end);
+        }
+        if spaces.collapse_spaces {
+            chars.dedup_by(|a, b| *a == ' ' && *b == ' ');
+        }
+        if chars.is_empty() {
+            return Err(Error::from(ErrorKind::EmptyInput));
+        }
+        let mut ret = String::with_capacity(code.len());
+        for ch in chars {
+            // 変換表から戻した文字なので必ずある
+            if let Some(digits) = self.tables.base_map.get(ch) {
+                ret.push_str(digits);
+            }
+        }
+        Ok(ret)
+    }
+
+    /// `a` と `b` を `canonicalize_code` でそろえた結果が同じか比べる
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// let c = Converter::new();
+    /// assert!(c.codes_equivalent("2104 43", "210443").unwrap());
+    /// assert!(!c.codes_equivalent("2104", "2143").unwrap());
+    /// ```
+    #[cfg(all(feature = "encode", feature = "decode"))]
+    pub fn codes_equivalent(&self, a: &str, b: &str) -> Result<bool, Error> {
+        Ok(self.canonicalize_code(a)? == self.canonicalize_code(b)?)
+    }
+
+    /// 数字の並びが何を表していそうかを, 確からしいものから順に `Hypothesis` で返す。
+    /// 空白と `-` は取り除いてから調べ, 数字以外が残る場合は `HypothesisKind::Unknown` だけを返す。
+    ///
+    /// 次の決まった規則で判定する。
+    /// - 予約語の数字と一致する: `ReservedPhrase` (`Confidence::High`)
+    /// - 2タッチ入力として最後まで戻せる: `TwoTouch`。かなの組が8割以上で3組以上なら `High`,
+    ///   5割以上なら `Medium`, それ以外は `Low`。濁点・半濁点もかなとして数える
+    /// - 日本の電話番号の形をしている: `PhoneNumberLike`。`070` `080` `090` `050` で始まる11桁,
+    ///   `0120` で始まる10桁, `0800` で始まる11桁は `High`, `00` 以外の `0` で始まる10桁は `Medium`
+    /// - どれにも当てはまらない: `Unknown` (`Low`)
+    ///
+    /// 同じ確からしさの場合は上の順に並べる。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// let c = Converter::new();
+    /// let classification = c.classify_digits("2503524261");
+    /// match &classification.best().kind {
+    ///     HypothesisKind::TwoTouch { preview, kana_percent } => {
+    ///         assert_eq!((preview.as_str(), *kana_percent), ("こんにちは", 100));
+    ///     }
+    ///     _ => unreachable!(),
+    /// }
+    /// ```
+    #[cfg(all(feature = "encode", feature = "decode"))]
+    pub fn classify_digits(&self, digits: &str) -> Classification {
+        let digits: String = digits
+            .chars()
+            .filter(|&ch| ch != '-' && !ch.is_whitespace())
+            .collect();
+        let mut hypotheses = Vec::new();
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            hypotheses.push(Hypothesis {
+                kind: HypothesisKind::Unknown,
+                confidence: Confidence::Low,
+            });
+            return Classification { hypotheses };
+        }
+        if let Some(bucket) = self.reserved().code_index.get(&digits.len()) {
+            let mut phrases: Vec<String> = bucket
+                .iter()
+                .filter(|(code, _)| *code == digits)
+                .map(|(_, key)| self.reserved().word_map[key].phrase.clone())
+                .collect();
+            if !phrases.is_empty() {
+                phrases.sort();
+                phrases.dedup();
+                hypotheses.push(Hypothesis {
+                    kind: HypothesisKind::ReservedPhrase { phrases },
+                    confidence: Confidence::High,
+                });
+            }
+        }
+        if let Ok(segments) = self.decode_with_trace(&digits, DakutenMode::Raw) {
+            let kana_percent = kana_percent(&segments);
+            let confidence = if kana_percent >= 80 && segments.len() >= 3 {
+                Confidence::High
+            } else if kana_percent >= 50 {
+                Confidence::Medium
+            } else {
+                Confidence::Low
+            };
+            let preview = self
+                .convert_from_two_touch_string_with(
+                    &digits,
+                    &DecodeOptions::default().with_dakuten_mode(DakutenMode::Composed),
+                )
+                .unwrap_or_default();
+            hypotheses.push(Hypothesis {
+                kind: HypothesisKind::TwoTouch {
+                    preview,
+                    kana_percent,
+                },
+                confidence,
+            });
+        }
+        if let Some(confidence) = phone_number_confidence(&digits) {
+            hypotheses.push(Hypothesis {
+                kind: HypothesisKind::PhoneNumberLike,
+                confidence,
+            });
+        }
+        if hypotheses.is_empty() {
+            hypotheses.push(Hypothesis {
+                kind: HypothesisKind::Unknown,
+                confidence: Confidence::Low,
+            });
+        }
+        hypotheses.sort_by_key(|hypothesis| std::cmp::Reverse(hypothesis.confidence));
+        Classification { hypotheses }
+    }
+
+    /// 最後に電話番号の数字がそのまま付いた2タッチ入力を, メッセージと電話番号に分ける。
+    /// 電話番号を付けない場合と, 後ろの10桁・11桁が `classify_digits` の電話番号の形をしている場合を
+    /// 試し, 前の部分を最後まで戻せる分け方を `CallbackSplits` で返す。
+    /// かなの組の割合が高いもの, 電話番号として確からしいもの, の順に並べ, 最も良いものを `best` にする。
+    /// `best` と同じくらい良い分け方がある場合は `CallbackSplits::is_ambiguous` がtrueになる。
+    ///
+    /// どの分け方でも戻せない場合は, 全体を戻した場合のエラーを返す。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// let c = Converter::new();
+    /// let splits = c.split_message_and_callback("250352426109012345678").unwrap();
+    /// assert_eq!(splits.best.message, "こんにちは");
+    /// assert_eq!(splits.best.callback.as_deref(), Some("09012345678"));
+    /// assert_eq!(splits.best.split, 10);
+    /// ```
+    #[cfg(all(feature = "encode", feature = "decode"))]
+    pub fn split_message_and_callback(&self, digits: &str) -> Result<CallbackSplits, Error> {
+        let decode = |code: &str| -> Result<(String, u8), Error> {
+            if code.is_empty() {
+                return Ok((String::new(), 0));
+            }
+            let segments = self.decode_with_trace(code, DakutenMode::Raw)?;
+            let message = self.convert_from_two_touch_string_with(
+                code,
+                &DecodeOptions::default().with_dakuten_mode(DakutenMode::Composed),
+            )?;
+            Ok((message, kana_percent(&segments)))
+        };
+        let whole = decode(digits);
+        let mut splits = Vec::new();
+        let mut ranks = Vec::new();
+        for &len in &[11, 10] {
+            if digits.len() < len || !digits.is_char_boundary(digits.len() - len) {
+                continue;
+            }
+            let split = digits.len() - len;
+            let callback = &digits[split..];
+            let confidence = match phone_number_confidence(callback) {
+                Some(confidence) if callback.bytes().all(|b| b.is_ascii_digit()) => confidence,
+                _ => continue,
+            };
+            if let Ok((message, kana_percent)) = decode(&digits[..split]) {
+                ranks.push((kana_percent, Some(confidence)));
+                splits.push(CallbackSplit {
+                    message,
+                    callback: Some(callback.to_string()),
+                    split,
+                    kana_percent,
+                });
+            }
+        }
+        match whole {
+            Ok((message, kana_percent)) => {
+                ranks.push((kana_percent, None));
+                splits.push(CallbackSplit {
+                    message,
+                    callback: None,
+                    split: digits.len(),
+                    kana_percent,
+                });
+            }
+            Err(e) if splits.is_empty() => return Err(e),
+            Err(_) => {}
+        }
+        let mut ranked: Vec<_> = ranks.into_iter().zip(splits).collect();
+        ranked.sort_by_key(|(rank, _)| std::cmp::Reverse(*rank));
+        let best_rank = ranked[0].0;
+        let ambiguous = ranked[1..].iter().any(|(rank, _)| rank.0 == best_rank.0);
+        let mut splits = ranked.into_iter().map(|(_, split)| split);
+        Ok(CallbackSplits {
+            best: splits.next().unwrap_or_default(),
+            alternatives: splits.collect(),
+            ambiguous,
+        })
+    }
+
+    /// 文章の中にある, `DEFAULT_MIN_CODE_DIGITS` 桁以上のASCIIの数字の並びを探し,
+    /// それぞれを `DakutenMode::Composed` で戻した結果を `Found` で返す。文章は変更しない。
+    /// 数字の並びの前後はどんな文字でもよく, `は2503524261と` のようにかなに挟まれていても見つける。
+    /// 全角数字は数字の並びに含めない。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// let c = Converter::new();
+    /// let found = c.extract_and_decode("当時は2503524261とか送ってた");
+    /// assert_eq!((found[0].range.clone(), found[0].digits.as_str()), (9..19, "2503524261"));
+    /// assert_eq!(found[0].result.as_deref(), Ok("こんにちは"));
+    /// ```
+    #[cfg(feature = "decode")]
+    pub fn extract_and_decode(&self, text: &str) -> Vec<Found> {
+        self.extract_and_decode_with(text, DEFAULT_MIN_CODE_DIGITS)
+    }
+
+    /// `min_digits` 桁以上の数字の並びを探して, `extract_and_decode` と同じように戻す
+    #[cfg(feature = "decode")]
+    pub fn extract_and_decode_with(&self, text: &str, min_digits: usize) -> Vec<Found> {
+        let options = DecodeOptions::default().with_dakuten_mode(DakutenMode::Composed);
+        let mut found = Vec::new();
+        let bytes = text.as_bytes();
+        let mut i = 0;
+        while i < bytes.len() {
+            if !bytes[i].is_ascii_digit() {
+                i += 1;
+                continue;
+            }
+            let start = i;
+            while i < bytes.len() && bytes[i].is_ascii_digit() {
+                i += 1;
+            }
+            if i - start < min_digits.max(1) {
+                continue;
+            }
+            let digits = &text[start..i];
+            found.push(Found {
+                range: start..i,
+                digits: digits.to_string(),
+                result: self.convert_from_two_touch_string_with(digits, &options),
+            });
+        }
+        found
+    }
+
+    /// 先頭の1桁が抜けたり最後に余計な1桁が付いたりして組がずれた数字を, ずれを直して戻す。
+    /// 先頭の1桁を飛ばすかどうかと, 最後の1桁を捨てるかどうかの4通りを試し,
+    /// `DakutenMode::Composed` で最後まで戻せたものを `RecoveryCandidate` で返す。
+    ///
+    /// `RecoveryCandidate::score` が高いもの, 予約語の表記を多く含むもの,
+    /// 飛ばした桁が少ないもの, の順に並べる。`ngram` featureでは, `score_decoded` が高いものを先に並べる。どれも戻せない場合は空のVecを返す。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// let c = Converter::new();
+    /// let candidates = c.decode_with_recovery("12503524261");
+    /// assert_eq!(candidates[0].message, "こんにちは");
+    /// assert_eq!(candidates[0].skipped, vec![0]);
+    /// ```
+    #[cfg(all(feature = "encode", feature = "decode"))]
+    pub fn decode_with_recovery(&self, digits: &str) -> Vec<RecoveryCandidate> {
+        let options = DecodeOptions::default().with_dakuten_mode(DakutenMode::Composed);
+        let mut candidates = Vec::new();
+        if !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return candidates;
+        }
+        for &skip_first in &[false, true] {
+            for &drop_last in &[false, true] {
+                let start = skip_first as usize;
+                let end = digits.len().saturating_sub(drop_last as usize);
+                if start >= end || !(end - start).is_multiple_of(2) {
+                    continue;
+                }
+                let code = &digits[start..end];
+                let segments = match self.decode_with_trace(code, DakutenMode::Raw) {
+                    Ok(segments) => segments,
+                    Err(_) => continue,
+                };
+                let message = match self.convert_from_two_touch_string_with(code, &options) {
+                    Ok(message) => message,
+                    Err(_) => continue,
+                };
+                let points: usize = segments
+                    .iter()
+                    .map(|segment| match CharClass::of(segment.ch) {
+                        CharClass::Kana => 2,
+                        CharClass::Latin => 1,
+                        _ if "゛゜".contains(segment.ch) => 2,
+                        _ => 0,
+                    })
+                    .sum();
+                let dictionary_hits = self
+                    .reserved()
+                    .word_map
+                    .values()
+                    .filter(|word| message.contains(word.phrase.as_str()))
+                    .count();
+                let mut skipped = Vec::new();
+                if skip_first {
+                    skipped.push(0);
+                }
+                if drop_last {
+                    skipped.push(digits.len() - 1);
+                }
+                candidates.push(RecoveryCandidate {
+                    message,
+                    skipped,
+                    score: (points * 50 / segments.len()) as u8,
+                    dictionary_hits,
+                });
+            }
+        }
+        candidates.sort_by_key(|candidate| {
+            (
+                std::cmp::Reverse(candidate.score),
+                std::cmp::Reverse(candidate.dictionary_hits),
+                candidate.skipped.len(),
+            )
+        });
+        #[cfg(feature = "ngram")]
+        candidates.sort_by(|a, b| {
+            self.score_decoded(&b.message)
+                .total_cmp(&self.score_decoded(&a.message))
+        });
+        candidates
+    }
+
+    /// 入力途中の数字 `prefix` の続きの候補を返す。キーを押すたびに呼べるように, 予約語は
+    /// Converterを作るときに作った索引から引く。予約語は最大 `MAX_COMPLETIONS` 個。
+    ///
+    /// - `prefix` が奇数桁の場合は組の途中なので, 次に押す数字ごとに, 今の組がなる文字を返す
+    /// - 偶数桁の場合は, 次に押す数字ごとに, 次の組がなりうる文字を返す
+    ///
+    /// `prefix` に数字以外が含まれる場合は空の `Completions` を返す。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// let c = Converter::new();
+    /// let completions = c.complete_code("2");
+    /// assert_eq!(completions.keys[0], KeyHint { digit: 1, chars: vec!['か'] });
+    /// let completions = c.complete_code("25");
+    /// assert_eq!(completions.decoded.as_deref(), Some("こ"));
+    /// ```
+    #[cfg(all(feature = "encode", feature = "decode"))]
+    pub fn complete_code(&self, prefix: &str) -> Completions {
+        self.complete_code_with(prefix, MAX_COMPLETIONS)
+    }
+
+    /// 予約語を最大 `max_phrases` 個にして, `complete_code` と同じように候補を返す
+    #[cfg(all(feature = "encode", feature = "decode"))]
+    pub fn complete_code_with(&self, prefix: &str, max_phrases: usize) -> Completions {
+        let mut completions = Completions::default();
+        if !prefix.bytes().all(|b| b.is_ascii_digit()) {
+            return completions;
+        }
+        let complete = &prefix[..(prefix.len() - prefix.len() % 2)];
+        completions.decoded = if complete.is_empty() {
+            Some(String::new())
+        } else {
+            self.convert_from_two_touch_string_with(
+                complete,
+                &DecodeOptions::default().with_dakuten_mode(DakutenMode::Composed),
+            )
+            .ok()
+        };
+        let pending = &prefix[complete.len()..];
+        for digit in (1..=9).chain(0..1) {
+            let chars: Vec<char> = if pending.is_empty() {
+                (1..=9)
+                    .chain(0..1)
+                    .filter_map(|second| self.decode_pair_digits(digit, second))
+                    .collect()
+            } else {
+                self.decode_pair(&format!("{}{}", pending, digit))
+                    .into_iter()
+                    .collect()
+            };
+            if !chars.is_empty() {
+                completions.keys.push(KeyHint { digit, chars });
+            }
+        }
+        completions.phrases = self
+            .reserved()
+            .prefix_index
+            .range((Cow::Owned(prefix.to_string()), String::new())..)
+            .take_while(|(code, _)| code.starts_with(prefix))
+            .take(max_phrases)
+            .map(|(code, key)| {
+                (
+                    code.to_string(),
+                    self.reserved().word_map[key].phrase.clone(),
+                )
+            })
+            .collect();
+        completions
+    }
+
+    /// `code` の `pair_index` 組目 (0始まり) の代わりになりそうな, 表にある2桁のコードを返す。
+    /// 1桁だけ違うコードと, 2桁を入れ替えたコードを候補にし, 前後の組と同じ種類の文字
+    /// (かな, 英字, 数字, 記号) になるもの, かな, 入れ替えたもの, の順に並べる。
+    /// `ngram` featureでは, それぞれの中で前後の文字と合わせた `score_decoded` が高いものを先に並べる。
+    /// 候補は最大 `MAX_CODE_FIXES` 個で, 組が範囲外の場合は空のVecを返す。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// let c = Converter::new();
+    /// let fixes = c.suggest_code_fixes("812280", 2);
+    /// assert!(fixes.len() <= MAX_CODE_FIXES);
+    /// assert!(fixes.contains(&"81".to_string())); // "812281" は "やきや"
+    /// ```
+    #[cfg(feature = "decode")]
+    pub fn suggest_code_fixes(&self, code: &str, pair_index: usize) -> Vec<String> {
+        let offset = pair_index * 2;
+        let pair = match code.get(offset..(offset + 2)) {
+            Some(pair) if pair.bytes().all(|b| b.is_ascii_digit()) => pair.as_bytes(),
+            _ => return Vec::new(),
+        };
+        let neighbor = |offset: Option<usize>| {
+            offset
+                .and_then(|offset| code.get(offset..(offset + 2)))
+                .and_then(|pair| self.decode_pair(pair))
+                .map(CharClass::of)
+        };
+        let neighbors = [neighbor(offset.checked_sub(2)), neighbor(Some(offset + 2))];
+
+        let mut fixes: Vec<(bool, CharClass, bool, String)> = Vec::new();
+        let mut push = |fix: [u8; 2], swapped: bool| {
+            let fix = String::from_utf8_lossy(&fix).into_owned();
+            if let Some(ch) = self.decode_pair(&fix) {
+                if fixes.iter().all(|(.., other)| *other != fix) {
+                    let class = CharClass::of(ch);
+                    let unlike = !neighbors.contains(&Some(class));
+                    fixes.push((unlike, class, !swapped, fix));
+                }
+            }
+        };
+        if pair[0] != pair[1] {
+            push([pair[1], pair[0]], true);
+        }
+        for i in 0..2 {
+            for digit in b'0'..=b'9' {
+                if digit != pair[i] {
+                    let mut fix = [pair[0], pair[1]];
+                    fix[i] = digit;
+                    push(fix, false);
+                }
+            }
+        }
+        fixes.sort();
+        #[cfg(feature = "ngram")]
+        {
+            // 同じ種類の候補の中では, 前後の文字と合わせた並びがもっともらしいものを先にする
+            let context = |fix: &str| -> f64 {
+                let around = |offset: Option<usize>| {
+                    offset
+                        .and_then(|offset| code.get(offset..(offset + 2)))
+                        .and_then(|pair| self.decode_pair(pair))
+                };
+                let text: String = [
+                    around(offset.checked_sub(2)),
+                    self.decode_pair(fix),
+                    around(Some(offset + 2)),
+                ]
+                .iter()
+                .flatten()
+                .collect();
+                self.score_decoded(&text)
+            };
+            fixes.sort_by(|a, b| {
+                (a.0, a.1, a.2)
+                    .cmp(&(b.0, b.1, b.2))
+                    .then_with(|| context(&b.3).total_cmp(&context(&a.3)))
+            });
+        }
+        fixes
+            .into_iter()
+            .take(MAX_CODE_FIXES)
+            .map(|(.., fix)| fix)
+            .collect()
+    }
+
+    /// 2タッチ入力を最後まで検査し, 見つかった問題をすべて返す。
+    /// 問題がなければ空のVecを返す。
+    /// 変換や `validate_two_touch` と同じく入力を2バイトずつに区切って調べ, `offset` はバイト位置になる。
+    /// 数字以外の文字は `NonDigit` として1回だけ報告し, その文字を含む組のコードは調べない。
+    /// `OddTrailingDigit` は入力のバイト数が奇数で, 最後が数字の場合だけ報告する。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// let c = Converter::new();
+    /// c.check_two_touch_string("8011a"); // [UnknownCode { code: "80", pair_index: 0, offset: 0 }, NonDigit { ch: 'a', offset: 4 }]
+    /// ```
+    #[cfg(feature = "decode")]
+    pub fn check_two_touch_string(&self, val: &str) -> Vec<DecodeIssue> {
+        let mut issues = Vec::new();
+        if val.is_empty() {
+            issues.push(DecodeIssue::Empty);
+            return issues;
+        }
+        if self.profile == Profile::NumericOnly {
+            return val
+                .char_indices()
+                .filter(|&(_, ch)| !NUMERIC_CHARS.contains(ch))
+                .map(|(offset, ch)| DecodeIssue::NonDigit { ch, offset })
+                .collect();
+        }
+        // 戻すときと同じく2バイトずつに区切り, 組の2文字目まで読んだところでコードを調べる
+        let bytes = val.as_bytes();
+        for (offset, ch) in val.char_indices() {
+            if !ch.is_ascii_digit() {
+                issues.push(DecodeIssue::NonDigit { ch, offset });
+                continue;
+            }
+            if offset % 2 == 0 || !bytes[offset - 1].is_ascii_digit() {
+                continue;
+            }
+            let code = &val[(offset - 1)..=offset];
+            if self.tables.inverse_table.get(code).is_none() {
+                issues.push(DecodeIssue::UnknownCode {
+                    code: code.to_string(),
+                    pair_index: offset / 2,
+                    offset: offset - 1,
+                });
+            }
+        }
+        if !val.len().is_multiple_of(2) && bytes[val.len() - 1].is_ascii_digit() {
+            issues.push(DecodeIssue::OddTrailingDigit {
+                offset: val.len() - 1,
+            });
+        }
+        issues
+    }
+
+    /// 2タッチ入力を検査した結果を返す。エラーにはならず, 空の入力でも結果を返す。
+    /// `errors` は `check_two_touch_string` と同じで, 変換はできても怪しいところは `warnings` に入る。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// let c = Converter::new();
+    /// let report = c.validate_two_touch("0421");
+    /// report.is_decodable; // true
+    /// report.warnings; // [MisplacedDakuten { code: "04", offset: 0 }]
+    /// ```
+    #[cfg(feature = "decode")]
+    pub fn validate_two_touch(&self, val: &str) -> CodeReport {
+        let errors = self.check_two_touch_string(val);
+        let mut warnings = Vec::new();
+        if self.profile != Profile::NumericOnly {
+            let pairs = val.as_bytes().chunks_exact(2).enumerate();
+            for (pair_index, pair) in pairs {
+                if pair != b"04" && pair != b"05" {
+                    continue;
+                }
+                let offset = pair_index * 2;
+                // 濁点・半濁点は前の文字と合わせて1文字になるはず
+                let composes = offset >= 2
+                    && val
+                        .get((offset - 2)..(offset + 2))
+                        .is_some_and(|code| self.tables.inverse_table.get(code).is_some());
+                if !composes {
+                    warnings.push(CodeWarning::MisplacedDakuten {
+                        code: val[offset..(offset + 2)].to_string(),
+                        offset,
+                    });
+                }
+            }
+        }
+        CodeReport {
+            is_decodable: errors.is_empty(),
+            errors,
+            warnings,
+        }
+    }
+
+    /// メッセージを送るのにかかる桁数, キーを押す回数, 表示される文字数, 入力にかかる時間の目安を,
+    /// 最も短い候補と1文字ずつ変換した候補についてそれぞれ返す。`MetricsOptions::default()` の設定を使う。
+    /// 1文字ずつ変換できない場合 (`ご苦労さん` など) は `literal` がNoneになる。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// # #[cfg(feature = "builtin-dictionary")] {
+    /// let c = Converter::new();
+    /// let metrics = c.metrics("ごくろうさん").unwrap();
+    /// assert_eq!(metrics.best.digits, 4);
+    /// assert_eq!(metrics.literal.unwrap().digits, 14);
+    /// # }
+    /// ```
+    #[cfg(all(feature = "encode", feature = "decode"))]
+    pub fn metrics(&self, val: &str) -> Result<MessageMetrics, Error> {
+        self.metrics_with(val, &MetricsOptions::default())
+    }
+
+    /// `options` の設定で `metrics` と同じように数える
+    #[cfg(all(feature = "encode", feature = "decode"))]
+    pub fn metrics_with(
+        &self,
+        val: &str,
+        options: &MetricsOptions,
+    ) -> Result<MessageMetrics, Error> {
+        options.validate()?;
+        let candidates = self.convert_to_two_touch(val)?;
+        let measure = |candidate: &Candidate| {
+            let digits = candidate.digits();
+            let display_chars = match candidate.kind() {
+                CandidateKind::Literal => self
+                    .display_len(digits, DakutenMode::Composed)
+                    .map(|len| len.len + len.incomplete as usize)
+                    .unwrap_or(digits.len()),
+                _ => digits.len(),
+            };
+            let keypresses = options.prefix.len() + digits.len() + options.suffix.len();
+            CandidateMetrics {
+                digits: digits.len(),
+                keypresses,
+                display_chars,
+                entry_time: Duration::from_secs_f64(keypresses as f64 / options.digits_per_second),
+            }
+        };
+        // 候補は必ず1つ以上ある
+        let best = candidates
+            .iter()
+            .min_by_key(|candidate| candidate.digits().len())
+            .map(measure)
+            .unwrap_or_default();
+        let literal = candidates
+            .iter()
+            .find(|candidate| *candidate.kind() == CandidateKind::Literal)
+            .map(measure);
+        Ok(MessageMetrics { best, literal })
+    }
+
+    /// 2タッチ入力を変換せずに, ポケベルに表示される文字数を数える。
+    /// 入力途中を想定して, 末尾の1桁は数えずに `incomplete` を立てる。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// let c = Converter::new();
+    /// c.display_len("2104", DakutenMode::Composed).unwrap(); // DisplayLen { len: 1, incomplete: false }
+    /// c.display_len("21042", DakutenMode::Raw).unwrap(); // DisplayLen { len: 2, incomplete: true }
+    /// ```
+    #[cfg(feature = "decode")]
+    pub fn display_len(&self, code: &str, mode: DakutenMode) -> Result<DisplayLen, Error> {
+        if self.profile == Profile::NumericOnly {
+            self.check_numeric(code)?;
+            return Ok(DisplayLen {
+                len: code.len(),
+                incomplete: false,
+            });
+        }
+        if let Some((offset, ch)) = code.char_indices().find(|(_, ch)| !ch.is_ascii()) {
+            return Err(Error::from(ErrorKind::NonAscii { ch, offset }));
+        }
+        let pairs = code.len() / 2;
+        let mut len = 0;
+        let mut i = 0;
+        while i < pairs {
+            let idx = i * 2;
+            len += 1;
+            if mode == DakutenMode::Composed
+                && i + 1 < pairs
+                && self.table_char(&code[idx..(idx + 4)]).is_some()
+            {
+                i += 2;
+            } else {
+                self.pair_char(&code[idx..(idx + 2)], i)?;
+                i += 1;
+            }
+        }
+        Ok(DisplayLen {
+            len,
+            incomplete: !code.len().is_multiple_of(2),
+        })
+    }
+
+    /// 2タッチ入力を変換し, 各文字がどの数字から変換されたかを返す。
+    /// `DakutenMode::Composed` では濁点・半濁点 (04, 05) を前の文字とまとめて1文字にする。
+    /// `offset` は `code` の先頭からの桁位置で, どちらのモードでも同じ数字を指す。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// let c = Converter::new();
+    /// let trace = c.decode_with_trace("2104", DakutenMode::Composed).unwrap();
+    /// assert_eq!((trace[0].ch, trace[0].composed), ('が', true));
+    /// ```
+    #[cfg(feature = "decode")]
+    pub fn decode_with_trace(
+        &self,
+        code: &str,
+        mode: DakutenMode,
+    ) -> Result<Vec<DecodedSegment>, Error> {
+        if code.is_empty() {
+            return Err(Error::from(ErrorKind::EmptyInput));
+        }
+        if let Some((offset, ch)) = code.char_indices().find(|(_, ch)| !ch.is_ascii()) {
+            return Err(Error::from(ErrorKind::NonAscii { ch, offset }));
+        }
+        if self.profile == Profile::NumericOnly {
+            self.check_numeric(code)?;
+            return Ok(code
+                .char_indices()
+                .map(|(offset, ch)| DecodedSegment {
+                    code: ch.to_string(),
+                    offset,
+                    ch,
+                    composed: false,
+                })
+                .collect());
+        }
+        if let Some((index, ch)) = code.char_indices().find(|(_, ch)| !ch.is_ascii_digit()) {
+            return Err(Error::from(ErrorKind::NonDigit { ch, index }));
+        }
+        if !code.len().is_multiple_of(2) {
+            return Err(Error::from(ErrorKind::OddLength { len: code.len() }));
+        }
+        let pairs = code.len() / 2;
+        let mut segments = Vec::new();
+        let mut i = 0;
+        while i < pairs {
+            let offset = i * 2;
+            let voiced = if mode == DakutenMode::Composed && i + 1 < pairs {
+                self.table_char(&code[offset..(offset + 4)])
+            } else {
+                None
+            };
+            let (ch, len) = match voiced {
+                Some(voiced) => (voiced, 4),
+                None => (self.pair_char(&code[offset..(offset + 2)], i)?, 2),
+            };
+            segments.push(DecodedSegment {
+                code: code[offset..(offset + len)].to_string(),
+                offset,
+                ch,
+                composed: len == 4,
+            });
+            i += len / 2;
+        }
+        Ok(segments)
+    }
+
+    /// 2タッチ入力を, 文字の種類の付いた `PagerSymbol` の並びに変換する。
+    /// 濁点・半濁点は前の文字とまとめ (`DakutenMode::Composed` と同じ), まとめられない場合は
+    /// `SymbolKind::Dakuten` / `SymbolKind::Handakuten` になる。
+    /// 結果の `to_string()` は `DakutenMode::Composed` で変換した文字列と同じ。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// let c = Converter::new();
+    /// let symbols = c.decode_symbols("210404").unwrap();
+    /// assert_eq!(symbols[0].kind, SymbolKind::VoicedKana('が'));
+    /// assert_eq!((symbols[1].kind, symbols[1].offset), (SymbolKind::Dakuten, 4));
+    /// assert_eq!(symbols.to_string(), "が゛");
+    /// ```
+    #[cfg(feature = "decode")]
+    pub fn decode_symbols(&self, code: &str) -> Result<PagerSymbols, Error> {
+        self.symbols(code, DakutenMode::Composed)
+    }
+
+    #[cfg(feature = "decode")]
+    fn symbols(&self, code: &str, mode: DakutenMode) -> Result<PagerSymbols, Error> {
+        let symbols = self
+            .decode_with_trace(code, mode)?
+            .into_iter()
+            .map(|segment| {
+                let kind = match segment.ch {
+                    ch if segment.composed => SymbolKind::VoicedKana(ch),
+                    '゛' => SymbolKind::Dakuten,
+                    '゜' => SymbolKind::Handakuten,
+                    ' ' => SymbolKind::Space,
+                    ch @ '0'..='9' => SymbolKind::Digit(ch as u8 - b'0'),
+                    ch => match CharClass::of(ch) {
+                        CharClass::Kana => SymbolKind::Kana(ch),
+                        CharClass::Latin => SymbolKind::Letter(ch),
+                        _ => SymbolKind::Symbol(ch),
+                    },
+                };
+                PagerSymbol {
+                    kind,
+                    offset: segment.offset,
+                }
+            })
+            .collect();
+        Ok(PagerSymbols(symbols))
+    }
+
+    /// 2タッチ入力を, 読み上げやすい文字列に変換する。
+    /// かな, 英字, 数字が続く部分はそのまま, 濁点や記号は `spoken_name` の言葉にして,
+    /// それぞれを `SpokenOptions::default()` の区切り (`、`) でつなぐ。
+    /// 濁点・半濁点は前の文字とまとめる。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// let c = Converter::new();
+    /// assert_eq!(c.decode_spoken("827175").unwrap(), "かっこ、まも");
+    /// ```
+    #[cfg(feature = "decode")]
+    pub fn decode_spoken(&self, code: &str) -> Result<String, Error> {
+        self.decode_spoken_with(code, &SpokenOptions::default())
+    }
+
+    /// `options` の設定で `decode_spoken` と同じように変換する。
+    /// `DakutenMode::Raw` では濁点・半濁点もすべて `だくてん` / `はんだくてん` と読む
+    #[cfg(feature = "decode")]
+    pub fn decode_spoken_with(&self, code: &str, options: &SpokenOptions) -> Result<String, Error> {
+        let mut words: Vec<String> = Vec::new();
+        let mut word = String::new();
+        for symbol in self.symbols(code, options.dakuten_mode)? {
+            let ch = symbol.as_char();
+            match spoken_name(ch) {
+                Some(name) => {
+                    if !word.is_empty() {
+                        words.push(std::mem::take(&mut word));
+                    }
+                    words.push(name.to_string());
+                }
+                None => word.push(ch),
+            }
+        }
+        if !word.is_empty() {
+            words.push(word);
+        }
+        Ok(words.join(&options.separator))
+    }
+
+    /// 入力を1文字ずつ変換してから `DakutenMode::Raw` で元に戻し, 戻らなかった文字を返す。
+    /// 予約語は使わず, 空白の整理と `{}` の扱いは `convert_to_two_touch_string` と同じ。
+    ///
+    /// 次のことを保証する。
+    /// - `digits` は `encode_with_trace` の数字をつなげたもの
+    /// - `decoded` は `digits` を `convert_from_two_touch_string` で戻した文字列
+    /// - `substitutions` にない文字は, そのまま `decoded` に残る
+    /// - 濁音・半濁音は `DakutenMode::Composed` で戻すと1文字になる。ただし入力が `か゛` のように
+    ///   濁点を別の文字として含む場合も `が` になる
+    ///
+    /// 逆に, 変換表の2桁のコードだけからなる数字は, 元に戻してから1文字ずつ変換すると同じ数字になる。
+    /// 空白だけの入力は, `SpaceOptions::trim_spaces` を指定した場合は何も残らないのでエラーになる。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// let c = Converter::new();
+    /// let rt = c.verify_roundtrip("がっこう").unwrap();
+    /// assert_eq!(rt.decoded.as_deref(), Some("か゛つこう"));
+    /// assert_eq!(rt.substitutions.len(), 2); // [DakutenSplit { index: 0, .. }, Normalized { index: 1, .. }]
+    /// assert!(c.verify_roundtrip("こんにちは").unwrap().is_exact());
+    /// ```
+    #[cfg(all(feature = "encode", feature = "decode"))]
+    pub fn verify_roundtrip(&self, val: &str) -> Result<RoundTrip, Error> {
+        let mut digits = String::new();
+        let mut substitutions = Vec::new();
+        let mut kept = HashSet::new();
+        for token in self.tokens(val, &self.options)? {
+            let (index, code) = match token {
+                Token::Char(index, ch) => match self.encode_char(ch) {
+                    Some(code) => (index, code),
+                    None => return Err(self.unsupported(ch, index)),
+                },
+                Token::Digits(index, ref code) => (index, code.as_str()),
+                Token::Passthrough(index, ref code) => {
+                    kept.extend(index..(index + code.len()));
+                    (index, code.as_str())
+                }
+            };
+            digits.push_str(code);
+            kept.insert(index);
+            let decoded: Option<String> = self
+                .decode_with_trace(code, DakutenMode::Raw)
+                .ok()
+                .map(|decoded| decoded.iter().map(|segment| segment.ch).collect());
+            let original = match token {
+                Token::Char(_, ch) => ch,
+                Token::Digits(..) | Token::Passthrough(..) => {
+                    substitutions.push(Substitution::RawDigits {
+                        index,
+                        digits: code.to_string(),
+                        decoded,
+                    });
+                    continue;
+                }
+            };
+            let decoded = match decoded {
+                Some(decoded) => decoded,
+                None => {
+                    substitutions.push(Substitution::Undecodable {
+                        index,
+                        original,
+                        code: code.to_string(),
+                    });
+                    continue;
+                }
+            };
+            let mut chars = decoded.chars();
+            let substitution = match (chars.next(), chars.next()) {
+                (Some(ch), None) if ch == original => continue,
+                (Some(ch), None) => Substitution::Normalized {
+                    index,
+                    original,
+                    decoded: ch,
+                },
+                _ if self.tables.expansion_map.contains_key(&original) => Substitution::Expanded {
+                    index,
+                    original,
+                    decoded,
+                },
+                _ => Substitution::DakutenSplit {
+                    index,
+                    original,
+                    decoded,
+                },
+            };
+            substitutions.push(substitution);
+        }
+        for token in tokenize(val, self.options.escapes)? {
+            if let Token::Char(index, ch) = token {
+                if !kept.contains(&index) {
+                    substitutions.push(Substitution::Removed {
+                        index,
+                        original: ch,
+                    });
+                }
+            }
+        }
+        substitutions.sort_by_key(Substitution::index);
+        let decoded = self
+            .convert_from_two_touch_string_with(&digits, &DecodeOptions::default())
+            .ok();
+        Ok(RoundTrip {
+            digits,
+            decoded,
+            substitutions,
+        })
+    }
+
+    /// 予約語に近い言い回しを探し, 編集距離が `max_distance` 以下の予約語とその数字を返す。
+    /// 比較の前に全角・半角, 大文字・小文字, カタカナ・ひらがなの違いをそろえ, 距離は文字単位で数える。
+    /// 距離の近い順に並べ, 距離が同じ場合は予約語の文字列順に並べる。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// let c = Converter::new();
+    /// c.suggest_reserved("ごくろうさま", 1); // [("ごくろうさん", ["5963"])]
+    /// ```
+    #[cfg(feature = "encode")]
+    pub fn suggest_reserved(&self, val: &str, max_distance: usize) -> Vec<(&str, &[Code])> {
+        let query: Vec<char> = self.fold_phrase(val).chars().collect();
+        let mut suggestions: Vec<(usize, &str, &[Code])> = self
+            .reserved()
+            .word_map
+            .iter()
+            .filter_map(|(key, word)| {
+                let key: Vec<char> = key.chars().collect();
+                let distance = edit_distance(&query, &key);
+                if distance <= max_distance {
+                    Some((distance, word.phrase.as_str(), word.codes.as_slice()))
+                } else {
+                    None
+                }
+            })
+            .collect();
+        suggestions.sort();
+        suggestions
+            .into_iter()
+            .map(|(_, phrase, codes)| (phrase, codes))
+            .collect()
+    }
+
+    /// 入力途中の言葉 `prefix` で始まる予約語とその数字を, 最大 `limit` 個返す。
+    /// `suggest_reserved` と同じく表記の違いをそろえてから比べ, 予約語の文字数, 文字列の順に並べる。
+    /// 漢字などを含む予約語は, 同じ数字のかなの予約語がある場合はそちらで引き, 重ねて返さない。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// # #[cfg(feature = "builtin-dictionary")] {
+    /// let c = Converter::new();
+    /// let suggestions = c.suggest_phrases("ヨロ", 5);
+    /// assert_eq!(suggestions[0].0, "よろしく");
+    /// assert_eq!(suggestions[0].1, ["4649"]);
+    /// # }
+    /// ```
+    #[cfg(feature = "encode")]
+    pub fn suggest_phrases(&self, prefix: &str, limit: usize) -> Vec<(&str, &[Code])> {
+        let prefix = self.fold_phrase(prefix);
+        let mut suggestions: Vec<(usize, &str, &ReservedWord)> = self
+            .reserved()
+            .word_map
+            .range(prefix.clone()..)
+            .take_while(|(key, _)| key.starts_with(&prefix))
+            .filter(|(key, word)| is_kana_phrase(key) || !self.has_kana_counterpart(word))
+            .map(|(key, word)| (key.chars().count(), key.as_str(), word))
+            .collect();
+        suggestions.sort_by(|a, b| (a.0, a.1).cmp(&(b.0, b.1)));
+        suggestions
+            .into_iter()
+            .take(limit)
+            .map(|(.., word)| (word.phrase.as_str(), word.codes.as_slice()))
+            .collect()
+    }
+
+    /// `query` に合う予約語を, 辞書の順 (表記の違いをそろえた予約語の文字列順) に返す。
+    /// 数字で探す場合は, どれか1つの数字が合えばその予約語のすべての数字を返す。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// # #[cfg(feature = "builtin-dictionary")] {
+    /// let c = Converter::new();
+    /// let entries = c.search_reserved(&DictionaryQuery::code("4649"));
+    /// assert_eq!(entries[0].phrase, "よろしく");
+    /// assert!(!c.search_reserved(&DictionaryQuery::phrase("*してる")).is_empty());
+    /// # }
+    /// ```
+    #[cfg(feature = "encode")]
+    pub fn search_reserved(&self, query: &DictionaryQuery) -> Vec<Entry> {
+        let pattern: Vec<char> = match query.target {
+            QueryTarget::Phrase => self.fold_phrase(&query.pattern).chars().collect(),
+            QueryTarget::Code => query.pattern.chars().collect(),
+        };
+        let glob = pattern.contains(&'*');
+        let matches = |text: &str| {
+            let text: Vec<char> = text.chars().collect();
+            if glob {
+                glob_match(&pattern, &text)
+            } else {
+                pattern.is_empty()
+                    || text
+                        .windows(pattern.len())
+                        .any(|window| window == pattern.as_slice())
+            }
+        };
+        self.reserved()
+            .word_map
+            .iter()
+            .filter(|(key, word)| match query.target {
+                QueryTarget::Phrase => matches(key),
+                QueryTarget::Code => word.codes.iter().any(|code| matches(code)),
+            })
+            .map(|(_, word)| Entry {
+                phrase: word.phrase.clone(),
+                codes: code_strings(&word.codes),
+            })
+            .collect()
+    }
+
+    /// 同じ数字を持つ, かなだけの予約語があるか
+    #[cfg(feature = "encode")]
+    fn has_kana_counterpart(&self, word: &ReservedWord) -> bool {
+        let bucket = match word.codes.first() {
+            Some(code) => self.reserved().code_index.get(&code.len()),
+            None => None,
+        };
+        bucket.into_iter().flatten().any(|(code, key)| {
+            *code == word.codes[0]
+                && is_kana_phrase(key)
+                && self.reserved().word_map[key].codes == word.codes
+        })
+    }
+
+    /// 書き写しの誤りなどで崩れた数字 `code` 全体を予約語の数字と比べ, 編集距離が `max_edits` 以下の
+    /// 予約語を返す。1桁の置き換え, 追加, 削除と, 隣り合う2桁の入れ替えをそれぞれ1回と数える。
+    /// 編集回数の少ない順に並べ, 同じ場合は予約語, 数字の順に並べる。
+    /// 桁数が `max_edits` より多く違う数字とは比べない。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// # #[cfg(feature = "builtin-dictionary")] {
+    /// let c = Converter::new();
+    /// let repairs = c.repair_against_dictionary("4694", 1);
+    /// assert_eq!(repairs[0].phrase, "よろしく");
+    /// assert_eq!((repairs[0].code.as_str(), repairs[0].edits), ("4649", 1));
+    /// # }
+    /// ```
+    #[cfg(all(feature = "encode", feature = "decode"))]
+    pub fn repair_against_dictionary(&self, code: &str, max_edits: usize) -> Vec<RepairCandidate> {
+        let min_len = code.len().saturating_sub(max_edits);
+        let max_len = code.len().saturating_add(max_edits);
+        let mut repairs: Vec<RepairCandidate> = self
+            .reserved()
+            .code_index
+            .range(min_len..=max_len)
+            .flat_map(|(_, bucket)| bucket.iter())
+            .filter_map(|(reserved, key)| {
+                let edits = digit_edit_distance(code.as_bytes(), reserved.as_bytes());
+                if edits > max_edits {
+                    return None;
+                }
+                Some(RepairCandidate {
+                    phrase: self.reserved().word_map[key].phrase.clone(),
+                    code: reserved.to_string(),
+                    edits,
+                })
+            })
+            .collect();
+        repairs.sort_by(|a, b| (a.edits, &a.phrase, &a.code).cmp(&(b.edits, &b.phrase, &b.code)));
+        repairs
+    }
+
+    /// 表記の違いをそろえて予約語を引く
+    #[cfg(feature = "encode")]
+    fn reserved_for(&self, val: &str) -> Option<&ReservedWord> {
+        self.reserved().word_map.get(&self.fold_phrase(val))
+    }
+
+    /// 予約語を追加する。同じ予約語がすでにある場合は置き換える。
+    /// 数字の確認は `ConverterBuilder::add_reserved_word` と同じ。
+    ///
+    /// `pokebell::encode` などが使う共有のConverterは変更できないので, 自分で作ったConverterに対して使う。
+    /// 複数のスレッドで共有する場合は `RwLock` などで包んでください。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// let mut c = Converter::new();
+    /// c.add_reserved_word("なおちゃん", &["703"]).unwrap();
+    /// c.convert_to_two_touch_string("なおちゃん").unwrap(); // ["703", "5115428103"]
+    /// ```
+    #[cfg(feature = "encode")]
+    pub fn add_reserved_word<S: AsRef<str>>(
+        &mut self,
+        phrase: &str,
+        codes: &[S],
+    ) -> Result<(), BuildError> {
+        let codes = reserved_codes(phrase, codes)?;
+        self.insert_reserved_word(phrase.to_string(), codes);
+        Ok(())
+    }
+
+    /// 予約語を取り除く。辞書にあった場合はtrueを返す
+    #[cfg(feature = "encode")]
+    pub fn remove_reserved_word(&mut self, phrase: &str) -> bool {
+        let key = self.fold_phrase(phrase);
+        if !self.reserved().word_map.contains_key(&key) {
+            return false;
+        }
+        self.reserved_mut().remove(&key);
+        true
+    }
+
+    /// 予約語をすべて取り除く。ほかのConverterと共有していた辞書は変えない
+    #[cfg(feature = "encode")]
+    pub fn clear_reserved_words(&mut self) {
+        self.reserved = OnceLock::from(Arc::default());
+    }
+
+    /// 辞書にある予約語の数
+    #[cfg(feature = "encode")]
+    pub fn reserved_len(&self) -> usize {
+        self.reserved().word_map.len()
+    }
+
+    /// 辞書の予約語と数字を, 予約語の順に返す。
+    /// 辞書の内容が同じなら, 毎回同じ順になる。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// let mut c = Converter::without_reserved_words();
+    /// c.add_reserved_word("よろしく", &["4649"]).unwrap();
+    /// c.add_reserved_word("おやすみ", &["833", "0833"]).unwrap();
+    /// let phrases: Vec<_> = c.reserved_phrases().map(|(phrase, _)| phrase).collect();
+    /// assert_eq!(phrases, ["おやすみ", "よろしく"]);
+    /// ```
+    #[cfg(feature = "encode")]
+    pub fn reserved_phrases(&self) -> impl Iterator<Item = (&str, &[Code])> {
+        let mut words: Vec<&ReservedWord> = self.reserved().word_map.values().collect();
+        words.sort_by(|a, b| a.phrase.cmp(&b.phrase));
+        words
+            .into_iter()
+            .map(|word| (word.phrase.as_str(), word.codes.as_slice()))
+    }
+
+    /// 予約語 `phrase` の数字を返す。表記の違いはそろえてから引く。
+    /// 既定の辞書の数字は埋め込んだ文字列を指す `Cow::Borrowed` になる
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// # #[cfg(feature = "builtin-dictionary")] {
+    /// let c = Converter::new();
+    /// assert_eq!(c.reserved_codes_for("よろしく").unwrap(), ["4649"]);
+    /// assert_eq!(c.reserved_codes_for("こんにちは"), None);
+    /// # }
+    /// ```
+    #[cfg(feature = "encode")]
+    pub fn reserved_codes_for(&self, phrase: &str) -> Option<&[Code]> {
+        self.reserved_for(phrase).map(|word| word.codes.as_slice())
+    }
+
+    /// 数字 `code` になる予約語を, 予約語の順に返す
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// # #[cfg(feature = "builtin-dictionary")] {
+    /// let c = Converter::new();
+    /// assert_eq!(c.phrases_for_code("4649"), ["よろしく"]);
+    /// # }
+    /// ```
+    #[cfg(feature = "encode")]
+    pub fn phrases_for_code(&self, code: &str) -> Vec<&str> {
+        self.reserved_words_for_code(code)
+            .into_iter()
+            .map(|word| word.phrase.as_str())
+            .collect()
+    }
+
+    /// 数字 `code` になる予約語を, 読み, 語呂合わせの由来と注記とともに予約語の順に返す。
+    /// 辞書の項目に書かれていないものは `None` か空になる
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// # #[cfg(feature = "builtin-dictionary")] {
+    /// let c = Converter::new();
+    /// let info = &c.explain_reserved("4649")[0];
+    /// assert_eq!(info.reading.as_deref(), Some("よろしく"));
+    /// assert_eq!(info.derivation[1], Pun::new("6", "ろ"));
+    /// # }
+    /// ```
+    #[cfg(feature = "encode")]
+    pub fn explain_reserved(&self, code: &str) -> Vec<ReservedEntryInfo> {
+        self.reserved_words_for_code(code)
+            .into_iter()
+            .map(|word| {
+                let metadata = word.metadata.as_deref();
+                ReservedEntryInfo {
+                    phrase: word.phrase.clone(),
+                    codes: code_strings(&word.codes),
+                    reading: metadata.and_then(|metadata| metadata.reading.clone()),
+                    derivation: metadata
+                        .map(|metadata| metadata.derivation.clone())
+                        .unwrap_or_default(),
+                    note: metadata.and_then(|metadata| metadata.note.clone()),
+                }
+            })
+            .collect()
+    }
+
+    #[cfg(feature = "encode")]
+    fn reserved_words_for_code(&self, code: &str) -> Vec<&ReservedWord> {
+        let mut words: Vec<&ReservedWord> = self
+            .reserved()
+            .prefix_index
+            .range((Cow::Owned(code.to_string()), String::new())..)
+            .take_while(|(reserved, _)| reserved == code)
+            .map(|(_, key)| &self.reserved().word_map[key])
+            .collect();
+        words.sort_by(|a, b| a.phrase.cmp(&b.phrase));
+        words
+    }
+
+    /// 辞書の予約語 `phrase` を, すでにある予約語と `strategy` で合わせる。
+    /// `MergeStrategy::Append` では, すでにある予約語の読みや由来を残す
+    #[cfg(feature = "encode")]
+    fn merge_reserved_word(&mut self, word: DictionaryWord, strategy: MergeStrategy) {
+        let DictionaryWord {
+            phrase,
+            codes,
+            source,
+            metadata,
+        } = word;
+        let existing = match self.reserved_for(&phrase) {
+            Some(existing) => existing,
+            None => return self.insert_reserved_word_from(phrase, codes, vec![source], metadata),
+        };
+        match strategy {
+            MergeStrategy::Replace => {
+                self.insert_reserved_word_from(phrase, codes, vec![source], metadata)
+            }
+            MergeStrategy::Append => {
+                let phrase = existing.phrase.clone();
+                let mut merged = existing.codes.clone();
+                for code in codes {
+                    if !merged.contains(&code) {
+                        merged.push(code);
+                    }
+                }
+                let mut sources = existing.sources.clone();
+                sources.push(source);
+                let metadata = existing.metadata.clone().or(metadata);
+                self.insert_reserved_word_from(phrase, merged, sources, metadata);
+            }
+            MergeStrategy::KeepExisting => {}
+        }
+    }
+
+    /// 予約語の辞書の全体を, 予約語を追加した辞書の名前とともに返す。
+    /// 辞書を重ねたときに, どの辞書の予約語が残ったかを確かめるのに使う。
+    /// 並び順は `fold_phrase` でそろえた予約語の順
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// # #[cfg(feature = "builtin-dictionary")] {
+    /// let c = Converter::new();
+    /// let entry = c.effective_dictionary().into_iter().find(|e| e.phrase == "よろしく").unwrap();
+    /// assert_eq!(entry.codes, vec!["4649"]);
+    /// assert_eq!(entry.sources, vec![BUILTIN_DICTIONARY]);
+    /// # }
+    /// ```
+    #[cfg(feature = "encode")]
+    pub fn effective_dictionary(&self) -> Vec<EffectiveEntry> {
+        self.reserved()
+            .word_map
+            .values()
+            .map(|word| EffectiveEntry {
+                phrase: word.phrase.clone(),
+                codes: code_strings(&word.codes),
+                sources: word
+                    .sources
+                    .iter()
+                    .map(|source| source.to_string())
+                    .collect(),
+            })
+            .collect()
+    }
+
+    /// 予約語の辞書に登録された表記と数字
+    #[cfg(feature = "encode")]
+    pub(crate) fn reserved_entry(&self, val: &str) -> Option<(&str, &[Code])> {
+        self.reserved_for(val)
+            .map(|word| (word.phrase.as_str(), word.codes.as_slice()))
+    }
+
+    /// 読みの分かる予約語を, ひらがなの読みと数字の組にして予約語の順に返す。
+    /// 辞書に読みがない予約語は, かなだけの表記なら表記を読みにする
+    #[cfg(feature = "encode")]
+    pub(crate) fn reserved_readings(&self) -> Vec<(String, &[Code])> {
+        let mut words: Vec<&ReservedWord> = self.reserved().word_map.values().collect();
+        words.sort_by(|a, b| a.phrase.cmp(&b.phrase));
+        words
+            .into_iter()
+            .filter_map(|word| {
+                let reading = word
+                    .metadata
+                    .as_ref()
+                    .and_then(|metadata| metadata.reading.as_deref())
+                    .and_then(kana_reading)
+                    .or_else(|| kana_reading(&word.phrase))?;
+                Some((reading, word.codes.as_slice()))
+            })
+            .collect()
+    }
+
+    #[cfg(feature = "encode")]
+    fn insert_reserved_word(&mut self, phrase: String, codes: Vec<Code>) {
+        self.insert_reserved_word_from(phrase, codes, Vec::new(), None);
+    }
+
+    /// 予約語を, 追加した辞書の名前とともに追加する
+    #[cfg(feature = "encode")]
+    fn insert_reserved_word_from(
+        &mut self,
+        phrase: String,
+        codes: Vec<Code>,
+        sources: Vec<Arc<str>>,
+        metadata: Option<Arc<ReservedMetadata>>,
+    ) {
+        let key = self.fold_phrase(&phrase);
+        let kind = CandidateKind::Reserved {
+            phrase: phrase.as_str().into(),
+        };
+        let candidates = codes
+            .iter()
+            .map(|code| Candidate {
+                kind: kind.clone(),
+                digits: code.as_ref().into(),
+            })
+            .collect();
+        let word = ReservedWord {
+            phrase,
+            codes,
+            candidates,
+            sources,
+            metadata,
+        };
+        self.reserved_mut().insert(key, word);
+    }
+
+    /// 予約語を比べるために, 全角・半角, 大文字・小文字, カタカナ・ひらがなの違いをそろえる
+    #[cfg(feature = "encode")]
+    pub(crate) fn fold_phrase(&self, val: &str) -> String {
+        let mut folded = String::with_capacity(val.len());
+        for ch in val.chars() {
+            let ch = match ch {
+                '\u{FF66}'..='\u{FF9D}' => HALF_WIDTH_KATAKANA
+                    .chars()
+                    .nth(ch as usize - 0xFF66)
+                    .unwrap_or(ch),
+                _ => ch,
+            };
+            let ch = match ch {
+                'ァ'..='ヶ' => std::char::from_u32(ch as u32 - 0x60).unwrap_or(ch),
+                _ => ch,
+            };
+            let ch = match (ch, folded.chars().last()) {
+                ('\u{FF9E}', Some('う')) => {
+                    folded.pop();
+                    'ゔ'
+                }
+                ('\u{FF9E}', Some(prev))
+                    if "かきくけこさしすせそたちつてとはひふへほ".contains(prev) =>
+                {
+                    folded.pop();
+                    std::char::from_u32(prev as u32 + 1).unwrap_or(prev)
+                }
+                ('\u{FF9F}', Some(prev)) if "はひふへほ".contains(prev) => {
+                    folded.pop();
+                    std::char::from_u32(prev as u32 + 2).unwrap_or(prev)
+                }
+                ('\u{FF9E}', _) => '゛',
+                ('\u{FF9F}', _) => '゜',
+                _ => ch,
+            };
+            folded.push(self.normalize(&ch.to_ascii_uppercase()));
+        }
+        folded
+    }
+
+    /// 2タッチ入力に変換する前に1文字ずつ行う正規化 (英字の大文字化と全角・小書き文字などの置き換え) をする。
+    /// 変換表にない文字でもそのまま返す。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// let c = Converter::new();
+    /// assert_eq!(c.normalize_char('t'), 'T');
+    /// assert_eq!(c.normalize_char('Ｔ'), 'T');
+    /// assert_eq!(c.normalize_char('ゃ'), 'や');
+    /// ```
+    #[cfg(feature = "encode")]
+    pub fn normalize_char(&self, ch: char) -> char {
+        self.normalize(&ch.to_ascii_uppercase())
+    }
+
+    /// 入力全体に `normalize_char` をかけ, ポケベル側で見える文字に近い形にする。
+    /// 文字数は変わらず, `convert_to_two_touch_string(normalize_text(s))` は
+    /// `convert_to_two_touch_string(s)` と同じ結果になる (エラーの場合はエラーの位置が同じになる)。
+    /// `ConvertOptions::with_escapes` を有効にしている場合, そのまま出力する `{0840}` の中は変えない。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// let c = Converter::new();
+    /// assert_eq!(c.normalize_text("こんにちは　ＴＥＬ"), "こんにちは TEL");
+    /// assert_eq!(c.normalize_text("Ａ{０}"), "A{0}");
+    /// let options = ConvertOptions::default().with_escapes(true);
+    /// let c = Converter::new().with_convert_options(options).unwrap();
+    /// assert_eq!(c.normalize_text("Ａ{０}"), "A{０}");
+    /// ```
+    #[cfg(feature = "encode")]
+    pub fn normalize_text(&self, val: &str) -> String {
+        let mut normalized = String::with_capacity(val.len());
+        let mut chars = val.chars().peekable();
+        let mut in_escape = false;
+        while let Some(ch) = chars.next() {
+            if in_escape {
+                in_escape = ch != '}';
+                normalized.push(ch);
+            } else if ch == '{' && self.options.escapes {
+                normalized.push(ch);
+                match chars.peek() {
+                    Some('{') => normalized.extend(chars.next()),
+                    _ => in_escape = true,
+                }
+            } else {
+                normalized.push(self.normalize_char(ch));
+            }
+        }
+        normalized
+    }
+
+    /// 1文字を2タッチ入力の数字に変換する。`normalize_char` による正規化をしてから変換表を引き,
+    /// 変換できない文字ならNoneを返す。`Profile::NumericOnly` では数字と記号をそのまま返す。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// let c = Converter::new();
+    /// assert_eq!(c.encode_char('か'), Some("21"));
+    /// assert_eq!(c.encode_char('が'), Some("2104"));
+    /// assert_eq!(c.encode_char('筋'), None);
+    /// ```
+    #[cfg(feature = "encode")]
+    pub fn encode_char(&self, ch: char) -> Option<&str> {
+        if self.profile == Profile::NumericOnly {
+            // 数字しか表示できないポケベルでは数字と記号をそのまま送る
+            let ch = self.normalize(&ch);
+            return NUMERIC_CHARS.find(ch).map(|i| &NUMERIC_CHARS[i..(i + 1)]);
+        }
+        #[cfg(feature = "decode")]
+        if let Some((_, code)) = self.tables.expansion_map.get(&ch) {
+            return Some(code.as_str()).filter(|code| self.fits_profile(code));
+        }
+        self.normalized_char(ch)
+            .filter(|&ch| self.profile.allows(ch))
+            .and_then(|ch| self.tables.base_map.get(ch))
+    }
+
+    /// 2桁のコードの変換表を, コードの1桁目, 2桁目の順に並べて返す。
+    /// 同じコードの文字が複数ある場合は文字の順に並べる。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// let c = Converter::new();
+    /// let table: Vec<(char, &str)> = c.table().take(2).collect();
+    /// assert_eq!(table, vec![('0', "00"), ('わ', "01")]);
+    /// ```
+    #[cfg(feature = "encode")]
+    pub fn table(&self) -> impl Iterator<Item = (char, &str)> {
+        self.sorted_table(|code| code.len() == 2)
+    }
+
+    /// 濁音・半濁音など, 2組のコードを合わせた4桁の変換表を `table` と同じ順に並べて返す
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// let c = Converter::new();
+    /// assert_eq!(c.composed_table().next(), Some(('が', "2104")));
+    /// ```
+    #[cfg(feature = "encode")]
+    pub fn composed_table(&self) -> impl Iterator<Item = (char, &str)> {
+        self.sorted_table(|code| code.len() == 4)
+    }
+
+    #[cfg(feature = "encode")]
+    fn sorted_table(&self, filter: fn(&str) -> bool) -> impl Iterator<Item = (char, &str)> {
+        let mut table: Vec<(char, &str)> = self
+            .tables
+            .base_map
+            .iter()
+            .filter(|&(_, code)| filter(code))
+            .collect();
+        table.sort_by(|a, b| a.1.cmp(b.1).then(a.0.cmp(&b.0)));
+        table.into_iter()
+    }
+
+    /// 変換の前に置き換える文字の表を, 置き換え前の文字の順に並べて返す。
+    /// 英字の小文字は表に含めず, 別に大文字にする (`normalize_char` を参照)。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// let c = Converter::new();
+    /// assert!(c.normalizations().any(|pair| pair == ('ぁ', 'あ')));
+    /// ```
+    #[cfg(feature = "encode")]
+    pub fn normalizations(&self) -> impl Iterator<Item = (char, char)> {
+        let mut normalizations: Vec<(char, char)> = self.tables.normalization_map.iter().collect();
+        normalizations.sort_unstable();
+        normalizations.into_iter()
+    }
+
+    /// 変換表, 正規化の表, 予約語の辞書を, `ConverterBuilder` で変えた後のまま書き出す。
+    /// 変換表は `table`, `composed_table` の順, 正規化の表は `normalizations` の順,
+    /// 予約語は `reserved_phrases` の順に並べるので, 表が同じなら毎回同じになる。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// let c = Converter::new();
+    /// let tables = c.export_tables();
+    /// assert_eq!(tables.version, TABLE_EXPORT_VERSION);
+    /// assert_eq!(tables.characters[1], CharacterCode { ch: 'わ', code: "01".to_string() });
+    /// ```
+    #[cfg(feature = "encode")]
+    pub fn export_tables(&self) -> TableExport {
+        TableExport {
+            version: TABLE_EXPORT_VERSION,
+            characters: self
+                .table()
+                .chain(self.composed_table())
+                .map(|(ch, code)| CharacterCode {
+                    ch,
+                    code: code.to_string(),
+                })
+                .collect(),
+            normalizations: self
+                .normalizations()
+                .map(|(from, to)| Normalization { from, to })
+                .collect(),
+            reserved_words: self
+                .reserved_phrases()
+                .map(|(phrase, codes)| Entry {
+                    phrase: phrase.to_string(),
+                    codes: code_strings(codes),
+                })
+                .collect(),
+        }
+    }
+
+    /// `ConverterBuilder::add_normalization_str` で追加した置き換えを, 置き換え前の文字の順に返す
+    #[cfg(all(feature = "serde", feature = "encode", feature = "decode"))]
+    pub(crate) fn expansions(&self) -> Vec<(char, &str)> {
+        let mut expansions: Vec<(char, &str)> = self
+            .tables
+            .expansion_map
+            .iter()
+            .map(|(&from, (to, _))| (from, to.as_str()))
+            .collect();
+        expansions.sort_unstable();
+        expansions
+    }
+
+    /// `convert_to_two_touch_string` などで使う設定
+    pub fn convert_options(&self) -> &ConvertOptions {
+        &self.options
+    }
+
+    /// 2桁の2タッチ入力を1文字に変換する。表にないコードや2桁でない場合はNoneを返す。
+    /// 濁点はまとめず, `convert_from_two_touch_string` が1組ずつ変換するのと同じ結果になる。
+    /// `Profile::NumericOnly` では数字を文字に変換しないので常にNone
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// let c = Converter::new();
+    /// assert_eq!(c.decode_pair("88"), Some(' '));
+    /// assert_eq!(c.decode_pair("2104"), None);
+    /// ```
+    #[cfg(feature = "decode")]
+    pub fn decode_pair(&self, pair: &str) -> Option<char> {
+        if pair.len() != 2 {
+            return None;
+        }
+        self.table_char(pair)
+    }
+
+    /// 変換表のコードを `Profile` で表示できる文字に戻す。表にないか表示できない文字ならNone
+    #[cfg(feature = "decode")]
+    fn table_char(&self, code: &str) -> Option<char> {
+        self.tables
+            .inverse_table
+            .get(code)
+            .filter(|&ch| self.profile.allows(ch))
+    }
+
+    /// 2タッチ入力の `pair_index` 番目の1組を戻す。
+    /// 表にないコードは `UnknownCode`, `Profile` で表示できない文字は `NotInProfile` のエラー
+    #[cfg(feature = "decode")]
+    fn pair_char(&self, pair: &str, pair_index: usize) -> Result<char, Error> {
+        let offset = pair_index * 2;
+        match self.tables.inverse_table.get(pair) {
+            Some(ch) if self.profile.allows(ch) => Ok(ch),
+            Some(ch) => Err(self.unsupported(ch, offset)),
+            None => Err(Error::from(ErrorKind::UnknownCode {
+                code: pair.to_string(),
+                pair_index,
+                offset,
+            })),
+        }
+    }
+
+    /// 数字が `Profile` で表示できる文字だけに戻せるか。`Profile::Full` では確かめない
+    #[cfg(all(feature = "encode", feature = "decode"))]
+    fn fits_profile(&self, code: &str) -> bool {
+        match self.profile {
+            Profile::Full | Profile::NumericOnly => true,
+            _ => self.decode_with_trace(code, DakutenMode::Composed).is_ok(),
+        }
+    }
+
+    /// 2タッチ入力の1組を, 1桁目と2桁目の数字 (0〜9) で指定して変換する
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// let c = Converter::new();
+    /// assert_eq!(c.decode_pair_digits(8, 1), Some('や'));
+    /// assert_eq!(c.decode_pair_digits(10, 1), None);
+    /// ```
+    #[cfg(feature = "decode")]
+    pub fn decode_pair_digits(&self, first: u8, second: u8) -> Option<char> {
+        if first > 9 || second > 9 {
+            return None;
+        }
+        let pair = [b'0' + first, b'0' + second];
+        std::str::from_utf8(&pair)
+            .ok()
+            .and_then(|pair| self.decode_pair(pair))
+    }
+
+    /// 1文字ずつ変換できない文字のエラー
+    #[cfg(any(feature = "encode", feature = "decode"))]
+    pub(crate) fn unsupported(&self, ch: char, index: usize) -> Error {
+        match &self.profile {
+            Profile::Full => Error::from(ErrorKind::UnsupportedCharacter { ch, index }),
+            profile => Error::from(ErrorKind::NotInProfile {
+                ch,
+                index,
+                profile: profile.clone(),
+            }),
+        }
+    }
+
+    /// `Profile::NumericOnly` で受け取った数字を検査する
+    #[cfg(feature = "decode")]
+    fn check_numeric(&self, val: &str) -> Result<(), Error> {
+        match val
+            .chars()
+            .enumerate()
+            .find(|&(_, ch)| !NUMERIC_CHARS.contains(ch))
+        {
+            Some((index, ch)) => Err(self.unsupported(ch, index)),
+            None => Ok(()),
+        }
+    }
+
+    /// 変換表で実際に使う文字。変換表にない文字ならNone
+    #[cfg(feature = "encode")]
+    fn normalized_char(&self, ch: char) -> Option<char> {
+        let ch = self.normalize_char(ch);
+        if self.tables.base_map.contains_key(ch) {
+            Some(ch)
+        } else {
+            None
+        }
+    }
+
+    #[cfg(feature = "encode")]
+    fn normalize(&self, ch: &char) -> char {
+        self.tables.normalization_map.get(*ch).unwrap_or(*ch)
+    }
+
+    /// Converterの初期化
+    /// (もっといい方法があるかもしれない)
+    ///
+    /// 予約語は既定の辞書のもの。`builtin-dictionary` featureを無効にした場合は予約語はなく,
+    /// `without_reserved_words` と同じになる。
+    /// `dict-stations` などのfeatureで有効にした追加の辞書は, 既定の辞書の後に `MergeStrategy::Append` で重ねる。
+    ///
+    /// 既定の辞書は予約語を最初に引くときに作り, `Converter::new()` で作ったすべてのConverterで共有する。
+    /// 予約語を使わない変換だけなら辞書は作らない。
+    pub fn new() -> Self {
+        Converter {
+            #[cfg(feature = "encode")]
+            reserved: OnceLock::new(),
+            ..Self::without_reserved_words()
+        }
+    }
+
+    /// 予約語の辞書。`Converter::new()` で作った場合は, 最初に呼んだときに既定の辞書を作る
+    #[cfg(feature = "encode")]
+    fn reserved(&self) -> &Arc<ReservedDictionary> {
+        self.reserved.get_or_init(builtin_reserved_dictionary)
+    }
+
+    /// 予約語の辞書を変えるために返す。ほかのConverterと共有している場合は複製する
+    #[cfg(feature = "encode")]
+    fn reserved_mut(&mut self) -> &mut ReservedDictionary {
+        self.reserved();
+        match self.reserved.get_mut() {
+            Some(reserved) => Arc::make_mut(reserved),
+            None => unreachable!("reserved dictionary is initialized above"),
+        }
+    }
+
+    /// 変換結果の候補の並べ方を設定する
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// let options = CandidateOptions::default().with_order(CandidateOrder::LiteralFirst);
+    /// let c = Converter::new().with_candidate_options(options).unwrap();
+    /// c.convert_to_two_touch_string("ごくろうさん").unwrap(); // ["25042395133103", "5963"]
+    /// ```
+    #[cfg(feature = "encode")]
+    pub fn with_candidate_options(mut self, options: CandidateOptions) -> Result<Self, Error> {
+        options.validate()?;
+        self.options.candidates = options;
+        Ok(self)
+    }
+
+    /// 2タッチ入力に変換する前の空白の扱いを設定する
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// let options = SpaceOptions::default()
+    ///     .with_trim_spaces(true)
+    ///     .with_collapse_spaces(true);
+    /// let c = Converter::new().with_space_options(options);
+    /// c.convert_to_two_touch_string("　こんにちは  ていく ").unwrap(); // ["250352426188441223"]
+    /// ```
+    #[cfg(feature = "encode")]
+    pub fn with_space_options(mut self, options: SpaceOptions) -> Self {
+        self.options.spaces = options;
+        self
+    }
+
+    /// 変換先のポケベルで表示できる文字の範囲を設定する
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// # #[cfg(feature = "builtin-dictionary")] {
+    /// let c = Converter::new().with_profile(Profile::NumericOnly);
+    /// c.convert_to_two_touch_string("0840").unwrap(); // ["0840"]
+    /// c.convert_to_two_touch_string("おはよう").unwrap(); // ["840"]
+    /// c.convert_to_two_touch_string("やきにく").unwrap_err(); // 'や' at index 0 cannot be displayed in the numeric-only profile
+    /// # }
+    /// ```
+    ///
+    /// 予約語の数字がその範囲で表示できるかは確かめない。確かめる場合は `ConverterBuilder::profile` を使う。
+    pub fn with_profile(mut self, profile: Profile) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    /// 変換先のポケベルで表示できる文字の範囲
+    pub fn profile(&self) -> &Profile {
+        &self.profile
+    }
+
+    /// 変換結果の数字の区切り方を設定する
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// # use std::num::NonZeroUsize;
+    /// let options = GroupingOptions::default().with_group_every(NonZeroUsize::new(4));
+    /// let c = Converter::new().with_grouping_options(options).unwrap();
+    /// # #[cfg(feature = "encode")]
+    /// c.convert_to_two_touch_string("こんにちは").unwrap(); // ["2503 5242 61"]
+    /// # #[cfg(feature = "decode")]
+    /// c.convert_from_two_touch_string("2503 5242 61").unwrap(); // "こんにちは"
+    /// ```
+    pub fn with_grouping_options(mut self, options: GroupingOptions) -> Result<Self, Error> {
+        options.validate()?;
+        self.options.grouping = options;
+        Ok(self)
+    }
+
+    /// `convert_to_two_touch_string` で使う設定をまとめて設定する。
+    /// `with_candidate_options` などで設定したものは置き換わる。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// let options = ConvertOptions::default().with_skip_unsupported(true);
+    /// let c = Converter::new().with_convert_options(options).unwrap();
+    /// # #[cfg(feature = "encode")]
+    /// c.convert_to_two_touch_string("焼肉やきにく").unwrap(); // ["81225223"]
+    /// ```
+    pub fn with_convert_options(mut self, options: ConvertOptions) -> Result<Self, Error> {
+        options.validate(&self)?;
+        self.options = options;
+        Ok(self)
+    }
+
+    /// 予約語を使わないConverterの初期化
+    /// 1文字ずつ変換した結果だけを返すようになり, 予約語の辞書も作らない。
+    pub fn without_reserved_words() -> Self {
+        // 表は pokebell-core の build.rs が data/table.tsv から作った定数をそのまま引くので, ここでは作らない。
+        // 空の表と辞書もすべてのConverterで共有する
+        static BUILTIN: OnceLock<Converter> = OnceLock::new();
+        BUILTIN
+            .get_or_init(|| {
+                Self::from_tables(ConversionTables {
+                    #[cfg(feature = "encode")]
+                    base_map: CharTable::Builtin,
+                    #[cfg(feature = "decode")]
+                    inverse_table: InverseTable::builtin(),
+                    #[cfg(feature = "encode")]
+                    normalization_map: NormalizationTable::Builtin,
+                    #[cfg(all(feature = "encode", feature = "decode"))]
+                    expansion_map: HashMap::new(),
+                })
+            })
+            .clone()
+    }
+
+    /// 変換表, 逆引きの表, 正規化の表と予約語の辞書が食い違っていないか確かめ, 見つかった誤りをすべて返す。
+    /// 誤りは変換表, 逆引きの表, 正規化の表, 予約語の順に, それぞれ文字や数字の順に並べる
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// assert_eq!(Converter::new().self_check(), Ok(()));
+    /// // 'A' の数字を 'Ä' に使うと, 'Ａ' の置き換え先の 'A' が変換できなくなる
+    /// let c = Converter::builder().override_char('Ä', "16").build().unwrap();
+    /// assert!(c.self_check().unwrap_err().contains(
+    ///     &ConsistencyError::UnencodableNormalization { from: 'Ａ', to: 'A' }
+    /// ));
+    /// ```
+    #[cfg(all(feature = "encode", feature = "decode"))]
+    pub fn self_check(&self) -> Result<(), Vec<ConsistencyError>> {
+        let mut errors = Vec::new();
+
+        let mut base: Vec<(char, &str)> = self.tables.base_map.iter().collect();
+        base.sort_unstable();
+        let mut chars_by_code: BTreeMap<&str, Vec<char>> = BTreeMap::new();
+        for &(ch, code) in &base {
+            if !(code.len() == 2 || code.len() == 4) || !code.bytes().all(|b| b.is_ascii_digit()) {
+                errors.push(ConsistencyError::InvalidCode {
+                    ch,
+                    code: code.to_string(),
+                });
+            }
+            chars_by_code.entry(code).or_default().push(ch);
+        }
+        for (&code, chars) in &chars_by_code {
+            if chars.len() > 1 {
+                errors.push(ConsistencyError::SharedCode {
+                    code: code.to_string(),
+                    chars: chars.clone(),
+                });
+            }
+        }
+
+        for (code, ch) in self.tables.inverse_table.iter() {
+            let forward = self.tables.base_map.get(ch);
+            if forward != Some(code.as_str()) {
+                errors.push(ConsistencyError::StaleInverse {
+                    code: code.to_string(),
+                    ch,
+                    forward: forward.map(str::to_string),
+                });
+            }
+        }
+        for &(ch, code) in &base {
+            let inverse = self.tables.inverse_table.get(code);
+            // 同じ数字の文字が複数ある場合は `SharedCode` で報告してある
+            if inverse != Some(ch) && chars_by_code[code].len() == 1 {
+                errors.push(ConsistencyError::MissingInverse {
+                    ch,
+                    code: code.to_string(),
+                    inverse,
+                });
+            }
+        }
+
+        for (from, to) in self.normalizations() {
+            if !self.tables.base_map.contains_key(to) {
+                errors.push(ConsistencyError::UnencodableNormalization { from, to });
+            }
+            if from != to && self.tables.base_map.contains_key(from) {
+                errors.push(ConsistencyError::ShadowedChar { from, to });
+            }
+        }
+        let mut expansions: Vec<(char, &(String, String))> = self
+            .tables
+            .expansion_map
+            .iter()
+            .map(|(&from, value)| (from, value))
+            .collect();
+        expansions.sort_unstable_by_key(|&(from, _)| from);
+        for (from, (to, code)) in expansions {
+            let expected: Option<String> = to
+                .chars()
+                .map(|ch| {
+                    self.normalized_char(ch)
+                        .and_then(|ch| self.tables.base_map.get(ch))
+                })
+                .collect();
+            if expected.as_deref() != Some(code.as_str()) {
+                errors.push(ConsistencyError::StaleExpansion {
+                    from,
+                    to: to.clone(),
+                    code: code.clone(),
+                });
+            }
+        }
+
+        let mut indexed: BTreeSet<(&str, &str)> = BTreeSet::new();
+        for (key, word) in &self.reserved().word_map {
+            for code in &word.codes {
+                if code.is_empty() || !code.bytes().all(|b| b.is_ascii_digit()) {
+                    errors.push(ConsistencyError::InvalidReservedCode {
+                        phrase: word.phrase.clone(),
+                        code: code.to_string(),
+                    });
+                }
+                indexed.insert((code.as_ref(), key.as_str()));
+            }
+        }
+        let by_length: BTreeSet<(&str, &str)> = self
+            .reserved()
+            .code_index
+            .iter()
+            .flat_map(|(&len, entries)| {
+                entries
+                    .iter()
+                    .map(move |(code, key)| (len, code.as_ref(), key.as_str()))
+            })
+            .filter(|&(len, code, _)| len == code.len())
+            .map(|(_, code, key)| (code, key))
+            .collect();
+        let by_prefix: BTreeSet<(&str, &str)> = self
+            .reserved()
+            .prefix_index
+            .iter()
+            .map(|(code, key)| (code.as_ref(), key.as_str()))
+            .collect();
+        let mut stale: BTreeSet<(&str, &str)> = BTreeSet::new();
+        for index in [&by_length, &by_prefix] {
+            stale.extend(index.symmetric_difference(&indexed));
+        }
+        for (code, key) in stale {
+            errors.push(ConsistencyError::StaleReservedIndex {
+                key: key.to_string(),
+                code: code.to_string(),
+            });
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// 変換表と逆引きの表, 正規化の表から, 予約語のないConverterを作る
+    fn from_tables(tables: ConversionTables) -> Self {
+        Converter {
+            tables: Arc::new(tables),
+            #[cfg(feature = "encode")]
+            reserved: OnceLock::from(Arc::default()),
+            options: ConvertOptions::default(),
+            profile: Profile::default(),
+        }
+    }
+
+    /// 変換表や予約語を変えたConverterを作るための `ConverterBuilder` を返す。
+    /// `Converter::new()` と同じ表から始める。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// # #[cfg(feature = "builtin-dictionary")] {
+    /// let c = Converter::builder()
+    ///     .add_reserved_word("なおちゃん", &["703"])
+    ///     .remove_reserved_word("ごくろうさん")
+    ///     .build()
+    ///     .unwrap();
+    /// c.convert_to_two_touch_string("なおちゃん").unwrap(); // ["703", "5115428103"]
+    /// c.convert_to_two_touch_string("ごくろうさん").unwrap(); // ["25042395133103"]
+    /// # }
+    /// ```
+    #[cfg(all(feature = "encode", feature = "decode"))]
+    pub fn builder() -> ConverterBuilder {
+        let ConversionTables {
+            base_map,
+            normalization_map,
+            ..
+        } = (*Self::without_reserved_words().tables).clone();
+        ConverterBuilder {
+            base_map: base_map.into_map(),
+            normalization_map: normalization_map.into_map(),
+            reserved_ops: default_reserved_words()
+                .into_iter()
+                .map(ReservedOp::Builtin)
+                .collect(),
+            added_normalizations: Vec::new(),
+            expansions: Vec::new(),
+            profile: Profile::default(),
+            check_consistency: false,
+            error: None,
+        }
+    }
+}
+
+/// 変換表や予約語の辞書を変えたConverterを作る
+///
+/// `Converter::builder()` で作り, 最後に `build` を呼びます。
+/// 途中で不正な指定があった場合は, 最初のものが `build` のエラーになります。
+#[cfg(all(feature = "encode", feature = "decode"))]
+pub struct ConverterBuilder {
+    base_map: HashMap<char, Code>,
+    normalization_map: HashMap<char, char>,
+    /// 予約語の追加と削除を指定された順に持つ。正規化の表が決まってから辞書を作る
+    reserved_ops: Vec<ReservedOp>,
+    added_normalizations: Vec<(char, char)>,
+    /// 1文字を複数の文字に置き換える規則。後から指定したものを優先する
+    expansions: Vec<(char, String)>,
+    profile: Profile,
+    /// デバッグビルドで, 作ったConverterを `Converter::self_check` で確かめる
+    check_consistency: bool,
+    error: Option<BuildError>,
+}
+
+#[cfg(all(feature = "encode", feature = "decode"))]
+enum ReservedOp {
+    /// 既定の辞書と追加の辞書の予約語。`Profile` で表示できない数字は取り除き,
+    /// すでにある予約語には `MergeStrategy::Append` で合わせる
+    Builtin(DictionaryWord),
+    Add(String, Vec<Code>, Option<Arc<ReservedMetadata>>),
+    Remove(String),
+    /// 名前の付いた辞書の予約語を, すでにある予約語と `strategy` で合わせる
+    Merge(DictionaryWord, MergeStrategy),
+}
+
+/// 辞書から重ねる予約語
+#[cfg(feature = "encode")]
+struct DictionaryWord {
+    phrase: String,
+    codes: Vec<Code>,
+    /// 辞書の名前
+    source: Arc<str>,
+    metadata: Option<Arc<ReservedMetadata>>,
+}
+
+/// 利用者が渡した数字を `Code` にする
+#[cfg(feature = "encode")]
+fn owned_codes<S: AsRef<str>>(codes: &[S]) -> Vec<Code> {
+    codes
+        .iter()
+        .map(|code| Cow::Owned(code.as_ref().to_string()))
+        .collect()
+}
+
+/// 公開する型に入れるために, `Code` を `String` にする
+#[cfg(feature = "encode")]
+fn code_strings(codes: &[Code]) -> Vec<String> {
+    codes.iter().map(|code| code.to_string()).collect()
+}
+
+/// 予約語の数字を確かめる。数字は1つ以上あり, どれも空でない数字だけの文字列でなければならない
+#[cfg(feature = "encode")]
+fn reserved_codes<S: AsRef<str>>(phrase: &str, codes: &[S]) -> Result<Vec<Code>, BuildError> {
+    let codes = owned_codes(codes);
+    let invalid = if codes.is_empty() {
+        Some(String::new())
+    } else {
+        codes
+            .iter()
+            .find(|code| code.is_empty() || !code.bytes().all(|b| b.is_ascii_digit()))
+            .map(|code| code.to_string())
+    };
+    match invalid {
+        Some(code) => Err(BuildError::InvalidReservedCode {
+            phrase: phrase.to_string(),
+            code,
+        }),
+        None => Ok(codes),
+    }
+}
+
+#[cfg(all(feature = "encode", feature = "decode"))]
+impl ConverterBuilder {
+    /// 予約語を追加する。同じ予約語がすでにある場合は置き換える。数字は1つ以上必要
+    pub fn add_reserved_word<S: AsRef<str>>(mut self, phrase: &str, codes: &[S]) -> Self {
+        match reserved_codes(phrase, codes) {
+            Ok(codes) => {
+                self.reserved_ops
+                    .push(ReservedOp::Add(phrase.to_string(), codes, None));
+                self
+            }
+            Err(err) => self.fail(err),
+        }
+    }
+
+    /// 予約語を取り除く。`build` の時点で辞書にない場合はエラー
+    pub fn remove_reserved_word(mut self, phrase: &str) -> Self {
+        self.reserved_ops
+            .push(ReservedOp::Remove(phrase.to_string()));
+        self
+    }
+
+    /// 文字と2タッチ入力の対応を追加する。
+    /// コードは2桁または4桁の数字で, すでにある文字やコードと重なる場合はエラーになる。
+    pub fn add_char_mapping(self, ch: char, code: &str) -> Self {
+        if let Some(existing) = self.base_map.get(&ch) {
+            let existing = existing.to_string();
+            return self.fail(BuildError::CharAlreadyMapped { ch, code: existing });
+        }
+        self.insert_char(ch, code, false)
+    }
+
+    /// 文字と2タッチ入力の対応を追加し, すでにある文字やコードと重なる場合は置き換える。
+    /// 同じコードだった文字は変換表から取り除く。
+    pub fn override_char(self, ch: char, code: &str) -> Self {
+        self.insert_char(ch, code, true)
+    }
+
+    fn insert_char(mut self, ch: char, code: &str, replace: bool) -> Self {
+        if !(code.len() == 2 || code.len() == 4) || !code.bytes().all(|b| b.is_ascii_digit()) {
+            return self.fail(BuildError::InvalidCode {
+                ch,
+                code: code.to_string(),
+            });
+        }
+        let existing = self
+            .base_map
+            .iter()
+            .find(|&(&other, other_code)| other != ch && other_code == code)
+            .map(|(&other, _)| other);
+        if let Some(existing) = existing {
+            if !replace {
+                return self.fail(BuildError::CodeCollision {
+                    ch,
+                    code: code.to_string(),
+                    existing,
+                });
+            }
+            self.base_map.remove(&existing);
+        }
+        self.base_map.insert(ch, Cow::Owned(code.to_string()));
+        self
+    }
+
+    /// 変換表から文字を取り除く。変換表にない場合は何もしない
+    #[cfg(feature = "serde")]
+    pub(crate) fn remove_char(mut self, ch: char) -> Self {
+        self.base_map.remove(&ch);
+        self
+    }
+
+    /// 変換の前に `from` を `to` に置き換えるようにする。組み込みの置き換えより優先する。
+    /// `to` は `build` の時点で変換表にある文字でなければならない。
+    pub fn add_normalization(mut self, from: char, to: char) -> Self {
+        self.expansions.retain(|&(other, _)| other != from);
+        self.normalization_map.insert(from, to);
+        self.added_normalizations.push((from, to));
+        self
+    }
+
+    /// 変換の前に `from` を複数の文字 `to` に置き換えるようにする。組み込みの置き換えより優先する。
+    /// `to` の文字は `build` の時点ですべて変換できなければならない。
+    /// 変換できない文字のエラーの位置は, 置き換える前の入力での位置になる。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// let c = Converter::builder()
+    ///     .add_normalization('、', ' ')
+    ///     .add_normalization_str('㈱', "(かふ)")
+    ///     .build()
+    ///     .unwrap();
+    /// assert_eq!(
+    ///     c.convert_to_two_touch_string("㈱、あ").unwrap(),
+    ///     c.convert_to_two_touch_string("(かふ) あ").unwrap()
+    /// );
+    /// ```
+    pub fn add_normalization_str(mut self, from: char, to: &str) -> Self {
+        self.expansions.retain(|&(other, _)| other != from);
+        self.added_normalizations
+            .retain(|&(other, _)| other != from);
+        self.normalization_map.remove(&from);
+        self.expansions.push((from, to.to_string()));
+        self
+    }
+
+    /// 変換先のポケベルで表示できる文字の範囲を設定する。
+    /// `add_reserved_word` で追加した予約語の数字がその範囲の文字に戻せない場合は,
+    /// `build` が `BuildError::ReservedNotInProfile` になる。
+    /// 既定の辞書の予約語は, 戻せない数字を取り除き, 数字が残らなければ辞書に入れない。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// let c = Converter::builder().profile(Profile::KanaOnly).build().unwrap();
+    /// assert_eq!(c.convert_to_two_touch_string("やきにく").unwrap(), vec!["81225223"]);
+    /// assert!(c.convert_to_two_touch_string("RUST").is_err());
+    /// ```
+    pub fn profile(mut self, profile: Profile) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    /// `true` にすると, デバッグビルド (`debug_assertions`) では `build` の最後に `Converter::self_check` を呼び,
+    /// 見つかった誤りを `BuildError::Inconsistent` にする。リリースビルドでは何もしない
+    pub fn check_consistency(mut self, enabled: bool) -> Self {
+        self.check_consistency = enabled;
+        self
+    }
+
+    /// Converterを作る
+    #[cfg(all(feature = "encode", feature = "decode"))]
+    pub fn build(self) -> Result<Converter, BuildError> {
+        if let Some(err) = self.error {
+            return Err(err);
+        }
+        if let Some(&(from, to)) = self
+            .added_normalizations
+            .iter()
+            .find(|(_, to)| !self.base_map.contains_key(to))
+        {
+            return Err(BuildError::InvalidNormalization { from, to });
+        }
+        let inverse_table =
+            InverseTable::new(self.base_map.iter().map(|(ch, code)| (code.as_ref(), *ch)));
+        let mut converter = Converter::from_tables(ConversionTables {
+            base_map: CharTable::Custom(self.base_map),
+            inverse_table,
+            normalization_map: NormalizationTable::Custom(self.normalization_map),
+            expansion_map: HashMap::new(),
+        });
+        converter.profile = self.profile;
+        for (from, to) in self.expansions {
+            let code: Option<String> = to.chars().map(|ch| converter.encode_char(ch)).collect();
+            match code {
+                Some(code) if !code.is_empty() => {
+                    Arc::make_mut(&mut converter.tables)
+                        .expansion_map
+                        .insert(from, (to, code));
+                }
+                _ => return Err(BuildError::InvalidExpansion { from, to }),
+            }
+        }
+        for op in self.reserved_ops {
+            match op {
+                ReservedOp::Builtin(mut word) => {
+                    word.codes.retain(|code| converter.fits_profile(code));
+                    if !word.codes.is_empty() {
+                        converter.merge_reserved_word(word, MergeStrategy::Append);
+                    }
+                }
+                ReservedOp::Add(phrase, codes, metadata) => {
+                    if let Some(code) = codes.iter().find(|code| !converter.fits_profile(code)) {
+                        let code = code.to_string();
+                        return Err(BuildError::ReservedNotInProfile { phrase, code });
+                    }
+                    converter.insert_reserved_word_from(phrase, codes, Vec::new(), metadata)
+                }
+                ReservedOp::Merge(word, strategy) => {
+                    if let Some(code) = word.codes.iter().find(|code| !converter.fits_profile(code))
+                    {
+                        let code = code.to_string();
+                        let phrase = word.phrase;
+                        return Err(BuildError::ReservedNotInProfile { phrase, code });
+                    }
+                    converter.merge_reserved_word(word, strategy);
+                }
+                ReservedOp::Remove(phrase) => {
+                    if !converter.remove_reserved_word(&phrase) {
+                        return Err(BuildError::UnknownReservedWord { phrase });
+                    }
+                }
+            }
+        }
+        if self.check_consistency && cfg!(debug_assertions) {
+            converter
+                .self_check()
+                .map_err(|errors| BuildError::Inconsistent { errors })?;
+        }
+        Ok(converter)
+    }
+
+    /// `add_dictionary` で読みや由来とともに予約語を追加する。数字は確かめてあるものとする
+    pub(crate) fn add_reserved_word_with(
+        mut self,
+        phrase: &str,
+        codes: &[String],
+        metadata: Option<Arc<ReservedMetadata>>,
+    ) -> Self {
+        self.reserved_ops.push(ReservedOp::Add(
+            phrase.to_string(),
+            owned_codes(codes),
+            metadata,
+        ));
+        self
+    }
+
+    /// `merge_dictionary` で合わせる予約語を追加する。数字は確かめてあるものとする
+    pub(crate) fn merge_reserved_word(
+        mut self,
+        phrase: &str,
+        codes: &[String],
+        source: &str,
+        metadata: Option<Arc<ReservedMetadata>>,
+        strategy: MergeStrategy,
+    ) -> Self {
+        let word = DictionaryWord {
+            phrase: phrase.to_string(),
+            codes: owned_codes(codes),
+            source: source.into(),
+            metadata,
+        };
+        self.reserved_ops.push(ReservedOp::Merge(word, strategy));
+        self
+    }
+
+    pub(crate) fn fail(mut self, err: BuildError) -> Self {
+        self.error.get_or_insert(err);
+        self
+    }
+}
+
+/// `ConverterBuilder::build` のエラー
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BuildError {
+    /// 2桁または4桁の数字ではないコード
+    InvalidCode { ch: char, code: String },
+    /// すでに変換表にある文字
+    CharAlreadyMapped {
+        ch: char,
+        /// 変換表にあったコード
+        code: String,
+    },
+    /// 他の文字と同じコード
+    CodeCollision {
+        ch: char,
+        code: String,
+        /// すでにそのコードだった文字
+        existing: char,
+    },
+    /// 予約語の数字が空か, 数字以外を含む
+    InvalidReservedCode { phrase: String, code: String },
+    /// 取り除こうとした予約語が辞書にない
+    UnknownReservedWord { phrase: String },
+    /// 置き換え先の文字が変換表にない
+    InvalidNormalization { from: char, to: char },
+    /// 置き換え先の文字列が空か, 変換できない文字を含む
+    InvalidExpansion { from: char, to: String },
+    /// 予約語の数字が `Profile` で表示できない文字に戻る
+    ReservedNotInProfile { phrase: String, code: String },
+    /// 辞書ファイルを読み込めない
+    InvalidDictionary { message: String },
+    /// `ConverterConfig` の変換の設定が正しくない
+    InvalidOptions { message: String },
+    /// 辞書の項目の値が正しくない
+    InvalidDictionaryEntry {
+        /// 何番目の項目か (0始まり)
+        index: usize,
+        field: &'static str,
+        value: String,
+    },
+    /// CSVの辞書の行が正しくない
+    InvalidDictionaryRow {
+        /// 1始まりの行番号
+        line: u64,
+        field: &'static str,
+        value: String,
+    },
+    /// `ConverterBuilder::check_consistency` で, 作ったConverterに誤りが見つかった
+    Inconsistent { errors: Vec<ConsistencyError> },
+}
+
+impl Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BuildError::InvalidCode { ch, code } => {
+                write!(
+                    f,
+                    "invalid code {:?} for {:?}: expected 2 or 4 digits",
+                    code, ch
+                )
+            }
+            BuildError::CharAlreadyMapped { ch, code } => {
+                write!(f, "{:?} is already mapped to {:?}", ch, code)
+            }
+            BuildError::CodeCollision { ch, code, existing } => write!(
+                f,
+                "code {:?} for {:?} is already used by {:?}",
+                code, ch, existing
+            ),
+            BuildError::InvalidReservedCode { phrase, code } => {
+                write!(f, "invalid code {:?} for reserved word {:?}", code, phrase)
+            }
+            BuildError::UnknownReservedWord { phrase } => {
+                write!(f, "{:?} is not a reserved word", phrase)
+            }
+            BuildError::InvalidNormalization { from, to } => write!(
+                f,
+                "cannot normalize {:?} to {:?}: {:?} is not in the table",
+                from, to, to
+            ),
+            BuildError::InvalidExpansion { from, to } => write!(
+                f,
+                "cannot normalize {:?} to {:?}: not convertible",
+                from, to
+            ),
+            BuildError::InvalidDictionaryRow { line, field, value } => write!(
+                f,
+                "invalid {} {:?} in dictionary line {}",
+                field, value, line
+            ),
+            BuildError::InvalidDictionary { message } => {
+                write!(f, "invalid dictionary: {}", message)
+            }
+            BuildError::InvalidOptions { message } => {
+                write!(f, "invalid options: {}", message)
+            }
+            BuildError::InvalidDictionaryEntry {
+                index,
+                field,
+                value,
+            } => write!(
+                f,
+                "invalid {} {:?} in dictionary entry {}",
+                field, value, index
+            ),
+            BuildError::ReservedNotInProfile { phrase, code } => write!(
+                f,
+                "code {:?} for reserved word {:?} cannot be displayed in the profile",
+                code, phrase
+            ),
+            BuildError::Inconsistent { errors } => {
+                write!(f, "inconsistent converter")?;
+                for (i, err) in errors.iter().enumerate() {
+                    write!(f, "{} {}", if i == 0 { ":" } else { ";" }, err)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+/// `Converter::self_check` で見つかった, 表の間の食い違い
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConsistencyError {
+    /// 変換表の数字が2桁か4桁の数字ではない
+    InvalidCode { ch: char, code: String },
+    /// 変換表で複数の文字が同じ数字になる。文字は順に並べる
+    SharedCode { code: String, chars: Vec<char> },
+    /// 逆引きの表の数字 `code` が `ch` に戻るが, 変換表では `ch` がその数字にならない
+    StaleInverse {
+        code: String,
+        ch: char,
+        /// 変換表での `ch` の数字
+        forward: Option<String>,
+    },
+    /// 変換表の文字 `ch` の数字が逆引きの表で `ch` に戻らない
+    MissingInverse {
+        ch: char,
+        code: String,
+        /// 逆引きの表で `code` が戻る文字
+        inverse: Option<char>,
+    },
+    /// 置き換え先の文字が変換表にない
+    UnencodableNormalization { from: char, to: char },
+    /// 変換表にある文字を置き換えるので, その文字の数字に変換できない
+    ShadowedChar { from: char, to: char },
+    /// 1文字を複数の文字にする置き換えの数字が, 置き換え先の文字列を変換したものと違う
+    StaleExpansion {
+        from: char,
+        to: String,
+        code: String,
+    },
+    /// 予約語の数字が空か, 数字以外を含む
+    InvalidReservedCode { phrase: String, code: String },
+    /// 予約語の数字の索引と辞書が食い違う
+    StaleReservedIndex { key: String, code: String },
+}
+
+impl Display for ConsistencyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConsistencyError::InvalidCode { ch, code } => {
+                write!(f, "code {:?} for {:?} is not 2 or 4 digits", code, ch)
+            }
+            ConsistencyError::SharedCode { code, chars } => {
+                write!(f, "code {:?} is shared by {:?}", code, chars)
+            }
+            ConsistencyError::StaleInverse { code, ch, forward } => write!(
+                f,
+                "code {:?} decodes to {:?}, which encodes to {:?}",
+                code, ch, forward
+            ),
+            ConsistencyError::MissingInverse { ch, code, inverse } => write!(
+                f,
+                "{:?} encodes to {:?}, which decodes to {:?}",
+                ch, code, inverse
+            ),
+            ConsistencyError::UnencodableNormalization { from, to } => write!(
+                f,
+                "{:?} is normalized to {:?}, which is not in the table",
+                from, to
+            ),
+            ConsistencyError::ShadowedChar { from, to } => write!(
+                f,
+                "{:?} is in the table but is normalized to {:?}",
+                from, to
+            ),
+            ConsistencyError::StaleExpansion { from, to, code } => write!(
+                f,
+                "{:?} expands to {:?} with code {:?}, which does not match the table",
+                from, to, code
+            ),
+            ConsistencyError::InvalidReservedCode { phrase, code } => {
+                write!(f, "invalid code {:?} for reserved word {:?}", code, phrase)
+            }
+            ConsistencyError::StaleReservedIndex { key, code } => write!(
+                f,
+                "index entry {:?} for reserved word {:?} does not match the dictionary",
+                code, key
+            ),
+        }
+    }
+}
+
+/// 既定の辞書の予約語のデータ。書き方は `reserved_words.tsv` の先頭のコメントを参照
+#[cfg(feature = "builtin-dictionary")]
+const RESERVED_WORDS: &str = include_str!("reserved_words.tsv");
+
+/// 既定の辞書に重ねる追加の辞書の名前とデータ。featureで有効にしたものを, この順に重ねる
+#[cfg(feature = "encode")]
+const DICTIONARY_PACKS: &[(&str, &str)] = &[
+    #[cfg(feature = "dict-stations")]
+    ("stations", include_str!("dictionaries/stations.tsv")),
+    #[cfg(feature = "dict-dates")]
+    ("dates", include_str!("dictionaries/dates.tsv")),
+];
+
+/// `reserved_words.tsv` と同じ書き方の予約語のデータを, 予約語, 数字と読みや由来の組にして行の順に返す。
+/// 読みを書いていない行は, かなだけの表記をひらがなにしたものを読みにする
+#[cfg(feature = "encode")]
+fn parse_reserved_words(
+    data: &'static str,
+) -> Vec<(String, Vec<Code>, Option<Arc<ReservedMetadata>>)> {
+    let mut words = Vec::new();
+    for line in data.lines() {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut columns = line.split('\t');
+        let codes: Vec<Code> = match columns.next() {
+            Some(codes) => codes.split(',').map(Cow::Borrowed).collect(),
+            None => continue,
+        };
+        let mut phrases = Vec::new();
+        let mut metadata = ReservedMetadata::default();
+        for column in columns {
+            if let Some(reading) = column.strip_prefix("reading=") {
+                metadata.reading = Some(reading.to_string());
+            } else if let Some(derivation) = column.strip_prefix("derivation=") {
+                metadata.derivation = derivation.split(' ').map(Pun::parse).collect();
+            } else if let Some(note) = column.strip_prefix("note=") {
+                metadata.note = Some(note.to_string());
+            } else {
+                phrases.push(column);
+            }
+        }
+        if metadata.reading.is_none() {
+            metadata.reading = phrases.iter().find_map(|phrase| kana_reading(phrase));
+        }
+        let metadata = if metadata == ReservedMetadata::default() {
+            None
+        } else {
+            Some(Arc::new(metadata))
+        };
+        for phrase in phrases {
+            words.push((phrase.to_string(), codes.clone(), metadata.clone()));
+        }
+    }
+    words
+}
+
+/// `Converter::new()` の予約語の辞書。既定の変換表で作った辞書を, 最初に使うときに1度だけ作る
+#[cfg(feature = "encode")]
+fn builtin_reserved_dictionary() -> Arc<ReservedDictionary> {
+    static BUILTIN: OnceLock<Arc<ReservedDictionary>> = OnceLock::new();
+    BUILTIN
+        .get_or_init(|| {
+            let mut converter = Converter::without_reserved_words();
+            for word in default_reserved_words() {
+                converter.merge_reserved_word(word, MergeStrategy::Append);
+            }
+            converter.reserved().clone()
+        })
+        .clone()
+}
+
+/// 既定の辞書と追加の辞書の予約語を, 重ねる順に返す。既定の辞書は予約語の順に並べる
+#[cfg(feature = "encode")]
+fn default_reserved_words() -> Vec<DictionaryWord> {
+    let mut data: Vec<(&str, &str)> = Vec::new();
+    #[cfg(feature = "builtin-dictionary")]
+    data.push((BUILTIN_DICTIONARY, RESERVED_WORDS));
+    data.extend_from_slice(DICTIONARY_PACKS);
+    let mut words = Vec::new();
+    for (name, data) in data {
+        let source: Arc<str> = name.into();
+        let mut parsed = parse_reserved_words(data);
+        if name == BUILTIN_DICTIONARY {
+            parsed.sort_by(|a, b| a.0.cmp(&b.0));
+        }
+        words.extend(
+            parsed
+                .into_iter()
+                .map(|(phrase, codes, metadata)| DictionaryWord {
+                    phrase,
+                    codes,
+                    source: source.clone(),
+                    metadata,
+                }),
+        );
+    }
+    words
+}
+
+impl Default for Converter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 文字から数字を引く変換表。既定の表は build.rs が作った関数で引き, builderで変えた場合だけHashMapを持つ
+#[cfg(feature = "encode")]
+#[derive(Debug, Clone)]
+enum CharTable {
+    Builtin,
+    #[cfg(feature = "decode")]
+    Custom(HashMap<char, Code>),
+}
+
+#[cfg(feature = "encode")]
+impl CharTable {
+    fn get(&self, ch: char) -> Option<&str> {
+        match self {
+            CharTable::Builtin => tables::builtin_code(ch),
+            #[cfg(feature = "decode")]
+            CharTable::Custom(map) => map.get(&ch).map(Cow::as_ref),
+        }
+    }
+
+    fn contains_key(&self, ch: char) -> bool {
+        self.get(ch).is_some()
+    }
+
+    /// 文字と数字の組。順は決まっていない
+    fn iter(&self) -> Box<dyn Iterator<Item = (char, &str)> + '_> {
+        match self {
+            CharTable::Builtin => Box::new(
+                tables::BASE_TABLE
+                    .iter()
+                    .chain(tables::COMPOSED_TABLE)
+                    .copied(),
+            ),
+            #[cfg(feature = "decode")]
+            CharTable::Custom(map) => Box::new(map.iter().map(|(&ch, code)| (ch, code.as_ref()))),
+        }
+    }
+
+    #[cfg(feature = "decode")]
+    fn into_map(self) -> HashMap<char, Code> {
+        match self {
+            CharTable::Builtin => tables::BASE_TABLE
+                .iter()
+                .chain(tables::COMPOSED_TABLE)
+                .map(|&(ch, code)| (ch, Cow::Borrowed(code)))
+                .collect(),
+            CharTable::Custom(map) => map,
+        }
+    }
+}
+
+/// 正規化の表。既定の表は build.rs が作った置き換える文字の順の表を二分探索で引く
+#[cfg(feature = "encode")]
+#[derive(Debug, Clone)]
+enum NormalizationTable {
+    Builtin,
+    #[cfg(feature = "decode")]
+    Custom(HashMap<char, char>),
+}
+
+#[cfg(feature = "encode")]
+impl NormalizationTable {
+    fn get(&self, ch: char) -> Option<char> {
+        match self {
+            NormalizationTable::Builtin => tables::NORMALIZATION
+                .binary_search_by_key(&ch, |&(from, _)| from)
+                .ok()
+                .map(|i| tables::NORMALIZATION[i].1),
+            #[cfg(feature = "decode")]
+            NormalizationTable::Custom(map) => map.get(&ch).copied(),
+        }
+    }
+
+    /// 置き換える文字と置き換え先の文字の組。順は決まっていない
+    fn iter(&self) -> Box<dyn Iterator<Item = (char, char)> + '_> {
+        match self {
+            NormalizationTable::Builtin => Box::new(tables::NORMALIZATION.iter().copied()),
+            #[cfg(feature = "decode")]
+            NormalizationTable::Custom(map) => Box::new(map.iter().map(|(&from, &to)| (from, to))),
+        }
+    }
+
+    #[cfg(feature = "decode")]
+    fn into_map(self) -> HashMap<char, char> {
+        match self {
+            NormalizationTable::Builtin => self.iter().collect(),
+            NormalizationTable::Custom(map) => map,
+        }
+    }
+}
+
+/// 変換表の数字から文字を引く表。2桁の数字は桁の値で直接引き, 4桁の数字は数字の順に並べて二分探索で引く
+#[cfg(feature = "decode")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct InverseTable {
+    /// `pairs[1桁目][2桁目]`
+    pairs: [[Option<char>; 10]; 10],
+    /// 4桁の数字と文字の組。`None` なら既定の `tables::COMPOSED_TABLE` を使う
+    composed: Option<Vec<(String, char)>>,
+}
+
+#[cfg(feature = "decode")]
+impl InverseTable {
+    /// 既定の変換表の逆引き。build.rs が作った定数を使うので, 何も確保しない
+    fn builtin() -> Self {
+        InverseTable {
+            pairs: tables::PAIR_TABLE,
+            composed: None,
+        }
+    }
+
+    /// 数字と文字の組から作る。2桁か4桁の数字でないものは入れない
+    #[cfg(all(feature = "encode", feature = "decode"))]
+    fn new<'a>(codes: impl IntoIterator<Item = (&'a str, char)>) -> Self {
+        let mut pairs = [[None; 10]; 10];
+        let mut composed = Vec::new();
+        for (code, ch) in codes {
+            match *code.as_bytes() {
+                [a @ b'0'..=b'9', b @ b'0'..=b'9'] => {
+                    pairs[usize::from(a - b'0')][usize::from(b - b'0')] = Some(ch);
+                }
+                [b'0'..=b'9', b'0'..=b'9', b'0'..=b'9', b'0'..=b'9'] => {
+                    composed.push((code.to_string(), ch));
+                }
+                _ => {}
+            }
+        }
+        composed.sort_unstable();
+        composed.dedup_by(|a, b| a.0 == b.0);
+        InverseTable {
+            pairs,
+            composed: Some(composed),
+        }
+    }
+
+    /// 2桁の数字を, 1桁目と2桁目の値 (0〜9) で引く
+    fn pair(&self, first: u8, second: u8) -> Option<char> {
+        self.pairs[usize::from(first)][usize::from(second)]
+    }
+
+    fn get(&self, code: &str) -> Option<char> {
+        match *code.as_bytes() {
+            [a @ b'0'..=b'9', b @ b'0'..=b'9'] => self.pair(a - b'0', b - b'0'),
+            [_, _, _, _] => match &self.composed {
+                Some(composed) => composed
+                    .binary_search_by(|(other, _)| other.as_str().cmp(code))
+                    .ok()
+                    .map(|i| composed[i].1),
+                None => tables::COMPOSED_TABLE
+                    .binary_search_by(|&(_, other)| other.cmp(code))
+                    .ok()
+                    .map(|i| tables::COMPOSED_TABLE[i].0),
+            },
+            _ => None,
+        }
+    }
+
+    /// 数字と文字の組を数字の順に返す
+    #[cfg(all(feature = "encode", feature = "decode"))]
+    fn iter(&self) -> impl Iterator<Item = (String, char)> + '_ {
+        let pairs = (0..100)
+            .filter_map(move |i| self.pairs[i / 10][i % 10].map(|ch| (format!("{:02}", i), ch)));
+        let composed: Vec<(String, char)> = match &self.composed {
+            Some(composed) => composed.clone(),
+            None => tables::COMPOSED_TABLE
+                .iter()
+                .map(|&(ch, code)| (code.to_string(), ch))
+                .collect(),
+        };
+        let mut all: Vec<(String, char)> = pairs.chain(composed).collect();
+        all.sort_unstable();
+        all.into_iter()
+    }
+}
+
+/// `Converter::new()` で作った, ライブラリ全体で共有するConverter。最初に使うときに作る
+#[cfg(any(feature = "encode", feature = "decode"))]
+pub(crate) fn shared_converter() -> &'static Converter {
+    static SHARED_CONVERTER: OnceLock<Converter> = OnceLock::new();
+    SHARED_CONVERTER.get_or_init(Converter::new)
+}
+
+/// 予約語の候補と1文字ずつ変換した結果の並び順
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum CandidateOrder {
+    /// 予約語の候補を辞書の順に並べ, 最後に1文字ずつ変換した結果を置く
+    #[default]
+    ReservedFirst,
+    /// 1文字ずつ変換した結果を先頭に置き, 続けて予約語の候補を辞書の順に並べる
+    LiteralFirst,
+}
+
+/// 変換結果の候補の並べ方
+///
+/// 1文字ずつ変換できず予約語の候補だけを返す場合, 並び順は辞書の順のままです。
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+#[non_exhaustive]
+pub struct CandidateOptions {
+    /// 並び順 (既定は `CandidateOrder::ReservedFirst`)
+    pub order: CandidateOrder,
+    /// 桁数の少ない順に並べ替え, 同じ候補を取り除く。
+    /// 桁数が同じ場合は `order` の並び順を保つ。
+    pub rank_by_length: bool,
+    /// 並べ替えた後, 候補をこの数までに減らす
+    pub max_candidates: Option<usize>,
+    /// `max_candidates` で減らすときも1文字ずつ変換した結果を残す (既定は `true`)。
+    /// 残す場合は最後の候補と入れ替える。`max_candidates` が1の場合は並び順の先頭だけを返し,
+    /// 1文字ずつ変換した結果は残さない。
+    pub keep_literal: bool,
+}
+
+impl Default for CandidateOptions {
+    fn default() -> Self {
+        CandidateOptions {
+            order: CandidateOrder::default(),
+            rank_by_length: false,
+            max_candidates: None,
+            keep_literal: true,
+        }
+    }
+}
+
+impl CandidateOptions {
+    /// 並び順を設定する
+    pub fn with_order(mut self, order: CandidateOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// 桁数の少ない順に並べ替えるか設定する
+    pub fn with_rank_by_length(mut self, rank_by_length: bool) -> Self {
+        self.rank_by_length = rank_by_length;
+        self
+    }
+
+    /// 候補の最大数を設定する
+    pub fn with_max_candidates(mut self, max_candidates: Option<usize>) -> Self {
+        self.max_candidates = max_candidates;
+        self
+    }
+
+    /// 候補を減らすときに1文字ずつ変換した結果を残すか設定する
+    pub fn with_keep_literal(mut self, keep_literal: bool) -> Self {
+        self.keep_literal = keep_literal;
+        self
+    }
+
+    /// 設定が正しいか確認する
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.max_candidates == Some(0) {
+            return Err(Error::from(ErrorKind::InvalidOption("max_candidates")));
+        }
+        Ok(())
+    }
+}
+
+/// 変換結果の数字の区切り方
+///
+/// 区切りは桁数だけで決めるので, `group_every` が奇数のときは2桁のコードの途中でも区切ります。
+/// 区切りを設定したConverterで2タッチ入力から変換するときは, 区切り文字を取り除いてから変換します。
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+#[non_exhaustive]
+pub struct GroupingOptions {
+    /// この桁数ごとに区切る (既定は区切らない)
+    pub group_every: Option<NonZeroUsize>,
+    /// 区切り文字 (既定は空白)
+    pub separator: char,
+}
+
+impl Default for GroupingOptions {
+    fn default() -> Self {
+        GroupingOptions {
+            group_every: None,
+            separator: ' ',
+        }
+    }
+}
+
+impl GroupingOptions {
+    /// 区切る桁数を設定する
+    pub fn with_group_every(mut self, group_every: Option<NonZeroUsize>) -> Self {
+        self.group_every = group_every;
+        self
+    }
+
+    /// 区切り文字を設定する
+    pub fn with_separator(mut self, separator: char) -> Self {
+        self.separator = separator;
+        self
+    }
+
+    /// 設定が正しいか確認する。区切り文字に数字は使えない。
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.separator.is_ascii_digit() {
+            return Err(Error::from(ErrorKind::InvalidOption("separator")));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "encode")]
+fn group_digits(digits: &str, every: usize, separator: char) -> String {
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / every);
+    for (i, ch) in digits.chars().enumerate() {
+        if i > 0 && i % every == 0 {
+            grouped.push(separator);
+        }
+        grouped.push(ch);
+    }
+    grouped
+}
+
+/// 2タッチ入力に変換する前の空白の扱い
+///
+/// 全角の空白のように正規化すると空白 (`88`) になる文字はすべて空白として扱います。
+/// 空白しかない入力は, 取り除いた結果が空になるのでエラーになります。
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+#[non_exhaustive]
+pub struct SpaceOptions {
+    /// 先頭と末尾の空白を取り除く
+    pub trim_spaces: bool,
+    /// 連続する空白を1つにまとめる
+    pub collapse_spaces: bool,
+}
+
+impl SpaceOptions {
+    /// 先頭と末尾の空白を取り除くか設定する
+    pub fn with_trim_spaces(mut self, trim_spaces: bool) -> Self {
+        self.trim_spaces = trim_spaces;
+        self
+    }
+
+    /// 連続する空白を1つにまとめるか設定する
+    pub fn with_collapse_spaces(mut self, collapse_spaces: bool) -> Self {
+        self.collapse_spaces = collapse_spaces;
+        self
+    }
+}
+
+/// 2タッチ入力に変換できない文字の扱い
+///
+/// `skip_unsupported` と `fallback` は同時に指定できません。
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct LenientOptions {
+    /// 変換できない文字を読み飛ばす
+    pub skip_unsupported: bool,
+    /// 変換できない文字をこの文字に置き換える
+    pub fallback: Option<char>,
+}
+
+impl LenientOptions {
+    /// 設定が正しいか確認する。
+    /// 代替文字は `converter` で2タッチ入力に変換できる文字でなければならない。
+    #[cfg(feature = "encode")]
+    pub fn validate(&self, converter: &Converter) -> Result<(), Error> {
+        if let Some(ch) = self.fallback {
+            if self.skip_unsupported {
+                return Err(Error::from(ErrorKind::ConflictingOptions(
+                    "skip_unsupported",
+                    "fallback",
+                )));
+            }
+            if converter.encode_char(ch).is_none() {
+                return Err(Error::from(ErrorKind::InvalidFallback { ch }));
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "encode")]
+    fn fallback_action(&self) -> Fallback {
+        match self.fallback {
+            Some(ch) => Fallback::Replace(ch.to_string()),
+            None if self.skip_unsupported => Fallback::Skip,
+            None => Fallback::Fail,
+        }
+    }
+}
+
+/// `convert_to_two_touch_string_with` などで使う, 2タッチ入力への変換の設定
+///
+/// 新しい設定を追加できるように `#[non_exhaustive]` にしています。
+/// `ConvertOptions::default()` から `with_` で始まるメソッドで作ってください。
+/// `skip_unsupported` と `fallback` は同時に指定できません。
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+#[non_exhaustive]
+pub struct ConvertOptions {
+    /// 候補の並べ方
+    pub candidates: CandidateOptions,
+    /// 変換する前の空白の扱い
+    pub spaces: SpaceOptions,
+    /// 変換結果の数字の区切り方
+    pub grouping: GroupingOptions,
+    /// 変換できない文字の扱い
+    pub lenient: LenientOptions,
+    /// 入力の最大文字数 (既定は制限なし)
+    pub max_input_chars: Option<usize>,
+    /// `渋谷(しぶや)` のような振り仮名を読むか (既定は読まない)
+    pub furigana: bool,
+    /// 続けて入力された数字の扱い (既定は `DigitRuns::Encode`)
+    pub digit_runs: DigitRuns,
+    /// `DigitRuns::Passthrough` でそのまま出力する数字の最小の長さ (既定は4)
+    pub min_digit_run: usize,
+    /// `{0840}` のように `{}` で囲まれた数字をそのまま出力するか (既定はしない)
+    pub escapes: bool,
+}
+
+impl Default for ConvertOptions {
+    fn default() -> Self {
+        ConvertOptions {
+            candidates: CandidateOptions::default(),
+            spaces: SpaceOptions::default(),
+            grouping: GroupingOptions::default(),
+            lenient: LenientOptions::default(),
+            max_input_chars: None,
+            furigana: false,
+            digit_runs: DigitRuns::Encode,
+            min_digit_run: 4,
+            escapes: false,
+        }
+    }
+}
+
+/// 入力の中で続けて入力された数字 (電話番号など) の扱い
+///
+/// 2タッチ入力からの変換では, そのまま出力した数字と変換した数字を区別できません。
+/// `Passthrough` で変換した結果を `convert_from_two_touch_string` で戻すと,
+/// 数字の部分は別の文字になるか, 桁がずれてエラーになります。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum DigitRuns {
+    /// 数字も1文字ずつ変換する (`0` は `00`)
+    Encode,
+    /// `ConvertOptions::min_digit_run` 文字以上続くASCIIの数字をそのまま出力する
+    Passthrough,
+}
+
+impl ConvertOptions {
+    /// 候補の並べ方を設定する
+    pub fn with_candidate_options(mut self, candidates: CandidateOptions) -> Self {
+        self.candidates = candidates;
+        self
+    }
+
+    /// 変換する前の空白の扱いを設定する
+    pub fn with_space_options(mut self, spaces: SpaceOptions) -> Self {
+        self.spaces = spaces;
+        self
+    }
+
+    /// 変換結果の数字の区切り方を設定する
+    pub fn with_grouping_options(mut self, grouping: GroupingOptions) -> Self {
+        self.grouping = grouping;
+        self
+    }
+
+    /// 変換できない文字を読み飛ばすか設定する
+    pub fn with_skip_unsupported(mut self, skip_unsupported: bool) -> Self {
+        self.lenient.skip_unsupported = skip_unsupported;
+        self
+    }
+
+    /// 変換できない文字を置き換える文字を設定する
+    pub fn with_fallback(mut self, fallback: Option<char>) -> Self {
+        self.lenient.fallback = fallback;
+        self
+    }
+
+    /// 振り仮名を読むか設定する。
+    /// 有効にすると, `渋谷(しぶや)` や `渋谷（しぶや）` のように変換できない文字の直後にある括弧の中が
+    /// かなだけの場合, 変換できない文字と括弧を取り除いてかなを変換する。
+    /// それ以外の括弧は今までどおり `(` と `)` として変換する。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// # #[cfg(feature = "encode")] {
+    /// let c = Converter::new();
+    /// let options = ConvertOptions::default().with_furigana(true);
+    /// let result = c.convert_to_two_touch_string_with("渋谷(しぶや)で(まってる)", &options).unwrap();
+    /// assert_eq!(
+    ///     result,
+    ///     c.convert_to_two_touch_string("しぶやで(まってる)").unwrap()
+    /// );
+    /// # }
+    /// ```
+    pub fn with_furigana(mut self, furigana: bool) -> Self {
+        self.furigana = furigana;
+        self
+    }
+
+    /// 続けて入力された数字の扱いを設定する。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// # #[cfg(feature = "encode")] {
+    /// let c = Converter::new();
+    /// let options = ConvertOptions::default().with_digit_runs(DigitRuns::Passthrough);
+    /// let result = c.convert_to_two_touch_string_with("TELして09012345678", &options).unwrap();
+    /// assert_eq!(result, vec!["401037324409012345678"]);
+    /// # }
+    /// ```
+    pub fn with_digit_runs(mut self, digit_runs: DigitRuns) -> Self {
+        self.digit_runs = digit_runs;
+        self
+    }
+
+    /// `DigitRuns::Passthrough` でそのまま出力する数字の最小の長さを設定する
+    pub fn with_min_digit_run(mut self, min_digit_run: usize) -> Self {
+        self.min_digit_run = min_digit_run;
+        self
+    }
+
+    /// `{0840}` のように `{}` で囲まれた数字をそのまま出力するか設定する。
+    /// 有効にすると, `{` と `}` そのものは `{{` と `}}` と書く。
+    /// 対応の取れない括弧, 何も囲まない `{}`, 数字以外の文字や奇数桁の数字を囲んだ場合はエラーになる。
+    ///
+    /// `{` と `}` は2タッチ入力の表にないので, `{{` と書いても既定では `ErrorKind::UnsupportedCharacter` になる。
+    /// 括弧を送るには `with_fallback` や `Fallback::Replace` で `(` などに置き換える。
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// # #[cfg(feature = "encode")] {
+    /// let c = Converter::new();
+    /// let options = ConvertOptions::default().with_escapes(true);
+    /// let result = c.convert_to_two_touch_string_with("あした{0840}ね", &options).unwrap();
+    /// assert_eq!(result, vec!["113241084054"]);
+    /// let err = c.convert_to_two_touch_string_with("{123}", &options).unwrap_err();
+    /// assert_eq!(*err.kind(), ErrorKind::OddLengthEscape { index: 0, len: 3 });
+    /// # }
+    /// ```
+    pub fn with_escapes(mut self, escapes: bool) -> Self {
+        self.escapes = escapes;
+        self
+    }
+
+    /// 入力の最大文字数を設定する。超えた場合は `ErrorKind::InputTooLong` になる
+    pub fn with_max_input_chars(mut self, max_input_chars: Option<usize>) -> Self {
+        self.max_input_chars = max_input_chars;
+        self
+    }
+
+    /// 設定が正しいか確認する。同時に指定できない設定や, `converter` で変換できない代替文字はエラーになる
+    #[cfg_attr(not(feature = "encode"), allow(unused_variables))]
+    pub fn validate(&self, converter: &Converter) -> Result<(), Error> {
+        self.candidates.validate()?;
+        self.grouping.validate()?;
+        // 代替文字は2タッチ入力に変換する場合だけ使う
+        #[cfg(feature = "encode")]
+        self.lenient.validate(converter)?;
+        Ok(())
+    }
+}
+
+/// `convert_from_two_touch_string_with` で使う, 2タッチ入力からの変換の設定
+///
+/// `ConvertOptions` と同じく `#[non_exhaustive]` にしています。
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct DecodeOptions {
+    /// 変換する前に取り除く区切り文字
+    pub separator: Option<char>,
+    /// 濁点・半濁点の扱い (既定は `DakutenMode::Raw`)
+    pub dakuten_mode: DakutenMode,
+    /// 区切り文字を除いた入力の最大桁数 (既定は制限なし)
+    pub max_input_digits: Option<usize>,
+}
+
+impl Default for DecodeOptions {
+    fn default() -> Self {
+        DecodeOptions {
+            separator: None,
+            dakuten_mode: DakutenMode::Raw,
+            max_input_digits: None,
+        }
+    }
+}
+
+impl DecodeOptions {
+    /// 取り除く区切り文字を設定する
+    pub fn with_separator(mut self, separator: Option<char>) -> Self {
+        self.separator = separator;
+        self
+    }
+
+    /// 濁点・半濁点の扱いを設定する
+    pub fn with_dakuten_mode(mut self, dakuten_mode: DakutenMode) -> Self {
+        self.dakuten_mode = dakuten_mode;
+        self
+    }
+
+    /// 入力の最大桁数を設定する。超えた場合は `ErrorKind::InputTooLong` になる
+    pub fn with_max_input_digits(mut self, max_input_digits: Option<usize>) -> Self {
+        self.max_input_digits = max_input_digits;
+        self
+    }
+
+    /// 設定が正しいか確認する。数字を区切り文字にはできない
+    pub fn validate(&self) -> Result<(), Error> {
+        match self.separator {
+            Some(separator) if separator.is_ascii_digit() => {
+                Err(Error::from(ErrorKind::InvalidOption("separator")))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// `Converter::convert_lines` の1行分の結果
+#[derive(Debug)]
+pub struct LineConversion {
+    /// 入力の何行目か (1始まり)
+    pub line: usize,
+    /// 前後の空白を取り除いた行
+    pub text: String,
+    pub result: Result<Vec<String>, Error>,
+}
+
+/// 2タッチ入力に変換できない文字をどう扱うか
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Fallback {
+    /// 読み飛ばす
+    Skip,
+    /// 2タッチ入力に変換できる文字列に置き換える
+    Replace(String),
+    /// エラーにする
+    Fail,
+}
+
+/// `Converter::convert_to_two_touch_string_lenient` の結果
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct LossyConversion {
+    /// 変換結果 (`convert_to_two_touch_string` と同じ並び)
+    pub candidates: Vec<String>,
+    /// 読み飛ばした文字と入力先頭からの文字位置
+    pub skipped: Vec<(usize, char)>,
+    /// 代替文字に置き換えた文字と入力先頭からの文字位置
+    pub substituted: Vec<(usize, char)>,
+    /// 予約語には一致したが1文字ずつの変換に失敗した場合, その理由
+    pub literal_error: Option<ErrorKind>,
+}
+
+/// `Profile::NumericOnly` で送れる文字
+#[cfg(any(feature = "encode", feature = "decode"))]
+const NUMERIC_CHARS: &str = "0123456789-*#";
+
+/// `suggest_code_fixes` で返す候補の最大数
+pub const MAX_CODE_FIXES: usize = 8;
+
+/// `Converter::effective_dictionary` で既定の辞書を表す名前
+pub const BUILTIN_DICTIONARY: &str = "builtin";
+
+/// `complete_code` で返す予約語の最大数
+pub const MAX_COMPLETIONS: usize = 10;
+
+/// `extract_and_decode` で調べる数字の並びの最小の桁数。年 (4桁) や値段 (5桁) を拾わないようにする
+pub const DEFAULT_MIN_CODE_DIGITS: usize = 6;
+
+/// 半角カナ (U+FF66 から U+FF9D) に対応する全角カタカナ
+#[cfg(feature = "encode")]
+const HALF_WIDTH_KATAKANA: &str = "ヲァィゥェォャュョッーアイウエオカキクケコサシスセソタチツテトナニヌネノハヒフヘホマミムメモヤユヨラリルレロワン";
+
+/// `Converter::encode_shortest` の結果
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ShortestEncoding {
+    /// 変換した数字
+    pub digits: String,
+    /// 予約語を使った区間と1文字ずつ変換した区間を入力の順に並べたもの
+    pub segments: Vec<ShortestSegment>,
+    /// 全体を1文字ずつ変換した場合の桁数。1文字ずつでは変換できない場合はNone
+    pub literal_len: Option<usize>,
+}
+
+/// `ShortestEncoding` の1区間
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ShortestSegment {
+    /// 入力での文字位置の範囲
+    pub span: std::ops::Range<usize>,
+    /// この区間の数字
+    pub code: String,
+    /// 予約語を使った場合はその表記。1文字ずつ変換した場合はNone
+    pub phrase: Option<String>,
+}
+
+/// `encode_shortest` で, ある位置までの最短の変換の最後の区間
+#[cfg(feature = "encode")]
+#[derive(Debug, Clone)]
+enum ShortestStep {
+    /// 指定したトークンを1文字ずつ変換した
+    Literal(usize),
+    /// 指定したトークンから予約語の数字と表記を使った
+    Reserved(usize, String, String),
+}
+
+/// `Converter::repair_against_dictionary` で見つかった予約語
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RepairCandidate {
+    /// 辞書に登録された表記
+    pub phrase: String,
+    /// 予約語の正しい数字
+    pub code: String,
+    /// 入力をこの数字にするのに必要な編集回数
+    pub edits: usize,
+}
+
+/// `Converter::classify_digits` の結果。確からしいものから順に並べた `Hypothesis` を持つ
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Classification {
+    /// 1つ以上の `Hypothesis`
+    pub hypotheses: Vec<Hypothesis>,
+}
+
+impl Classification {
+    /// 最も確からしい `Hypothesis` を返す
+    pub fn best(&self) -> &Hypothesis {
+        &self.hypotheses[0]
+    }
+}
+
+/// 数字の並びが表していそうなものと, その確からしさ
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Hypothesis {
+    pub kind: HypothesisKind,
+    pub confidence: Confidence,
+}
+
+/// `Hypothesis` の種類
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", rename_all = "snake_case"))]
+#[non_exhaustive]
+pub enum HypothesisKind {
+    /// 予約語の数字。一致した表記を並べる
+    ReservedPhrase { phrases: Vec<String> },
+    /// 2タッチ入力。`DakutenMode::Composed` で戻した文字列と, かなの組の割合 (%)
+    TwoTouch { preview: String, kana_percent: u8 },
+    /// 日本の電話番号
+    PhoneNumberLike,
+    /// 分からない
+    Unknown,
+}
+
+/// `Hypothesis` の確からしさ
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Confidence {
+    Low,
+    Medium,
+    High,
+}
+
+/// `Converter::search_reserved` で探す条件
+///
+/// `*` を含む場合は, `*` を0文字以上の任意の文字とみなして全体が合うものを探す (`*してる`, `10*`)。
+/// 含まない場合は, その文字列を含むものを探す。表記で探す場合は, 全角・半角, 大文字・小文字,
+/// カタカナ・ひらがなの違いをそろえて比べる。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DictionaryQuery {
+    target: QueryTarget,
+    pattern: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum QueryTarget {
+    Phrase,
+    Code,
+}
+
+impl DictionaryQuery {
+    /// 予約語の表記で探す
+    pub fn phrase(pattern: &str) -> Self {
+        DictionaryQuery {
+            target: QueryTarget::Phrase,
+            pattern: pattern.to_string(),
+        }
+    }
+
+    /// 予約語の数字で探す
+    pub fn code(pattern: &str) -> Self {
+        DictionaryQuery {
+            target: QueryTarget::Code,
+            pattern: pattern.to_string(),
+        }
+    }
+}
+
+/// `Converter::explain_reserved` で返す, 予約語の読みと語呂合わせの由来
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ReservedEntryInfo {
+    /// 辞書に登録された表記
+    pub phrase: String,
+    /// 予約語のすべての数字
+    pub codes: Vec<String>,
+    pub reading: Option<String>,
+    /// 先頭の数字の, どの桁をどの音に読むか
+    pub derivation: Vec<Pun>,
+    pub note: Option<String>,
+}
+
+/// 語呂合わせで, 数字 `digits` を `reading` と読むこと
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Pun {
+    pub digits: String,
+    pub reading: String,
+}
+
+impl Pun {
+    pub fn new(digits: &str, reading: &str) -> Self {
+        Pun {
+            digits: digits.to_string(),
+            reading: reading.to_string(),
+        }
+    }
+
+    /// `4よ` のように, 数字と読みを続けて書いたものを読む
+    #[cfg(feature = "encode")]
+    fn parse(token: &str) -> Self {
+        let split = token
+            .find(|ch: char| !ch.is_ascii_digit())
+            .unwrap_or(token.len());
+        Pun::new(&token[..split], &token[split..])
+    }
+}
+
+/// `TableExport` の形式の版。項目の形や意味を変えたら上げる
+pub const TABLE_EXPORT_VERSION: u32 = 1;
+
+/// `Converter::export_tables` で書き出す表
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TableExport {
+    /// 形式の版 (`TABLE_EXPORT_VERSION`)
+    pub version: u32,
+    /// 変換表
+    pub characters: Vec<CharacterCode>,
+    /// 正規化の表
+    pub normalizations: Vec<Normalization>,
+    /// 予約語の辞書
+    pub reserved_words: Vec<Entry>,
+}
+
+/// 変換表の1文字
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CharacterCode {
+    pub ch: char,
+    pub code: String,
+}
+
+/// 正規化の表の1組。`from` を変換の前に `to` に置き換える
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Normalization {
+    pub from: char,
+    pub to: char,
+}
+
+/// 予約語の辞書の1項目
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Entry {
+    /// 辞書に登録された表記
+    pub phrase: String,
+    /// 予約語のすべての数字
+    pub codes: Vec<String>,
+}
+
+/// `Converter::effective_dictionary` で返す, 予約語の辞書の1項目
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct EffectiveEntry {
+    /// 辞書に登録された表記
+    pub phrase: String,
+    pub codes: Vec<String>,
+    /// 予約語を追加した辞書の名前を追加した順に並べたもの。
+    /// 既定の辞書は `BUILTIN_DICTIONARY` で, `add_reserved_word` で追加した場合は空
+    pub sources: Vec<String>,
+}
+
+/// `Converter::complete_code` の結果
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Completions {
+    /// 入力済みの組を `DakutenMode::Composed` で戻した文字列。戻せない場合はNone
+    pub decoded: Option<String>,
+    /// 次に押す数字ごとの文字。文字にならない数字は含めない
+    pub keys: Vec<KeyHint>,
+    /// 数字が `prefix` で始まる予約語の数字と表記。数字の順に並べる
+    pub phrases: Vec<(String, String)>,
+}
+
+/// 次に押す数字と, それでなる文字
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct KeyHint {
+    pub digit: u8,
+    /// 組の途中では1文字, 組の始めでは次の組がなりうる文字
+    pub chars: Vec<char>,
+}
+
+/// `Converter::extract_and_decode` で見つけた数字の並び
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Found {
+    /// 文章の中の位置 (バイト単位)
+    pub range: std::ops::Range<usize>,
+    /// 見つけた数字
+    pub digits: String,
+    /// 戻した文字列か, 戻せなかった理由
+    pub result: Result<String, Error>,
+}
+
+/// `Converter::decode_with_recovery` で戻せた候補
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RecoveryCandidate {
+    /// `DakutenMode::Composed` で戻したメッセージ
+    pub message: String,
+    /// 飛ばした桁の位置 (0始まり)
+    pub skipped: Vec<usize>,
+    /// もっともらしさ (0から100)。かな (濁点・半濁点を含む) の組を2点, 英字の組を1点, それ以外を0点として,
+    /// 全部かなの場合を100とした割合
+    pub score: u8,
+    /// メッセージに含まれる予約語の表記の数
+    pub dictionary_hits: usize,
+}
+
+/// `Converter::split_message_and_callback` の結果
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CallbackSplits {
+    /// 最も良い分け方
+    pub best: CallbackSplit,
+    /// 他に試して戻せた分け方。良いものから順に並べる
+    pub alternatives: Vec<CallbackSplit>,
+    ambiguous: bool,
+}
+
+impl CallbackSplits {
+    /// `best` とかなの組の割合が同じ分け方が `alternatives` にある場合はtrue
+    pub fn is_ambiguous(&self) -> bool {
+        self.ambiguous
+    }
+}
+
+/// 2タッチ入力のメッセージと, 最後に付いた電話番号の分け方
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CallbackSplit {
+    /// `DakutenMode::Composed` で戻したメッセージ
+    pub message: String,
+    /// 電話番号。付いていないとした場合はNone
+    pub callback: Option<String>,
+    /// 電話番号が始まる位置 (バイト単位)。付いていない場合は入力の長さ
+    pub split: usize,
+    /// メッセージのかなの組の割合 (%)
+    pub kana_percent: u8,
+}
+
+/// 戻した組のうち, かなの組の割合 (%)。濁点・半濁点もかなとして数える
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn kana_percent(segments: &[DecodedSegment]) -> u8 {
+    if segments.is_empty() {
+        return 0;
+    }
+    let kana = segments
+        .iter()
+        .filter(|segment| {
+            CharClass::of(segment.ch) == CharClass::Kana || "゛゜".contains(segment.ch)
+        })
+        .count();
+    (kana * 100 / segments.len()) as u8
+}
+
+/// 日本の電話番号の形をしていれば, その確からしさを返す
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn phone_number_confidence(digits: &str) -> Option<Confidence> {
+    let high = match digits.len() {
+        10 => digits.starts_with("0120"),
+        11 => ["070", "080", "090", "050", "0800"]
+            .iter()
+            .any(|prefix| digits.starts_with(prefix)),
+        _ => false,
+    };
+    if high {
+        Some(Confidence::High)
+    } else if digits.len() == 10 && digits.starts_with('0') && !digits.starts_with("00") {
+        Some(Confidence::Medium)
+    } else {
+        None
+    }
+}
+
+#[cfg(feature = "encode")]
+impl ReservedDictionary {
+    /// 予約語を `key` で入れ, 数字を索引に入れる。同じ `key` の予約語は置き換える
+    fn insert(&mut self, key: String, word: ReservedWord) {
+        // 置き換える予約語と同じ数字があっても索引から消えないように, 先に取り除いてから入れる
+        self.remove(&key);
+        for code in &word.codes {
+            self.code_index
+                .entry(code.len())
+                .or_default()
+                .push((code.clone(), key.clone()));
+            self.prefix_index.insert((code.clone(), key.clone()));
+        }
+        self.word_map.insert(key, word);
+    }
+
+    /// 予約語 `key` を取り除き, 数字を1つずつ索引から取り除く
+    fn remove(&mut self, key: &str) -> Option<ReservedWord> {
+        let word = self.word_map.remove(key)?;
+        for code in &word.codes {
+            self.prefix_index.remove(&(code.clone(), key.to_string()));
+            if let Some(bucket) = self.code_index.get_mut(&code.len()) {
+                if let Some(i) = bucket.iter().position(|(c, k)| c == code && k == key) {
+                    bucket.remove(i);
+                }
+            }
+        }
+        Some(word)
+    }
+}
+
+/// 予約語と対応する数字
+#[cfg(feature = "encode")]
+#[derive(Clone)]
+struct ReservedWord {
+    /// 辞書に登録された表記
+    phrase: String,
+    codes: Vec<Code>,
+    /// `codes` をそのまま候補にしたもの。変換のたびに文字列を作らないように持っておく
+    candidates: Vec<Candidate>,
+    /// 予約語を追加した辞書の名前。`add_reserved_word` で追加した場合は空
+    sources: Vec<Arc<str>>,
+    /// 辞書の項目に書かれた読みや由来。同じ項目の表記で共有する
+    metadata: Option<Arc<ReservedMetadata>>,
+}
+
+/// 辞書の項目の読み, 語呂合わせの由来と注記
+#[cfg(feature = "encode")]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct ReservedMetadata {
+    pub(crate) reading: Option<String>,
+    pub(crate) derivation: Vec<Pun>,
+    pub(crate) note: Option<String>,
+}
+
+/// 入力の長さが `limit` を超えていないか確かめる
+#[cfg(any(feature = "encode", feature = "decode"))]
+fn check_input_len<I: Iterator<Item = char>>(chars: I, limit: Option<usize>) -> Result<(), Error> {
+    let limit = match limit {
+        Some(limit) => limit,
+        None => return Ok(()),
+    };
+    let len = chars.count();
+    if len > limit {
+        return Err(Error::from(ErrorKind::InputTooLong { len, limit }));
+    }
+    Ok(())
+}
+
+/// 2タッチ入力が数字だけで, 桁数が偶数か確かめる
+#[cfg(feature = "decode")]
+fn check_pair_digits(val: &str) -> Result<(), Error> {
+    if !all_ascii_digits(val.as_bytes()) {
+        // 誤りの場所を決めるのは遅くてよいので, 文字ごとに調べる
+        if let Some((offset, ch)) = val.char_indices().find(|(_, ch)| !ch.is_ascii()) {
+            return Err(Error::from(ErrorKind::NonAscii { ch, offset }));
+        }
+        if let Some((index, ch)) = val.char_indices().find(|(_, ch)| !ch.is_ascii_digit()) {
+            return Err(Error::from(ErrorKind::NonDigit { ch, index }));
+        }
+    }
+    if !val.len().is_multiple_of(2) {
+        return Err(Error::from(ErrorKind::OddLength { len: val.len() }));
+    }
+    Ok(())
+}
+
+/// `bytes` がすべてASCIIの数字か。自動でベクトル化されるように, 16バイトずつ短絡せずに調べる
+#[cfg(feature = "decode")]
+fn all_ascii_digits(bytes: &[u8]) -> bool {
+    let mut chunks = bytes.chunks_exact(16);
+    for chunk in &mut chunks {
+        if !chunk.iter().fold(true, |ok, b| ok & b.is_ascii_digit()) {
+            return false;
+        }
+    }
+    chunks.remainder().iter().all(u8::is_ascii_digit)
+}
+
+/// 2タッチ入力に変換する文字列の要素
+#[cfg(feature = "encode")]
+enum Token {
+    /// 入力先頭からの文字位置と文字
+    Char(usize, char),
+    /// `{` の文字位置と, `{}` で囲まれた, そのまま出力する数字
+    Digits(usize, String),
+    /// `DigitRuns::Passthrough` でそのまま出力する数字の並びの, 先頭の文字位置と数字
+    Passthrough(usize, String),
+}
+
+#[cfg(feature = "encode")]
+impl Token {
+    fn as_char(&self) -> Option<char> {
+        match *self {
+            Token::Char(_, ch) => Some(ch),
+            Token::Digits(..) | Token::Passthrough(..) => None,
+        }
+    }
+}
+
+/// `tokens` が `(かな)` または `（かな）` で始まる場合, かなの文字数を返す
+#[cfg(feature = "encode")]
+fn furigana_reading(tokens: &[Token]) -> Option<usize> {
+    let close = match tokens.first().and_then(Token::as_char) {
+        Some('(') => ')',
+        Some('（') => '）',
+        _ => return None,
+    };
+    let is_kana = |ch: char| matches!(ch, 'ぁ'..='ゖ' | 'ァ'..='ヺ' | 'ー');
+    let len = tokens[1..]
+        .iter()
+        .take_while(|token| token.as_char().is_some_and(is_kana))
+        .count();
+    match tokens.get(len + 1).and_then(Token::as_char) {
+        Some(ch) if ch == close && len > 0 => Some(len),
+        _ => None,
+    }
+}
+
+/// `min_len` 文字以上続くASCIIの数字を `Token::Passthrough` にまとめる
+#[cfg(feature = "encode")]
+fn pass_digit_runs(tokens: Vec<Token>, min_len: usize) -> Vec<Token> {
+    let mut result = Vec::with_capacity(tokens.len());
+    let mut run: Vec<Token> = Vec::new();
+    let flush = |run: &mut Vec<Token>, result: &mut Vec<Token>| {
+        if run.len() >= min_len.max(1) {
+            let index = token_start(&run[0]);
+            let digits = run.iter().filter_map(Token::as_char).collect();
+            result.push(Token::Passthrough(index, digits));
+            run.clear();
+        } else {
+            result.append(run);
+        }
+    };
+    for token in tokens {
+        match token {
+            Token::Char(_, ch) if ch.is_ascii_digit() => run.push(token),
+            token => {
+                flush(&mut run, &mut result);
+                result.push(token);
+            }
+        }
+    }
+    flush(&mut run, &mut result);
+    result
+}
+
+/// トークンの入力での開始位置
+#[cfg(feature = "encode")]
+fn token_start(token: &Token) -> usize {
+    match *token {
+        Token::Char(index, _) | Token::Digits(index, _) | Token::Passthrough(index, _) => index,
+    }
+}
+
+/// トークンの入力での終了位置。`{{` と `}}`, `{}` で囲まれた数字は括弧も含める
+#[cfg(feature = "encode")]
+fn token_end(chars: &[char], token: &Token) -> usize {
+    match *token {
+        Token::Char(index, ch) if (ch == '{' || ch == '}') && chars.get(index + 1) == Some(&ch) => {
+            index + 2
+        }
+        Token::Char(index, _) => index + 1,
+        Token::Digits(index, ref digits) => index + digits.len() + 2,
+        Token::Passthrough(index, ref digits) => index + digits.len(),
+    }
+}
+
+/// `escapes` が有効なら, `{0840}` のように `{}` で囲まれた数字をそのまま出力する部分として切り出す。
+/// `{{` と `}}` はそれぞれ1文字の `{` と `}` になる。
+/// 2タッチ入力は2桁ずつ戻すので, 囲まれた数字が奇数桁の場合は `{` の位置を指すエラーになる。
+/// 無効なら括弧も含めて1文字ずつにする
+#[cfg(feature = "encode")]
+fn tokenize(val: &str, escapes: bool) -> Result<Vec<Token>, Error> {
+    if !escapes {
+        return Ok(val
+            .chars()
+            .enumerate()
+            .map(|(i, ch)| Token::Char(i, ch))
+            .collect());
+    }
+    let mut tokens = Vec::new();
+    let mut chars = val.chars().enumerate().peekable();
+    while let Some((index, ch)) = chars.next() {
+        match ch {
+            '{' if chars.peek().map(|&(_, ch)| ch) == Some('{') => {
+                chars.next();
+                tokens.push(Token::Char(index, '{'));
+            }
+            '}' if chars.peek().map(|&(_, ch)| ch) == Some('}') => {
+                chars.next();
+                tokens.push(Token::Char(index, '}'));
+            }
+            '{' => {
+                let start = index;
+                let mut digits = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '}')) => break,
+                        Some((_, ch)) if ch.is_ascii_digit() => digits.push(ch),
+                        Some((index, ch)) => {
+                            return Err(Error::from(ErrorKind::NonDigitInEscape { ch, index }))
+                        }
+                        None => return Err(Error::from(ErrorKind::UnbalancedBrace { index })),
+                    }
+                }
+                if digits.is_empty() {
+                    return Err(Error::from(ErrorKind::EmptyEscape { index: start }));
+                }
+                if !digits.len().is_multiple_of(2) {
+                    return Err(Error::from(ErrorKind::OddLengthEscape {
+                        index: start,
+                        len: digits.len(),
+                    }));
+                }
+                tokens.push(Token::Digits(start, digits));
+            }
+            '}' => return Err(Error::from(ErrorKind::UnbalancedBrace { index })),
+            _ => tokens.push(Token::Char(index, ch)),
+        }
+    }
+    Ok(tokens)
+}
+
+/// 変換候補の種類
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", rename_all = "snake_case"))]
+pub enum CandidateKind {
+    /// 予約語 (語呂合わせ)。`phrase` は辞書に登録された表記
+    Reserved { phrase: Arc<str> },
+    /// 1文字ずつ変換したもの
+    Literal,
+    /// 文中の予約語と1文字ずつの変換を組み合わせたもの
+    Segmented,
+}
+
+/// 種類の付いた変換候補
+///
+/// `serde` featureでは `kind` と `digits` の2つのフィールドを持つオブジェクトになる。
+/// `kind` は `type` (`"reserved"`, `"literal"`, `"segmented"`) で種類を表し, 予約語の場合は `phrase` を持つ。
+///
+/// ## Example
+/// ```
+/// # use pokebell::two_touch_input::*;
+/// # #[cfg(all(feature = "serde", feature = "builtin-dictionary"))]
+/// # {
+/// let c = Converter::new();
+/// let candidates = c.convert_to_two_touch("ごくろうさん").unwrap();
+/// assert_eq!(
+///     serde_json::to_string(&candidates).unwrap(),
+///     r#"[{"kind":{"type":"reserved","phrase":"ごくろうさん"},"digits":"5963"},{"kind":{"type":"literal"},"digits":"25042395133103"}]"#
+/// );
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Candidate {
+    kind: CandidateKind,
+    digits: Arc<str>,
+}
+
+impl Candidate {
+    /// 2タッチ入力の数字
+    pub fn digits(&self) -> &str {
+        &self.digits
+    }
+
+    pub fn kind(&self) -> &CandidateKind {
+        &self.kind
+    }
+
+    pub fn is_reserved(&self) -> bool {
+        matches!(self.kind, CandidateKind::Reserved { .. })
+    }
+
+    /// 予約語の候補なら辞書に登録された表記
+    pub fn phrase(&self) -> Option<&str> {
+        match self.kind {
+            CandidateKind::Reserved { ref phrase } => Some(phrase),
+            _ => None,
+        }
+    }
+
+    pub fn into_digits(self) -> String {
+        self.digits.to_string()
+    }
+}
+
+/// `encode_with_trace` で返す, 入力の1文字と変換した数字の対応
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct EncodedSegment {
+    /// 区間の種類
+    pub kind: SegmentKind,
+    /// 入力された文字。`{}` で囲まれた数字の場合は `{`, そのまま出力した数字の場合は先頭の数字
+    pub original: char,
+    /// 変換表で実際に使った文字。`{}` で囲まれた数字の場合はNone
+    pub normalized: Option<char>,
+    /// 入力先頭からの文字位置
+    pub index: usize,
+    /// 変換した数字
+    pub code: String,
+    /// 変換した数字全体の中での `code` の位置
+    pub span: std::ops::Range<usize>,
+}
+
+/// `EncodedSegment` の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum SegmentKind {
+    /// 1文字を変換した
+    Encoded,
+    /// `{}` で囲まれた数字
+    Escape,
+    /// `DigitRuns::Passthrough` でそのまま出力した数字
+    Raw,
+}
+
+/// `Converter::decode_spoken` で記号などを読み上げる言葉
+const SPOKEN_NAMES: &[(char, &str)] = &[
+    ('゛', "だくてん"),
+    ('゜', "はんだくてん"),
+    ('(', "かっこ"),
+    (')', "かっことじ"),
+    ('?', "はてな"),
+    ('!', "びっくり"),
+    ('-', "ハイフン"),
+    ('/', "スラッシュ"),
+    ('\\', "えん"),
+    ('&', "アンド"),
+    ('*', "アスタリスク"),
+    ('#', "シャープ"),
+    (' ', "スペース"),
+];
+
+/// 記号などを読み上げる言葉。かな, 英字, 数字などそのまま読める文字ではNone
+///
+/// ## Example
+/// ```
+/// # use pokebell::two_touch_input::spoken_name;
+/// assert_eq!(spoken_name('#'), Some("シャープ"));
+/// assert_eq!(spoken_name('あ'), None);
+/// ```
+pub fn spoken_name(ch: char) -> Option<&'static str> {
+    SPOKEN_NAMES
+        .iter()
+        .find(|&&(symbol, _)| symbol == ch)
+        .map(|&(_, name)| name)
+}
+
+/// `Converter::decode_spoken_with` で使う, 読み上げ用の変換の設定
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SpokenOptions {
+    /// 言葉の区切り (既定は `、`)
+    pub separator: String,
+    /// 濁点・半濁点の扱い (既定は `DakutenMode::Composed`)
+    pub dakuten_mode: DakutenMode,
+}
+
+impl Default for SpokenOptions {
+    fn default() -> Self {
+        SpokenOptions {
+            separator: "、".to_string(),
+            dakuten_mode: DakutenMode::Composed,
+        }
+    }
+}
+
+impl SpokenOptions {
+    /// 言葉の区切りを設定する
+    pub fn with_separator(mut self, separator: &str) -> Self {
+        self.separator = separator.to_string();
+        self
+    }
+
+    /// 濁点・半濁点の扱いを設定する
+    pub fn with_dakuten_mode(mut self, dakuten_mode: DakutenMode) -> Self {
+        self.dakuten_mode = dakuten_mode;
+        self
+    }
+}
+
+/// `Converter::convert_multipart_with` で各部分に付ける番号の設定
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct MultipartOptions {
+    /// 番号を付ける位置 (既定は `LabelPosition::Prefix`)
+    pub position: LabelPosition,
+    /// 番号と全体の数の区切り (既定は `/`)
+    pub separator: char,
+}
+
+impl Default for MultipartOptions {
+    fn default() -> Self {
+        MultipartOptions {
+            position: LabelPosition::Prefix,
+            separator: '/',
+        }
+    }
+}
+
+impl MultipartOptions {
+    /// 番号を付ける位置を設定する
+    pub fn with_position(mut self, position: LabelPosition) -> Self {
+        self.position = position;
+        self
+    }
+
+    /// 番号と全体の数の区切りを設定する
+    pub fn with_separator(mut self, separator: char) -> Self {
+        self.separator = separator;
+        self
+    }
+
+    #[cfg(feature = "encode")]
+    fn label(&self, index: usize, total: usize) -> String {
+        format!("{}{}{}", index, self.separator, total)
+    }
+}
+
+/// 分けた各部分に番号を付ける位置
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LabelPosition {
+    /// 先頭
+    Prefix,
+    /// 末尾
+    Suffix,
+}
+
+/// `Converter::decode_symbols` で返す, 種類の付いた1文字
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PagerSymbol {
+    pub kind: SymbolKind,
+    /// 2タッチ入力の先頭からの桁位置
+    pub offset: usize,
+}
+
+impl PagerSymbol {
+    /// 表示する文字
+    pub fn as_char(&self) -> char {
+        match self.kind {
+            SymbolKind::Kana(ch)
+            | SymbolKind::VoicedKana(ch)
+            | SymbolKind::Letter(ch)
+            | SymbolKind::Symbol(ch) => ch,
+            SymbolKind::Digit(digit) => char::from(b'0' + digit),
+            SymbolKind::Dakuten => '゛',
+            SymbolKind::Handakuten => '゜',
+            SymbolKind::Space => ' ',
+        }
+    }
+}
+
+impl Display for PagerSymbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_char())
+    }
+}
+
+/// `PagerSymbol` の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(tag = "type", content = "ch", rename_all = "snake_case")
+)]
+pub enum SymbolKind {
+    /// ひらがな
+    Kana(char),
+    /// 濁点・半濁点をまとめた4桁のかな
+    VoicedKana(char),
+    /// 英字 (大文字)
+    Letter(char),
+    /// 数字 (0〜9)
+    Digit(u8),
+    /// 前の文字とまとめられなかった濁点
+    Dakuten,
+    /// 前の文字とまとめられなかった半濁点
+    Handakuten,
+    Space,
+    /// それ以外の記号
+    Symbol(char),
+}
+
+/// `Converter::decode_symbols` の結果。`to_string()` で文字列にできる
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PagerSymbols(pub Vec<PagerSymbol>);
+
+impl std::ops::Deref for PagerSymbols {
+    type Target = [PagerSymbol];
+
+    fn deref(&self) -> &[PagerSymbol] {
+        &self.0
+    }
+}
+
+impl IntoIterator for PagerSymbols {
+    type Item = PagerSymbol;
+    type IntoIter = std::vec::IntoIter<PagerSymbol>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl Display for PagerSymbols {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.iter().try_for_each(|symbol| Display::fmt(symbol, f))
+    }
+}
+
+/// `decode_with_trace` で返す, 2タッチ入力の数字と変換した文字の対応
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DecodedSegment {
+    /// 変換元の数字 (2桁, 濁点をまとめた場合は4桁)
+    pub code: String,
+    /// 入力先頭からの桁位置
+    pub offset: usize,
+    /// 変換した文字
+    pub ch: char,
+    /// 濁点・半濁点の数字を前の文字とまとめたかどうか
+    pub composed: bool,
+}
+
+/// `Converter::verify_roundtrip` の結果
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RoundTrip {
+    /// 1文字ずつ変換した数字
+    pub digits: String,
+    /// `digits` を元に戻した文字列。`{}` で囲まれた数字などで戻せない場合はNone
+    pub decoded: Option<String>,
+    /// 入力の文字がそのまま戻らなかった部分。入力での文字位置の順に並べる
+    pub substitutions: Vec<Substitution>,
+}
+
+impl RoundTrip {
+    /// 入力がそのまま戻るかどうか
+    pub fn is_exact(&self) -> bool {
+        self.substitutions.is_empty()
+    }
+}
+
+/// `RoundTrip` で, 入力の1文字 (または `{}` で囲まれた数字) がどう変わったか
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", rename_all = "snake_case"))]
+pub enum Substitution {
+    /// 正規化で別の文字になった (`ぁ` が `あ`, `a` が `A` など)
+    Normalized {
+        index: usize,
+        original: char,
+        decoded: char,
+    },
+    /// 濁音・半濁音が, 元の文字と `゛` / `゜` の2文字になった
+    DakutenSplit {
+        index: usize,
+        original: char,
+        decoded: String,
+    },
+    /// `ConverterBuilder::add_normalization_str` で複数の文字になった
+    Expanded {
+        index: usize,
+        original: char,
+        decoded: String,
+    },
+    /// 空白の整理で取り除かれた
+    Removed { index: usize, original: char },
+    /// `{}` で囲まれた数字。戻した文字は入力と関係がなく, 戻せない場合はNone
+    RawDigits {
+        index: usize,
+        digits: String,
+        decoded: Option<String>,
+    },
+    /// 変換した数字が元に戻せない (`ConverterBuilder` で追加した4桁のコードなど)
+    Undecodable {
+        index: usize,
+        original: char,
+        code: String,
+    },
+}
+
+impl Substitution {
+    /// 入力先頭からの文字位置
+    pub fn index(&self) -> usize {
+        match *self {
+            Substitution::Normalized { index, .. }
+            | Substitution::DakutenSplit { index, .. }
+            | Substitution::Expanded { index, .. }
+            | Substitution::Removed { index, .. }
+            | Substitution::RawDigits { index, .. }
+            | Substitution::Undecodable { index, .. } => index,
+        }
+    }
+}
+
+/// `suggest_code_fixes` で候補を並べるための文字の種類。前にあるものほど候補として優先する
+#[cfg(any(feature = "encode", feature = "decode"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum CharClass {
+    Kana,
+    Latin,
+    Digit,
+    Symbol,
+}
+
+#[cfg(any(feature = "encode", feature = "decode"))]
+impl CharClass {
+    fn of(ch: char) -> CharClass {
+        match ch {
+            'ぁ'..='ん' => CharClass::Kana,
+            'A'..='Z' => CharClass::Latin,
+            '0'..='9' => CharClass::Digit,
+            _ => CharClass::Symbol,
+        }
+    }
+}
+
+/// 1文字ずつ変換した結果
+#[cfg(feature = "encode")]
+#[derive(Default)]
+struct Literal {
+    digits: String,
+    skipped: Vec<(usize, char)>,
+    substituted: Vec<(usize, char)>,
+}
+
+/// 変換先のポケベルで表示できる文字の範囲
+///
+/// `KanaOnly` と `Chars` では, 変換表のうち範囲の文字だけを変換とその逆に使う。
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[non_exhaustive]
+pub enum Profile {
+    /// 2タッチ入力で入力できるすべての文字
+    #[default]
+    Full,
+    /// 数字表示のみのポケベル。
+    /// 数字と `-`, `*`, `#` をそのまま送り, 予約語は数字に変換するが, かなや英字は変換しない
+    NumericOnly,
+    /// ひらがな (濁音・半濁音を含む) と濁点・半濁点, 空白だけ
+    KanaOnly,
+    /// 指定した文字だけ。文字は `normalize_char` で正規化した後のもので,
+    /// `DakutenMode::Raw` で濁音を戻すには `゛`, `゜` も必要
+    Chars(BTreeSet<char>),
+}
+
+impl Profile {
+    /// 指定した文字だけの `Profile::Chars` を作る
+    ///
+    /// ## Example
+    /// ```
+    /// # use pokebell::two_touch_input::*;
+    /// let c = Converter::new().with_profile(Profile::chars("やきにく".chars()));
+    /// # #[cfg(feature = "encode")]
+    /// assert_eq!(c.convert_to_two_touch_string("にく").unwrap(), vec!["5223"]);
+    /// # #[cfg(feature = "decode")]
+    /// assert!(c.convert_from_two_touch_string("11").is_err());
+    /// ```
+    pub fn chars<I: IntoIterator<Item = char>>(chars: I) -> Profile {
+        Profile::Chars(chars.into_iter().collect())
+    }
+
+    /// 変換表の文字を使えるか。`NumericOnly` では変換表を使わない
+    #[cfg(any(feature = "encode", feature = "decode"))]
+    fn allows(&self, ch: char) -> bool {
+        match self {
+            Profile::Full => true,
+            Profile::NumericOnly => false,
+            Profile::KanaOnly => CharClass::of(ch) == CharClass::Kana || "゛゜ ".contains(ch),
+            Profile::Chars(chars) => chars.contains(&ch),
+        }
+    }
+}
+
+impl Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Profile::Full => write!(f, "full"),
+            Profile::NumericOnly => write!(f, "numeric-only"),
+            Profile::KanaOnly => write!(f, "kana-only"),
+            Profile::Chars(_) => write!(f, "custom"),
+        }
+    }
+}
+
+/// 濁点・半濁点の扱い
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum DakutenMode {
+    /// "2104" を "が" のように1文字として扱う
+    Composed,
+    /// "2104" を "か゛" のように2文字として扱う
+    Raw,
+}
+
+/// `Converter::metrics_with` で使う, 入力にかかる時間などの設定
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct MetricsOptions {
+    /// 1秒間に押せる数字の数 (既定は2)
+    pub digits_per_second: f64,
+    /// メッセージの前に押す数字や記号 (フリーワード入力の `*2*2` など。既定は空)
+    pub prefix: String,
+    /// メッセージの後に押す数字や記号 (`##` など。既定は空)
+    pub suffix: String,
+}
+
+impl Default for MetricsOptions {
+    fn default() -> Self {
+        MetricsOptions {
+            digits_per_second: 2.0,
+            prefix: String::new(),
+            suffix: String::new(),
+        }
+    }
+}
+
+impl MetricsOptions {
+    /// 1秒間に押せる数字の数を設定する
+    pub fn with_digits_per_second(mut self, digits_per_second: f64) -> Self {
+        self.digits_per_second = digits_per_second;
+        self
+    }
+
+    /// メッセージの前後に押すものを設定する
+    pub fn with_framing(mut self, prefix: &str, suffix: &str) -> Self {
+        self.prefix = prefix.to_string();
+        self.suffix = suffix.to_string();
+        self
+    }
+
+    /// 設定が正しいか確認する。1秒間に押せる数字の数は正の有限の値でなければならない
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.digits_per_second.is_finite() && self.digits_per_second > 0.0 {
+            Ok(())
+        } else {
+            Err(Error::from(ErrorKind::InvalidOption("digits_per_second")))
+        }
+    }
+}
+
+/// `Converter::metrics` の結果
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MessageMetrics {
+    /// 最も短い候補
+    pub best: CandidateMetrics,
+    /// 1文字ずつ変換した候補。変換できない場合はNone
+    pub literal: Option<CandidateMetrics>,
+}
+
+/// 1つの候補を送るのにかかるもの
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CandidateMetrics {
+    /// 桁数
+    pub digits: usize,
+    /// キーを押す回数。桁数に `MetricsOptions` の前後に押すものを足したもの
+    pub keypresses: usize,
+    /// 表示される文字数。1文字ずつ変換した候補は `DakutenMode::Composed` で戻した文字数,
+    /// 予約語を使う候補は数字のまま表示されるものとして桁数を数える
+    pub display_chars: usize,
+    /// 入力にかかる時間の目安
+    pub entry_time: Duration,
+}
+
+/// `Converter::display_len` の結果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DisplayLen {
+    /// 表示される文字数
+    pub len: usize,
+    /// 末尾に入力途中の1桁が残っている
+    pub incomplete: bool,
+}
+
+/// `Converter::check_encodable` で見つかった, 変換できない理由
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", rename_all = "snake_case"))]
+pub enum EncodeIssue {
+    /// 入力が空 (空白を取り除いて何も残らない場合も含む)
+    Empty,
+    /// 2タッチ入力に変換できない文字と入力先頭からの文字位置
+    UnsupportedCharacter { ch: char, index: usize },
+    /// `{}` の書き方が正しくない
+    InvalidEscape { index: usize },
+    /// 入力が `ConvertOptions::max_input_chars` より長い
+    InputTooLong { len: usize, limit: usize },
+}
+
+/// `Converter::validate_two_touch` の結果
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CodeReport {
+    /// 変換できない原因
+    pub errors: Vec<DecodeIssue>,
+    /// 変換はできるが, 入力の誤りかもしれないところ
+    pub warnings: Vec<CodeWarning>,
+    /// `errors` が空なら `convert_from_two_touch_string` で変換できる
+    pub is_decodable: bool,
+}
+
+impl CodeReport {
+    /// エラーも警告もない
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty() && self.warnings.is_empty()
+    }
+}
+
+/// 2タッチ入力として変換はできるが, 入力の誤りかもしれないところ
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", rename_all = "snake_case"))]
+#[non_exhaustive]
+pub enum CodeWarning {
+    /// 濁点・半濁点 (04, 05) が付けられない文字の後や先頭にある
+    MisplacedDakuten { code: String, offset: usize },
+}
+
+/// `Converter::check_two_touch_string` で見つかった問題
+///
+/// 位置はすべて入力先頭からのバイト位置で, 入力への参照は持ちません。
+/// `serde` featureでは `{"kind": "non_digit", "ch": "a", "offset": 4}` の形になります。
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", rename_all = "snake_case"))]
+pub enum DecodeIssue {
+    /// 入力が空
+    Empty,
+    /// 2タッチ入力の表に存在しないコード
+    UnknownCode {
+        code: String,
+        pair_index: usize,
+        offset: usize,
+    },
+    /// 桁数が奇数で最後の1桁が余っている
+    OddTrailingDigit { offset: usize },
+    /// 数字以外の文字
+    NonDigit { ch: char, offset: usize },
+}
+
+/// エラーの種類
+///
+/// 新しい種類を追加できるように `#[non_exhaustive]` にしています。
+/// 以前は変換の失敗はすべて `ParseError` でしたが, 現在は空の入力は `EmptyInput`,
+/// 2タッチ入力への変換では `UnsupportedCharacter` など,
+/// 2タッチ入力からの変換では `UnknownCode` / `OddLength` / `NonDigit` / `NonAscii`
+/// のいずれかになり, `ParseError` は返しません。
+/// `matches!(kind, ErrorKind::ParseError)` で判定していた場合は,
+/// これらの種類も含めるか `_` で受けるように書き換えてください。
+///
+/// `serde` featureでは `{"kind": "unknown_code", "detail": {"code": "80", ...}}` のように,
+/// `kind` に種類を, `detail` に位置などの値を入れたオブジェクトになる。`Error` も同じ形になる。
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(tag = "kind", content = "detail", rename_all = "snake_case")
+)]
+pub enum ErrorKind {
+    /// 入力を解釈できない (現在は返さない。互換性のために残している)
+    ParseError,
+    /// 入力が空 (空白を取り除いて何も残らない場合も含む)
+    EmptyInput,
+    /// 2タッチ入力に変換できない文字
+    UnsupportedCharacter {
+        /// 入力された (正規化前の) 文字
+        ch: char,
+        /// 入力先頭からの文字位置 (0始まり)
+        index: usize,
+    },
+    /// `{` または `}` の対応が取れていない
+    UnbalancedBrace {
+        /// 入力先頭からの文字位置
+        index: usize,
+    },
+    /// `{}` の中に数字以外の文字がある
+    NonDigitInEscape { ch: char, index: usize },
+    /// `{}` の中に数字がない
+    EmptyEscape {
+        /// `{` の入力先頭からの文字位置
+        index: usize,
+    },
+    /// `{}` の中の数字が奇数桁
+    OddLengthEscape {
+        /// `{` の入力先頭からの文字位置
+        index: usize,
+        /// 囲まれた数字の桁数
+        len: usize,
+    },
+    /// 2タッチ入力の表に存在しないコード
+    UnknownCode {
+        /// 変換できなかった2桁のコード
+        code: String,
+        /// 何組目のコードか (0始まり)
+        pair_index: usize,
+        /// 入力先頭からのバイト位置
+        offset: usize,
+    },
+    /// 2タッチ入力の桁数が奇数
+    OddLength { len: usize },
+    /// 2タッチ入力にASCII以外の文字が含まれている
+    NonAscii { ch: char, offset: usize },
+    /// 代替文字が2タッチ入力に変換できない
+    InvalidFallback { ch: char },
+    /// 同時に指定できない設定が指定された
+    ConflictingOptions(&'static str, &'static str),
+    /// 設定の値が正しくない
+    InvalidOption(&'static str),
+    /// 出力先への書き込みに失敗した
+    WriteError,
+    /// 予約語の辞書にない言い回し
+    UnknownReservedWord { phrase: String },
+    /// 数字だけのはずの入力に数字以外の文字がある
+    NonDigit {
+        ch: char,
+        /// 入力先頭からの文字位置
+        index: usize,
+    },
+    /// 設定された `Profile` では表示できない文字
+    NotInProfile {
+        ch: char,
+        /// 入力先頭からの文字位置
+        index: usize,
+        profile: Profile,
+    },
+    /// HTMLのルビの書き方が正しくない
+    InvalidRuby {
+        /// HTMLの先頭からの文字位置
+        index: usize,
+        reason: &'static str,
+    },
+    /// 入力が設定された上限より長い
+    InputTooLong {
+        /// 入力の長さ (文字数または桁数)
+        len: usize,
+        limit: usize,
+    },
+    /// `scheme::scheme_by_name` に知らない名前を渡した
+    UnknownScheme { name: String },
+    /// `message::Template` の差し込み位置に値がない
+    MissingPlaceholder { name: String },
+    /// `message::Template` にない差し込み位置の値
+    UnexpectedPlaceholder { name: String },
+    /// `Converter::encode_to_slice` などの出力先が短い
+    BufferTooSmall {
+        /// 必要な長さ (`encode_to_slice` はバイト数, `decode_to_slice` は文字数)
+        required: usize,
+        /// 渡された出力先の長さ
+        len: usize,
+    },
+}
+
+impl Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorKind::ParseError => write!(f, "parse error"),
+            ErrorKind::EmptyInput => write!(f, "empty input"),
+            ErrorKind::UnsupportedCharacter { ch, index } => {
+                write!(f, "unsupported character {:?} at index {}", ch, index)
+            }
+            ErrorKind::UnknownReservedWord { phrase } => {
+                write!(f, "{:?} is not a reserved word", phrase)
+            }
+            ErrorKind::NonDigit { ch, index } => {
+                write!(f, "non-digit character {:?} at index {}", ch, index)
+            }
+            ErrorKind::NotInProfile { ch, index, profile } => write!(
+                f,
+                "{:?} at index {} cannot be displayed in the {} profile",
+                ch, index, profile
+            ),
+            ErrorKind::UnbalancedBrace { index } => {
+                write!(f, "unbalanced brace at index {}", index)
+            }
+            ErrorKind::EmptyEscape { index } => write!(f, "empty braces at index {}", index),
+            ErrorKind::OddLengthEscape { index, len } => {
+                write!(
+                    f,
+                    "odd number of digits ({}) in braces at index {}",
+                    len, index
+                )
+            }
+            ErrorKind::NonDigitInEscape { ch, index } => {
+                write!(
+                    f,
+                    "non-digit character {:?} in braces at index {}",
+                    ch, index
+                )
+            }
+            ErrorKind::UnknownCode {
+                code,
+                pair_index,
+                offset,
+            } => write!(
+                f,
+                "unknown code \"{}\" at pair {} (offset {})",
+                code, pair_index, offset
+            ),
+            ErrorKind::OddLength { len } => write!(f, "odd number of digits ({})", len),
+            ErrorKind::NonAscii { ch, offset } => {
+                write!(f, "non-ASCII character {:?} at offset {}", ch, offset)
+            }
+            ErrorKind::InvalidFallback { ch } => {
+                write!(f, "fallback character {:?} is not encodable", ch)
+            }
+            ErrorKind::ConflictingOptions(a, b) => {
+                write!(f, "conflicting options: {} and {}", a, b)
+            }
+            ErrorKind::InvalidOption(name) => write!(f, "invalid value for option {}", name),
+            ErrorKind::WriteError => write!(f, "failed to write output"),
+            ErrorKind::InvalidRuby { index, reason } => {
+                write!(f, "invalid ruby markup at index {}: {}", index, reason)
+            }
+            ErrorKind::InputTooLong { len, limit } => {
+                write!(f, "input too long: {} exceeds the limit of {}", len, limit)
+            }
+            ErrorKind::UnknownScheme { name } => write!(f, "unknown pager scheme {:?}", name),
+            ErrorKind::MissingPlaceholder { name } => {
+                write!(f, "no value for placeholder {:?}", name)
+            }
+            ErrorKind::UnexpectedPlaceholder { name } => {
+                write!(f, "{:?} is not a placeholder in the template", name)
+            }
+            ErrorKind::BufferTooSmall { required, len } => {
+                write!(
+                    f,
+                    "output buffer too small: {} required but {} available",
+                    required, len
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ErrorKind {}
+
+/// 隣り合う2桁の入れ替えも1回と数える編集距離 (制限付きダメラウ・レーベンシュタイン距離)
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn digit_edit_distance(a: &[u8], b: &[u8]) -> usize {
+    let mut rows = vec![(0..=b.len()).collect::<Vec<usize>>()];
+    for i in 1..=a.len() {
+        let mut row = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            row[j] = (rows[i - 1][j] + 1)
+                .min(row[j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                row[j] = row[j].min(rows[i - 2][j - 2] + 1);
+            }
+        }
+        rows.push(row);
+    }
+    rows[a.len()][b.len()]
+}
+
+/// `*` を0文字以上の任意の文字として, `text` 全体が `pattern` に合うか
+#[cfg(feature = "encode")]
+fn glob_match(pattern: &[char], text: &[char]) -> bool {
+    // 最後に見た `*` の位置と, その `*` に合わせ始めた `text` の位置
+    let mut star: Option<(usize, usize)> = None;
+    let (mut p, mut t) = (0, 0);
+    while t < text.len() {
+        if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == text[t] {
+            p += 1;
+            t += 1;
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&ch| ch == '*')
+}
+
+/// `fold_phrase` でそろえた予約語が, ひらがなと長音だけでできているか
+#[cfg(feature = "encode")]
+fn is_kana_phrase(key: &str) -> bool {
+    key.chars().all(|ch| matches!(ch, 'ぁ'..='ゖ' | 'ー'))
+}
+
+/// かなだけの表記 `phrase` を, カタカナをひらがなにして読みとして返す
+#[cfg(feature = "encode")]
+fn kana_reading(phrase: &str) -> Option<String> {
+    let reading: String = phrase
+        .chars()
+        .map(|ch| match ch {
+            'ァ'..='ヶ' => std::char::from_u32(ch as u32 - 0x60).unwrap_or(ch),
+            _ => ch,
+        })
+        .collect();
+    if reading.is_empty() || !is_kana_phrase(&reading) {
+        return None;
+    }
+    Some(reading)
+}
+
+/// 文字単位の編集距離 (レーベンシュタイン距離)
+#[cfg(feature = "encode")]
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == cb { prev } else { prev + 1 };
+            prev = row[j + 1];
+            row[j + 1] = cost.min(row[j] + 1).min(prev + 1);
+        }
+    }
+    row[b.len()]
+}
+
+use std::fmt;
+use std::fmt::Display;
+
+/// 変換のエラー
+///
+/// `std::error::Error` を実装しているので, `?` で `Box<dyn std::error::Error>` などに変換できます。
+/// 以前は `failure` クレートの `Fail` を実装していましたが, 現在は使っていません。
+/// 置き換える場合は次のように書き換えてください。
+///
+/// - `Error::new(Context::new(kind))` と `Error::from(Context<ErrorKind>)` は `Error::from(kind)` または `Error::new(kind)`
+/// - `Fail::cause` は `std::error::Error::source` (常にNone)
+/// - `Fail::backtrace` はなくなりました
+///
+/// `kind()` と, `Display` で表示する文字列は以前と同じです。
+///
+/// ## 0.1からの移行
+///
+/// 0.2.0では `failure` を使わなくなったほか, 次のように変わりました。
+///
+/// - `ErrorKind` は `#[non_exhaustive]` になり, 2タッチ入力から戻すときの `ParseError` は
+///   `UnknownCode` / `OddLength` / `NonAscii` などに分かれました。`match` には `_` の腕が必要です
+/// - `convert_to_two_touch_string` と `convert_from_two_touch_string` は `String` の代わりに
+///   `AsRef<str>` を受け取ります。`String` はそのまま渡せます
+/// - 設定の構造体も `#[non_exhaustive]` なので, `Default::default()` と `with_` で始まるメソッドで作ってください
+/// - Rust 1.87以降が必要です
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    kind: ErrorKind,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Display::fmt(&self.kind, f)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl Error {
+    pub fn new(kind: ErrorKind) -> Error {
+        Error { kind }
+    }
+
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    /// エラーの種類を取り出す
+    pub fn into_kind(self) -> ErrorKind {
+        self.kind
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for Error {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.kind().serialize(serializer)
+    }
+}
+
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Error {
+        Error { kind }
+    }
+}
+
+/// `pokebell_core` の変換のエラーを, 同じ名前の `ErrorKind` にする
+impl From<pokebell_core::Error> for ErrorKind {
+    fn from(err: pokebell_core::Error) -> ErrorKind {
+        use pokebell_core::Error as CoreError;
+        match err {
+            CoreError::EmptyInput => ErrorKind::EmptyInput,
+            CoreError::UnsupportedCharacter { ch, index } => {
+                ErrorKind::UnsupportedCharacter { ch, index }
+            }
+            CoreError::UnknownCode {
+                code,
+                pair_index,
+                offset,
+            } => ErrorKind::UnknownCode {
+                code: code.iter().map(|&b| char::from(b)).collect(),
+                pair_index,
+                offset,
+            },
+            CoreError::OddLength { len } => ErrorKind::OddLength { len },
+            CoreError::NonAscii { ch, offset } => ErrorKind::NonAscii { ch, offset },
+            CoreError::NonDigit { ch, index } => ErrorKind::NonDigit { ch, index },
+            CoreError::BufferTooSmall { required, len } => {
+                ErrorKind::BufferTooSmall { required, len }
+            }
+        }
+    }
+}
+
+impl From<pokebell_core::Error> for Error {
+    fn from(err: pokebell_core::Error) -> Error {
+        Error::from(ErrorKind::from(err))
+    }
+}
+
+#[cfg(all(test, any(feature = "encode", feature = "decode")))]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::cell::Cell;
+
+    /// テストのスレッドごとに確保した回数を数える
+    struct CountingAllocator;
+
+    thread_local! {
+        static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    /// `f` を呼ぶ間にこのスレッドで確保した回数
+    #[cfg(all(feature = "encode", feature = "decode"))]
+    fn count_allocations<T>(f: impl FnOnce() -> T) -> (T, usize) {
+        let before = ALLOCATIONS.with(Cell::get);
+        let value = f();
+        (value, ALLOCATIONS.with(Cell::get) - before)
+    }
+
+    #[cfg(all(feature = "encode", feature = "decode"))]
+    #[test]
+    fn test_clone_shares_tables() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Converter>();
+
+        let c = Converter::builder()
+            .add_char_mapping('☆', "9090")
+            .add_normalization_str('㈱', "(かふ)")
+            .add_reserved_word("なおちゃん", &["703"])
+            .build()
+            .unwrap();
+        let (mut cloned, allocations) = count_allocations(|| c.clone());
+        assert_eq!(allocations, 0);
+        assert!(Arc::ptr_eq(&c.tables, &cloned.tables));
+        assert!(Arc::ptr_eq(c.reserved(), cloned.reserved()));
+
+        // 予約語を変えると, 共有していた辞書を複製してから変える
+        cloned.add_reserved_word("すし", &["44"]).unwrap();
+        assert!(cloned.remove_reserved_word("なおちゃん"));
+        assert!(!Arc::ptr_eq(c.reserved(), cloned.reserved()));
+        assert!(Arc::ptr_eq(&c.tables, &cloned.tables));
+        assert_eq!(c.reserved_codes_for("すし"), None);
+        assert_eq!(c.reserved_codes_for("なおちゃん").unwrap(), ["703"]);
+        assert_eq!(cloned.reserved_codes_for("すし").unwrap(), ["44"]);
+        assert_eq!(c.self_check(), Ok(()));
+        assert_eq!(cloned.self_check(), Ok(()));
+        // 共有していなければ複製しない
+        let reserved = Arc::as_ptr(cloned.reserved());
+        cloned.add_reserved_word("おすし", &["0044"]).unwrap();
+        assert_eq!(Arc::as_ptr(cloned.reserved()), reserved);
+
+        let mut cleared = c.clone();
+        cleared.clear_reserved_words();
+        assert_eq!(cleared.reserved_len(), 0);
+        assert_eq!(
+            c.convert_to_two_touch_string("なおちゃん").unwrap()[0],
+            "703"
+        );
+    }
+
+    #[cfg(feature = "encode")]
+    #[test]
+    fn test_lazy_reserved_dictionary() {
+        // 辞書を作る前のConverterを, 複数のスレッドから同時に引く
+        let c = Converter::new();
+        assert!(c.reserved.get().is_none());
+        let barrier = std::sync::Barrier::new(8);
+        let results: Vec<(usize, Vec<String>)> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..8)
+                .map(|_| {
+                    scope.spawn(|| {
+                        barrier.wait();
+                        let result = c.convert_to_two_touch_string("ごくろうさん").unwrap();
+                        (Arc::as_ptr(c.reserved()) as usize, result)
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        assert!(results.windows(2).all(|pair| pair[0] == pair[1]));
+        #[cfg(feature = "builtin-dictionary")]
+        assert_eq!(results[0].1[0], "5963");
+        // `Converter::new()` で作ったConverterは同じ辞書を共有する
+        let other = Converter::new();
+        assert!(Arc::ptr_eq(c.reserved(), other.reserved()));
+        assert_eq!(c.reserved_len(), default_reserved_words().len());
+
+        // 作る前に変えても, 既定の辞書に重ねる
+        let mut edited = Converter::new();
+        edited.add_reserved_word("すし", &["44"]).unwrap();
+        assert_eq!(edited.reserved_len(), c.reserved_len() + 1);
+        assert!(!Arc::ptr_eq(c.reserved(), edited.reserved()));
+        assert_eq!(other.reserved_len(), c.reserved_len());
+    }
+
+    #[cfg(all(feature = "encode", feature = "decode"))]
+    #[test]
+    fn test_allocations() {
+        // 既定の変換表は埋め込んだ定数を引くので, 最初に作った後は何も確保しない
+        Converter::without_reserved_words();
+        let (c, allocations) = count_allocations(Converter::without_reserved_words);
+        assert_eq!(allocations, 0);
+        assert_eq!(c.convert_to_two_touch_string("あ").unwrap(), ["11"]);
+
+        // 既定の辞書は引くまで作らない
+        let (c, allocations) = count_allocations(Converter::new);
+        assert_eq!(allocations, 0);
+        assert!(c.reserved.get().is_none());
+        assert_eq!(c.convert_from_two_touch_string("11").unwrap(), "あ");
+        assert!(c.reserved.get().is_none());
+
+        // 既定の辞書の数字は埋め込んだデータを指し, 利用者が追加した数字だけを確保する
+        let mut c = Converter::new();
+        c.add_reserved_word("すし", &["44"]).unwrap();
+        for word in c.reserved().word_map.values() {
+            let builtin = !word.sources.is_empty();
+            for code in &word.codes {
+                assert_eq!(matches!(code, Cow::Borrowed(_)), builtin, "{}", word.phrase);
+            }
+        }
+        let borrowed = c
+            .reserved()
+            .prefix_index
+            .iter()
+            .filter(|(code, _)| matches!(code, Cow::Borrowed(_)))
+            .count();
+        assert_eq!(borrowed, c.reserved().prefix_index.len() - 1);
+    }
+
+    #[cfg(feature = "builtin-dictionary")]
+    #[test]
+    fn test_reserved_introspection() {
+        let c = Converter::new();
+        assert_eq!(
+            c.phrases_for_code("106"),
+            ["TEL", "まってる", "テル", "待ってる"]
+        );
+        assert_eq!(c.phrases_for_code("99999"), Vec::<&str>::new());
+        assert_eq!(c.phrases_for_code("1410"), ["あいしてる", "愛してる"]);
+        assert_eq!(
+            c.reserved_codes_for("愛してる").unwrap(),
+            ["14106", "114106", "1410"]
+        );
+        assert_eq!(c.reserved_codes_for("ｔｅｌ"), None);
+        let phrases: Vec<&str> = c.reserved_phrases().map(|(phrase, _)| phrase).collect();
+        assert_eq!(phrases.len(), c.reserved_len());
+        assert!(phrases.windows(2).all(|pair| pair[0] < pair[1]));
+        for (phrase, codes) in c.reserved_phrases() {
+            assert_eq!(c.reserved_codes_for(phrase), Some(codes));
+            for code in codes {
+                assert!(c.phrases_for_code(code).contains(&phrase));
+            }
+        }
+    }
+
+    #[cfg(all(feature = "encode", feature = "decode"))]
+    #[test]
+    fn test_inverse_table() {
+        let customized = Converter::builder()
+            .override_char('Ä', "16")
+            .add_char_mapping('☆', "9090")
+            .build()
+            .unwrap();
+        for c in [Converter::new(), customized] {
+            // 以前の, 数字を文字列のキーにした逆引きの表と比べる
+            let map: HashMap<String, char> = c
+                .tables
+                .base_map
+                .iter()
+                .map(|(ch, code)| (code.to_string(), ch))
+                .collect();
+            for i in 0..100 {
+                let pair = format!("{:02}", i);
+                assert_eq!(
+                    c.tables.inverse_table.get(&pair),
+                    map.get(&pair).copied(),
+                    "{}",
+                    pair
+                );
+                assert_eq!(c.decode_pair(&pair), map.get(&pair).copied(), "{}", pair);
+            }
+            for (code, &ch) in &map {
+                assert_eq!(c.tables.inverse_table.get(code), Some(ch), "{}", code);
+            }
+            let entries: Vec<(String, char)> = c.tables.inverse_table.iter().collect();
+            assert_eq!(entries.len(), map.len());
+            for code in ["", "1", "1a", "a1", "210", "21a4", "99999", "０１"] {
+                assert_eq!(c.tables.inverse_table.get(code), None, "{}", code);
+            }
+        }
+    }
+
+    #[cfg(all(feature = "encode", feature = "decode"))]
+    #[test]
+    fn test_self_check() {
+        assert_eq!(Converter::new().self_check(), Ok(()));
+        assert_eq!(Converter::without_reserved_words().self_check(), Ok(()));
+        assert_eq!(Converter::builder().build().unwrap().self_check(), Ok(()));
+        let mut c = Converter::new();
+        c.add_reserved_word("よろしく", &["4649", "4640"]).unwrap();
+        c.add_reserved_word("よろしく", &["4649"]).unwrap();
+        assert_eq!(c.self_check(), Ok(()));
+        assert_eq!(c.phrases_for_code("4649"), ["よろしく"]);
+
+        let c = Converter::builder()
+            .override_char('Ä', "16")
+            .add_normalization('を', 'お')
+            .build()
+            .unwrap();
+        let errors = c.self_check().unwrap_err();
+        assert_eq!(
+            errors,
+            [
+                ConsistencyError::ShadowedChar {
+                    from: 'を',
+                    to: 'お'
+                },
+                ConsistencyError::UnencodableNormalization {
+                    from: 'Ａ',
+                    to: 'A'
+                },
+            ]
+        );
+        let err = Converter::builder()
+            .override_char('Ä', "16")
+            .check_consistency(true)
+            .build()
+            .err();
+        if cfg!(debug_assertions) {
+            assert_eq!(
+                err,
+                Some(BuildError::Inconsistent {
+                    errors: vec![ConsistencyError::UnencodableNormalization {
+                        from: 'Ａ',
+                        to: 'A'
+                    }]
+                })
+            );
+            assert_eq!(
+                err.unwrap().to_string(),
+                "inconsistent converter: 'Ａ' is normalized to 'A', which is not in the table"
+            );
+        } else {
+            assert_eq!(err, None);
+        }
+
+        // 変換表の食い違いはbuilderでは作れないので, 表を直接渡す
+        let base_map: HashMap<char, Code> = [('あ', "11"), ('い', "11"), ('う', "1")]
+            .iter()
+            .map(|&(ch, code)| (ch, Cow::Borrowed(code)))
+            .collect();
+        let inverse_table = InverseTable::new([("11", 'あ'), ("12", 'い')]);
+        let c = Converter::from_tables(ConversionTables {
+            base_map: CharTable::Custom(base_map),
+            inverse_table,
+            normalization_map: NormalizationTable::Custom(HashMap::new()),
+            expansion_map: HashMap::new(),
+        });
+        assert_eq!(
+            c.self_check().unwrap_err(),
+            [
+                ConsistencyError::InvalidCode {
+                    ch: 'う',
+                    code: "1".to_string()
+                },
+                ConsistencyError::SharedCode {
+                    code: "11".to_string(),
+                    chars: vec!['あ', 'い']
+                },
+                ConsistencyError::StaleInverse {
+                    code: "12".to_string(),
+                    ch: 'い',
+                    forward: Some("11".to_string())
+                },
+                ConsistencyError::MissingInverse {
+                    ch: 'う',
+                    code: "1".to_string(),
+                    inverse: None
+                },
+            ]
+        );
+
+        let mut c = Converter::builder()
+            .add_normalization_str('㈱', "(かふ)")
+            .build()
+            .unwrap();
+        let tables = Arc::make_mut(&mut c.tables);
+        tables.expansion_map.get_mut(&'㈱').unwrap().1 = "82".to_string();
+        c.insert_reserved_word("すし".to_string(), vec![Cow::Borrowed("4a")]);
+        c.reserved_mut().prefix_index.clear();
+        let errors = c.self_check().unwrap_err();
+        assert_eq!(
+            errors[0],
+            ConsistencyError::StaleExpansion {
+                from: '㈱',
+                to: "(かふ)".to_string(),
+                code: "82".to_string()
+            }
+        );
+        assert_eq!(
+            errors[1],
+            ConsistencyError::InvalidReservedCode {
+                phrase: "すし".to_string(),
+                code: "4a".to_string()
+            }
+        );
+        assert!(errors.contains(&ConsistencyError::StaleReservedIndex {
+            key: "すし".to_string(),
+            code: "4a".to_string()
+        }));
+    }
+
+    #[cfg(feature = "builtin-dictionary")]
+    #[test]
+    fn test_explain_reserved() {
+        let c = Converter::new();
+        let derivation = vec![
+            Pun::new("5", "ご"),
+            Pun::new("9", "く"),
+            Pun::new("6", "ろう"),
+            Pun::new("3", "さん"),
+        ];
+        let infos = c.explain_reserved("5963");
+        assert_eq!(
+            infos
+                .iter()
+                .map(|info| info.phrase.as_str())
+                .collect::<Vec<_>>(),
+            ["ごくろうさん", "ご苦労さん"]
+        );
+        for info in &infos {
+            assert_eq!(info.codes, ["5963"]);
+            assert_eq!(info.reading.as_deref(), Some("ごくろうさん"));
+            assert_eq!(info.derivation, derivation);
+        }
+        assert_eq!(
+            c.explain_reserved("8181")[0].reading.as_deref(),
+            Some("ばいばい")
+        );
+        assert_eq!(c.explain_reserved("39")[0].derivation, []);
+        assert_eq!(c.explain_reserved("99999"), []);
+
+        for (phrase, codes) in c.reserved_phrases() {
+            for info in c.explain_reserved(&codes[0]) {
+                if info.phrase != phrase || info.derivation.is_empty() {
+                    continue;
+                }
+                let digits: String = info
+                    .derivation
+                    .iter()
+                    .map(|pun| pun.digits.as_str())
+                    .collect();
+                let reading: String = info
+                    .derivation
+                    .iter()
+                    .map(|pun| pun.reading.as_str())
+                    .collect();
+                assert_eq!(digits, codes[0], "{}", phrase);
+                assert_eq!(info.reading, Some(reading), "{}", phrase);
+            }
+        }
+
+        // 利用者が追加した予約語には由来がない
+        let mut c = c;
+        c.add_reserved_word("ごくろうさま", &["5963"]).unwrap();
+        let info = &c.explain_reserved("5963")[0];
+        assert_eq!(info.phrase, "ごくろうさま");
+        assert_eq!(info.reading, None);
+    }
+
+    // 追加の辞書を有効にした場合は既定の表と違うので比べない
+    #[cfg(all(
+        feature = "serde",
+        feature = "builtin-dictionary",
+        not(feature = "dict-stations"),
+        not(feature = "dict-dates")
+    ))]
+    #[test]
+    fn test_export_tables_golden() {
+        // 表を変えた場合は `UPDATE_GOLDEN=1 cargo test --features serde` で書き直し, 差分を確かめる
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/src/testdata/tables.json");
+        let json = serde_json::to_string_pretty(&Converter::new().export_tables()).unwrap() + "\n";
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            std::fs::write(path, &json).unwrap();
+        }
+        assert_eq!(json, std::fs::read_to_string(path).unwrap());
+    }
+
+    #[cfg(all(feature = "encode", feature = "decode"))]
+    #[test]
+    fn test_export_tables() {
+        let c = Converter::builder()
+            .override_char('ゐ', "02")
+            .add_normalization('ｱ', 'あ')
+            .add_reserved_word("なおちゃん", &["703"])
+            .build()
+            .unwrap();
+        let tables = c.export_tables();
+        assert_eq!(tables.characters.len(), 120);
+        assert_eq!(
+            tables.characters[2],
+            CharacterCode {
+                ch: 'ゐ',
+                code: "02".to_string()
+            }
+        );
+        assert!(tables.characters.iter().all(|entry| entry.ch != 'を'));
+        assert!(tables.normalizations.contains(&Normalization {
+            from: 'ｱ',
+            to: 'あ'
+        }));
+        assert!(tables.reserved_words.contains(&Entry {
+            phrase: "なおちゃん".to_string(),
+            codes: vec!["703".to_string()],
+        }));
+        assert_eq!(tables, c.export_tables());
+    }
+
+    #[cfg(feature = "builtin-dictionary")]
+    #[test]
+    // `String` を受け取っていたころのテストをそのまま残し, `String` も渡せることを確かめる
+    #[allow(clippy::unnecessary_to_owned)]
+    fn test_convert_to_two_touch_string_normal() {
+        let c = Converter::new();
+        let result = c
+            .convert_to_two_touch_string("こんにちは".to_string())
+            .unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0], "2503524261");
+
+        let result = c
+            .convert_to_two_touch_string("ごくろうさん".to_string())
+            .unwrap();
+        let expected = vec!["5963".to_string(), "25042395133103".to_string()];
+        assert_eq!(result, expected);
+
+        let result = c
+            .convert_to_two_touch_string("ご苦労さん".to_string())
+            .unwrap();
+        let expected = vec!["5963".to_string()];
+        assert_eq!(result, expected);
+
+        let result = c
+            .convert_to_two_touch_string("だA*（￥ぽＧ".to_string())
+            .unwrap();
+        let expected = vec!["410416868276650527".to_string()];
+        assert_eq!(result, expected);
+    }
+    #[cfg(feature = "builtin-dictionary")]
+    #[test]
+    fn test_convert_to_two_touch_string_reserved_literal_error() {
+        let c = Converter::new();
+        // 漢字を含む予約語は予約語の候補だけを返し, 変換に失敗したことを記録する
+        let result = c
+            .convert_to_two_touch_string_with_fallback("ご苦労さん", |_, _| Fallback::Fail)
+            .unwrap();
+        assert_eq!(result.candidates, vec!["5963".to_string()]);
+        assert_eq!(
+            result.literal_error,
+            Some(ErrorKind::UnsupportedCharacter {
+                ch: '苦', index: 1
+            })
+        );
+
+        let result = c
+            .convert_to_two_touch_string_with_fallback("ごくろうさん", |_, _| Fallback::Fail)
+            .unwrap();
+        assert_eq!(result.literal_error, None);
+
+        // 予約語に一致しなければ途中で失敗した場合もエラーになる
+        let err = c.convert_to_two_touch_string("ごくろうさん筋").unwrap_err();
+        assert_eq!(
+            *err.kind(),
+            ErrorKind::UnsupportedCharacter {
+                ch: '筋', index: 6
+            }
+        );
+    }
+
+    #[cfg(feature = "builtin-dictionary")]
+    #[test]
+    fn test_convert_to_two_touch() {
+        let c = Converter::new();
+        let candidates = c.convert_to_two_touch("ごくろうさん").unwrap();
+        let kinds: Vec<bool> = candidates.iter().map(Candidate::is_reserved).collect();
+        assert_eq!(kinds, vec![true, false]);
+        assert_eq!(candidates[0].phrase(), Some("ごくろうさん"));
+        assert_eq!(candidates[1].kind(), &CandidateKind::Literal);
+        let digits: Vec<&str> = candidates.iter().map(Candidate::digits).collect();
+        assert_eq!(
+            digits,
+            c.convert_to_two_touch_string("ごくろうさん").unwrap()
+        );
+
+        let candidates = c.convert_to_two_touch("こんにちは").unwrap();
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0].kind(), &CandidateKind::Literal);
+        assert_eq!(candidates[0].digits(), "2503524261");
+    }
+
+    #[cfg(all(feature = "serde", feature = "encode", feature = "decode"))]
+    #[test]
+    fn test_serde() {
+        let c = Converter::new();
+        let candidates = c.convert_to_two_touch("ごくろうさん").unwrap();
+        let json = serde_json::to_string(&candidates).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Vec<Candidate>>(&json).unwrap(),
+            candidates
+        );
+
+        let trace = c.encode_with_trace("がA").unwrap();
+        let json = serde_json::to_value(&trace).unwrap();
+        assert_eq!(
+            json[1],
+            serde_json::json!({
+                "kind": "encoded",
+                "original": "A",
+                "normalized": "A",
+                "index": 1,
+                "code": "16",
+                "span": { "start": 4, "end": 6 },
+            })
+        );
+        assert_eq!(
+            serde_json::from_value::<Vec<EncodedSegment>>(json).unwrap(),
+            trace
+        );
+
+        let trace = c.decode_with_trace("2104", DakutenMode::Composed).unwrap();
+        let json = serde_json::to_string(&trace).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Vec<DecodedSegment>>(&json).unwrap(),
+            trace
+        );
+
+        let issues = c.check_two_touch_string("8011a");
+        let json = serde_json::to_value(&issues).unwrap();
+        assert_eq!(
+            json[1],
+            serde_json::json!({ "kind": "non_digit", "ch": "a", "offset": 4 })
+        );
+        assert_eq!(
+            serde_json::from_value::<Vec<DecodeIssue>>(json).unwrap(),
+            issues
+        );
+
+        let err = c.convert_from_two_touch_string("8011").unwrap_err();
+        assert_eq!(
+            serde_json::to_value(&err).unwrap(),
+            serde_json::json!({
+                "kind": "unknown_code",
+                "detail": { "code": "80", "pair_index": 0, "offset": 0 },
+            })
+        );
+        assert_eq!(
+            serde_json::to_value(ErrorKind::ParseError).unwrap(),
+            serde_json::json!({ "kind": "parse_error" })
+        );
+    }
+
+    #[cfg(all(feature = "builtin-dictionary", feature = "decode"))]
+    #[test]
+    fn test_convert_options() {
+        let c = Converter::new();
+        let options = ConvertOptions::default()
+            .with_candidate_options(
+                CandidateOptions::default().with_order(CandidateOrder::LiteralFirst),
+            )
+            .with_fallback(Some('?'));
+        let result = c
+            .convert_to_two_touch_string_with("ごくろうさん", &options)
+            .unwrap();
+        assert_eq!(result, vec!["25042395133103", "5963"]);
+        let result = c
+            .convert_to_two_touch_string_with("焼肉", &options)
+            .unwrap();
+        assert_eq!(result, vec!["6767"]);
+        // Converterの設定は変わらない
+        assert_eq!(
+            c.convert_to_two_touch_string("ごくろうさん").unwrap()[0],
+            "5963"
+        );
+
+        let options = options.with_skip_unsupported(true);
+        assert_eq!(
+            options.validate(&c).unwrap_err().kind(),
+            &ErrorKind::ConflictingOptions("skip_unsupported", "fallback")
+        );
+        assert!(c
+            .convert_to_two_touch_string_with("ごくろうさん", &options)
+            .is_err());
+        assert!(Converter::new().with_convert_options(options).is_err());
+
+        let options = ConvertOptions::default().with_skip_unsupported(true);
+        let c = Converter::new().with_convert_options(options).unwrap();
+        assert_eq!(
+            c.convert_to_two_touch_string("焼肉やきにく").unwrap(),
+            vec!["81225223"]
+        );
+
+        let options = DecodeOptions::default()
+            .with_separator(Some('-'))
+            .with_dakuten_mode(DakutenMode::Composed);
+        assert_eq!(
+            c.convert_from_two_touch_string_with("2104-4325-13", &options)
+                .unwrap(),
+            "がつこう"
+        );
+        let options = options.with_dakuten_mode(DakutenMode::Raw);
+        assert_eq!(
+            c.convert_from_two_touch_string_with("2104-4325-13", &options)
+                .unwrap(),
+            "か゛つこう"
+        );
+        let options = options.with_separator(Some('1'));
+        assert_eq!(
+            options.validate().unwrap_err().kind(),
+            &ErrorKind::InvalidOption("separator")
+        );
+    }
+
+    #[cfg(feature = "builtin-dictionary")]
+    #[test]
+    fn test_candidate_order() {
+        let c = Converter::new();
+        let result = c.convert_to_two_touch_string("ごくろうさん").unwrap();
+        assert_eq!(
+            result,
+            vec!["5963".to_string(), "25042395133103".to_string()]
+        );
+
+        let options = CandidateOptions::default().with_order(CandidateOrder::LiteralFirst);
+        let c = Converter::new().with_candidate_options(options).unwrap();
+        let result = c.convert_to_two_touch_string("ごくろうさん").unwrap();
+        assert_eq!(
+            result,
+            vec!["25042395133103".to_string(), "5963".to_string()]
+        );
+        let result = c.convert_to_two_touch_string("あいしてる").unwrap();
+        let expected = vec!["1112324493", "14106", "114106", "1410"];
+        assert_eq!(result, expected);
+        let result = c.convert_to_two_touch_string("ご苦労さん").unwrap();
+        assert_eq!(result, vec!["5963".to_string()]);
+    }
+
+    #[cfg(feature = "builtin-dictionary")]
+    #[test]
+    fn test_rank_by_length() {
+        let options = CandidateOptions::default().with_rank_by_length(true);
+        let mut c = Converter::new().with_candidate_options(options).unwrap();
+        let result = c.convert_to_two_touch_string("あいしてる").unwrap();
+        let expected = vec!["1410", "14106", "114106", "1112324493"];
+        assert_eq!(result, expected);
+
+        // 予約語の候補が1文字ずつ変換した結果と同じなら1つだけ残す
+        c.insert_reserved_word("あい".to_string(), owned_codes(&["1112", "7", "1112"]));
+        let result = c.convert_to_two_touch_string("あい").unwrap();
+        assert_eq!(result, vec!["7", "1112"]);
+
+        let options = CandidateOptions::default()
+            .with_order(CandidateOrder::LiteralFirst)
+            .with_rank_by_length(true);
+        let c = c.with_candidate_options(options).unwrap();
+        let result = c.convert_to_two_touch_string("あい").unwrap();
+        assert_eq!(result, vec!["7", "1112"]);
+    }
+
+    #[cfg(feature = "builtin-dictionary")]
+    #[test]
+    fn test_max_candidates() {
+        let options = CandidateOptions::default()
+            .with_order(CandidateOrder::LiteralFirst)
+            .with_max_candidates(Some(1));
+        let c = Converter::new().with_candidate_options(options).unwrap();
+        let result = c.convert_to_two_touch_string("ごくろうさん").unwrap();
+        assert_eq!(result, vec!["25042395133103"]);
+
+        let options = CandidateOptions::default().with_max_candidates(Some(1));
+        let c = Converter::new().with_candidate_options(options).unwrap();
+        let result = c.convert_to_two_touch_string("ごくろうさん").unwrap();
+        assert_eq!(result, vec!["5963"]);
+        let result = c.convert_to_two_touch_string("あいしてる").unwrap();
+        assert_eq!(result, vec!["14106"]);
+
+        // 既定では1文字ずつ変換した結果は残る
+        let options = CandidateOptions::default().with_max_candidates(Some(2));
+        let c = Converter::new()
+            .with_candidate_options(options.clone())
+            .unwrap();
+        let result = c.convert_to_two_touch_string("あいしてる").unwrap();
+        assert_eq!(result, vec!["14106", "1112324493"]);
+        let result = c.convert_to_two_touch_string("愛してる").unwrap();
+        assert_eq!(result, vec!["14106", "114106"]);
+        let options = options.with_keep_literal(false);
+        let c = Converter::new().with_candidate_options(options).unwrap();
+        let result = c.convert_to_two_touch_string("あいしてる").unwrap();
+        assert_eq!(result, vec!["14106", "114106"]);
+
+        let options = CandidateOptions::default().with_max_candidates(Some(0));
+        let err = Converter::new()
+            .with_candidate_options(options)
+            .err()
+            .unwrap();
+        assert_eq!(*err.kind(), ErrorKind::InvalidOption("max_candidates"));
+    }
+
+    #[cfg(all(feature = "builtin-dictionary", feature = "decode"))]
+    #[test]
+    fn test_converter_builder() {
+        let c = Converter::builder()
+            .add_reserved_word("なおちゃん", &["703"])
+            .remove_reserved_word("ごくろうさん")
+            .add_char_mapping('ゑ', "80")
+            .add_normalization('ヱ', 'ゑ')
+            .build()
+            .unwrap();
+        assert_eq!(
+            c.convert_to_two_touch_string("なおちゃん").unwrap(),
+            vec!["703", "5115428103"]
+        );
+        assert_eq!(
+            c.convert_to_two_touch_string("ごくろうさん").unwrap(),
+            vec!["25042395133103"]
+        );
+        let encoded = c.convert_to_two_touch_string("ゑヱかし").unwrap();
+        assert_eq!(encoded, vec!["80802132"]);
+        assert_eq!(
+            c.convert_from_two_touch_string(&encoded[0]).unwrap(),
+            "ゑゑかし"
+        );
+        assert_eq!(c.decode_pair("80"), Some('ゑ'));
+
+        let err = Converter::builder()
+            .add_char_mapping('ゑ', "11")
+            .build()
+            .err()
+            .unwrap();
+        assert_eq!(
+            err,
+            BuildError::CodeCollision {
+                ch: 'ゑ',
+                code: "11".to_string(),
+                existing: 'あ',
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "code \"11\" for 'ゑ' is already used by 'あ'"
+        );
+        let err = Converter::builder()
+            .add_char_mapping('あ', "80")
+            .build()
+            .err()
+            .unwrap();
+        assert_eq!(
+            err,
+            BuildError::CharAlreadyMapped {
+                ch: 'あ',
+                code: "11".to_string(),
+            }
+        );
+        let err = Converter::builder()
+            .add_char_mapping('ゑ', "8")
+            .add_char_mapping('あ', "80")
+            .build()
+            .err()
+            .unwrap();
+        assert_eq!(
+            err,
+            BuildError::InvalidCode {
+                ch: 'ゑ',
+                code: "8".to_string(),
+            }
+        );
+
+        let c = Converter::builder()
+            .override_char('ゑ', "11")
+            .build()
+            .unwrap();
+        assert_eq!(c.convert_from_two_touch_string("11").unwrap(), "ゑ");
+        assert!(c.convert_to_two_touch_string("あい").is_err());
+
+        assert_eq!(
+            Converter::builder()
+                .remove_reserved_word("なおちゃん")
+                .build()
+                .err(),
+            Some(BuildError::UnknownReservedWord {
+                phrase: "なおちゃん".to_string()
+            })
+        );
+        assert_eq!(
+            Converter::builder()
+                .add_reserved_word("なおちゃん", &["70a"])
+                .build()
+                .err(),
+            Some(BuildError::InvalidReservedCode {
+                phrase: "なおちゃん".to_string(),
+                code: "70a".to_string(),
+            })
+        );
+        assert_eq!(
+            Converter::builder()
+                .add_normalization('ヱ', 'ゑ')
+                .build()
+                .err(),
+            Some(BuildError::InvalidNormalization {
+                from: 'ヱ',
+                to: 'ゑ'
+            })
+        );
+    }
+
+    #[cfg(all(feature = "encode", feature = "decode"))]
+    #[test]
+    fn test_custom_normalization() {
+        let c = Converter::builder()
+            .add_normalization('〜', '-')
+            .add_normalization('※', '*')
+            .add_normalization('、', ' ')
+            .add_normalization_str('㈱', "(かふ)")
+            .build()
+            .unwrap();
+        assert_eq!(
+            c.convert_to_two_touch_string("あ〜※、㈱").unwrap(),
+            c.convert_to_two_touch_string("あ-* (かふ)").unwrap()
+        );
+        // エラーの位置は置き換える前の文字で数える
+        assert_eq!(
+            c.convert_to_two_touch_string("㈱〜筋").unwrap_err().kind(),
+            &ErrorKind::UnsupportedCharacter {
+                ch: '筋', index: 2
+            }
+        );
+        let trace = c.encode_with_trace("㈱あ").unwrap();
+        assert_eq!(trace[1].index, 1);
+        assert_eq!(trace[1].span.start, trace[0].code.len());
+
+        // 組み込みの置き換えより優先する
+        let c = Converter::builder()
+            .add_normalization('ぁ', 'ま')
+            .build()
+            .unwrap();
+        assert_eq!(c.encode_char('ぁ'), Some("71"));
+
+        assert_eq!(
+            Converter::builder()
+                .add_normalization_str('㈱', "(株)")
+                .build()
+                .err(),
+            Some(BuildError::InvalidExpansion {
+                from: '㈱',
+                to: "(株)".to_string()
+            })
+        );
+        assert!(Converter::builder()
+            .add_normalization_str('㈱', "")
+            .build()
+            .is_err());
+    }
+
+    #[cfg(all(feature = "encode", feature = "decode"))]
+    #[test]
+    fn test_input_length_limits() {
+        let c = Converter::new();
+        let options = ConvertOptions::default().with_max_input_chars(Some(5));
+        assert!(c
+            .convert_to_two_touch_string_with("こんにちは", &options)
+            .is_ok());
+        assert_eq!(
+            c.convert_to_two_touch_string_with("こんにちはA", &options)
+                .unwrap_err()
+                .kind(),
+            &ErrorKind::InputTooLong { len: 6, limit: 5 }
+        );
+
+        let options = DecodeOptions::default()
+            .with_separator(Some('-'))
+            .with_max_input_digits(Some(4));
+        assert!(c
+            .convert_from_two_touch_string_with("81-22", &options)
+            .is_ok());
+        assert_eq!(
+            c.convert_from_two_touch_string_with("81-22-52", &options)
+                .unwrap_err()
+                .kind(),
+            &ErrorKind::InputTooLong { len: 6, limit: 4 }
+        );
+    }
+
+    #[cfg(feature = "encode")]
+    #[test]
+    fn test_std_error() {
+        fn encode(val: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+            Ok(Converter::new().convert_to_two_touch_string(val)?)
+        }
+        let err = encode("筋肉").unwrap_err();
+        assert!(err.source().is_none());
+        assert_eq!(err.to_string(), "unsupported character '筋' at index 0");
+        let err = err.downcast::<Error>().unwrap();
+        assert_eq!(
+            err.into_kind(),
+            ErrorKind::UnsupportedCharacter {
+                ch: '筋', index: 0
+            }
+        );
+    }
+
+    #[cfg(all(feature = "encode", feature = "decode"))]
+    #[test]
+    fn test_error_kinds() {
+        let c = Converter::new();
+        let cases = vec![
+            (
+                c.convert_to_two_touch_string("").unwrap_err(),
+                ErrorKind::EmptyInput,
+                "empty input",
+            ),
+            (
+                c.convert_from_two_touch_string("").unwrap_err(),
+                ErrorKind::EmptyInput,
+                "empty input",
+            ),
+            (
+                c.convert_from_two_touch_string("112").unwrap_err(),
+                ErrorKind::OddLength { len: 3 },
+                "odd number of digits (3)",
+            ),
+            (
+                c.convert_from_two_touch_string("11a1").unwrap_err(),
+                ErrorKind::NonDigit { ch: 'a', index: 2 },
+                "non-digit character 'a' at index 2",
+            ),
+            (
+                c.convert_from_two_touch_string("1180").unwrap_err(),
+                ErrorKind::UnknownCode {
+                    code: "80".to_string(),
+                    pair_index: 1,
+                    offset: 2,
+                },
+                "unknown code \"80\" at pair 1 (offset 2)",
+            ),
+            (
+                c.convert_to_two_touch_string("あ筋").unwrap_err(),
+                ErrorKind::UnsupportedCharacter {
+                    ch: '筋', index: 1
+                },
+                "unsupported character '筋' at index 1",
+            ),
+        ];
+        for (err, kind, text) in cases {
+            assert_eq!(err.kind(), &kind);
+            assert_eq!(err.to_string(), text);
+        }
+        assert_eq!(
+            c.decode_with_trace("1a", DakutenMode::Raw)
+                .unwrap_err()
+                .kind(),
+            &ErrorKind::NonDigit { ch: 'a', index: 1 }
+        );
+    }
+
+    #[cfg(all(feature = "builtin-dictionary", feature = "decode"))]
+    #[test]
+    fn test_encode_best() {
+        let c = Converter::new();
+        assert_eq!(c.encode_best("ごくろうさん").unwrap(), "5963");
+        assert_eq!(c.encode_best("こんにちは").unwrap(), "2503524261");
+        // 同じ長さの候補は予約語を選ぶ
+        let c = Converter::builder()
+            .add_reserved_word("あい", &["9999"])
+            .build()
+            .unwrap();
+        assert_eq!(c.encode_best("あい").unwrap(), "9999");
+        let options = ConvertOptions::default().with_candidate_options(
+            CandidateOptions::default().with_order(CandidateOrder::LiteralFirst),
+        );
+        assert_eq!(c.encode_best_with("あい", &options).unwrap(), "1112");
+        for val in ["ごくろうさん", "あいしてる", "おはよう", "やきにく"] {
+            let options = ConvertOptions::default()
+                .with_candidate_options(CandidateOptions::default().with_rank_by_length(true));
+            assert_eq!(
+                c.encode_best(val).unwrap(),
+                c.convert_to_two_touch_string_with(val, &options).unwrap()[0]
+            );
+        }
+        assert!(c.encode_best("筋肉").is_err());
+    }
+
+    #[cfg(feature = "decode")]
+    #[test]
+    fn test_suggest_code_fixes() {
+        let c = Converter::new();
+        // 最初の組: 前後がかななので, かなになる候補を先に並べる
+        let fixes = c.suggest_code_fixes("802252", 0);
+        assert!(!fixes.is_empty() && fixes.len() <= MAX_CODE_FIXES);
+        assert_eq!(fixes[0], "81");
+        let classes: Vec<CharClass> = fixes
+            .iter()
+            .map(|fix| CharClass::of(c.decode_pair(fix).unwrap()))
+            .collect();
+        assert!(classes.windows(2).all(|pair| pair[0] <= pair[1]));
+        // 最後の組
+        let fixes = c.suggest_code_fixes("812280", 2);
+        assert!(fixes.contains(&"81".to_string()));
+        assert!(fixes.len() <= MAX_CODE_FIXES);
+        // 入れ替えた候補は1桁違う候補より前
+        let fixes = c.suggest_code_fixes("2112", 0);
+        assert_eq!(fixes[0], "12");
+
+        assert!(c.suggest_code_fixes("8022", 2).is_empty());
+        assert!(c.suggest_code_fixes("8a22", 0).is_empty());
+    }
+
+    #[cfg(all(feature = "builtin-dictionary", feature = "decode"))]
+    #[test]
+    fn test_repair_against_dictionary() {
+        let c = Converter::new();
+        let exact = c.repair_against_dictionary("4649", 0);
+        assert_eq!(
+            exact,
+            vec![RepairCandidate {
+                phrase: "よろしく".to_string(),
+                code: "4649".to_string(),
+                edits: 0,
+            }]
+        );
+        let repairs = c.repair_against_dictionary("4694", 1);
+        assert_eq!(repairs[0].code, "4649");
+        assert!(repairs.iter().all(|repair| repair.edits == 1));
+        let repairs = c.repair_against_dictionary("464", 1);
+        assert!(repairs.iter().any(|repair| repair.code == "4649"));
+        assert!(c.repair_against_dictionary("9999999999", 1).is_empty());
+
+        let mut c = Converter::new();
+        c.add_reserved_word("よろしく", &["1234"]).unwrap();
+        assert!(c.repair_against_dictionary("4649", 0).is_empty());
+        assert_eq!(c.repair_against_dictionary("1234", 0).len(), 1);
+        assert!(c.remove_reserved_word("よろしく"));
+        assert!(c.repair_against_dictionary("1234", 0).is_empty());
+    }
+
+    #[cfg(all(feature = "encode", feature = "decode"))]
+    #[test]
+    fn test_digit_edit_distance() {
+        assert_eq!(digit_edit_distance(b"4649", b"4649"), 0);
+        assert_eq!(digit_edit_distance(b"4694", b"4649"), 1);
+        assert_eq!(digit_edit_distance(b"464", b"4649"), 1);
+        assert_eq!(digit_edit_distance(b"", b"49"), 2);
+    }
+
+    #[cfg(all(feature = "builtin-dictionary", feature = "decode"))]
+    #[test]
+    fn test_encode_shortest() {
+        let c = Converter::builder()
+            .add_reserved_word("あいう", &["123456"])
+            .add_reserved_word("いうえ", &["7"])
+            .build()
+            .unwrap();
+        // 先に始まる予約語を使うと8桁になる
+        let greedy = c
+            .convert_to_two_touch_string_segmented("あいうえ", &LenientOptions::default())
+            .unwrap();
+        assert_eq!(greedy.candidates[0], "12345614");
+        let result = c.encode_shortest("あいうえ").unwrap();
+        assert_eq!(result.digits, "117");
+        assert_eq!(result.literal_len, Some(8));
+        assert_eq!(
+            result.segments,
+            vec![
+                ShortestSegment {
+                    span: 0..1,
+                    code: "11".to_string(),
+                    phrase: None,
+                },
+                ShortestSegment {
+                    span: 1..4,
+                    code: "7".to_string(),
+                    phrase: Some("いうえ".to_string()),
+                },
+            ]
+        );
+
+        // 同じ桁数なら予約語を使わない
+        let c = Converter::builder()
+            .add_reserved_word("あい", &["1112"])
+            .build()
+            .unwrap();
+        let result = c.encode_shortest("あい").unwrap();
+        assert_eq!(result.segments.len(), 1);
+        assert_eq!(result.segments[0].phrase, None);
+        assert_eq!(result.literal_len, Some(4));
+
+        let c = Converter::new()
+            .with_convert_options(ConvertOptions::default().with_escapes(true))
+            .unwrap();
+        let result = c.encode_shortest("ご苦労さん{00}").unwrap();
+        assert_eq!(result.digits, "596300");
+        assert_eq!(result.segments[1].span, 5..9);
+        assert_eq!(
+            c.encode_shortest("ご苦労さん筋").unwrap_err().kind(),
+            &ErrorKind::UnsupportedCharacter {
+                ch: '筋', index: 5
+            }
+        );
+        assert_eq!(
+            c.encode_shortest("筋ごくろうさん").unwrap_err().kind(),
+            &ErrorKind::UnsupportedCharacter {
+                ch: '筋', index: 0
+            }
+        );
+    }
+
+    #[cfg(feature = "builtin-dictionary")]
+    #[test]
+    fn test_furigana() {
+        let c = Converter::new();
+        let options = ConvertOptions::default().with_furigana(true);
+        let convert = |val: &str| c.convert_to_two_touch_string_with(val, &options);
+        // 予約語にも一致する
+        assert_eq!(convert("渋谷（しぶや）").unwrap(), vec!["428", "32630481"]);
+        assert_eq!(
+            convert("渋谷(しぶや)で待ってる").unwrap_err().kind(),
+            &ErrorKind::UnsupportedCharacter {
+                ch: '待', index: 8
+            }
+        );
+        assert_eq!(
+            convert("渋谷(しぶや)でまってる").unwrap(),
+            c.convert_to_two_touch_string("しぶやでまってる").unwrap()
+        );
+        // 先頭の括弧や, かな以外を含む括弧はそのまま
+        assert_eq!(
+            convert("(あ)い").unwrap(),
+            c.convert_to_two_touch_string("(あ)い").unwrap()
+        );
+        assert_eq!(
+            convert("あ(い)").unwrap(),
+            c.convert_to_two_touch_string("あ(い)").unwrap()
+        );
+        assert_eq!(
+            convert("漢(かA)").unwrap_err().kind(),
+            &ErrorKind::UnsupportedCharacter {
+                ch: '漢', index: 0
+            }
+        );
+        // 入れ子や対応しない括弧
+        assert!(convert("漢(か(な))").is_err());
+        assert!(convert("漢(かな").is_err());
+        assert!(convert("漢(かな）").is_err());
+        assert_eq!(
+            convert("漢(か)(な)").unwrap(),
+            c.convert_to_two_touch_string("か(な)").unwrap()
+        );
+        // 有効にしない場合はエラー
+        assert!(c.convert_to_two_touch_string("渋谷(しぶや)").is_err());
+    }
+
+    #[cfg(all(feature = "encode", feature = "decode"))]
+    #[test]
+    fn test_digit_runs() {
+        let c = Converter::new();
+        let options = ConvertOptions::default().with_digit_runs(DigitRuns::Passthrough);
+        let result = c
+            .convert_to_two_touch_string_with("TELして09012345678", &options)
+            .unwrap();
+        assert_eq!(result, vec!["401037324409012345678"]);
+        // 短い数字は変換する
+        assert_eq!(
+            c.convert_to_two_touch_string_with("123あ", &options)
+                .unwrap(),
+            c.convert_to_two_touch_string("123あ").unwrap()
+        );
+        let options = options.with_min_digit_run(2);
+        assert_eq!(
+            c.convert_to_two_touch_string_with("1あ23", &options)
+                .unwrap(),
+            vec!["961123"]
+        );
+
+        let c = Converter::new()
+            .with_convert_options(ConvertOptions::default().with_digit_runs(DigitRuns::Passthrough))
+            .unwrap();
+        let trace = c.encode_with_trace("あ0901234").unwrap();
+        assert_eq!(trace[0].kind, SegmentKind::Encoded);
+        assert_eq!(trace[1].kind, SegmentKind::Raw);
+        assert_eq!((trace[1].index, trace[1].original), (1, '0'));
+        assert_eq!(trace[1].code, "0901234");
+        let rt = c.verify_roundtrip("あ0901234").unwrap();
+        assert_eq!(rt.substitutions.len(), 1);
+    }
+
+    #[cfg(feature = "decode")]
+    #[test]
+    fn test_decode_symbols() {
+        let c = Converter::new();
+        let symbols = c.decode_symbols("250459868884").unwrap();
+        let expected = vec![
+            (SymbolKind::VoicedKana('ご'), 0),
+            (SymbolKind::Letter('X'), 4),
+            (SymbolKind::Symbol('*'), 6),
+            (SymbolKind::Space, 8),
+            (SymbolKind::Symbol(')'), 10),
+        ];
+        let kinds: Vec<(SymbolKind, usize)> = symbols.iter().map(|s| (s.kind, s.offset)).collect();
+        assert_eq!(kinds, expected);
+        let options = DecodeOptions::default().with_dakuten_mode(DakutenMode::Composed);
+        assert_eq!(
+            symbols.to_string(),
+            c.convert_from_two_touch_string_with("250459868884", &options)
+                .unwrap()
+        );
+
+        let symbols = c.decode_symbols("041605969786").unwrap();
+        let kinds: Vec<SymbolKind> = symbols.into_iter().map(|s| s.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                SymbolKind::Dakuten,
+                SymbolKind::Letter('A'),
+                SymbolKind::Handakuten,
+                SymbolKind::Digit(1),
+                SymbolKind::Digit(2),
+                SymbolKind::Symbol('*'),
+            ]
+        );
+        assert!(c.decode_symbols("80").is_err());
+    }
+
+    #[cfg(all(feature = "encode", feature = "decode"))]
+    #[test]
+    fn test_decode_spoken() {
+        let c = Converter::new();
+        let mut code = String::new();
+        c.encode_into("(がっこう)#1 TELぱ゛", &mut code).unwrap();
+        assert_eq!(
+            c.decode_spoken(&code).unwrap(),
+            "かっこ、がつこう、かっことじ、シャープ、1、スペース、TELぱ、だくてん"
+        );
+        let options = SpokenOptions::default()
+            .with_separator(" ")
+            .with_dakuten_mode(DakutenMode::Raw);
+        assert_eq!(
+            c.decode_spoken_with(&code, &options).unwrap(),
+            "かっこ か だくてん つこう かっことじ シャープ 1 スペース TELは はんだくてん だくてん"
+        );
+        assert!(c.decode_spoken("80").is_err());
+        for (ch, _) in SPOKEN_NAMES {
+            assert!(c.encode_char(*ch).is_some());
+        }
+    }
+
+    #[cfg(all(feature = "encode", feature = "decode"))]
+    #[test]
+    fn test_canonicalize_code() {
+        let c = Converter::new();
+        let mut code = String::new();
+        c.encode_into(" がっこう  ", &mut code).unwrap();
+        assert_eq!(c.canonicalize_code(&code).unwrap(), code);
+        assert_eq!(c.canonicalize_code("21 04 43\t25").unwrap(), "21044325");
+        assert!(c.codes_equivalent("2104", "21 04").unwrap());
+        assert!(!c.codes_equivalent("8821", "21").unwrap());
+        assert_eq!(
+            c.canonicalize_code("2").unwrap_err().kind(),
+            &ErrorKind::OddLength { len: 1 }
+        );
+        assert_eq!(
+            c.canonicalize_code(" ").unwrap_err().kind(),
+            &ErrorKind::EmptyInput
+        );
+
+        let spaces = SpaceOptions::default()
+            .with_trim_spaces(true)
+            .with_collapse_spaces(true);
+        let c = Converter::new().with_space_options(spaces);
+        assert_eq!(c.canonicalize_code(&code).unwrap(), "2104432513");
+        assert_eq!(c.canonicalize_code("888821888843").unwrap(), "218843");
+        assert!(c.codes_equivalent("8821", "21").unwrap());
+        assert_eq!(
+            c.canonicalize_code("8888").unwrap_err().kind(),
+            &ErrorKind::EmptyInput
+        );
+
+        let grouping = GroupingOptions::default()
+            .with_group_every(NonZeroUsize::new(4))
+            .with_separator('-');
+        let c = Converter::new().with_grouping_options(grouping).unwrap();
+        assert_eq!(c.canonicalize_code("2104-4325-13").unwrap(), "2104432513");
+    }
+
+    #[cfg(all(feature = "builtin-dictionary", feature = "decode"))]
+    #[test]
+    fn test_classify_digits() {
+        let c = Converter::new();
+        let kinds = |digits: &str| -> Vec<(HypothesisKind, Confidence)> {
+            c.classify_digits(digits)
+                .hypotheses
+                .into_iter()
+                .map(|h| (h.kind, h.confidence))
+                .collect()
+        };
+        assert_eq!(
+            kinds("2503524261"),
+            vec![(
+                HypothesisKind::TwoTouch {
+                    preview: "こんにちは".to_string(),
+                    kana_percent: 100
+                },
+                Confidence::High
+            )]
+        );
+        assert_eq!(
+            kinds("090-1234-5678"),
+            vec![(HypothesisKind::PhoneNumberLike, Confidence::High)]
+        );
+        assert_eq!(
+            kinds("0312345678")[0],
+            (HypothesisKind::PhoneNumberLike, Confidence::Medium)
+        );
+        assert_eq!(
+            kinds("5963")[0].0,
+            HypothesisKind::ReservedPhrase {
+                phrases: vec!["ごくろうさん".to_string(), "ご苦労さん".to_string()]
+            }
+        );
+        assert_eq!(
+            kinds("8080"),
+            vec![(HypothesisKind::Unknown, Confidence::Low)]
+        );
+        assert_eq!(
+            kinds("12a4"),
+            vec![(HypothesisKind::Unknown, Confidence::Low)]
+        );
+        assert_eq!(kinds(""), vec![(HypothesisKind::Unknown, Confidence::Low)]);
+    }
+
+    #[cfg(all(feature = "builtin-dictionary", feature = "decode"))]
+    #[test]
+    fn test_classify_digits_ambiguous() {
+        let c = Converter::new();
+        let hypotheses = c.classify_digits("4649").hypotheses;
+        assert_eq!(hypotheses.len(), 2);
+        assert_eq!(
+            hypotheses[0].kind,
+            HypothesisKind::ReservedPhrase {
+                phrases: vec!["よろしく".to_string()]
+            }
+        );
+        assert_eq!(
+            hypotheses[1],
+            Hypothesis {
+                kind: HypothesisKind::TwoTouch {
+                    preview: "PS".to_string(),
+                    kana_percent: 0
+                },
+                confidence: Confidence::Low
+            }
+        );
+    }
+
+    #[cfg(all(feature = "encode", feature = "decode"))]
+    #[test]
+    fn test_split_message_and_callback() {
+        let c = Converter::new();
+        let splits = c
+            .split_message_and_callback("250352426109012345678")
+            .unwrap();
+        assert_eq!(
+            splits.best,
+            CallbackSplit {
+                message: "こんにちは".to_string(),
+                callback: Some("09012345678".to_string()),
+                split: 10,
+                kana_percent: 100,
+            }
+        );
+        assert!(splits.alternatives.is_empty());
+        assert!(!splits.is_ambiguous());
+
+        let splits = c.split_message_and_callback("2503524261").unwrap();
+        assert_eq!(splits.best.callback, None);
+        assert_eq!(splits.best.split, 10);
+
+        let splits = c.split_message_and_callback("09012345678").unwrap();
+        assert_eq!(splits.best.message, "");
+        assert_eq!(splits.best.split, 0);
+
+        assert!(c.split_message_and_callback("2580").is_err());
+    }
+
+    #[cfg(all(feature = "encode", feature = "decode"))]
+    #[test]
+    fn test_split_message_and_callback_ambiguous() {
+        let c = Converter::new();
+        // 後ろ10桁を電話番号とみても, 全体をメッセージとみても, どちらも全部かなになる
+        let splits = c.split_message_and_callback("11110312121212").unwrap();
+        assert!(splits.is_ambiguous());
+        let callbacks: Vec<_> = std::iter::once(&splits.best)
+            .chain(&splits.alternatives)
+            .map(|split| split.callback.as_deref())
+            .collect();
+        assert_eq!(callbacks, vec![Some("0312121212"), None]);
+    }
+
+    #[cfg(feature = "decode")]
+    #[test]
+    fn test_extract_and_decode() {
+        let c = Converter::new();
+        let text = "当時は2503524261とか送ってた。電話は09012345678, 値段は12800円で, １2104432513と書いた。";
+        let found = c.extract_and_decode(text);
+        assert_eq!(found.len(), 3);
+        assert_eq!(&text[found[0].range.clone()], "2503524261");
+        assert_eq!(found[0].result.as_deref(), Ok("こんにちは"));
+        assert_eq!(found[1].digits, "09012345678");
+        assert_eq!(
+            found[1].result.as_ref().unwrap_err().kind(),
+            &ErrorKind::OddLength { len: 11 }
+        );
+        assert_eq!(found[2].digits, "2104432513");
+        assert_eq!(found[2].result.as_deref(), Ok("がつこう"));
+        assert_eq!(&text[found[2].range.clone()], "2104432513");
+
+        let found = c.extract_and_decode_with(text, 5);
+        assert_eq!(found.len(), 4);
+        assert_eq!(found[2].digits, "12800");
+        assert!(found[2].result.is_err());
+        assert!(c.extract_and_decode("数字なし").is_empty());
+    }
+
+    #[cfg(all(feature = "builtin-dictionary", feature = "decode"))]
+    #[test]
+    fn test_decode_with_recovery() {
+        let c = Converter::new();
+        // 先頭に余計な1桁が付いたもの。最後の1桁を捨てても戻せるが, 英字が混じる
+        assert!(c.convert_from_two_touch_string("12503524261").is_err());
+        assert_eq!(
+            c.decode_with_recovery("12503524261"),
+            vec![
+                RecoveryCandidate {
+                    message: "こんにちは".to_string(),
+                    skipped: vec![0],
+                    score: 100,
+                    dictionary_hits: 0,
+                },
+                RecoveryCandidate {
+                    message: "いYそけF".to_string(),
+                    skipped: vec![10],
+                    score: 80,
+                    dictionary_hits: 0,
+                },
+            ]
+        );
+
+        let candidates = c.decode_with_recovery("2503524261");
+        assert_eq!(candidates[0].message, "こんにちは");
+        assert!(candidates[0].skipped.is_empty());
+
+        let candidates = c.decode_with_recovery("185953223");
+        assert_eq!(candidates[0].message, "よろしく");
+        assert_eq!(candidates[0].dictionary_hits, 1);
+        assert!(c.decode_with_recovery("").is_empty());
+        assert!(c.decode_with_recovery("25a3").is_empty());
+    }
+
+    #[cfg(all(feature = "builtin-dictionary", feature = "decode"))]
+    #[test]
+    fn test_complete_code() {
+        let mut c = Converter::new();
+        c.add_reserved_word("こんや", &["2503"]).unwrap();
+        c.add_reserved_word("こいよ", &["2591"]).unwrap();
+        let empty = c.complete_code("");
+        assert_eq!(empty.decoded.as_deref(), Some(""));
+        assert_eq!(empty.keys.len(), 10);
+        assert_eq!(empty.keys[1].chars[..5], ['か', 'き', 'く', 'け', 'こ']);
+        assert_eq!(empty.phrases.len(), MAX_COMPLETIONS);
+
+        let two = c.complete_code("2");
+        assert_eq!(two.decoded.as_deref(), Some(""));
+        let chars: Vec<char> = two.keys.iter().flat_map(|key| key.chars.clone()).collect();
+        assert_eq!(chars[..5], ['か', 'き', 'く', 'け', 'こ']);
+        assert!(two.keys.iter().all(|key| key.chars.len() == 1));
+
+        let twenty_five = c.complete_code("25");
+        assert_eq!(twenty_five.decoded.as_deref(), Some("こ"));
+        assert_eq!(twenty_five.keys, empty.keys);
+        assert_eq!(
+            twenty_five.phrases,
+            vec![
+                ("2503".to_string(), "こんや".to_string()),
+                ("2591".to_string(), "こいよ".to_string())
+            ]
+        );
+
+        let two_fifty = c.complete_code("250");
+        assert_eq!(two_fifty.decoded.as_deref(), Some("こ"));
+        assert!(two_fifty
+            .keys
+            .iter()
+            .any(|key| key.digit == 4 && key.chars == ['゛']));
+        assert_eq!(
+            two_fifty.phrases,
+            vec![("2503".to_string(), "こんや".to_string())]
+        );
+        assert_eq!(c.complete_code_with("25", 1).phrases.len(), 1);
+
+        assert_eq!(c.complete_code("2a"), Completions::default());
+    }
+
+    #[cfg(all(feature = "builtin-dictionary", feature = "decode"))]
+    #[test]
+    fn test_suggest_phrases() {
+        let c = Converter::new();
+        let suggestions = c.suggest_phrases("お", 100);
+        let phrases: Vec<&str> = suggestions.iter().map(|(phrase, _)| *phrase).collect();
+        assert!(phrases.len() > 3);
+        assert!(phrases.iter().all(|phrase| phrase.starts_with('お')));
+        assert!(phrases.contains(&"おくれる"));
+        assert!(!phrases.contains(&"遅れる"));
+        let lens: Vec<usize> = phrases
+            .iter()
+            .map(|phrase| phrase.chars().count())
+            .collect();
+        assert!(lens.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(c.suggest_phrases("お", 2).len(), 2);
+        assert_eq!(c.suggest_phrases("オ", 100), suggestions);
+
+        // 同じ数字のかなの予約語がなければ, 漢字の予約語も返す
+        let c = Converter::builder()
+            .add_reserved_word("会いたい", &["1101"])
+            .build()
+            .unwrap();
+        let phrases: Vec<&str> = c
+            .suggest_phrases("会い", 10)
+            .iter()
+            .map(|(phrase, _)| *phrase)
+            .collect();
+        assert_eq!(phrases, vec!["会いたい"]);
+        assert!(c.suggest_phrases("ぬ", 10).is_empty());
+        assert!(c.suggest_phrases("", 100).len() < c.reserved_len());
+    }
+
+    #[cfg(feature = "builtin-dictionary")]
+    #[test]
+    fn test_search_reserved() {
+        let c = Converter::new();
+        let phrases = |query: DictionaryQuery| -> Vec<String> {
+            c.search_reserved(&query)
+                .into_iter()
+                .map(|entry| entry.phrase)
+                .collect()
+        };
+        let found = phrases(DictionaryQuery::phrase("*してる"));
+        assert!(found.contains(&"なにしてる".to_string()));
+        assert!(found.contains(&"何してる".to_string()));
+        assert!(found.iter().all(|phrase| phrase.ends_with("してる")));
+        assert_eq!(phrases(DictionaryQuery::phrase("*シテル")), found);
+        assert_eq!(
+            phrases(DictionaryQuery::phrase("tel*")),
+            vec!["TEL", "TELほしい", "TEL欲しい"]
+        );
+        assert_eq!(phrases(DictionaryQuery::phrase("し*う")), vec!["しきゅう"]);
+
+        let entries = c.search_reserved(&DictionaryQuery::code("106"));
+        assert!(entries
+            .iter()
+            .all(|entry| entry.codes.iter().any(|code| code.contains("106"))));
+        assert!(entries.iter().any(|entry| entry.phrase == "まってる"));
+        assert!(entries.iter().any(|entry| entry.phrase == "おくれてる"));
+        let entries = c.search_reserved(&DictionaryQuery::code("*40"));
+        assert!(entries.contains(&Entry {
+            phrase: "おはよう".to_string(),
+            codes: vec!["840".to_string(), "0840".to_string()],
+        }));
+        assert!(entries
+            .iter()
+            .all(|entry| entry.codes.iter().any(|code| code.ends_with("40"))));
+
+        assert!(c.search_reserved(&DictionaryQuery::phrase("ぬ")).is_empty());
+        assert!(c
+            .search_reserved(&DictionaryQuery::code("*77777*"))
+            .is_empty());
+        assert_eq!(
+            c.search_reserved(&DictionaryQuery::code("")).len(),
+            c.reserved_len()
+        );
+    }
+
+    #[cfg(all(feature = "builtin-dictionary", feature = "decode"))]
+    #[test]
+    fn test_metrics() {
+        let c = Converter::new();
+        let metrics = c.metrics("がっこう").unwrap();
+        assert_eq!(metrics.literal.as_ref(), Some(&metrics.best));
+        assert_eq!(
+            metrics.best,
+            CandidateMetrics {
+                digits: 10,
+                keypresses: 10,
+                display_chars: 4,
+                entry_time: Duration::from_secs(5),
+            }
+        );
+
+        // 予約語だけのメッセージ
+        let metrics = c.metrics("ごくろうさん").unwrap();
+        assert_eq!((metrics.best.digits, metrics.best.display_chars), (4, 4));
+        let literal = metrics.literal.unwrap();
+        assert_eq!((literal.digits, literal.display_chars), (14, 6));
+
+        // 1文字ずつは変換できないが予約語がある
+        let metrics = c.metrics("ご苦労さん").unwrap();
+        assert_eq!(metrics.best.digits, 4);
+        assert_eq!(metrics.literal, None);
+
+        let options = MetricsOptions::default()
+            .with_digits_per_second(4.0)
+            .with_framing("*2*2", "##");
+        let metrics = c.metrics_with("がっこう", &options).unwrap();
+        assert_eq!(metrics.best.keypresses, 16);
+        assert_eq!(metrics.best.entry_time, Duration::from_secs(4));
+
+        let options = MetricsOptions::default().with_digits_per_second(0.0);
+        assert_eq!(
+            c.metrics_with("がっこう", &options).unwrap_err().kind(),
+            &ErrorKind::InvalidOption("digits_per_second")
+        );
+        assert!(c.metrics("筋肉").is_err());
+    }
+
+    #[cfg(feature = "encode")]
+    #[test]
+    fn test_edit_reserved_words() {
+        let mut c = Converter::new();
+        let len = c.reserved_len();
+        c.add_reserved_word("なおちゃん", &["703"]).unwrap();
+        assert_eq!(c.reserved_len(), len + 1);
+        assert_eq!(
+            c.convert_to_two_touch_string("なおちゃん").unwrap(),
+            vec!["703", "5115428103"]
+        );
+        assert!(c.remove_reserved_word("なおちゃん"));
+        assert!(!c.remove_reserved_word("なおちゃん"));
+        assert_eq!(
+            c.convert_to_two_touch_string("なおちゃん").unwrap(),
+            vec!["5115428103"]
+        );
+        assert_eq!(
+            c.add_reserved_word("なおちゃん", &[] as &[&str]),
+            Err(BuildError::InvalidReservedCode {
+                phrase: "なおちゃん".to_string(),
+                code: String::new(),
+            })
+        );
+
+        c.clear_reserved_words();
+        assert_eq!(c.reserved_len(), 0);
+        assert_eq!(
+            c.convert_to_two_touch_string("ごくろうさん").unwrap(),
+            vec!["25042395133103"]
+        );
+    }
+
+    #[cfg(feature = "encode")]
+    #[test]
+    fn test_without_reserved_words() {
+        let c = Converter::without_reserved_words();
+        for word in &["ごくろうさん", "あいしてる", "こんにちは", "TEL"] {
+            let result = c.convert_to_two_touch_string(word).unwrap();
+            assert_eq!(result.len(), 1);
+        }
+        let result = c.convert_to_two_touch_string("ごくろうさん").unwrap();
+        assert_eq!(result, vec!["25042395133103".to_string()]);
+        assert!(c.convert_to_two_touch_string("ご苦労さん").is_err());
+    }
+
+    #[cfg(feature = "encode")]
+    #[test]
+    #[allow(clippy::unnecessary_to_owned)]
+    fn test_convert_to_two_touch_string_error() {
+        let c = Converter::new();
+        let result = c.convert_to_two_touch_string("筋肉".to_string());
+        assert!(result.is_err());
+        let result = c.convert_to_two_touch_string("".to_string());
+        assert!(result.is_err());
+        let result = c.convert_to_two_touch_string("@".to_string());
+        assert!(result.is_err());
+    }
+    #[cfg(feature = "encode")]
+    #[test]
+    fn test_convert_to_two_touch_string_error_kind() {
+        let c = Converter::new();
+        let err = c.convert_to_two_touch_string("筋こんにちは").unwrap_err();
+        assert_eq!(
+            *err.kind(),
+            ErrorKind::UnsupportedCharacter {
+                ch: '筋', index: 0
+            }
+        );
+
+        let err = c.convert_to_two_touch_string("こんにちは筋").unwrap_err();
+        assert_eq!(
+            *err.kind(),
+            ErrorKind::UnsupportedCharacter {
+                ch: '筋', index: 5
+            }
+        );
+        assert_eq!(err.to_string(), "unsupported character '筋' at index 5");
+
+        // 全角文字は正規化前の文字と位置を返す
+        let err = c
+            .convert_to_two_touch_string("ＡＢＣあいう＠えおかきくけこ")
+            .unwrap_err();
+        assert_eq!(
+            *err.kind(),
+            ErrorKind::UnsupportedCharacter {
+                ch: '＠', index: 6
+            }
+        );
+    }
+
+    #[cfg(feature = "builtin-dictionary")]
+    #[test]
+    fn test_convert_to_two_touch_string_lossy() {
+        let c = Converter::new();
+        let result = c.convert_to_two_touch_string_lossy("やき肉🍖").unwrap();
+        assert_eq!(result.candidates, vec!["8122".to_string()]);
+        assert_eq!(result.skipped, vec![(2, '肉'), (3, '🍖')]);
+
+        let result = c.convert_to_two_touch_string_lossy("こんにちは").unwrap();
+        assert_eq!(result.candidates, vec!["2503524261".to_string()]);
+        assert!(result.skipped.is_empty());
+
+        // 予約語に一致すれば文字をすべて読み飛ばしても結果を返す
+        let result = c.convert_to_two_touch_string_lossy("渋谷").unwrap();
+        assert_eq!(result.candidates, vec!["428".to_string()]);
+        assert_eq!(result.skipped, vec![(0, '渋'), (1, '谷')]);
+        assert_eq!(
+            result.literal_error,
+            Some(ErrorKind::UnsupportedCharacter {
+                ch: '渋', index: 0
+            })
+        );
+
+        let err = c.convert_to_two_touch_string_lossy("筋肉").unwrap_err();
+        assert_eq!(
+            *err.kind(),
+            ErrorKind::UnsupportedCharacter {
+                ch: '筋', index: 0
+            }
+        );
+        assert!(c.convert_to_two_touch_string_lossy("").is_err());
+    }
+
+    #[cfg(feature = "encode")]
+    #[test]
+    fn test_convert_to_two_touch_string_lenient() {
+        let c = Converter::new();
+        let options = LenientOptions {
+            skip_unsupported: false,
+            fallback: Some('?'),
+        };
+        let result = c
+            .convert_to_two_touch_string_lenient("筋肉A", &options)
+            .unwrap();
+        assert_eq!(result.candidates, vec!["676716".to_string()]);
+        assert_eq!(result.substituted, vec![(0, '筋'), (1, '肉')]);
+        assert!(result.skipped.is_empty());
+
+        let options = LenientOptions {
+            skip_unsupported: false,
+            fallback: Some('　'),
+        };
+        let result = c
+            .convert_to_two_touch_string_lenient("あ肉", &options)
+            .unwrap();
+        assert_eq!(result.candidates, vec!["1188".to_string()]);
+
+        let options = LenientOptions::default();
+        let err = c
+            .convert_to_two_touch_string_lenient("あ肉", &options)
+            .unwrap_err();
+        assert_eq!(
+            *err.kind(),
+            ErrorKind::UnsupportedCharacter {
+                ch: '肉', index: 1
+            }
+        );
+
+        let options = LenientOptions {
+            skip_unsupported: true,
+            fallback: Some('?'),
+        };
+        let err = options.validate(&c).unwrap_err();
+        assert_eq!(
+            *err.kind(),
+            ErrorKind::ConflictingOptions("skip_unsupported", "fallback")
+        );
+        assert!(c
+            .convert_to_two_touch_string_lenient("あ", &options)
+            .is_err());
+
+        let options = LenientOptions {
+            skip_unsupported: false,
+            fallback: Some('@'),
+        };
+        let err = options.validate(&c).unwrap_err();
+        assert_eq!(*err.kind(), ErrorKind::InvalidFallback { ch: '@' });
+    }
+
+    #[cfg(feature = "encode")]
+    #[test]
+    fn test_convert_to_two_touch_string_with_fallback() {
+        let c = Converter::new();
+        let transliterate = |ch, _| match ch {
+            '駅' => Fallback::Replace("えき".to_string()),
+            '時' => Fallback::Replace("じ".to_string()),
+            '🚃' => Fallback::Skip,
+            // 変換できない文字を返しても再帰しない
+            '筋' => Fallback::Replace("筋".to_string()),
+            _ => Fallback::Fail,
+        };
+        let result = c
+            .convert_to_two_touch_string_with_fallback("🚃駅に3時", transliterate)
+            .unwrap();
+        assert_eq!(result.candidates, vec!["142252983204".to_string()]);
+        assert_eq!(result.skipped, vec![(0, '🚃')]);
+        assert_eq!(result.substituted, vec![(1, '駅'), (4, '時')]);
+
+        let err = c
+            .convert_to_two_touch_string_with_fallback("あ筋", transliterate)
+            .unwrap_err();
+        assert_eq!(
+            *err.kind(),
+            ErrorKind::UnsupportedCharacter {
+                ch: '筋', index: 1
+            }
+        );
+
+        let err = c
+            .convert_to_two_touch_string_with_fallback("あ肉", transliterate)
+            .unwrap_err();
+        assert_eq!(
+            *err.kind(),
+            ErrorKind::UnsupportedCharacter {
+                ch: '肉', index: 1
+            }
+        );
+    }
+
+    #[cfg(feature = "builtin-dictionary")]
+    #[test]
+    fn test_convert_to_two_touch_string_segmented() {
+        let c = Converter::new();
+        let options = LenientOptions::default();
+        // "しぶや" の予約語と "で" を1文字ずつ変換した数字の後に, 数字の部分をそのまま続ける
+        let result = c
+            .convert_to_two_touch_string_segmented("しぶやで4649", &options)
+            .unwrap();
+        assert_eq!(
+            result.candidates,
+            vec!["42844044649", "32630481440499069909"]
+        );
+        assert!(result.candidates[0].contains(&format!("428{}4649", "4404")));
+        // 続く数字が短ければ1文字ずつ変換する
+        let result = c
+            .convert_to_two_touch_string_segmented("しぶやで49", &options)
+            .unwrap();
+        assert_eq!(result.candidates[0], "42844049909");
+        // 予約語がなくても, 数字をそのまま出力した候補を返す
+        let result = c
+            .convert_to_two_touch_string_segmented("あ4649", &options)
+            .unwrap();
+        assert_eq!(result.candidates, vec!["114649", "1199069909"]);
+
+        let result = c
+            .convert_to_two_touch_string_segmented("しぶやでよろしく", &options)
+            .unwrap();
+        assert_eq!(
+            result.candidates,
+            vec!["42844044649", "32630481440485953223"]
+        );
+        assert_eq!(result.literal_error, None);
+
+        // 最も長い予約語を使う ("いま" ではなく "いまどこ")
+        let result = c
+            .convert_to_two_touch_string_segmented("いまどこ?", &options)
+            .unwrap();
+        assert_eq!(result.candidates[0], "1010567");
+
+        // 予約語の数字が複数あれば組み合わせを返す
+        let result = c
+            .convert_to_two_touch_string_segmented("渋谷でおはよう", &options)
+            .unwrap();
+        assert_eq!(result.candidates, vec!["4284404840", "42844040840"]);
+        assert_eq!(
+            result.literal_error,
+            Some(ErrorKind::UnsupportedCharacter {
+                ch: '渋', index: 0
+            })
+        );
+
+        // 予約語以外の部分は options に従う
+        let err = c
+            .convert_to_two_touch_string_segmented("渋谷で肉", &options)
+            .unwrap_err();
+        assert_eq!(
+            *err.kind(),
+            ErrorKind::UnsupportedCharacter {
+                ch: '肉', index: 3
+            }
+        );
+        let options = LenientOptions {
+            skip_unsupported: true,
+            fallback: None,
+        };
+        let result = c
+            .convert_to_two_touch_string_segmented("渋谷で肉", &options)
+            .unwrap();
+        assert_eq!(result.candidates, vec!["4284404"]);
+        assert_eq!(result.skipped, vec![(3, '肉')]);
+        assert_eq!(
+            result.literal_error,
+            Some(ErrorKind::UnsupportedCharacter {
+                ch: '渋', index: 0
+            })
+        );
+        let options = LenientOptions {
+            skip_unsupported: false,
+            fallback: Some('?'),
+        };
+        let result = c
+            .convert_to_two_touch_string_segmented("渋谷で肉", &options)
+            .unwrap();
+        assert_eq!(result.candidates, vec!["428440467"]);
+        assert_eq!(result.substituted, vec![(3, '肉')]);
+
+        // 予約語が含まれなければ通常の変換と同じ
+        let result = c
+            .convert_to_two_touch_string_segmented("こんにちは", &options)
+            .unwrap();
+        assert_eq!(result.candidates, vec!["2503524261"]);
+    }
+
+    #[cfg(feature = "builtin-dictionary")]
+    #[test]
+    fn test_reserved_word_lookup_normalization() {
+        let c = Converter::new();
+        for word in &["TEL", "tel", "Tel", "ＴＥＬ", "テル", "てる", "ﾃﾙ"] {
+            let result = c.convert_to_two_touch_string(word).unwrap();
+            assert_eq!(result[0], "106", "{}", word);
+        }
+        let result = c.convert_to_two_touch_string("ｻﾝｷｭｰ").unwrap();
+        assert_eq!(result, vec!["39", "999"]);
+        let result = c.convert_to_two_touch_string("thank you").unwrap();
+        assert_eq!(result[0..2], ["39", "999"]);
+        let result = c.convert_to_two_touch_string("ﾊﾞｲﾊﾞｲ").unwrap();
+        assert_eq!(result, vec!["8181"]);
+        let result = c.convert_to_two_touch_string("ばいばい").unwrap();
+        assert_eq!(result[0], "8181");
+
+        // 漢字の予約語はそのまま
+        let result = c.convert_to_two_touch_string("ご苦労さん").unwrap();
+        assert_eq!(result, vec!["5963"]);
+        assert!(c.convert_to_two_touch_string("ご苦労様").is_err());
+
+        // 表記の違いをそろえても予約語が重ならない
+        assert_eq!(c.reserved().word_map.len(), default_reserved_words().len());
+    }
+
+    #[cfg(all(feature = "builtin-dictionary", feature = "decode"))]
+    #[test]
+    fn test_builtin_reserved_words() {
+        let records: Vec<Vec<&str>> = RESERVED_WORDS
+            .lines()
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| line.split('\t').collect())
+            .collect();
+        assert_eq!(records.len(), 44);
+        for record in &records {
+            assert!(record.len() >= 2, "{:?}", record);
+            assert!(record[0]
+                .split(',')
+                .all(|code| !code.is_empty() && code.bytes().all(|b| b.is_ascii_digit())));
+        }
+        let map: HashMap<String, Vec<Code>> = parse_reserved_words(RESERVED_WORDS)
+            .into_iter()
+            .map(|(phrase, codes, _)| (phrase, codes))
+            .collect();
+        assert_eq!(map.len(), 78);
+
+        let c = Converter::new();
+        let first = |phrase: &str| c.convert_to_two_touch_string(phrase).unwrap()[0].clone();
+        assert_eq!(first("会いたいよ"), first("あいたいよ"));
+        assert_eq!(first("あいたいよ"), "11014");
+        assert_eq!(first("遅れる"), "0906");
+        assert_eq!(first("さみしい"), "3341");
+        assert_eq!(first("早く行く"), "88919");
+        assert_eq!(first("でーとしよ"), "101044");
+        assert_eq!(first("愛してる"), "14106");
+        assert_eq!(map["おはよう"], vec!["840", "0840"]);
+    }
+
+    #[cfg(all(feature = "encode", feature = "decode"))]
+    #[test]
+    fn test_dictionary_packs() {
+        use crate::dictionary::{Dictionary, DictionaryEntry, DictionaryLint, LintKind};
+
+        // 追加の辞書も利用者の辞書と同じように確かめ, 既定の辞書やほかの追加の辞書と重ならないようにする
+        let dictionary = |name: &str, data: &'static str| {
+            let entries = parse_reserved_words(data)
+                .iter()
+                .map(|(phrase, codes, _)| DictionaryEntry::new(phrase, codes))
+                .collect();
+            Dictionary::new(name, entries)
+        };
+        let mut dictionaries = Vec::new();
+        #[cfg(feature = "builtin-dictionary")]
+        dictionaries.push(dictionary(BUILTIN_DICTIONARY, RESERVED_WORDS));
+        for &(name, data) in DICTIONARY_PACKS {
+            assert!(!parse_reserved_words(data).is_empty(), "{}", name);
+            dictionaries.push(dictionary(name, data));
+        }
+        let report = DictionaryLint::check(&dictionaries);
+        assert_eq!(report.errors, []);
+        assert!(!report.warnings.iter().any(|lint| matches!(
+            lint.kind,
+            LintKind::Duplicate { .. } | LintKind::ConflictingCodes { .. }
+        )));
+        assert!(Converter::builder().build().is_ok());
+    }
+
+    #[cfg(feature = "dict-stations")]
+    #[test]
+    fn test_dictionary_pack_stations() {
+        let c = Converter::new();
+        assert_eq!(c.convert_to_two_touch_string("八王子").unwrap(), ["802"]);
+        assert_eq!(c.phrases_for_code("0038"), ["おおみや", "大宮"]);
+        let entry = c
+            .effective_dictionary()
+            .into_iter()
+            .find(|entry| entry.phrase == "小岩")
+            .unwrap();
+        assert_eq!(entry.sources, ["stations"]);
+    }
+
+    #[cfg(all(feature = "dict-dates", feature = "decode"))]
+    #[test]
+    fn test_dictionary_pack_dates() {
+        let c = Converter::new();
+        assert_eq!(
+            c.convert_to_two_touch_string("会いに行く").unwrap(),
+            ["1219"]
+        );
+        assert_eq!(c.reserved_codes_for("ごごごじ").unwrap(), ["5552"]);
+        // 利用者の辞書が優先する
+        let c = Converter::builder()
+            .add_reserved_word("一緒に", &["0000"])
+            .build()
+            .unwrap();
+        assert_eq!(c.convert_to_two_touch_string("一緒に").unwrap(), ["0000"]);
+    }
+
+    #[cfg(feature = "builtin-dictionary")]
+    #[test]
+    fn test_suggest_reserved() {
+        let c = Converter::new();
+        let gokurou = vec![Cow::Borrowed("5963")];
+        let result = c.suggest_reserved("ごくろうさま", 1);
+        assert_eq!(result, vec![("ごくろうさん", gokurou.as_slice())]);
+        let result = c.suggest_reserved("ごくろさま", 1);
+        assert!(result.is_empty());
+        let result = c.suggest_reserved("ごくろさま", 2);
+        assert_eq!(result, vec![("ごくろうさん", gokurou.as_slice())]);
+
+        let sabishii = vec![Cow::Borrowed("33414")];
+        let samishii = vec![Cow::Borrowed("3341")];
+        let result = c.suggest_reserved("サミシイヨ", 1);
+        assert_eq!(
+            result,
+            vec![
+                ("さびしいよ", sabishii.as_slice()),
+                ("さみしい", samishii.as_slice())
+            ]
+        );
+
+        assert_eq!(edit_distance(&['あ', 'い'], &['い', 'あ']), 2);
+        assert_eq!(edit_distance(&[], &['あ']), 1);
+    }
+
+    #[cfg(feature = "builtin-dictionary")]
+    #[test]
+    fn test_convert_to_two_touch_string_raw_digits() {
+        // 既定では括弧も1文字ずつ変換する
+        let c = Converter::new();
+        let err = c.convert_to_two_touch_string("あした{0840}ね").unwrap_err();
+        assert_eq!(
+            *err.kind(),
+            ErrorKind::UnsupportedCharacter { ch: '{', index: 3 }
+        );
+        assert_eq!(c.unsupported_chars("あ{08}"), vec![(1, '{'), (4, '}')]);
+
+        let c = Converter::new()
+            .with_convert_options(ConvertOptions::default().with_escapes(true))
+            .unwrap();
+        let result = c.convert_to_two_touch_string("あした{0840}ね").unwrap();
+        assert_eq!(result, vec!["113241084054"]);
+        let result = c.convert_to_two_touch_string("{0840}").unwrap();
+        assert_eq!(result, vec!["0840"]);
+        let result = c
+            .convert_to_two_touch_string_segmented("しぶや{0312345678}", &LenientOptions::default())
+            .unwrap();
+        assert_eq!(result.candidates[0], "4280312345678");
+
+        let err = c.convert_to_two_touch_string("あ{08").unwrap_err();
+        assert_eq!(*err.kind(), ErrorKind::UnbalancedBrace { index: 1 });
+        let err = c.convert_to_two_touch_string("あ08}").unwrap_err();
+        assert_eq!(*err.kind(), ErrorKind::UnbalancedBrace { index: 3 });
+        let err = c.convert_to_two_touch_string("あ{0a}").unwrap_err();
+        assert_eq!(
+            *err.kind(),
+            ErrorKind::NonDigitInEscape { ch: 'a', index: 3 }
+        );
+        // 2桁ずつ戻せない数字は囲めない
+        let err = c.convert_to_two_touch_string("あ{08}{123}").unwrap_err();
+        assert_eq!(*err.kind(), ErrorKind::OddLengthEscape { index: 5, len: 3 });
+        assert_eq!(
+            err.to_string(),
+            "odd number of digits (3) in braces at index 5"
+        );
+
+        // 何も囲まない括弧は予約語を隠さないようにエラーにする
+        let err = c.convert_to_two_touch_string("ごくろうさん{}").unwrap_err();
+        assert_eq!(*err.kind(), ErrorKind::EmptyEscape { index: 6 });
+        assert_eq!(err.to_string(), "empty braces at index 6");
+        assert_eq!(
+            c.check_encodable("ごくろうさん{}"),
+            Err(EncodeIssue::InvalidEscape { index: 6 })
+        );
+
+        // 括弧そのものは2つ重ねて書くが, 表にないので既定では変換できない
+        let err = c.convert_to_two_touch_string("あ{{").unwrap_err();
+        assert_eq!(
+            *err.kind(),
+            ErrorKind::UnsupportedCharacter { ch: '{', index: 1 }
+        );
+        let err = c.convert_to_two_touch_string("あ}}").unwrap_err();
+        assert_eq!(
+            *err.kind(),
+            ErrorKind::UnsupportedCharacter { ch: '}', index: 1 }
+        );
+        let result = c
+            .convert_to_two_touch_string_with_fallback("{{あ}}", |ch, _| match ch {
+                '{' => Fallback::Replace("(".to_string()),
+                '}' => Fallback::Replace(")".to_string()),
+                _ => Fallback::Fail,
+            })
+            .unwrap();
+        assert_eq!(result.candidates, vec!["821184"]);
+        assert_eq!(c.unsupported_chars("あ{{{08}"), vec![(1, '{')]);
+    }
+
+    #[cfg(feature = "builtin-dictionary")]
+    #[test]
+    fn test_space_options() {
+        let val = "　こんにちは  ていく ";
+        let c = Converter::new();
+        let result = c.convert_to_two_touch_string(val).unwrap();
+        assert_eq!(result, vec!["882503524261888844122388"]);
+
+        let options = SpaceOptions::default()
+            .with_trim_spaces(true)
+            .with_collapse_spaces(true);
+        let c = Converter::new().with_space_options(options);
+        let result = c.convert_to_two_touch_string(val).unwrap();
+        assert_eq!(result, vec!["250352426188441223"]);
+        let result = c.convert_to_two_touch_string(" thank  you ").unwrap();
+        assert_eq!(result[0..2], ["39", "999"]);
+
+        let options = SpaceOptions::default().with_collapse_spaces(true);
+        let c = Converter::new().with_space_options(options);
+        let result = c.convert_to_two_touch_string(val).unwrap();
+        assert_eq!(result, vec!["8825035242618844122388"]);
+
+        // 空白しかなければ空の入力と同じくエラーになる
+        let options = SpaceOptions::default().with_trim_spaces(true);
+        let c = Converter::new().with_space_options(options);
+        let err = c.convert_to_two_touch_string(" 　 ").unwrap_err();
+        assert_eq!(*err.kind(), ErrorKind::EmptyInput);
+    }
+
+    #[cfg(all(feature = "builtin-dictionary", feature = "decode"))]
+    #[test]
+    fn test_kana_only() {
+        let c = Converter::builder()
+            .profile(Profile::KanaOnly)
+            .build()
+            .unwrap();
+        let err = c.convert_to_two_touch_string("RUST").unwrap_err();
+        assert_eq!(
+            err.kind(),
+            &ErrorKind::NotInProfile {
+                ch: 'R',
+                index: 0,
+                profile: Profile::KanaOnly,
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "'R' at index 0 cannot be displayed in the kana-only profile"
+        );
+        let err = c.convert_from_two_touch_string("815904").unwrap_err();
+        assert_eq!(
+            err.kind(),
+            &ErrorKind::NotInProfile {
+                ch: 'X',
+                index: 2,
+                profile: Profile::KanaOnly,
+            }
+        );
+        assert_eq!(c.decode_pair("59"), None);
+        assert_eq!(c.convert_from_two_touch_string("2104").unwrap(), "か゛");
+
+        // 既定の辞書はかなに戻せる数字だけが残る
+        let entries = c.search_reserved(&DictionaryQuery::phrase("*"));
+        assert!(!entries.is_empty());
+        assert!(entries.iter().all(|entry| entry
+            .codes
+            .iter()
+            .all(|code| c.convert_from_two_touch_string(code).is_ok())));
+        assert!(c
+            .search_reserved(&DictionaryQuery::phrase("よろしく"))
+            .is_empty());
+
+        let err = Converter::builder()
+            .profile(Profile::KanaOnly)
+            .add_reserved_word("エックス", &["59"])
+            .build()
+            .err();
+        assert_eq!(
+            err,
+            Some(BuildError::ReservedNotInProfile {
+                phrase: "エックス".to_string(),
+                code: "59".to_string(),
+            })
+        );
+        let c = Converter::builder()
+            .profile(Profile::KanaOnly)
+            .add_reserved_word("やきにく", &["8122"])
+            .build()
+            .unwrap();
+        assert_eq!(
+            c.convert_to_two_touch_string("やきにく").unwrap(),
+            vec!["8122", "81225223"]
+        );
+    }
+
+    #[cfg(all(feature = "encode", feature = "decode"))]
+    #[test]
+    fn test_profiles_keep_kana() {
+        let profiles = [
+            Profile::Full,
+            Profile::KanaOnly,
+            Profile::chars("やきにく".chars()),
+        ];
+        for profile in profiles {
+            let c = Converter::new().with_profile(profile.clone());
+            let code = c.convert_to_two_touch_string("やきにく").unwrap();
+            assert_eq!(code, vec!["81225223"], "{}", profile);
+            assert_eq!(
+                c.convert_from_two_touch_string("81225223").unwrap(),
+                "やきにく"
+            );
+        }
+
+        let c = Converter::new().with_profile(Profile::chars("が".chars()));
+        assert_eq!(
+            c.decode_with_trace("2104", DakutenMode::Composed).unwrap()[0].ch,
+            'が'
+        );
+        let err = c.convert_from_two_touch_string("2104").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "'か' at index 0 cannot be displayed in the custom profile"
+        );
+    }
+
+    #[cfg(all(feature = "builtin-dictionary", feature = "decode"))]
+    #[test]
+    fn test_numeric_only() {
+        let c = Converter::new().with_profile(Profile::NumericOnly);
+        let result = c.convert_to_two_touch_string("03-1234-5678").unwrap();
+        assert_eq!(result, vec!["03-1234-5678"]);
+        let result = c.convert_to_two_touch_string("0840").unwrap();
+        assert_eq!(result, vec!["0840"]);
+        let result = c.convert_to_two_touch_string("よろしく").unwrap();
+        assert_eq!(result, vec!["4649"]);
+        let result = c.convert_to_two_touch_string_lossy("よろしく").unwrap();
+        assert_eq!(
+            result.literal_error,
+            Some(ErrorKind::NotInProfile {
+                ch: 'よ',
+                index: 0,
+                profile: Profile::NumericOnly,
+            })
+        );
+        let err = c.convert_to_two_touch_string("あした10じ").unwrap_err();
+        assert_eq!(
+            err.kind(),
+            &ErrorKind::NotInProfile {
+                ch: 'あ',
+                index: 0,
+                profile: Profile::NumericOnly,
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "'あ' at index 0 cannot be displayed in the numeric-only profile"
+        );
+
+        assert_eq!(c.convert_from_two_touch_string("0840").unwrap(), "0840");
+        assert_eq!(
+            c.check_two_touch_string("08a0"),
+            vec![DecodeIssue::NonDigit { ch: 'a', offset: 2 }]
+        );
+        assert_eq!(
+            c.display_len("4649", DakutenMode::Composed).unwrap(),
+            DisplayLen {
+                len: 4,
+                incomplete: false,
+            }
+        );
+    }
+
+    #[cfg(feature = "encode")]
+    #[test]
+    fn test_convert_lines() {
+        let c = Converter::new();
+        let lines = c.convert_lines("こんにちは\r\n 筋肉 \r\n\r\nやきにく\r\n", false);
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].line, 1);
+        assert_eq!(lines[0].result.as_ref().unwrap(), &vec!["2503524261"]);
+        assert_eq!(lines[1].line, 2);
+        assert_eq!(lines[1].text, "筋肉");
+        assert!(lines[1].result.is_err());
+        assert_eq!(lines[2].line, 4);
+        assert_eq!(lines[2].result.as_ref().unwrap(), &vec!["81225223"]);
+
+        let lines = c.convert_lines("おはよう。やきにく。\nこんにちは", true);
+        let numbers: Vec<usize> = lines.iter().map(|l| l.line).collect();
+        assert_eq!(numbers, vec![1, 1, 2]);
+        assert_eq!(lines[1].text, "やきにく");
+        assert_eq!(c.convert_lines("やきにく。", false)[0].text, "やきにく。");
+    }
+
+    #[cfg(all(feature = "encode", feature = "decode"))]
+    #[test]
+    fn test_convert_multipart() {
+        let c = Converter::without_reserved_words();
+        let val = "あいうえおかきくけこさしすせそ";
+        // 6文字ずつ分けると3つになるが, 番号の3文字を足すと超えるので3文字ずつの5つに分ける
+        assert_eq!(c.convert_split(val, 6).unwrap().len(), 3);
+        let parts = c.convert_multipart(val, 6).unwrap();
+        let decoded: Vec<String> = parts
+            .iter()
+            .map(|part| c.convert_from_two_touch_string(part).unwrap())
+            .collect();
+        assert_eq!(
+            decoded,
+            vec![
+                "1/5あいう",
+                "2/5えおか",
+                "3/5きくけ",
+                "4/5こさし",
+                "5/5すせそ"
+            ]
+        );
+
+        // 10以上に分けると番号が長くなるので, もう一度分け直す
+        let val = "あいうえおかきくけこ".repeat(3);
+        let parts = c.convert_multipart(&val, 6).unwrap();
+        assert_eq!(parts.len(), 30);
+        for part in &parts {
+            let len = c.display_len(part, DakutenMode::Raw).unwrap();
+            assert!(len.len <= 6);
+        }
+        assert_eq!(
+            c.convert_from_two_touch_string(&parts[29]).unwrap(),
+            "30/30こ"
+        );
+
+        let options = MultipartOptions::default()
+            .with_position(LabelPosition::Suffix)
+            .with_separator('-');
+        let parts = c.convert_multipart_with("あいうえお", 4, &options).unwrap();
+        assert_eq!(c.convert_from_two_touch_string(&parts[0]).unwrap(), "あ1-5");
+
+        // 分けずに収まれば番号を付けない
+        assert_eq!(c.convert_multipart("あい", 6).unwrap(), vec!["1112"]);
+        let err = c.convert_multipart("あいうえお", 3).unwrap_err();
+        assert_eq!(*err.kind(), ErrorKind::InvalidOption("max_display_chars"));
+    }
+
+    #[cfg(feature = "encode")]
+    #[test]
+    fn test_convert_multipart_tokens() {
+        // `{}` で囲まれた数字や変換できない文字の置き換えは `convert_split` と同じように分けて変換する
+        let options = ConvertOptions::default()
+            .with_escapes(true)
+            .with_fallback(Some('?'));
+        let c = Converter::without_reserved_words()
+            .with_convert_options(options)
+            .unwrap();
+        let val = "あした{0840}ね肉です";
+        let parts = c.convert_multipart(val, 6).unwrap();
+        // 番号の3文字を除いた残りの3文字ずつに分ける
+        let split = c.convert_split(val, 3).unwrap();
+        assert_eq!(parts.len(), split.len());
+        for (i, (part, chunk)) in parts.iter().zip(&split).enumerate() {
+            let label = c
+                .encode_best(&format!("{}/{}", i + 1, parts.len()))
+                .unwrap();
+            assert_eq!(part.strip_prefix(&label), Some(chunk[0].as_str()));
+        }
+        assert_eq!(
+            parts,
+            vec!["966099113241", "976099084054", "986099674404", "99609933"]
+        );
+
+        // 分けずに収まる場合は予約語も使う
+        #[cfg(feature = "builtin-dictionary")]
+        assert_eq!(
+            Converter::new()
+                .convert_multipart("ごくろうさん", 12)
+                .unwrap(),
+            vec!["5963"]
+        );
+    }
+
+    #[cfg(all(feature = "encode", feature = "decode"))]
+    #[test]
+    fn test_convert_split() {
+        let c = Converter::without_reserved_words();
+        let val = "あしたは しちじに いけぶくろの えきに まってるからね!!";
+        assert_eq!(val.chars().count(), 30);
+        let chunks = c.convert_split(val, 12).unwrap();
+        assert_eq!(chunks.len(), 3);
+        let decoded: Vec<String> = chunks
+            .iter()
+            .map(|chunk| c.convert_from_two_touch_string(&chunk[0]).unwrap())
+            .collect();
+        let expected = vec![
+            "あしたは しちし゛に ",
+            "いけふ゛くろの えきに ",
+            "まつてるからね!!",
+        ];
+        assert_eq!(decoded, expected);
+        let literal = c.convert_to_two_touch_string(val).unwrap();
+        let whole = c.convert_from_two_touch_string(&literal[0]).unwrap();
+        assert_eq!(decoded.concat(), whole);
+        for chunk in &chunks {
+            let len = c.display_len(&chunk[0], DakutenMode::Raw).unwrap();
+            assert!(len.len <= 12);
+        }
+
+        // 区切りがなければ文字数で切る
+        let chunks = c.convert_split("あいうえおかきくけこ", 4).unwrap();
+        let lens: Vec<usize> = chunks.iter().map(|chunk| chunk[0].len() / 2).collect();
+        assert_eq!(lens, vec![4, 4, 2]);
+        // 濁音は途中で切らない
+        let chunks = c.convert_split("ががが", 3).unwrap();
+        assert_eq!(chunks, vec![vec!["2104"], vec!["2104"], vec!["2104"]]);
+
+        let err = c.convert_split("が", 1).err().unwrap();
+        assert_eq!(*err.kind(), ErrorKind::InvalidOption("max_display_chars"));
+        let err = c.convert_split("あい肉", 2).err().unwrap();
+        assert_eq!(
+            *err.kind(),
+            ErrorKind::UnsupportedCharacter {
+                ch: '肉', index: 2
+            }
+        );
+    }
+
+    #[cfg(feature = "encode")]
+    #[test]
+    fn test_convert_split_tokens() {
+        // `{}` で囲まれた数字は変換した数字で数え, 途中で分けない
+        let c = Converter::without_reserved_words()
+            .with_convert_options(ConvertOptions::default().with_escapes(true))
+            .unwrap();
+        assert_eq!(
+            c.convert_split("あいう{0840}えお", 4).unwrap(),
+            vec![vec!["111213"], vec!["08401415"]]
+        );
+        let err = c.convert_split("あ{084008}", 2).unwrap_err();
+        assert_eq!(*err.kind(), ErrorKind::InvalidOption("max_display_chars"));
+
+        // 取り除いた空白は数えない
+        let spaces = SpaceOptions::default()
+            .with_trim_spaces(true)
+            .with_collapse_spaces(true);
+        let c = Converter::without_reserved_words().with_space_options(spaces);
+        assert_eq!(
+            c.convert_split(" あ   い ", 3).unwrap(),
+            vec![vec!["118812"]]
+        );
+
+        // 数字の区切りは数えず, 各部分を区切る
+        let grouping = GroupingOptions::default().with_group_every(NonZeroUsize::new(2));
+        let c = Converter::without_reserved_words()
+            .with_grouping_options(grouping)
+            .unwrap();
+        assert_eq!(
+            c.convert_split("あいうえ", 2).unwrap(),
+            vec![vec!["11 12"], vec!["13 14"]]
+        );
+
+        // 変換できない文字は `convert_to_two_touch_string` と同じように置き換えてから数える
+        let c = Converter::without_reserved_words()
+            .with_convert_options(ConvertOptions::default().with_fallback(Some('?')))
+            .unwrap();
+        assert_eq!(
+            c.convert_split("あ肉い", 2).unwrap(),
+            vec![vec!["1167"], vec!["12"]]
+        );
+    }
+
+    #[cfg(all(feature = "builtin-dictionary", feature = "decode"))]
+    #[test]
+    fn test_grouping_options() {
+        let options = GroupingOptions::default().with_group_every(NonZeroUsize::new(4));
+        let c = Converter::new().with_grouping_options(options).unwrap();
+        let result = c.convert_to_two_touch_string("こんにちは").unwrap();
+        assert_eq!(result, vec!["2503 5242 61"]);
+        let result = c.convert_to_two_touch_string("ごくろうさん").unwrap();
+        assert_eq!(result, vec!["5963", "2504 2395 1331 03"]);
+        let decoded = c.convert_from_two_touch_string(&result[1]).unwrap();
+        assert_eq!(decoded, "こ゛くろうさん");
+
+        // 奇数桁で区切ると2桁のコードの途中でも区切るが, 元に戻せる
+        let options = GroupingOptions::default()
+            .with_group_every(NonZeroUsize::new(3))
+            .with_separator('-');
+        let c = Converter::new().with_grouping_options(options).unwrap();
+        let result = c.convert_to_two_touch_string("こんにちは").unwrap();
+        assert_eq!(result, vec!["250-352-426-1"]);
+        let decoded = c.convert_from_two_touch_string(&result[0]).unwrap();
+        assert_eq!(decoded, "こんにちは");
+
+        let options = GroupingOptions::default().with_separator('0');
+        let err = Converter::new()
+            .with_grouping_options(options)
+            .err()
+            .unwrap();
+        assert_eq!(*err.kind(), ErrorKind::InvalidOption("separator"));
+    }
+
+    #[cfg(feature = "encode")]
+    #[test]
+    fn test_encode_iter() {
+        let c = Converter::new();
+        let codes: Vec<&str> = c
+            .encode_iter("ごくろうさん")
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(codes.concat(), "25042395133103");
+
+        let mut iter = c.encode_iter("あ肉い");
+        assert_eq!(iter.next().unwrap().unwrap(), "11");
+        let err = iter.next().unwrap().unwrap_err();
+        assert_eq!(
+            *err.kind(),
+            ErrorKind::UnsupportedCharacter {
+                ch: '肉', index: 1
+            }
+        );
+        assert!(iter.next().is_none());
+        assert_eq!(c.encode_iter("").count(), 0);
+    }
+
+    #[cfg(all(feature = "encode", feature = "decode"))]
+    #[test]
+    fn test_fixed_buffers() {
+        let c = Converter::without_reserved_words();
+        // ちょうどの長さ
+        let mut digits = [0; 14];
+        let (written, allocations) =
+            count_allocations(|| c.encode_to_slice("ごくろうさん", &mut digits).unwrap());
+        assert_eq!((written, allocations), (14, 0));
+        assert_eq!(&digits, b"25042395133103");
+        let mut chars = ['\0'; 7];
+        let (written, allocations) =
+            count_allocations(|| c.decode_to_slice("25042395133103", &mut chars).unwrap());
+        assert_eq!((written, allocations), (7, 0));
+        assert_eq!(chars.iter().collect::<String>(), "こ゛くろうさん");
+
+        // 短い場合は必要な長さを返す
+        let err = c
+            .encode_to_slice("ごくろうさん", &mut digits[..13])
+            .unwrap_err();
+        assert_eq!(
+            *err.kind(),
+            ErrorKind::BufferTooSmall {
+                required: 14,
+                len: 13
+            }
+        );
+        let err = c.decode_to_slice("25042395133103", &mut []).unwrap_err();
+        assert_eq!(
+            *err.kind(),
+            ErrorKind::BufferTooSmall {
+                required: 7,
+                len: 0
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "output buffer too small: 7 required but 0 available"
+        );
+        // 変換できない場合は, 長さよりそのエラーを返す
+        let err = c.encode_to_slice("やき肉", &mut [0; 2]).unwrap_err();
+        assert_eq!(
+            *err.kind(),
+            ErrorKind::UnsupportedCharacter {
+                ch: '肉', index: 2
+            }
+        );
+        let err = c.decode_to_slice("811a", &mut ['\0'; 1]).unwrap_err();
+        assert_eq!(*err.kind(), ErrorKind::NonDigit { ch: 'a', index: 3 });
+
+        // 確保するAPIと同じ結果になる
+        let mut digits = [0; 64];
+        let mut chars = ['\0'; 32];
+        for text in ["こんにちは 12:00", "ABC", "", "がっこう", "。", "ﾔｷﾆｸ"] {
+            let mut expected = String::new();
+            match c.encode_into(text, &mut expected) {
+                Ok(_) => {
+                    let written = c.encode_to_slice(text, &mut digits).unwrap();
+                    assert_eq!(&digits[..written], expected.as_bytes(), "{}", text);
+                }
+                Err(err) => assert_eq!(c.encode_to_slice(text, &mut digits), Err(err)),
+            }
         }
-        let mut ret = Vec::new();
-        if let Some(reserved) = self.reserved_word_map.get(&val) {
-            ret.append(&mut reserved.clone());
+        // pokebell_core の確保しない変換とも同じ結果になる
+        let mut core_digits = [0; 64];
+        let mut core_chars = ['\0'; 32];
+        for text in [
+            "こんにちは 12:00",
+            "ｔｅｌ",
+            "",
+            "がっこう",
+            "やき肉",
+            "ﾔｷﾆｸ",
+        ] {
+            let expected = c.encode_to_slice(text, &mut digits);
+            let (result, allocations) =
+                count_allocations(|| pokebell_core::encode_to_slice(text, &mut core_digits));
+            assert_eq!(allocations, 0, "{}", text);
+            assert_eq!(result.map_err(Error::from), expected, "{}", text);
+            if let Ok(written) = expected {
+                assert_eq!(core_digits[..written], digits[..written], "{}", text);
+            }
         }
-        let chars = val.chars();
-        let mut normal = String::new();
-        for mut ch in chars {
-            if ch.is_ascii_alphabetic() {
-                ch = ch.to_ascii_uppercase();
+        for code in ["81225223", "2104", "", "1", "11１", "8009", "0123", "811a"] {
+            let expected = c.decode_to_slice(code, &mut chars);
+            let (result, allocations) =
+                count_allocations(|| pokebell_core::decode_to_slice(code, &mut core_chars));
+            assert_eq!(allocations, 0, "{}", code);
+            assert_eq!(result.map_err(Error::from), expected, "{}", code);
+            if let Ok(written) = expected {
+                assert_eq!(core_chars[..written], chars[..written], "{}", code);
             }
-            let ch = self.normalize(&ch);
-            match self.base_map.get(&ch) {
-                Some(s) => normal += s,
-                None => {
-                    if ret.is_empty() {
-                        return Err(Error::from(ErrorKind::ParseError));
-                    }
-                    return Ok(ret);
-                }
-            };
         }
-        ret.push(normal);
-        Ok(ret)
-    }
 
-    /// 2タッチ入力から日本語に変換する。
-    /// 濁点等は別の文字になる。
-    /// 例: 2104 -> か゛
-    ///
-    /// ## Example
-    /// ```
-    /// let c = Converter::new();
-    /// c.convert_from_two_touch_string("81225223".to_string()).unwrap(); // "やきにく"
-    /// ```
-    pub fn convert_from_two_touch_string(&self, val: String) -> Result<String, Error> {
-        if val.len() % 2 != 0 || val.is_empty() || !val.is_ascii() {
-            return Err(Error::from(ErrorKind::ParseError));
+        let numeric = Converter::new().with_profile(Profile::NumericOnly);
+        for c in [&c, &numeric] {
+            for code in ["81225223", "2104", "", "1", "11１", "8009", "0123"] {
+                let expected =
+                    c.convert_from_two_touch_string_with(code, &DecodeOptions::default());
+                let result = c
+                    .decode_to_slice(code, &mut chars)
+                    .map(|written| chars[..written].iter().collect::<String>());
+                assert_eq!(result, expected, "{}", code);
+            }
         }
-        let mut ret = String::new();
-        for i in 0..(val.len() / 2) {
-            let idx = i * 2;
-            match self.inversed_base_map.get(&val[idx..(idx + 2)]) {
-                Some(ch) => ret.push(*ch),
-                None => return Err(Error::from(ErrorKind::ParseError)),
-            };
+    }
+
+    #[cfg(all(feature = "encode", feature = "decode"))]
+    proptest! {
+        #[test]
+        fn prop_fixed_buffers_match(
+            chars in proptest::collection::vec(proptest::sample::select(encodable_chars()), 0..16),
+            len in 0..40usize,
+        ) {
+            let c = Converter::without_reserved_words();
+            let text: String = chars.iter().collect();
+            let mut expected = String::new();
+            c.encode_into(&text, &mut expected).unwrap();
+            let mut digits = vec![0; len];
+            match c.encode_to_slice(&text, &mut digits) {
+                Ok(written) => prop_assert_eq!(&digits[..written], expected.as_bytes()),
+                Err(err) => {
+                    prop_assert!(expected.len() > len);
+                    prop_assert_eq!(
+                        err.kind(),
+                        &ErrorKind::BufferTooSmall { required: expected.len(), len }
+                    );
+                }
+            }
+            if let Ok(decoded) = c.convert_from_two_touch_string(&expected) {
+                let mut out = vec!['\0'; len];
+                let count = decoded.chars().count();
+                match c.decode_to_slice(&expected, &mut out) {
+                    Ok(written) => prop_assert_eq!(out[..written].iter().collect::<String>(), decoded),
+                    Err(err) => prop_assert_eq!(
+                        err.kind(),
+                        &ErrorKind::BufferTooSmall { required: count, len }
+                    ),
+                }
+            }
         }
-        Ok(ret)
     }
 
-    fn normalize(&self, ch: &char) -> char {
-        match self.normalization_map.get(ch) {
-            Some(nc) => *nc,
-            None => *ch,
+    #[cfg(feature = "decode")]
+    #[test]
+    fn test_decode_into() {
+        let c = Converter::new();
+        let mut out = String::from("> ");
+        c.decode_into("81225223", &mut out).unwrap();
+        assert_eq!(out, "> やきにく");
+
+        // 同じ `String` を使い回しても, `convert_from_two_touch_string` と同じ結果になる
+        let mut buf = String::with_capacity(64);
+        let capacity = buf.capacity();
+        let codes = [
+            "81225223",
+            "2503524261",
+            "210443",
+            "0123",
+            "",
+            "1",
+            "1a",
+            "11１",
+            "1108",
+            "4604",
+        ];
+        for code in codes {
+            buf.clear();
+            match c.convert_from_two_touch_string(code) {
+                Ok(expected) => {
+                    c.decode_into(code, &mut buf).unwrap();
+                    assert_eq!(buf, expected);
+                }
+                Err(expected) => {
+                    // エラーの場合は呼ぶ前の内容に戻す
+                    buf.push('前');
+                    assert_eq!(c.decode_into(code, &mut buf).unwrap_err(), expected);
+                    assert_eq!(buf, "前");
+                }
+            }
         }
+        assert_eq!(buf.capacity(), capacity);
+
+        let grouping = GroupingOptions::default().with_group_every(NonZeroUsize::new(4));
+        let c = c.with_grouping_options(grouping).unwrap();
+        buf.clear();
+        c.decode_into("8122 5223", &mut buf).unwrap();
+        assert_eq!(buf, "やきにく");
+        let c = Converter::new().with_profile(Profile::NumericOnly);
+        buf.clear();
+        c.decode_into("0123", &mut buf).unwrap();
+        assert_eq!(buf, "0123");
     }
 
-    /// Converterの初期化
-    /// (もっといい方法があるかもしれない)
-    pub fn new() -> Self {
-        let mut base_map = HashMap::new();
-        // see https://ja.wikipedia.org/wiki/2%E3%82%BF%E3%83%83%E3%83%81%E5%85%A5%E5%8A%9B
-        base_map.insert('あ', "11".to_string());
-        base_map.insert('い', "12".to_string());
-        base_map.insert('う', "13".to_string());
-        base_map.insert('え', "14".to_string());
-        base_map.insert('お', "15".to_string());
-        base_map.insert('か', "21".to_string());
-        base_map.insert('き', "22".to_string());
-        base_map.insert('く', "23".to_string());
-        base_map.insert('け', "24".to_string());
-        base_map.insert('こ', "25".to_string());
-        base_map.insert('さ', "31".to_string());
-        base_map.insert('し', "32".to_string());
-        base_map.insert('す', "33".to_string());
-        base_map.insert('せ', "34".to_string());
-        base_map.insert('そ', "35".to_string());
-        base_map.insert('た', "41".to_string());
-        base_map.insert('ち', "42".to_string());
-        base_map.insert('つ', "43".to_string());
-        base_map.insert('て', "44".to_string());
-        base_map.insert('と', "45".to_string());
-        base_map.insert('な', "51".to_string());
-        base_map.insert('に', "52".to_string());
-        base_map.insert('ぬ', "53".to_string());
-        base_map.insert('ね', "54".to_string());
-        base_map.insert('の', "55".to_string());
-        base_map.insert('は', "61".to_string());
-        base_map.insert('ひ', "62".to_string());
-        base_map.insert('ふ', "63".to_string());
-        base_map.insert('へ', "64".to_string());
-        base_map.insert('ほ', "65".to_string());
-        base_map.insert('ま', "71".to_string());
-        base_map.insert('み', "72".to_string());
-        base_map.insert('む', "73".to_string());
-        base_map.insert('め', "74".to_string());
-        base_map.insert('も', "75".to_string());
-        base_map.insert('や', "81".to_string());
-        base_map.insert('(', "82".to_string());
-        base_map.insert('ゆ', "83".to_string());
-        base_map.insert(')', "84".to_string());
-        base_map.insert('よ', "85".to_string());
-        base_map.insert('ら', "91".to_string());
-        base_map.insert('り', "92".to_string());
-        base_map.insert('る', "93".to_string());
-        base_map.insert('れ', "94".to_string());
-        base_map.insert('ろ', "95".to_string());
-        base_map.insert('わ', "01".to_string());
-        base_map.insert('を', "02".to_string());
-        base_map.insert('ん', "03".to_string());
-        base_map.insert('゛', "04".to_string());
-        base_map.insert('゜', "05".to_string());
-
-        base_map.insert('A', "16".to_string());
-        base_map.insert('B', "17".to_string());
-        base_map.insert('C', "18".to_string());
-        base_map.insert('D', "19".to_string());
-        base_map.insert('E', "10".to_string());
-        base_map.insert('F', "26".to_string());
-        base_map.insert('G', "27".to_string());
-        base_map.insert('H', "28".to_string());
-        base_map.insert('I', "29".to_string());
-        base_map.insert('J', "20".to_string());
-        base_map.insert('K', "36".to_string());
-        base_map.insert('L', "37".to_string());
-        base_map.insert('M', "38".to_string());
-        base_map.insert('N', "39".to_string());
-        base_map.insert('O', "30".to_string());
-        base_map.insert('P', "46".to_string());
-        base_map.insert('Q', "47".to_string());
-        base_map.insert('R', "48".to_string());
-        base_map.insert('S', "49".to_string());
-        base_map.insert('T', "40".to_string());
-        base_map.insert('U', "56".to_string());
-        base_map.insert('V', "57".to_string());
-        base_map.insert('W', "58".to_string());
-        base_map.insert('X', "59".to_string());
-        base_map.insert('Y', "50".to_string());
-        base_map.insert('Z', "66".to_string());
-        base_map.insert('?', "67".to_string());
-        base_map.insert('!', "68".to_string());
-        base_map.insert('-', "69".to_string());
-        base_map.insert('/', "60".to_string());
-        base_map.insert('\\', "76".to_string());
-        base_map.insert('&', "77".to_string());
-        base_map.insert('*', "86".to_string());
-        base_map.insert('#', "87".to_string());
-        base_map.insert(' ', "88".to_string());
-        base_map.insert('1', "96".to_string());
-        base_map.insert('2', "97".to_string());
-        base_map.insert('3', "98".to_string());
-        base_map.insert('4', "99".to_string());
-        base_map.insert('5', "90".to_string());
-        base_map.insert('6', "06".to_string());
-        base_map.insert('7', "07".to_string());
-        base_map.insert('8', "08".to_string());
-        base_map.insert('9', "09".to_string());
-        base_map.insert('0', "00".to_string());
-
-        base_map.insert('が', "2104".to_string());
-        base_map.insert('ぎ', "2204".to_string());
-        base_map.insert('ぐ', "2304".to_string());
-        base_map.insert('げ', "2404".to_string());
-        base_map.insert('ご', "2504".to_string());
-        base_map.insert('ざ', "3104".to_string());
-        base_map.insert('じ', "3204".to_string());
-        base_map.insert('ず', "3304".to_string());
-        base_map.insert('ぜ', "3404".to_string());
-        base_map.insert('ぞ', "3504".to_string());
-        base_map.insert('だ', "4104".to_string());
-        base_map.insert('ぢ', "4204".to_string());
-        base_map.insert('づ', "4304".to_string());
-        base_map.insert('で', "4404".to_string());
-        base_map.insert('ど', "4504".to_string());
-        base_map.insert('ば', "6104".to_string());
-        base_map.insert('び', "6204".to_string());
-        base_map.insert('ぶ', "6304".to_string());
-        base_map.insert('べ', "6404".to_string());
-        base_map.insert('ぼ', "6504".to_string());
-
-        base_map.insert('ぱ', "6105".to_string());
-        base_map.insert('ぴ', "6205".to_string());
-        base_map.insert('ぷ', "6305".to_string());
-        base_map.insert('ぺ', "6405".to_string());
-        base_map.insert('ぽ', "6505".to_string());
-
-        let mut inversed_base_map = HashMap::with_capacity(base_map.len());
-        for (key, value) in &base_map {
-            inversed_base_map.insert(value.clone(), key.clone());
-        }
-
-        let mut normalization_map = HashMap::new();
-        normalization_map.insert('ぁ', 'あ');
-        normalization_map.insert('ぃ', 'い');
-        normalization_map.insert('ぅ', 'う');
-        normalization_map.insert('ぇ', 'え');
-        normalization_map.insert('ぉ', 'お');
-        normalization_map.insert('っ', 'つ');
-        normalization_map.insert('ゃ', 'や');
-        normalization_map.insert('ゅ', 'ゆ');
-        normalization_map.insert('ょ', 'よ');
-        normalization_map.insert('（', '(');
-        normalization_map.insert('）', ')');
-        normalization_map.insert('Ａ', 'A');
-        normalization_map.insert('Ｂ', 'B');
-        normalization_map.insert('Ｃ', 'C');
-        normalization_map.insert('Ｄ', 'D');
-        normalization_map.insert('Ｅ', 'E');
-        normalization_map.insert('Ｆ', 'F');
-        normalization_map.insert('Ｇ', 'G');
-        normalization_map.insert('Ｈ', 'H');
-        normalization_map.insert('Ｉ', 'I');
-        normalization_map.insert('Ｊ', 'J');
-        normalization_map.insert('Ｋ', 'J');
-        normalization_map.insert('Ｌ', 'L');
-        normalization_map.insert('Ｍ', 'M');
-        normalization_map.insert('Ｎ', 'N');
-        normalization_map.insert('Ｏ', 'O');
-        normalization_map.insert('Ｐ', 'P');
-        normalization_map.insert('Ｑ', 'Q');
-        normalization_map.insert('Ｒ', 'R');
-        normalization_map.insert('Ｓ', 'S');
-        normalization_map.insert('Ｔ', 'T');
-        normalization_map.insert('Ｕ', 'U');
-        normalization_map.insert('Ｖ', 'V');
-        normalization_map.insert('Ｗ', 'W');
-        normalization_map.insert('Ｘ', 'X');
-        normalization_map.insert('Ｙ', 'Y');
-        normalization_map.insert('Ｚ', 'Z');
-        normalization_map.insert('？', '?');
-        normalization_map.insert('！', '!');
-        normalization_map.insert('－', '-');
-        normalization_map.insert('／', '/');
-        normalization_map.insert('￥', '\\');
-        normalization_map.insert('＆', '&');
-        normalization_map.insert('＊', '*');
-        normalization_map.insert('＃', '#');
-        normalization_map.insert('　', ' ');
-        normalization_map.insert('１', '1');
-        normalization_map.insert('２', '2');
-        normalization_map.insert('３', '3');
-        normalization_map.insert('４', '4');
-        normalization_map.insert('５', '5');
-        normalization_map.insert('６', '6');
-        normalization_map.insert('７', '7');
-        normalization_map.insert('８', '8');
-        normalization_map.insert('９', '9');
-        normalization_map.insert('０', '0');
-        normalization_map.insert('ー', '-');
-
-        // see https://koma-yome.com/archives/724
-        let mut reserved_word_map = HashMap::new();
-        reserved_word_map.insert("今".to_string(), vec!["10".to_string()]);
-        reserved_word_map.insert("いま".to_string(), vec!["10".to_string()]);
-        reserved_word_map.insert("海".to_string(), vec!["41".to_string()]);
-        reserved_word_map.insert("うみ".to_string(), vec!["41".to_string()]);
-        reserved_word_map.insert("シー".to_string(), vec!["41".to_string()]);
-        reserved_word_map.insert("しー".to_string(), vec!["41".to_string()]);
-        reserved_word_map.insert("至急".to_string(), vec!["49".to_string()]);
-        reserved_word_map.insert("しきゅう".to_string(), vec!["49".to_string()]);
-        reserved_word_map.insert("待ってる".to_string(), vec!["106".to_string()]);
-        reserved_word_map.insert("まってる".to_string(), vec!["106".to_string()]);
-        reserved_word_map.insert("TEL".to_string(), vec!["106".to_string()]);
-        reserved_word_map.insert("ＴＥＬ".to_string(), vec!["106".to_string()]);
-        reserved_word_map.insert("テル".to_string(), vec!["106".to_string()]);
-        reserved_word_map.insert("遅れてる".to_string(), vec!["9106".to_string()]);
-        reserved_word_map.insert("おくれてる".to_string(), vec!["9106".to_string()]);
-        reserved_word_map.insert(
-            "愛してる".to_string(),
-            vec![
-                "14106".to_string(),
-                "114106".to_string(),
-                "1410".to_string(),
-            ],
-        );
-        reserved_word_map.insert(
-            "あいしてる".to_string(),
-            vec![
-                "14106".to_string(),
-                "114106".to_string(),
-                "1410".to_string(),
-            ],
-        );
-        reserved_word_map.insert("何してる".to_string(), vec!["724106".to_string()]);
-        reserved_word_map.insert("なにしてる".to_string(), vec!["724106".to_string()]);
-        reserved_word_map.insert(
-            "起きてる".to_string(),
-            vec!["09106".to_string(), "9106".to_string()],
-        );
-        reserved_word_map.insert(
-            "おきてる".to_string(),
-            vec!["09106".to_string(), "9106".to_string()],
-        );
-        reserved_word_map.insert("行くよ".to_string(), vec!["194".to_string()]);
-        reserved_word_map.insert("いくよ".to_string(), vec!["194".to_string()]);
-        reserved_word_map.insert("池袋".to_string(), vec!["269".to_string()]);
-        reserved_word_map.insert("いけぶくろ".to_string(), vec!["269".to_string()]);
-        reserved_word_map.insert("渋谷".to_string(), vec!["428".to_string()]);
-        reserved_word_map.insert("しぶや".to_string(), vec!["428".to_string()]);
-        reserved_word_map.insert("おやすみ".to_string(), vec!["833".to_string()]);
-        reserved_word_map.insert(
-            "おはよう".to_string(),
-            vec!["840".to_string(), "0840".to_string()],
-        );
-        reserved_word_map.insert("ハロー".to_string(), vec!["860".to_string()]);
-        reserved_word_map.insert("はやく".to_string(), vec!["889".to_string()]);
-        reserved_word_map.insert("早く".to_string(), vec!["889".to_string()]);
-        reserved_word_map.insert(
-            "サンキュー".to_string(),
-            vec!["39".to_string(), "999".to_string()],
-        );
-        reserved_word_map.insert(
-            "Thank you".to_string(),
-            vec!["39".to_string(), "999".to_string()],
-        );
-        reserved_word_map.insert(
-            "thank you".to_string(),
-            vec!["39".to_string(), "999".to_string()],
-        );
-        reserved_word_map.insert("会えない".to_string(), vec!["1871".to_string()]);
-        reserved_word_map.insert("あえない".to_string(), vec!["1871".to_string()]);
-        reserved_word_map.insert("さよなら".to_string(), vec!["3470".to_string()]);
-        reserved_word_map.insert("寒いよ".to_string(), vec!["3614".to_string()]);
-        reserved_word_map.insert("さむいよ".to_string(), vec!["3614".to_string()]);
-        reserved_word_map.insert("仕事".to_string(), vec!["4510".to_string()]);
-        reserved_word_map.insert("しごと".to_string(), vec!["4510".to_string()]);
-        reserved_word_map.insert("横浜".to_string(), vec!["4580".to_string()]);
-        reserved_word_map.insert("よこはま".to_string(), vec!["4580".to_string()]);
-        reserved_word_map.insert("よろしく".to_string(), vec!["4649".to_string()]);
-        reserved_word_map.insert("ファイト".to_string(), vec!["5110".to_string()]);
-        reserved_word_map.insert("お仕事ファイト".to_string(), vec!["045105110".to_string()]);
-        reserved_word_map.insert("おしごとふぁいと".to_string(), vec!["045105110".to_string()]);
-        reserved_word_map.insert("ふぁいと".to_string(), vec!["5110".to_string()]);
-        reserved_word_map.insert("ご苦労さん".to_string(), vec!["5963".to_string()]);
-        reserved_word_map.insert("ごくろうさん".to_string(), vec!["5963".to_string()]);
-        reserved_word_map.insert("バイト".to_string(), vec!["8110".to_string()]);
-        reserved_word_map.insert("ばいと".to_string(), vec!["8110".to_string()]);
-        reserved_word_map.insert("バイバイ".to_string(), vec!["8181".to_string()]);
-        reserved_word_map.insert("ばいばい".to_string(), vec!["8181".to_string()]);
-        reserved_word_map.insert("今どこ".to_string(), vec!["10105".to_string()]);
-        reserved_word_map.insert("いまどこ".to_string(), vec!["10105".to_string()]);
-        reserved_word_map.insert("会いたいよ".to_string(), vec!["110149".to_string()]);
-        reserved_word_map.insert("あいたいよ".to_string(), vec!["11014".to_string()]);
-        reserved_word_map.insert("着いたよ".to_string(), vec!["21104".to_string()]);
-        reserved_word_map.insert("ついたよ".to_string(), vec!["21104".to_string()]);
-        reserved_word_map.insert("寂しいよ".to_string(), vec!["33414".to_string()]);
-        reserved_word_map.insert("さびしいよ".to_string(), vec!["33414".to_string()]);
-        reserved_word_map.insert("デートしよ".to_string(), vec!["101044".to_string()]);
-        reserved_word_map.insert("でーとしよ".to_string(), vec!["101044".to_string()]);
-        reserved_word_map.insert("TEL欲しい".to_string(), vec!["106841".to_string()]);
-        reserved_word_map.insert("TELほしい".to_string(), vec!["106841".to_string()]);
-        reserved_word_map.insert("ごめんなさい".to_string(), vec!["500731".to_string()]);
-        reserved_word_map.insert("早くして".to_string(), vec!["889410".to_string()]);
-        reserved_word_map.insert("はやくして".to_string(), vec!["889410".to_string()]);
-        reserved_word_map.insert("どこにいるの".to_string(), vec!["1052167".to_string()]);
-        reserved_word_map.insert("今から行くよ".to_string(), vec!["1056194".to_string()]);
-        reserved_word_map.insert("いまからいくよ".to_string(), vec!["1056194".to_string()]);
-        reserved_word_map.insert("ボウリング行こ".to_string(), vec!["015".to_string()]);
-        reserved_word_map.insert("ボウリングいこ".to_string(), vec!["015".to_string()]);
-        reserved_word_map.insert("遅れる".to_string(), vec!["090".to_string()]);
-        reserved_word_map.insert("おくれる".to_string(), vec!["090".to_string()]);
-        reserved_word_map.insert(
-            "ずっと一緒にいようね".to_string(),
-            vec!["2101442147".to_string()],
-        );
-        reserved_word_map.insert(
-            "ずっと一緒にいよーね".to_string(),
-            vec!["21014421479".to_string()],
-        );
-        reserved_word_map.insert(
-            "ずっといっしょにいようね".to_string(),
-            vec!["2101442147".to_string()],
-        );
-        reserved_word_map.insert(
-            "ずっといっしょにいよーね".to_string(),
-            vec!["21014421479".to_string()],
+    #[cfg(feature = "encode")]
+    #[test]
+    fn test_encode_into() {
+        let c = Converter::new();
+        let mut buf = String::with_capacity(64);
+        let written = c.encode_to_string_buf("ごくろうさん", &mut buf).unwrap();
+        assert_eq!(written, 14);
+        assert_eq!(buf, "25042395133103");
+        let capacity = buf.capacity();
+        let written = c.encode_to_string_buf("やきにく", &mut buf).unwrap();
+        assert_eq!(written, 8);
+        assert_eq!(buf, "81225223");
+        assert_eq!(buf.capacity(), capacity);
+
+        // エラーの場合はそれまでの数字が残る
+        let err = c.encode_to_string_buf("やき肉", &mut buf).unwrap_err();
+        assert_eq!(
+            *err.kind(),
+            ErrorKind::UnsupportedCharacter {
+                ch: '肉', index: 2
+            }
         );
+        assert_eq!(buf, "8122");
 
-        Converter {
-            base_map: base_map,
-            inversed_base_map: inversed_base_map,
-            normalization_map: normalization_map,
-            reserved_word_map: reserved_word_map,
+        struct Full;
+        impl fmt::Write for Full {
+            fn write_str(&mut self, _: &str) -> fmt::Result {
+                Err(fmt::Error)
+            }
         }
+        let err = c.encode_into("あ", &mut Full).unwrap_err();
+        assert_eq!(*err.kind(), ErrorKind::WriteError);
     }
-}
 
-#[derive(Debug, Fail)]
-pub enum ErrorKind {
-    #[fail(display = "parse error")]
-    ParseError,
-}
+    #[cfg(feature = "encode")]
+    #[test]
+    fn test_encode_with_trace() {
+        let c = Converter::new()
+            .with_convert_options(ConvertOptions::default().with_escapes(true))
+            .unwrap();
+        let val = "がっこうへgo! {0840}";
+        let trace = c.encode_with_trace(val).unwrap();
+        let literal = c.convert_to_two_touch_string(val).unwrap();
+        let digits: String = trace.iter().map(|s| s.code.as_str()).collect();
+        assert_eq!(&digits, literal.last().unwrap());
+        for segment in &trace {
+            assert_eq!(&digits[segment.span.clone()], segment.code);
+        }
+        assert_eq!(trace[0].normalized, Some('が'));
+        assert_eq!(trace[0].span, 0..4);
+        assert_eq!(trace[1].original, 'っ');
+        assert_eq!(trace[1].normalized, Some('つ'));
+        assert_eq!((trace[1].index, trace[1].span.clone()), (1, 4..6));
+        assert_eq!(trace[5].original, 'g');
+        assert_eq!(trace[5].normalized, Some('G'));
+        let escape = trace.last().unwrap();
+        assert_eq!((escape.index, escape.normalized), (9, None));
+        assert_eq!(escape.code, "0840");
 
-/* ----------- failure boilerplate ----------- */
+        assert_eq!(
+            c.encode_with_trace("ごくろう筋肉").unwrap_err().kind(),
+            &ErrorKind::UnsupportedCharacter {
+                ch: '筋', index: 4
+            }
+        );
+    }
 
-use failure::{Backtrace, Context, Fail};
-use std::fmt;
-use std::fmt::Display;
+    #[cfg(feature = "builtin-dictionary")]
+    #[test]
+    fn test_is_encodable() {
+        let c = Converter::new();
+        assert!(c.is_encodable("ＫＡＮＡ"));
+        assert!(c.is_encodable("ご苦労さん"));
+        assert!(!c.is_encodable("こんにちは筋肉"));
+        assert_eq!(c.first_unsupported("こんにちは筋肉"), Some((5, '筋')));
+        assert_eq!(c.first_unsupported("こんにちは"), None);
 
-#[derive(Debug)]
-pub struct Error {
-    inner: Context<ErrorKind>,
-}
+        assert!(!c.is_encodable(""));
+        assert_eq!(c.first_unsupported(""), None);
+        assert_eq!(c.check_encodable(""), Err(EncodeIssue::Empty));
+        assert_eq!(
+            c.check_encodable("あ{08"),
+            Err(EncodeIssue::UnsupportedCharacter { ch: '{', index: 1 })
+        );
+        let escapes = Converter::new()
+            .with_convert_options(ConvertOptions::default().with_escapes(true))
+            .unwrap();
+        assert_eq!(
+            escapes.check_encodable("あ{08"),
+            Err(EncodeIssue::InvalidEscape { index: 1 })
+        );
+        assert_eq!(
+            escapes.check_encodable("あ{080}"),
+            Err(EncodeIssue::InvalidEscape { index: 1 })
+        );
 
-impl Fail for Error {
-    fn cause(&self) -> Option<&dyn Fail> {
-        self.inner.cause()
+        let c = Converter::new().with_space_options(SpaceOptions::default().with_trim_spaces(true));
+        assert_eq!(c.check_encodable("  "), Err(EncodeIssue::Empty));
+        let c = Converter::new().with_profile(Profile::NumericOnly);
+        assert!(c.is_encodable("0840"));
+        assert_eq!(c.first_unsupported("08あ"), Some((2, 'あ')));
     }
 
-    fn backtrace(&self) -> Option<&Backtrace> {
-        self.inner.backtrace()
+    #[cfg(feature = "encode")]
+    #[test]
+    fn test_normalize_text() {
+        let c = Converter::new();
+        assert_eq!(c.normalize_text("こんにちは　ＴＥＬ"), "こんにちは TEL");
+        assert_eq!(c.normalize_text("ぁっ"), "あつ");
+        assert_eq!(c.normalize_text("筋肉"), "筋肉");
+        assert_eq!(c.normalize_text("{{Ａ}}{０}Ａ"), "{{A}}{0}A");
+        let c = Converter::new()
+            .with_convert_options(ConvertOptions::default().with_escapes(true))
+            .unwrap();
+        assert_eq!(c.normalize_text("{{Ａ}}{０}Ａ"), "{{A}}{０}A");
+        assert_eq!(c.normalize_text("{０"), "{０");
     }
-}
 
-impl Display for Error {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        Display::fmt(&self.inner, f)
+    /// バイトを直接読むようにする前の `convert_from_two_touch_string_with`。結果を比べるのに使う
+    #[cfg(feature = "decode")]
+    fn decode_reference(
+        c: &Converter,
+        val: &str,
+        options: &DecodeOptions,
+    ) -> Result<String, Error> {
+        options.validate()?;
+        check_input_len(
+            val.chars().filter(|&ch| Some(ch) != options.separator),
+            options.max_input_digits,
+        )?;
+        let val: String = match options.separator {
+            Some(separator) => val.chars().filter(|&ch| ch != separator).collect(),
+            None => val.to_string(),
+        };
+        if options.dakuten_mode == DakutenMode::Composed {
+            return c
+                .decode_with_trace(&val, DakutenMode::Composed)
+                .map(|segments| segments.iter().map(|segment| segment.ch).collect());
+        }
+        if val.is_empty() {
+            return Err(Error::from(ErrorKind::EmptyInput));
+        }
+        if c.profile == Profile::NumericOnly {
+            return c.check_numeric(&val).map(|()| val.to_string());
+        }
+        if let Some((offset, ch)) = val.char_indices().find(|(_, ch)| !ch.is_ascii()) {
+            return Err(Error::from(ErrorKind::NonAscii { ch, offset }));
+        }
+        if let Some((index, ch)) = val.char_indices().find(|(_, ch)| !ch.is_ascii_digit()) {
+            return Err(Error::from(ErrorKind::NonDigit { ch, index }));
+        }
+        if !val.len().is_multiple_of(2) {
+            return Err(Error::from(ErrorKind::OddLength { len: val.len() }));
+        }
+        let mut ret = String::new();
+        for (i, pair) in val.as_bytes().chunks_exact(2).enumerate() {
+            let code = std::str::from_utf8(pair).unwrap();
+            ret.push(c.pair_char(code, i)?);
+        }
+        Ok(ret)
     }
-}
 
-impl Error {
-    pub fn new(inner: Context<ErrorKind>) -> Error {
-        Error { inner }
+    #[cfg(feature = "decode")]
+    #[test]
+    fn test_all_ascii_digits() {
+        assert!(all_ascii_digits(b""));
+        assert!(all_ascii_digits("0123456789".repeat(5).as_bytes()));
+        for len in [1, 15, 16, 17, 40] {
+            for i in 0..len {
+                let mut bytes = vec![b'5'; len];
+                bytes[i] = b'/';
+                assert!(!all_ascii_digits(&bytes), "{} {}", len, i);
+                bytes[i] = b':';
+                assert!(!all_ascii_digits(&bytes), "{} {}", len, i);
+            }
+        }
+        assert!(!all_ascii_digits("１１".as_bytes()));
     }
 
-    pub fn kind(&self) -> &ErrorKind {
-        self.inner.get_context()
-    }
-}
+    proptest! {
+        #[cfg(feature = "decode")]
+        #[test]
+        fn prop_decode_matches_reference(
+            val in proptest::collection::vec(
+                prop_oneof![
+                    8 => proptest::char::range('0', '9'),
+                    1 => proptest::sample::select(vec![' ', '-', 'a', 'あ', '１']),
+                ],
+                0..48,
+            ).prop_map(|chars| chars.into_iter().collect::<String>()),
+            profile in proptest::sample::select(vec![
+                Profile::Full,
+                Profile::KanaOnly,
+                Profile::NumericOnly,
+                Profile::Chars("あいう".chars().collect()),
+            ]),
+            separator in proptest::option::of(proptest::sample::select(vec![' ', '-'])),
+            max_input_digits in proptest::option::of(0..40usize),
+        ) {
+            let c = Converter::new().with_profile(profile);
+            let options = DecodeOptions::default()
+                .with_separator(separator)
+                .with_max_input_digits(max_input_digits);
+            prop_assert_eq!(
+                c.convert_from_two_touch_string_with(&val, &options),
+                decode_reference(&c, &val, &options)
+            );
+        }
 
-impl From<ErrorKind> for Error {
-    fn from(kind: ErrorKind) -> Error {
-        Error {
-            inner: Context::new(kind),
+        #[cfg(feature = "decode")]
+        #[test]
+        fn prop_decode_pairs_matches_reference(
+            pairs in proptest::collection::vec(0..100u8, 1..64),
+        ) {
+            let c = Converter::new();
+            let val: String = pairs.iter().map(|pair| format!("{:02}", pair)).collect();
+            let options = DecodeOptions::default();
+            prop_assert_eq!(
+                c.convert_from_two_touch_string_with(&val, &options),
+                decode_reference(&c, &val, &options)
+            );
+            let mut out = String::from("前");
+            match (c.decode_into(&val, &mut out), c.convert_from_two_touch_string(&val)) {
+                (Ok(()), Ok(expected)) => prop_assert_eq!(out, format!("前{}", expected)),
+                (Err(err), Err(expected)) => {
+                    prop_assert_eq!(err, expected);
+                    prop_assert_eq!(out, "前");
+                }
+                (result, expected) => prop_assert!(false, "{:?} != {:?}", result, expected),
+            }
+        }
+
+        #[cfg(feature = "builtin-dictionary")]
+        #[test]
+        fn prop_normalize_text_keeps_conversion(
+            pieces in proptest::collection::vec(
+                prop_oneof![
+                    proptest::sample::select(sample_chars()).prop_map(String::from),
+                    proptest::sample::select(sample_phrases()),
+                ],
+                0..8,
+            )
+        ) {
+            let c = Converter::new();
+            let val: String = pieces.concat();
+            let normalized = c.normalize_text(&val);
+            let expected = c.convert_to_two_touch_string(&val);
+            let result = c.convert_to_two_touch_string(normalized);
+            match (result, expected) {
+                (Ok(result), Ok(expected)) => prop_assert_eq!(result, expected),
+                (Err(result), Err(expected)) => prop_assert_eq!(
+                    std::mem::discriminant(result.kind()),
+                    std::mem::discriminant(expected.kind())
+                ),
+                (result, expected) => prop_assert!(false, "{:?} != {:?}", result, expected),
+            }
+        }
+
+        #[cfg(all(feature = "encode", feature = "decode"))]
+        #[test]
+        fn prop_roundtrip_encodable(
+            chars in proptest::collection::vec(proptest::sample::select(encodable_chars()), 1..12)
+        ) {
+            let c = Converter::new();
+            let val: String = chars.iter().collect();
+            let rt = c.verify_roundtrip(&val).unwrap();
+            let mut literal = String::new();
+            c.encode_into(&val, &mut literal).unwrap();
+            prop_assert_eq!(&rt.digits, &literal);
+
+            // substitutionsにない文字はそのまま戻る
+            let mut expected = String::new();
+            let mut substitutions = rt.substitutions.iter().peekable();
+            for (index, &ch) in chars.iter().enumerate() {
+                match substitutions.peek() {
+                    Some(substitution) if substitution.index() == index => {
+                        match substitutions.next() {
+                            Some(Substitution::Normalized { decoded, .. }) => expected.push(*decoded),
+                            Some(Substitution::DakutenSplit { decoded, .. }) => expected += decoded,
+                            other => prop_assert!(false, "unexpected {:?}", other),
+                        }
+                    }
+                    _ => expected.push(ch),
+                }
+            }
+            prop_assert_eq!(rt.decoded.as_deref(), Some(expected.as_str()));
+
+            // 濁点を別に入力しなければ, 濁点をまとめて戻すと正規化した入力になる
+            if !val.contains(['゛', '゜']) {
+                let options = DecodeOptions::default().with_dakuten_mode(DakutenMode::Composed);
+                prop_assert_eq!(
+                    c.convert_from_two_touch_string_with(&rt.digits, &options).unwrap(),
+                    c.normalize_text(&val)
+                );
+            }
+        }
+
+        #[cfg(all(feature = "encode", feature = "decode"))]
+        #[test]
+        fn prop_roundtrip_codes(
+            codes in proptest::collection::vec(
+                proptest::sample::select(
+                    Converter::new().table().map(|(_, code)| code.to_string()).collect::<Vec<_>>()
+                ),
+                1..12,
+            )
+        ) {
+            let c = Converter::new();
+            let digits = codes.concat();
+            let decoded = c.convert_from_two_touch_string(&digits).unwrap();
+            let mut encoded = String::new();
+            c.encode_into(&decoded, &mut encoded).unwrap();
+            prop_assert_eq!(encoded, digits);
+        }
+
+        #[cfg(all(feature = "encode", feature = "decode"))]
+        #[test]
+        fn prop_canonicalize_iff_same_decoded(
+            a in proptest::collection::vec(proptest::sample::select(canonical_samples()), 1..6),
+            b in proptest::collection::vec(proptest::sample::select(canonical_samples()), 1..6),
+        ) {
+            let c = Converter::new();
+            let (a, b) = (a.join(" "), b.concat());
+            let same_decoded =
+                c.convert_from_two_touch_string(a.replace(' ', "")).unwrap()
+                    == c.convert_from_two_touch_string(&b).unwrap();
+            prop_assert_eq!(c.codes_equivalent(&a, &b).unwrap(), same_decoded);
+            let canonical = c.canonicalize_code(&a).unwrap();
+            prop_assert_eq!(c.canonicalize_code(&canonical).unwrap(), canonical);
         }
     }
-}
 
-impl From<Context<ErrorKind>> for Error {
-    fn from(inner: Context<ErrorKind>) -> Error {
-        Error { inner }
+    /// プロパティテストで使う, 同じ文字列に戻る組み合わせが多いコード
+    #[cfg(all(feature = "encode", feature = "decode"))]
+    fn canonical_samples() -> Vec<String> {
+        vec!["21", "04", "2104", "43", "88", "4388"]
+            .into_iter()
+            .map(String::from)
+            .collect()
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// プロパティテストで使う, 変換できる文字
+    #[cfg(all(feature = "encode", feature = "decode"))]
+    fn encodable_chars() -> Vec<char> {
+        let c = Converter::new();
+        sample_chars()
+            .into_iter()
+            .filter(|&ch| c.encode_char(ch).is_some())
+            .collect()
+    }
+
+    #[cfg(all(feature = "encode", feature = "decode"))]
     #[test]
-    fn test_convert_to_two_touch_string_normal() {
+    fn test_verify_roundtrip() {
         let c = Converter::new();
-        let result = c
-            .convert_to_two_touch_string("こんにちは".to_string())
-            .unwrap();
-        assert_eq!(result.len(), 1);
-        assert_eq!(result[0], "2503524261");
+        let rt = c.verify_roundtrip("ぱぁa").unwrap();
+        assert_eq!(rt.decoded.as_deref(), Some("は゜あA"));
+        assert_eq!(
+            rt.substitutions,
+            vec![
+                Substitution::DakutenSplit {
+                    index: 0,
+                    original: 'ぱ',
+                    decoded: "は゜".to_string()
+                },
+                Substitution::Normalized {
+                    index: 1,
+                    original: 'ぁ',
+                    decoded: 'あ'
+                },
+                Substitution::Normalized {
+                    index: 2,
+                    original: 'a',
+                    decoded: 'A'
+                },
+            ]
+        );
 
-        let result = c
-            .convert_to_two_touch_string("ごくろうさん".to_string())
+        let c = Converter::new()
+            .with_convert_options(ConvertOptions::default().with_escapes(true))
             .unwrap();
-        let expected = vec!["5963".to_string(), "25042395133103".to_string()];
-        assert_eq!(result, expected);
+        let rt = c.verify_roundtrip("{80}あ").unwrap();
+        assert_eq!(rt.decoded, None);
+        assert_eq!(
+            rt.substitutions,
+            vec![Substitution::RawDigits {
+                index: 0,
+                digits: "80".to_string(),
+                decoded: None
+            }]
+        );
 
-        let result = c
-            .convert_to_two_touch_string("ご苦労さん".to_string())
-            .unwrap();
-        let expected = vec!["5963".to_string()];
-        assert_eq!(result, expected);
+        let c = Converter::new().with_space_options(SpaceOptions::default().with_trim_spaces(true));
+        let rt = c.verify_roundtrip(" あ").unwrap();
+        assert_eq!(
+            rt.substitutions,
+            vec![Substitution::Removed {
+                index: 0,
+                original: ' '
+            }]
+        );
+        assert!(c.verify_roundtrip("  ").is_err());
+        assert!(Converter::new().verify_roundtrip("  ").unwrap().is_exact());
 
-        let result = c
-            .convert_to_two_touch_string("だA*（￥ぽＧ".to_string())
-            .unwrap();
-        let expected = vec!["410416868276650527".to_string()];
-        assert_eq!(result, expected);
+        // 全角の英字は半角の英字に戻る
+        let c = Converter::new();
+        let full: String = ('Ａ'..='Ｚ').collect();
+        assert_eq!(
+            c.verify_roundtrip(&full).unwrap().decoded.as_deref(),
+            Some("ABCDEFGHIJKLMNOPQRSTUVWXYZ")
+        );
+    }
+
+    /// プロパティテストで使う文字: 変換表と正規化の表の文字と, 変換できない文字
+    #[cfg(feature = "encode")]
+    #[cfg(any(feature = "decode", feature = "builtin-dictionary"))]
+    fn sample_chars() -> Vec<char> {
+        let c = Converter::new();
+        let mut chars: Vec<char> = c.tables.base_map.iter().map(|(ch, _)| ch).collect();
+        chars.extend(c.tables.normalization_map.iter().map(|(from, _)| from));
+        chars.extend("abcxyzｶﾞﾊﾟ筋肉{}".chars());
+        chars.sort_unstable();
+        chars
+    }
+
+    #[cfg(feature = "builtin-dictionary")]
+    fn sample_phrases() -> Vec<String> {
+        let c = Converter::new();
+        let mut phrases: Vec<String> = c
+            .reserved()
+            .word_map
+            .values()
+            .map(|word| word.phrase.clone())
+            .collect();
+        phrases.sort();
+        phrases
     }
+
+    #[cfg(feature = "encode")]
     #[test]
-    fn test_convert_to_two_touch_string_error() {
+    fn test_unsupported_chars() {
         let c = Converter::new();
-        let result = c.convert_to_two_touch_string("筋肉".to_string());
-        assert!(result.is_err());
-        let result = c.convert_to_two_touch_string("".to_string());
-        assert!(result.is_err());
-        let result = c.convert_to_two_touch_string("@".to_string());
-        assert!(result.is_err());
+        assert!(c.unsupported_chars("こんにちはＴＥＬ").is_empty());
+        let result = c.unsupported_chars("筋肉こんにちは筋");
+        assert_eq!(result, vec![(0, '筋'), (1, '肉'), (7, '筋')]);
+    }
+
+    #[cfg(all(feature = "encode", feature = "decode"))]
+    #[test]
+    fn test_encode_char_decode_pair() {
+        let c = Converter::new();
+        assert_eq!(c.encode_char('ｶ'), None);
+        assert_eq!(c.encode_char('ｔ'), None);
+        assert_eq!(c.encode_char('t'), Some("40"));
+        assert_eq!(c.encode_char('ぁ'), c.encode_char('あ'));
+        for ch in "ごくろうさん ABC!".chars() {
+            let code = c.encode_char(ch).unwrap();
+            let expected = c.convert_to_two_touch_string(ch.to_string()).unwrap();
+            assert_eq!(code, expected.last().unwrap());
+        }
+        for first in 0..10 {
+            for second in 0..10 {
+                let pair = format!("{}{}", first, second);
+                assert_eq!(
+                    c.decode_pair(&pair),
+                    c.convert_from_two_touch_string(&pair)
+                        .ok()
+                        .and_then(|s| s.chars().next())
+                );
+                assert_eq!(c.decode_pair_digits(first, second), c.decode_pair(&pair));
+            }
+        }
+        assert_eq!(c.decode_pair("8"), None);
+
+        let c = Converter::new().with_profile(Profile::NumericOnly);
+        assert_eq!(c.encode_char('８'), Some("8"));
+        assert_eq!(c.decode_pair("88"), None);
+    }
+
+    #[cfg(feature = "encode")]
+    #[test]
+    fn test_table() {
+        let c = Converter::new();
+        let table: Vec<(char, &str)> = c.table().collect();
+        assert!(table.windows(2).all(|w| w[0].1 <= w[1].1));
+        assert!(table.iter().all(|&(_, code)| code.len() == 2));
+        assert!(table.contains(&('や', "81")));
+        assert_eq!(table, c.table().collect::<Vec<_>>());
+        for (ch, code) in c.composed_table() {
+            assert_eq!(code.len(), 4);
+            assert_eq!(c.encode_char(ch), Some(code));
+        }
+        assert_eq!(
+            c.table().count() + c.composed_table().count(),
+            c.tables.base_map.iter().count()
+        );
+
+        let normalizations: Vec<(char, char)> = c.normalizations().collect();
+        assert_eq!(
+            normalizations.len(),
+            c.tables.normalization_map.iter().count()
+        );
+        assert!(normalizations.windows(2).all(|w| w[0].0 < w[1].0));
     }
+
+    #[cfg(feature = "decode")]
     #[test]
+    #[allow(clippy::unnecessary_to_owned)]
     fn test_convert_from_two_touch_string_normal() {
         let c = Converter::new();
         let result = c
@@ -520,7 +9695,9 @@ mod tests {
         assert_eq!(result, "こ゛X* )");
     }
 
+    #[cfg(feature = "decode")]
     #[test]
+    #[allow(clippy::unnecessary_to_owned)]
     fn test_convert_from_two_touch_string_error() {
         let c = Converter::new();
         let result = c.convert_from_two_touch_string("8080".to_string());
@@ -535,4 +9712,258 @@ mod tests {
         let result = c.convert_from_two_touch_string("筋肉".to_string());
         assert!(result.is_err());
     }
+
+    #[cfg(all(feature = "encode", feature = "decode"))]
+    #[test]
+    fn test_convert_accepts_str() {
+        let c = Converter::new();
+        let text = String::from("やきにく");
+        assert_eq!(
+            c.convert_to_two_touch_string("やきにく").unwrap(),
+            c.convert_to_two_touch_string(&text).unwrap()
+        );
+        let code = String::from("81225223");
+        assert_eq!(
+            c.convert_from_two_touch_string("81225223").unwrap(),
+            "やきにく"
+        );
+        assert_eq!(c.convert_from_two_touch_string(&code).unwrap(), "やきにく");
+    }
+
+    #[cfg(feature = "decode")]
+    #[test]
+    fn test_display_len() {
+        let c = Converter::new();
+        let complete = |len| DisplayLen {
+            len,
+            incomplete: false,
+        };
+        let result = c.display_len("2503524261", DakutenMode::Composed).unwrap();
+        assert_eq!(result, complete(5));
+        let result = c.display_len("2104", DakutenMode::Composed).unwrap();
+        assert_eq!(result, complete(1));
+        let result = c.display_len("2104", DakutenMode::Raw).unwrap();
+        assert_eq!(result, complete(2));
+        let result = c.display_len("1104", DakutenMode::Composed).unwrap();
+        assert_eq!(result, complete(2));
+        let result = c.display_len("", DakutenMode::Composed).unwrap();
+        assert_eq!(result, complete(0));
+
+        let result = c.display_len("21042", DakutenMode::Composed).unwrap();
+        assert_eq!(
+            result,
+            DisplayLen {
+                len: 1,
+                incomplete: true,
+            }
+        );
+
+        assert!(c.display_len("8080", DakutenMode::Raw).is_err());
+        assert!(c.display_len("筋肉", DakutenMode::Raw).is_err());
+    }
+
+    #[cfg(feature = "decode")]
+    #[test]
+    fn test_decode_with_trace() {
+        let c = Converter::new();
+        let raw = c
+            .decode_with_trace("250459868884", DakutenMode::Raw)
+            .unwrap();
+        let offsets: Vec<usize> = raw.iter().map(|s| s.offset).collect();
+        assert_eq!(offsets, vec![0, 2, 4, 6, 8, 10]);
+        assert!(raw.iter().all(|s| s.code.len() == 2 && !s.composed));
+        let decoded: String = raw.iter().map(|s| s.ch).collect();
+        assert_eq!(
+            decoded,
+            c.convert_from_two_touch_string("250459868884").unwrap()
+        );
+
+        let composed = c
+            .decode_with_trace("250459868884", DakutenMode::Composed)
+            .unwrap();
+        assert_eq!(
+            composed[0],
+            DecodedSegment {
+                code: "2504".to_string(),
+                offset: 0,
+                ch: 'ご',
+                composed: true,
+            }
+        );
+        let offsets: Vec<usize> = composed.iter().map(|s| s.offset).collect();
+        assert_eq!(offsets, vec![0, 4, 6, 8, 10]);
+        assert_eq!(&composed[1..], &raw[2..]);
+
+        assert_eq!(
+            c.decode_with_trace("2580", DakutenMode::Composed)
+                .unwrap_err()
+                .kind(),
+            &ErrorKind::UnknownCode {
+                code: "80".to_string(),
+                pair_index: 1,
+                offset: 2,
+            }
+        );
+        assert_eq!(
+            c.decode_with_trace("250", DakutenMode::Raw)
+                .unwrap_err()
+                .kind(),
+            &ErrorKind::OddLength { len: 3 }
+        );
+    }
+
+    #[cfg(feature = "decode")]
+    #[test]
+    fn test_check_two_touch_string() {
+        let c = Converter::new();
+        assert!(c.check_two_touch_string("250459868884").is_empty());
+        assert_eq!(c.check_two_touch_string(""), vec![DecodeIssue::Empty]);
+
+        let issues = c.check_two_touch_string("80118a3");
+        let expected = vec![
+            DecodeIssue::UnknownCode {
+                code: "80".to_string(),
+                pair_index: 0,
+                offset: 0,
+            },
+            DecodeIssue::NonDigit { ch: 'a', offset: 5 },
+            DecodeIssue::OddTrailingDigit { offset: 6 },
+        ];
+        assert_eq!(issues, expected);
+
+        // 最後の数字以外の文字は奇数桁として報告しない
+        let issues = c.check_two_touch_string("8011a");
+        let expected = vec![
+            DecodeIssue::UnknownCode {
+                code: "80".to_string(),
+                pair_index: 0,
+                offset: 0,
+            },
+            DecodeIssue::NonDigit { ch: 'a', offset: 4 },
+        ];
+        assert_eq!(issues, expected);
+
+        // ASCII以外の文字もバイト数で区切りに数え, 組の位置は変換と同じになる
+        let issues = c.check_two_touch_string("11筋80");
+        let expected = vec![
+            DecodeIssue::NonDigit {
+                ch: '筋',
+                offset: 2,
+            },
+            DecodeIssue::OddTrailingDigit { offset: 6 },
+        ];
+        assert_eq!(issues, expected);
+        let issues = c.check_two_touch_string("1筋8080");
+        let expected = vec![
+            DecodeIssue::NonDigit {
+                ch: '筋',
+                offset: 1,
+            },
+            DecodeIssue::UnknownCode {
+                code: "80".to_string(),
+                pair_index: 2,
+                offset: 4,
+            },
+            DecodeIssue::UnknownCode {
+                code: "80".to_string(),
+                pair_index: 3,
+                offset: 6,
+            },
+        ];
+        assert_eq!(issues, expected);
+        // 変換のエラーと同じ位置を返す
+        let err = c.convert_from_two_touch_string("118011").unwrap_err();
+        let issues = c.check_two_touch_string("118011");
+        assert_eq!(
+            issues,
+            vec![DecodeIssue::UnknownCode {
+                code: "80".to_string(),
+                pair_index: 1,
+                offset: 2,
+            }]
+        );
+        assert_eq!(
+            *err.kind(),
+            ErrorKind::UnknownCode {
+                code: "80".to_string(),
+                pair_index: 1,
+                offset: 2,
+            }
+        );
+    }
+
+    #[cfg(feature = "decode")]
+    #[test]
+    fn test_validate_two_touch() {
+        let c = Converter::new();
+        let report = c.validate_two_touch("250459868884");
+        assert!(report.is_empty());
+        assert!(report.is_decodable);
+
+        let report = c.validate_two_touch("25045");
+        assert!(!report.is_decodable);
+        assert_eq!(
+            report.errors,
+            vec![DecodeIssue::OddTrailingDigit { offset: 4 }]
+        );
+
+        let report = c.validate_two_touch("25a404");
+        assert!(!report.is_decodable);
+        assert_eq!(
+            report.errors,
+            vec![DecodeIssue::NonDigit { ch: 'a', offset: 2 }]
+        );
+        assert_eq!(
+            report.warnings,
+            vec![CodeWarning::MisplacedDakuten {
+                code: "04".to_string(),
+                offset: 4,
+            }]
+        );
+
+        let report = c.validate_two_touch("1104");
+        assert!(report.is_decodable);
+        assert_eq!(
+            report.warnings,
+            vec![CodeWarning::MisplacedDakuten {
+                code: "04".to_string(),
+                offset: 2,
+            }]
+        );
+
+        let report = c.validate_two_touch("");
+        assert!(!report.is_decodable);
+        assert_eq!(report.errors, vec![DecodeIssue::Empty]);
+    }
+
+    #[cfg(feature = "decode")]
+    #[test]
+    fn test_convert_from_two_touch_string_error_kind() {
+        let c = Converter::new();
+        let err = c.convert_from_two_touch_string("8080").unwrap_err();
+        assert_eq!(
+            *err.kind(),
+            ErrorKind::UnknownCode {
+                code: "80".to_string(),
+                pair_index: 0,
+                offset: 0,
+            }
+        );
+        assert_eq!(err.to_string(), "unknown code \"80\" at pair 0 (offset 0)");
+
+        let err = c.convert_from_two_touch_string("118011").unwrap_err();
+        assert_eq!(err.to_string(), "unknown code \"80\" at pair 1 (offset 2)");
+
+        let err = c.convert_from_two_touch_string("111").unwrap_err();
+        assert_eq!(*err.kind(), ErrorKind::OddLength { len: 3 });
+
+        let err = c.convert_from_two_touch_string("11筋").unwrap_err();
+        assert_eq!(
+            *err.kind(),
+            ErrorKind::NonAscii {
+                ch: '筋',
+                offset: 2
+            }
+        );
+    }
 }